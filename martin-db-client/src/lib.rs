@@ -0,0 +1,212 @@
+//! Async client for the martin-db web server, so applications can run SQL without building
+//! the HTTP requests themselves.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), martin_db_client::Error> {
+//! let client = martin_db_client::Client::new("http://127.0.0.1:3000");
+//! client.execute("INSERT INTO users VALUES (1, 'ada')").await?;
+//! let params = [martin_db_client::Value::Integer(1)];
+//! client.query_with("SELECT name FROM users WHERE id = ?", &params).await?;
+//! for row in client.query("SELECT id, name FROM users").await?.rows {
+//!     println!("{:?} {:?}", row.get("id"), row.get("name"));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod row;
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+pub use row::{Row, Value};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The server could not parse or execute the statement.
+    #[error("{0}")]
+    Query(String),
+
+    #[error("Unexpected response: {0}")]
+    Decode(String),
+}
+
+/// The outcome of a statement: a message such as `1 row inserted.`, and the rows of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub message: String,
+    pub headers: Vec<String>,
+    /// The type of each column, e.g. `INT`, `TEXT` or `TIMESTAMP`, so values can be rendered
+    /// without guessing from their text.
+    pub types: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl QueryResult {
+    /// The type of `column`, matched like `Row::get`.
+    pub fn column_type(&self, column: &str) -> Option<&str> {
+        let i = row::column_position(&self.headers, column)?;
+        self.types.get(i).map(String::as_str)
+    }
+}
+
+#[derive(Serialize)]
+struct QueryRequest<'a> {
+    sql: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<&'a [Value]>,
+}
+
+/// The body of a `/query` response.
+#[derive(Deserialize)]
+struct QueryResponse {
+    message: String,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// Missing from servers that predate column types.
+    #[serde(default)]
+    types: Vec<String>,
+    error: Option<String>,
+}
+
+/// A handle on one database of a running server. Cloning is cheap and shares connections.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    tenant: Option<String>,
+    session: Option<String>,
+}
+
+impl Client {
+    /// A client for the server at `base_url`, e.g. `http://127.0.0.1:3000`, using its default
+    /// database.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            tenant: None,
+            session: None,
+        }
+    }
+
+    /// Targets the database of `tenant` instead of the default one.
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Sends every statement in the server-side session `id`, so variables and savepoints
+    /// carry over between them. The server drops sessions left unused for a while.
+    pub fn session(mut self, id: impl Into<String>) -> Self {
+        self.session = Some(id.into());
+        self
+    }
+
+    /// Runs one statement and returns its rows.
+    pub async fn query(&self, sql: &str) -> Result<QueryResult, Error> {
+        self.send(sql, None, false).await
+    }
+
+    /// Runs one statement with its `?` placeholders bound to `params`, in order, and returns
+    /// its rows. The server binds the values, so they never need quoting or escaping.
+    pub async fn query_with(&self, sql: &str, params: &[Value]) -> Result<QueryResult, Error> {
+        self.send(sql, Some(params), false).await
+    }
+
+    /// Runs a statement as a dry run: the server checks it, constraints included, and undoes
+    /// it. Returns the message the statement would have produced.
+    pub async fn validate(&self, sql: &str) -> Result<String, Error> {
+        self.send(sql, None, true)
+            .await
+            .map(|result| result.message)
+    }
+
+    async fn send(
+        &self,
+        sql: &str,
+        params: Option<&[Value]>,
+        dry_run: bool,
+    ) -> Result<QueryResult, Error> {
+        let mut url = self.url("query");
+        if dry_run {
+            url.push_str("?dry_run=true");
+        }
+        let mut request = self.http.post(url).json(&QueryRequest { sql, params });
+        if let Some(session) = &self.session {
+            request = request.header("x-session-id", session);
+        }
+        let response: QueryResponse = request.send().await?.error_for_status()?.json().await?;
+        if let Some(error) = response.error {
+            return Err(Error::Query(error));
+        }
+
+        let headers: Arc<[String]> = response.headers.clone().into();
+        let rows = response
+            .rows
+            .into_iter()
+            .map(|values| {
+                let values = values
+                    .iter()
+                    .map(|v| Value::parse(v).ok_or_else(|| Error::Decode(v.clone())))
+                    .collect::<Result<_, _>>()?;
+                Ok(Row::new(headers.clone(), values))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(QueryResult {
+            message: response.message,
+            headers: response.headers,
+            types: response.types,
+            rows,
+        })
+    }
+
+    /// Runs a statement that returns no rows, such as an `INSERT`, and returns its message.
+    pub async fn execute(&self, sql: &str) -> Result<String, Error> {
+        self.query(sql).await.map(|result| result.message)
+    }
+
+    /// Like `execute`, with the `?` placeholders of `sql` bound to `params`.
+    pub async fn execute_with(&self, sql: &str, params: &[Value]) -> Result<String, Error> {
+        self.query_with(sql, params)
+            .await
+            .map(|result| result.message)
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        match &self.tenant {
+            Some(tenant) => format!("{}/t/{}/{}", self.base_url, tenant, endpoint),
+            None => format!("{}/{}", self.base_url, endpoint),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls() {
+        let client = Client::new("http://localhost:3000/");
+        assert_eq!(client.url("query"), "http://localhost:3000/query");
+        let client = client.tenant("acme");
+        assert_eq!(client.url("query"), "http://localhost:3000/t/acme/query");
+    }
+
+    #[test]
+    fn test_column_types() {
+        let result = QueryResult {
+            message: "Success".into(),
+            headers: vec!["users.id".into(), "created_at".into()],
+            types: vec!["INT".into(), "TIMESTAMP".into()],
+            rows: vec![],
+        };
+        assert_eq!(result.column_type("id"), Some("INT"));
+        assert_eq!(result.column_type("created_at"), Some("TIMESTAMP"));
+        assert_eq!(result.column_type("name"), None);
+    }
+}
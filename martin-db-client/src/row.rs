@@ -0,0 +1,223 @@
+use std::ops::Index;
+use std::sync::Arc;
+
+/// A value of a result row, mirroring the engine's `Value`. Also the type of query parameters,
+/// sent as a JSON number, string or `null`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Integer(i32),
+    BigInt(i64),
+    Real(f64),
+    Text(String),
+    Null,
+}
+
+impl Value {
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer, widened to 64 bits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some((*n).into()),
+            Value::BigInt(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer or real as an `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(f64::from(*n)),
+            Value::BigInt(n) => Some(*n as f64),
+            Value::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
+
+    /// Parses a value as the server renders it: `Integer(7)`, `BigInt(7)`, `Real(1.5)`,
+    /// `Text("ada")` or `Null`, with the text escaped like a Rust string literal. Decimals,
+    /// dates and timestamps, such as `Decimal(19.90)` or `Date(2024-01-31)`, are read as their
+    /// text, so no digit of a decimal is lost.
+    pub(crate) fn parse(rendered: &str) -> Option<Value> {
+        if rendered == "Null" {
+            return Some(Value::Null);
+        }
+        if let Some(n) = rendered
+            .strip_prefix("Integer(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return n.parse().ok().map(Value::Integer);
+        }
+        if let Some(n) = rendered
+            .strip_prefix("BigInt(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return n.parse().ok().map(Value::BigInt);
+        }
+        if let Some(r) = rendered
+            .strip_prefix("Real(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return r.parse().ok().map(Value::Real);
+        }
+        for prefix in ["Decimal(", "Date(", "Timestamp("] {
+            if let Some(text) = rendered
+                .strip_prefix(prefix)
+                .and_then(|s| s.strip_suffix(')'))
+            {
+                return Some(Value::Text(text.to_string()));
+            }
+        }
+        let quoted = rendered.strip_prefix("Text(\"")?.strip_suffix("\")")?;
+        unescape(quoted).map(Value::Text)
+    }
+}
+
+/// Undoes the escapes of Rust's `Debug` output for strings.
+fn unescape(quoted: &str) -> Option<String> {
+    let mut text = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        text.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                let code = chars.as_str().strip_prefix('{')?;
+                let (hex, rest) = code.split_once('}')?;
+                let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                chars = rest.chars();
+                c
+            }
+            other => other,
+        });
+    }
+    Some(text)
+}
+
+/// One row of a query result. Values are read by position or by column name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    headers: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub(crate) fn new(headers: Arc<[String]>, values: Vec<Value>) -> Self {
+        Self { headers, values }
+    }
+
+    /// The value of `column`. Like in SQL, a bare name also matches a joined header such as
+    /// `users.id`, as long as only one column has that name.
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.values.get(column_position(&self.headers, column)?)
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The position of `column` in `headers`, where a bare name also matches a single joined
+/// header such as `users.id`.
+pub(crate) fn column_position(headers: &[String], column: &str) -> Option<usize> {
+    if let Some(i) = headers.iter().position(|h| h == column) {
+        return Some(i);
+    }
+    let suffix = format!(".{}", column);
+    let mut matches = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.ends_with(&suffix));
+    match (matches.next(), matches.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+impl Index<usize> for Row {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        &self.values[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(Value::parse("Integer(-7)"), Some(Value::Integer(-7)));
+        assert_eq!(Value::parse("Null"), Some(Value::Null));
+        let rendered = format!("{:?}", "say \"hi\"\n\u{1}");
+        assert_eq!(
+            Value::parse(&format!("Text({})", rendered)),
+            Some(Value::Text("say \"hi\"\n\u{1}".into()))
+        );
+        assert_eq!(Value::parse("Real(-0.5)"), Some(Value::Real(-0.5)));
+        assert_eq!(
+            Value::parse("BigInt(1700000000000)"),
+            Some(Value::BigInt(1_700_000_000_000))
+        );
+        assert_eq!(
+            Value::parse("Timestamp(2024-01-31 12:00:00)"),
+            Some(Value::Text("2024-01-31 12:00:00".into()))
+        );
+        assert_eq!(
+            Value::parse("Decimal(-19.90)"),
+            Some(Value::Text("-19.90".into()))
+        );
+        assert_eq!(Value::parse("Float(1.5)"), None);
+
+        let headers: Arc<[String]> =
+            vec!["users.id".to_string(), "teams.id".into(), "name".into()].into();
+        let row = Row::new(
+            headers,
+            vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Text("ada".into()),
+            ],
+        );
+        assert_eq!(row.get("name").and_then(Value::as_str), Some("ada"));
+        assert_eq!(row.get("teams.id"), Some(&Value::Integer(2)));
+        assert_eq!(row.get("id"), None);
+        assert_eq!(row[0].as_i32(), Some(1));
+    }
+}
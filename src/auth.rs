@@ -0,0 +1,323 @@
+//! Authentication of the requests to the web server. A provider turns the headers of a request
+//! into the principal it runs as, and is chosen with the `auth` key of the server's config file:
+//!
+//! - `{"api_keys": {"<key>": "<principal>", ...}}`: `Authorization: Bearer <key>`;
+//! - `{"htpasswd": "<path>"}`: HTTP Basic, checked against the bcrypt entries of an htpasswd
+//!   file (`htpasswd -B`);
+//! - `{"jwt": {"secret": "...", "issuer": "...", "audience": "..."}}`: `Authorization: Bearer`
+//!   with an HS256 JSON Web Token, running as its `sub` claim. `issuer` and `audience` are
+//!   optional.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::http::{HeaderMap, header};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::engine::schedules::unix_now;
+
+/// Decides who a request runs as.
+pub trait AuthProvider: Send + Sync {
+    /// The principal the credentials in `headers` belong to, or why the request is refused.
+    fn authenticate(&self, headers: &HeaderMap) -> Result<String, String>;
+
+    /// The `WWW-Authenticate` header sent back with a refusal.
+    fn challenge(&self) -> &'static str {
+        "Bearer"
+    }
+}
+
+/// The `auth` key of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum AuthConfig {
+    ApiKeys(HashMap<String, String>),
+    Htpasswd(PathBuf),
+    Jwt {
+        secret: String,
+        issuer: Option<String>,
+        audience: Option<String>,
+    },
+}
+
+impl AuthConfig {
+    /// Builds the configured provider, reading the files it names.
+    pub fn provider(&self) -> Result<Arc<dyn AuthProvider>, String> {
+        Ok(match self {
+            AuthConfig::ApiKeys(keys) => Arc::new(ApiKeys { keys: keys.clone() }),
+            AuthConfig::Htpasswd(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read htpasswd file {}: {}", path.display(), e))?;
+                Arc::new(
+                    Htpasswd::parse(&text)
+                        .map_err(|e| format!("Invalid htpasswd file {}: {}", path.display(), e))?,
+                )
+            }
+            AuthConfig::Jwt {
+                secret,
+                issuer,
+                audience,
+            } => Arc::new(Jwt {
+                secret: secret.clone().into_bytes(),
+                issuer: issuer.clone(),
+                audience: audience.clone(),
+            }),
+        })
+    }
+}
+
+/// Static API keys, each standing for a principal.
+pub struct ApiKeys {
+    keys: HashMap<String, String>,
+}
+
+impl AuthProvider for ApiKeys {
+    fn authenticate(&self, headers: &HeaderMap) -> Result<String, String> {
+        let key = credentials(headers, "Bearer")?;
+        self.keys
+            .get(key)
+            .cloned()
+            .ok_or_else(|| "Unknown API key".into())
+    }
+}
+
+/// The users of an htpasswd file and their bcrypt hashes.
+pub struct Htpasswd {
+    users: HashMap<String, String>,
+    /// A hash checked for unknown users, so they take as long to refuse as wrong passwords.
+    dummy: String,
+}
+
+impl Htpasswd {
+    /// Reads `user:hash` lines, skipping blank lines and `#` comments.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut users = HashMap::new();
+        let mut cost = bcrypt::DEFAULT_COST;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (user, hash) = line
+                .split_once(':')
+                .ok_or_else(|| format!("line {} is not user:hash", i + 1))?;
+            let entry_cost = hash
+                .strip_prefix("$2")
+                .and_then(|rest| rest.get(2..4))
+                .and_then(|c| c.parse().ok())
+                .filter(|c| (4..=31).contains(c))
+                .ok_or_else(|| {
+                    format!(
+                        "line {} is not a bcrypt entry; create it with htpasswd -B",
+                        i + 1
+                    )
+                })?;
+            if users.is_empty() {
+                cost = entry_cost;
+            }
+            users.insert(user.to_string(), hash.to_string());
+        }
+        let dummy = bcrypt::hash("", cost).map_err(|e| e.to_string())?;
+        Ok(Htpasswd { users, dummy })
+    }
+}
+
+impl AuthProvider for Htpasswd {
+    fn authenticate(&self, headers: &HeaderMap) -> Result<String, String> {
+        let encoded = credentials(headers, "Basic")?;
+        let decoded = STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or("Malformed Basic credentials")?;
+        let (user, password) = decoded
+            .split_once(':')
+            .ok_or("Malformed Basic credentials")?;
+        let valid = match self.users.get(user) {
+            Some(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            None => {
+                let _ = bcrypt::verify(password, &self.dummy);
+                false
+            }
+        };
+        if !valid {
+            return Err("Invalid user name or password".into());
+        }
+        Ok(user.to_string())
+    }
+
+    fn challenge(&self) -> &'static str {
+        "Basic realm=\"martin-db\""
+    }
+}
+
+/// JSON Web Tokens signed with HS256 and a shared secret.
+pub struct Jwt {
+    secret: Vec<u8>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    sub: String,
+    exp: Option<u64>,
+    nbf: Option<u64>,
+    iss: Option<String>,
+    #[serde(default)]
+    aud: Audience,
+}
+
+/// The `aud` claim, which may be one audience or a list of them.
+#[derive(Deserialize, Default)]
+#[serde(untagged)]
+enum Audience {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::None => false,
+            Audience::One(aud) => aud == audience,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+impl AuthProvider for Jwt {
+    fn authenticate(&self, headers: &HeaderMap) -> Result<String, String> {
+        let token = credentials(headers, "Bearer")?;
+        let claims = self.verify(token, unix_now())?;
+        Ok(claims.sub)
+    }
+}
+
+impl Jwt {
+    fn verify(&self, token: &str, now: u64) -> Result<JwtClaims, String> {
+        let malformed = || "Malformed token".to_string();
+        let mut parts = token.split('.');
+        let (Some(signed_header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed());
+        };
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| malformed());
+
+        let header: JwtHeader =
+            serde_json::from_slice(&decode(signed_header)?).map_err(|_| malformed())?;
+        if header.alg != "HS256" {
+            return Err(format!("Unsupported token algorithm {}", header.alg));
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("any key length");
+        mac.update(format!("{}.{}", signed_header, payload).as_bytes());
+        mac.verify_slice(&decode(signature)?)
+            .map_err(|_| "Invalid token signature")?;
+
+        let claims: JwtClaims =
+            serde_json::from_slice(&decode(payload)?).map_err(|_| malformed())?;
+        if claims.exp.is_some_and(|exp| now >= exp) {
+            return Err("The token has expired".into());
+        }
+        if claims.nbf.is_some_and(|nbf| now < nbf) {
+            return Err("The token is not valid yet".into());
+        }
+        if let Some(issuer) = &self.issuer
+            && claims.iss.as_ref() != Some(issuer)
+        {
+            return Err("The token has another issuer".into());
+        }
+        if let Some(audience) = &self.audience
+            && !claims.aud.contains(audience)
+        {
+            return Err("The token is meant for another audience".into());
+        }
+        Ok(claims)
+    }
+}
+
+/// The credentials following `scheme` in the `Authorization` header.
+fn credentials<'a>(headers: &'a HeaderMap, scheme: &str) -> Result<&'a str, String> {
+    let value = headers
+        .get(header::AUTHORIZATION)
+        .ok_or("Missing credentials")?
+        .to_str()
+        .map_err(|_| "Malformed credentials")?;
+    value
+        .split_once(' ')
+        .filter(|(s, _)| s.eq_ignore_ascii_case(scheme))
+        .map(|(_, credentials)| credentials.trim())
+        .ok_or_else(|| format!("Expected {} credentials", scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    fn sign(secret: &str, claims: &str) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims);
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", header, payload).as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}.{}", header, payload, signature)
+    }
+
+    #[test]
+    fn test_auth_providers() {
+        let config: AuthConfig = serde_json::from_str(r#"{"api_keys": {"k1": "ci"}}"#).unwrap();
+        let keys = config.provider().unwrap();
+        assert_eq!(
+            keys.authenticate(&authorization("Bearer k1")).unwrap(),
+            "ci"
+        );
+        assert!(keys.authenticate(&authorization("Bearer k2")).is_err());
+        assert!(keys.authenticate(&HeaderMap::new()).is_err());
+
+        let hash = bcrypt::hash("s3cret", 4).unwrap();
+        let htpasswd = Htpasswd::parse(&format!("# admins\nmartin:{}\n", hash)).unwrap();
+        let basic =
+            |credentials: &str| authorization(&format!("Basic {}", STANDARD.encode(credentials)));
+        assert_eq!(
+            htpasswd.authenticate(&basic("martin:s3cret")).unwrap(),
+            "martin"
+        );
+        assert!(htpasswd.authenticate(&basic("martin:guess")).is_err());
+        assert!(htpasswd.authenticate(&basic("mallory:s3cret")).is_err());
+        assert!(htpasswd.dummy.starts_with("$2b$04$"));
+        assert!(Htpasswd::parse("martin:{SHA}abc").is_err());
+
+        let config: AuthConfig =
+            serde_json::from_str(r#"{"jwt": {"secret": "shh", "audience": "db"}}"#).unwrap();
+        let jwt = config.provider().unwrap();
+        let token = sign("shh", r#"{"sub": "ada", "aud": ["db", "ui"]}"#);
+        let bearer = |token: &str| authorization(&format!("Bearer {}", token));
+        assert_eq!(jwt.authenticate(&bearer(&token)).unwrap(), "ada");
+        for token in [
+            sign("guess", r#"{"sub": "ada", "aud": "db"}"#),
+            sign("shh", r#"{"sub": "ada", "aud": "db", "exp": 1}"#),
+            sign("shh", r#"{"sub": "ada", "aud": "ui"}"#),
+        ] {
+            assert!(jwt.authenticate(&bearer(&token)).is_err());
+        }
+    }
+}
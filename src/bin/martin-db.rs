@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use martin_db::{
+    engine::limits::Limits,
+    storage::{
+        Format, convert, export_archive, import_archive, load_with_format, save_with_format,
+    },
+};
+
+const USAGE: &str =
+    "Usage: martin-db convert [--from json|binary] [--to json|binary] <input> <output>
+       martin-db gen [--seed N] <database> <table> <count>
+       martin-db export <database> <archive>
+       martin-db import [--max-load-size <size>] <archive> <database>
+       martin-db diff-schema <database> <target>
+
+Formats default to the file extension: `.mdb` is binary, anything else JSON.
+`gen` fills an existing table with random rows that fit its schema.
+`export` bundles a database into a single tar archive that `import` restores, refusing
+a database larger than --max-load-size (bytes, or with a k/m/g suffix).
+`diff-schema` prints the statements that give <database> the schema of <target>.";
+
+/// Administrative commands that operate on database files directly.
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("convert") => run_convert(args),
+        Some("gen") => run_gen(args),
+        Some("export") => run_export(args),
+        Some("import") => run_import(args),
+        Some("diff-schema") => run_diff_schema(args),
+        Some("--help") | Some("-h") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => bail!("Unknown command: {}\n\n{}", other, USAGE),
+    }
+}
+
+/// Loads a database with one codec and saves it with another.
+fn run_convert(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut from = None;
+    let mut to = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" | "--to" => {
+                let value = args
+                    .next()
+                    .with_context(|| format!("{} needs a format", arg))?;
+                let format: Format = value.parse().map_err(anyhow::Error::msg)?;
+                if arg == "--from" {
+                    from = Some(format);
+                } else {
+                    to = Some(format);
+                }
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [input, output] = paths.as_slice() else {
+        bail!("Expected an input and an output file\n\n{}", USAGE);
+    };
+    let from = from.unwrap_or_else(|| Format::from_path(input));
+    let to = to.unwrap_or_else(|| Format::from_path(output));
+
+    convert(input, from, output, to)?;
+    println!(
+        "Converted {} ({}) to {} ({})",
+        input.display(),
+        from,
+        output.display(),
+        to
+    );
+    Ok(())
+}
+
+/// Inserts generated rows into a table of a database file, for benchmarks and demos.
+fn run_gen(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut seed = None;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().context("--seed needs a number")?;
+                seed = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid --seed: {}", value))?,
+                );
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let [path, table, count] = positional.as_slice() else {
+        bail!("Expected a database, a table and a row count\n\n{}", USAGE);
+    };
+    let count: usize = count
+        .parse()
+        .with_context(|| format!("Invalid row count: {}", count))?;
+    let seed = seed.unwrap_or_else(martin_db::engine::schedules::unix_now);
+
+    let path = PathBuf::from(path);
+    let format = Format::from_path(&path);
+    let mut db = load_with_format(&path, format)?;
+    db.generate_rows(table, count, seed)?;
+    save_with_format(&db, &path, format)?;
+    println!("Generated {} rows in {} (seed {})", count, table, seed);
+    Ok(())
+}
+
+/// Bundles a database file into an archive to move to another machine.
+fn run_export(args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let [database, archive] = paths(args)?;
+    let db = load_with_format(&database, Format::from_path(&database))?;
+    let manifest = export_archive(&db, &archive)?;
+    println!(
+        "Exported {} tables from {} to {}",
+        manifest.tables.len(),
+        database.display(),
+        archive.display()
+    );
+    Ok(())
+}
+
+/// Restores an archive written by `export` into a database file.
+fn run_import(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut limits = Limits::default();
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-load-size" => {
+                let size = args.next().context("--max-load-size needs a size")?;
+                limits.max_load_bytes =
+                    Some(Limits::parse_size(&size).map_err(anyhow::Error::msg)?);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [archive, database] = paths(positional.into_iter())?;
+    let (db, manifest) = import_archive(&archive, limits)?;
+    save_with_format(&db, &database, Format::from_path(&database))?;
+    println!(
+        "Imported {} tables from {} to {}",
+        manifest.tables.len(),
+        archive.display(),
+        database.display()
+    );
+    Ok(())
+}
+
+/// Prints the migration script from one database's schema to another's. Differences the
+/// script can't apply are listed on stderr, and make the command fail.
+fn run_diff_schema(args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let [database, target] = paths(args)?;
+    let db = load_with_format(&database, Format::from_path(&database))?;
+    let target_db = load_with_format(&target, Format::from_path(&target))?;
+    let diff = db.schema_diff(&target_db);
+    for statement in &diff.statements {
+        println!("{};", statement);
+    }
+    if !diff.unsupported.is_empty() {
+        for difference in &diff.unsupported {
+            eprintln!("Not migrated: {}", difference);
+        }
+        bail!(
+            "{} differences need to be migrated by hand",
+            diff.unsupported.len()
+        );
+    }
+    Ok(())
+}
+
+/// The two file arguments of `export`, `import` and `diff-schema`.
+fn paths(args: impl Iterator<Item = String>) -> anyhow::Result<[PathBuf; 2]> {
+    let paths: Vec<PathBuf> = args.map(PathBuf::from).collect();
+    <[PathBuf; 2]>::try_from(paths).map_err(|_| anyhow::anyhow!("Expected two files\n\n{}", USAGE))
+}
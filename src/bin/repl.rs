@@ -1,20 +1,124 @@
+use std::path::Path;
+
 use martin_db::{
-    engine::ExecutionResult,
-    parser::parse,
-    storage::{load_from_disk, save_to_disk},
+    Database, DbError,
+    cli::{arg_value, json_style_from_args, limits_from_args, policy_from_args, storage_from_args},
+    engine::{ExecutionResult, migrations::Migration, session::Session},
+    parser::{parse_script, split_statements},
+    storage::{PersistenceManager, PersistencePolicy},
 };
 use prettytable::{Cell, Row, Table};
 use rustyline::{DefaultEditor, error::ReadlineError};
 
+/// Builds the persistence manager from `--db <path>` (`:memory:` keeps everything in memory)
+/// and `--persist <always|manual|N|Ts>`. Defaults to flushing the usual database file after
+/// every statement.
+fn persistence_from_args() -> Result<PersistenceManager, String> {
+    Ok(PersistenceManager::new(
+        storage_from_args(),
+        policy_from_args()?,
+    ))
+}
+
+/// Registers the `.sql` files of `--migrations <dir>` (default `migrations`) and applies the
+/// pending ones.
+fn migrate(db: &mut Database) -> Result<Vec<String>, DbError> {
+    let dir = arg_value("migrations").unwrap_or_else(|| "migrations".into());
+    for migration in Migration::load_dir(Path::new(&dir))? {
+        db.register_migration(migration)?;
+    }
+    db.migrate()
+}
+
+fn print_result(result: ExecutionResult) {
+    match result {
+        ExecutionResult::Message(msg) => println!("{}", msg),
+        ExecutionResult::Data { headers, rows, .. } => {
+            let mut table = Table::new();
+            table.add_row(Row::new(
+                headers.into_iter().map(|s| Cell::new(&s)).collect(),
+            ));
+            for r in rows {
+                table.add_row(Row::new(
+                    r.into_iter()
+                        .map(|v| Cell::new(&format!("{:?}", v)))
+                        .collect(),
+                ));
+            }
+            table.printstd();
+        }
+    }
+}
+
+/// Runs the statements of the SQL file at `path` in order, printing each result, and stops at
+/// the first failure. The whole file is parsed first, so a syntax error runs nothing.
+/// Statements that ran before a failure stay applied unless `transactional` is set, in which
+/// case the failure undoes the whole script.
+fn read_script(
+    db: &mut Database,
+    session: &mut Session,
+    path: &str,
+    transactional: bool,
+) -> Result<usize, String> {
+    let sql =
+        std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let statements = parse_script(&sql).map_err(|e| e.render(&sql))?;
+    let count = statements.len();
+    let run = |db: &mut Database| {
+        for (i, stmt) in statements.into_iter().enumerate() {
+            match db.execute_with_session(stmt, session) {
+                Ok(result) => print_result(result),
+                Err(e) => return Err(format!("Statement {} failed: {}", i + 1, e)),
+            }
+        }
+        Ok(count)
+    };
+    if transactional {
+        db.atomically(run)
+            .map_err(|e| format!("{}; the script was rolled back", e))
+    } else {
+        run(db)
+    }
+}
+
 fn main() -> anyhow::Result<()> {
-    let mut db = load_from_disk().unwrap_or_else(|_| {
-        println!("Initializing a new Database.");
-        martin_db::Database::new()
-    });
+    let mut persistence = persistence_from_args().map_err(anyhow::Error::msg)?;
+    let limits = limits_from_args().map_err(anyhow::Error::msg)?;
+    let json_style = json_style_from_args().map_err(anyhow::Error::msg)?;
+    // `--repair` fixes a database failing the integrity check instead of refusing it.
+    let repair = std::env::args().any(|arg| arg == "--repair");
+    let mut db = match persistence.load_checked(limits, repair) {
+        Ok((db, repaired)) => {
+            for issue in repaired {
+                println!("Repaired: {}", issue);
+            }
+            db
+        }
+        // Starting over would overwrite the database on the next flush.
+        Err(err @ (DbError::StorageFull(_) | DbError::IntegrityViolation(_))) => {
+            return Err(err.into());
+        }
+        Err(_) => {
+            println!("Initializing a new Database.");
+            let mut db = Database::new();
+            db.limits = limits;
+            db
+        }
+    };
+    db.json_style = json_style;
+
+    let user = std::env::var("USER").unwrap_or_else(|_| "repl".into());
+    let mut session = Session::new(user);
+    // Whoever runs the REPL can read the file anyway.
+    session.unmask = true;
+    session.admin = true;
 
     let mut rl = DefaultEditor::new()?;
     println!("Martin Db challenge for pesapal");
-    println!("Type 'exit' to quit.");
+    println!(
+        "Type 'exit' to quit, '.flush' to save pending changes, '.migrate' to apply migrations."
+    );
+    println!("'.read [--transaction] <file>' runs a script, all-or-nothing with --transaction.");
 
     loop {
         let readline = rl.readline("sql> ");
@@ -24,43 +128,69 @@ fn main() -> anyhow::Result<()> {
                 if trimmed == "exit" {
                     break;
                 }
+                if trimmed == ".migrate" {
+                    match migrate(&mut db) {
+                        Ok(applied) if applied.is_empty() => println!("No pending migrations."),
+                        Ok(applied) => {
+                            println!("Applied: {}", applied.join(", "));
+                            persistence.after_statement(&mut db)?;
+                        }
+                        Err(e) => println!("Migration Error: {}", e),
+                    }
+                    continue;
+                }
+                if let Some(args) = trimmed
+                    .strip_prefix(".read")
+                    .filter(|args| args.is_empty() || args.starts_with(' '))
+                {
+                    let args: Vec<&str> = args.split_whitespace().collect();
+                    let (transactional, path) = match args[..] {
+                        ["--transaction", path] => (true, path),
+                        [path] => (false, path),
+                        _ => {
+                            println!("Usage: .read [--transaction] <file>");
+                            continue;
+                        }
+                    };
+                    match read_script(&mut db, &mut session, path, transactional) {
+                        Ok(count) => println!("Ran {} statements from {}.", count, path),
+                        Err(e) => println!("Script Error: {}", e),
+                    }
+                    // Flushed once the script is over, so a rolled back script never reaches
+                    // the disk halfway.
+                    persistence.after_statement(&mut db)?;
+                    let _ = rl.add_history_entry(trimmed);
+                    continue;
+                }
+                if trimmed == ".flush" {
+                    match persistence.flush(&mut db) {
+                        Ok(()) => println!("Flushed."),
+                        Err(e) => println!("Flush Error: {}", e),
+                    }
+                    continue;
+                }
 
-                match parse(trimmed) {
-                    Ok(stmt) => {
-                        // Check if it's a mutating query to save later
-                        let is_mutation = matches!(
-                            stmt,
-                            martin_db::parser::Statement::CreateTable { .. }
-                                | martin_db::parser::Statement::Insert { .. }
-                        );
-
-                        match db.execute(stmt) {
-                            Ok(result) => {
-                                match result {
-                                    ExecutionResult::Message(msg) => println!("{}", msg),
-                                    ExecutionResult::Data { headers, rows } => {
-                                        let mut table = Table::new();
-                                        table.add_row(Row::new(
-                                            headers.into_iter().map(|s| Cell::new(&s)).collect(),
-                                        ));
-                                        for r in rows {
-                                            table.add_row(Row::new(
-                                                r.into_iter()
-                                                    .map(|v| Cell::new(&format!("{:?}", v)))
-                                                    .collect(),
-                                            ));
-                                        }
-                                        table.printstd();
-                                    }
+                // Blank lines and lines holding nothing but a comment.
+                if split_statements(trimmed).is_empty() {
+                    continue;
+                }
+                // A line may hold several statements, run in order until one fails.
+                match parse_script(trimmed) {
+                    Ok(statements) => {
+                        for stmt in statements {
+                            match db.execute_with_session(stmt, &mut session) {
+                                Ok(result) => {
+                                    print_result(result);
+                                    persistence.after_statement(&mut db)?;
                                 }
-                                if is_mutation {
-                                    save_to_disk(&db)?;
+                                Err(e) => {
+                                    println!("Execution Error: {}", e);
+                                    break;
                                 }
                             }
-                            Err(e) => println!("Execution Error: {}", e),
                         }
                     }
-                    Err(e) => println!("Syntax Error: {}", e),
+                    Err(e) => println!("Syntax Error: {}", e.render(trimmed)),
                 }
                 let _ = rl.add_history_entry(trimmed);
             }
@@ -68,5 +198,10 @@ fn main() -> anyhow::Result<()> {
             _ => (),
         }
     }
+
+    // Write-back policies may still hold changes; only a manual policy leaves them unsaved.
+    if persistence.policy() != PersistencePolicy::Manual {
+        persistence.flush(&mut db)?;
+    }
     Ok(())
 }
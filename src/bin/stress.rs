@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use martin_db::cli::arg_value;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const USAGE: &str =
+    "Usage: stress [--addr 127.0.0.1:3000] [--clients 8] [--requests 200] [--writes 20]
+
+Runs a mixed read/write load against a running web server's /query endpoint. Each of the
+`--clients` concurrent clients sends `--requests` queries, `--writes` percent of them INSERTs
+and the rest SELECTs. Prints latency percentiles and the server's lock contention metrics.";
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    error: Option<String>,
+}
+
+/// Reads a numeric `--<name>` flag, falling back to `default` when it is absent.
+fn arg_number(name: &str, default: usize) -> anyhow::Result<usize> {
+    match arg_value(name) {
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("Invalid --{}: {}", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// Sends a bare HTTP/1.1 request and returns the response body.
+async fn request(addr: &str, method: &str, path: &str, body: &str) -> anyhow::Result<String> {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .with_context(|| format!("Cannot connect to {}", addr))?;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("Malformed HTTP response")?;
+    if !head.starts_with("HTTP/1.1 200") {
+        bail!("{}", head.lines().next().unwrap_or_default());
+    }
+    Ok(body.to_string())
+}
+
+/// Runs one statement through `/query`, turning an execution error into an `Err`.
+async fn query(addr: &str, sql: &str) -> anyhow::Result<()> {
+    let body = serde_json::json!({ "sql": sql }).to_string();
+    let response: QueryResponse =
+        serde_json::from_str(&request(addr, "POST", "/query", &body).await?)?;
+    match response.error {
+        Some(e) => bail!(e),
+        None => Ok(()),
+    }
+}
+
+/// One client's share of the load: returns the latency of every successful request and the
+/// number of failed ones.
+async fn run_client(
+    addr: String,
+    table: String,
+    client: usize,
+    requests: usize,
+    write_pct: usize,
+) -> (Vec<Duration>, usize) {
+    let mut latencies = Vec::with_capacity(requests);
+    let mut errors = 0;
+
+    for i in 0..requests {
+        // Spread the writes evenly instead of bunching them at the start
+        let is_write = (i * write_pct) / 100 != ((i + 1) * write_pct) / 100;
+        let sql = if is_write {
+            let id = client * requests + i;
+            format!("INSERT INTO {} VALUES ({}, 'client-{}')", table, id, client)
+        } else if i % 2 == 0 {
+            format!("SELECT COUNT(*) FROM {}", table)
+        } else {
+            format!("SELECT * FROM {} WHERE id = {}", table, client * requests)
+        };
+
+        let started = Instant::now();
+        match query(&addr, &sql).await {
+            Ok(()) => latencies.push(started.elapsed()),
+            Err(e) => {
+                errors += 1;
+                if errors == 1 {
+                    eprintln!("client {}: {}", client, e);
+                }
+            }
+        }
+    }
+    (latencies, errors)
+}
+
+/// The latency below which `pct` percent of the sorted samples fall.
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) * pct) / 100]
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    if std::env::args().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let addr = arg_value("addr").unwrap_or_else(|| "127.0.0.1:3000".into());
+    let clients = arg_number("clients", 8)?;
+    let requests = arg_number("requests", 200)?;
+    let write_pct = arg_number("writes", 20)?;
+    if write_pct > 100 {
+        bail!("--writes is a percentage between 0 and 100");
+    }
+
+    // A fresh table per run, so repeated runs against one server don't collide
+    let table = format!("stress_{}", std::process::id());
+    query(
+        &addr,
+        &format!("CREATE TABLE {} (id INT PRIMARY, client TEXT)", table),
+    )
+    .await?;
+
+    let started = Instant::now();
+    let handles: Vec<_> = (0..clients)
+        .map(|client| {
+            tokio::spawn(run_client(
+                addr.clone(),
+                table.clone(),
+                client,
+                requests,
+                write_pct,
+            ))
+        })
+        .collect();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for handle in handles {
+        let (client_latencies, client_errors) = handle.await?;
+        latencies.extend(client_latencies);
+        errors += client_errors;
+    }
+    let elapsed = started.elapsed();
+    latencies.sort();
+
+    println!(
+        "{} requests from {} clients in {:.2?} ({:.0} req/s), {} errors",
+        clients * requests,
+        clients,
+        elapsed,
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        errors
+    );
+    println!(
+        "latency p50 {:.2?}  p90 {:.2?}  p99 {:.2?}  max {:.2?}",
+        percentile(&latencies, 50),
+        percentile(&latencies, 90),
+        percentile(&latencies, 99),
+        latencies.last().copied().unwrap_or_default()
+    );
+
+    let metrics = request(&addr, "GET", "/metrics", "").await?;
+    for line in metrics.lines().filter(|l| l.starts_with("martin_db_lock_")) {
+        println!("{}", line);
+    }
+    Ok(())
+}
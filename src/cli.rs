@@ -0,0 +1,86 @@
+//! Command-line flags shared by the binaries: `--<name> <value>` lookups and the flags that
+//! pick the database file, how it is flushed and how much it may hold.
+
+use crate::{
+    engine::limits::Limits,
+    storage::{JsonStyle, PersistencePolicy, Storage},
+};
+
+/// Reads `--<name> <value>` (or `--<name>=<value>`) from the command line.
+pub fn arg_value(name: &str) -> Option<String> {
+    find_arg(std::env::args().skip(1), name)
+}
+
+fn find_arg(mut args: impl Iterator<Item = String>, name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|v| v.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// Reads `--db <path>`, the database file. `:memory:` keeps everything in memory.
+pub fn storage_from_args() -> Storage {
+    arg_value("db").map(Storage::new).unwrap_or_default()
+}
+
+/// Reads `--persist <always|manual|N|Ts>`. Defaults to flushing after every statement.
+pub fn policy_from_args() -> Result<PersistencePolicy, String> {
+    arg_value("persist").map_or(Ok(PersistencePolicy::EveryStatement), |s| s.parse())
+}
+
+/// Reads `--json <pretty|compact|auto>`, the layout of JSON database files.
+pub fn json_style_from_args() -> Result<JsonStyle, String> {
+    arg_value("json").map_or(Ok(JsonStyle::Auto), |s| s.parse())
+}
+
+/// Reads the storage caps from `--max-rows <N>`, `--max-size <bytes|64k|10m|2g>` and
+/// `--max-load-size <bytes|64k|10m|2g>`. `--max-size` caps the size of the values in memory,
+/// see `Limits::max_bytes`.
+pub fn limits_from_args() -> Result<Limits, String> {
+    let max_rows = match arg_value("max-rows") {
+        Some(n) => Some(
+            n.parse()
+                .map_err(|_| format!("Invalid --max-rows: {}", n))?,
+        ),
+        None => None,
+    };
+    let max_bytes = arg_value("max-size")
+        .map(|s| Limits::parse_size(&s))
+        .transpose()?;
+    let max_load_bytes = arg_value("max-load-size")
+        .map(|s| Limits::parse_size(&s))
+        .transpose()?;
+    Ok(Limits {
+        max_rows,
+        max_bytes,
+        max_load_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_arg() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let find = |line: &str, name: &str| find_arg(args(line).into_iter(), name);
+
+        assert_eq!(find("--db a.db --persist 5", "db").as_deref(), Some("a.db"));
+        assert_eq!(
+            find("--db a.db --persist=5s", "persist").as_deref(),
+            Some("5s")
+        );
+        assert_eq!(find("--max-size=10m", "max-size").as_deref(), Some("10m"));
+        // A flag is not matched by a longer one it prefixes, and needs a value.
+        assert_eq!(find("--max-size-x 1", "max-size"), None);
+        assert_eq!(find("--db", "db"), None);
+        assert_eq!(find("db a.db", "db"), None);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use crate::{
+    DbError,
+    engine::{Decimal, Real, Value, relation::Relation},
+    parser::{AggregateFunction, Expr, SelectItem},
+};
+
+/// Running total of `SUM` and `AVG`. It stays an integer until a decimal or a real is added,
+/// and a decimal until a real is.
+#[derive(Clone, Copy)]
+enum Total {
+    Integer(i64),
+    Decimal(Decimal),
+    Real(f64),
+}
+
+impl Total {
+    fn add(self, func: AggregateFunction, value: &Value) -> Result<Total, DbError> {
+        let overflow = || DbError::TypeError(format!("{} overflows BIGINT", func));
+        let decimal_overflow = || DbError::TypeError(format!("{} overflows DECIMAL", func));
+        Ok(match (self, value) {
+            (Total::Integer(t), Value::Integer(v)) => Total::Integer(t + *v as i64),
+            (Total::Integer(t), Value::BigInt(v)) => {
+                Total::Integer(t.checked_add(*v).ok_or_else(overflow)?)
+            }
+            (Total::Integer(t), Value::Decimal(v)) => Total::Decimal(
+                Decimal::from_integer(t)
+                    .checked_add(*v)
+                    .ok_or_else(decimal_overflow)?,
+            ),
+            (Total::Integer(t), Value::Real(v)) => Total::Real(t as f64 + v.get()),
+            (Total::Decimal(t), Value::Integer(_) | Value::BigInt(_) | Value::Decimal(_)) => {
+                let v = Decimal::from_value(value).unwrap();
+                Total::Decimal(t.checked_add(v).ok_or_else(decimal_overflow)?)
+            }
+            (Total::Decimal(t), Value::Real(v)) => Total::Real(t.to_f64() + v.get()),
+            (Total::Real(t), Value::Decimal(v)) => Total::Real(t + v.to_f64()),
+            (Total::Real(t), Value::Integer(v)) => Total::Real(t + f64::from(*v)),
+            (Total::Real(t), Value::BigInt(v)) => Total::Real(t + *v as f64),
+            (Total::Real(t), Value::Real(v)) => Total::Real(t + v.get()),
+            (_, other) => {
+                return Err(DbError::TypeError(format!(
+                    "{} expects numeric values, got {:?}",
+                    func, other
+                )));
+            }
+        })
+    }
+}
+
+/// Running state of one aggregate call within one group.
+enum Accumulator {
+    Count(i64),
+    Sum(Option<Total>),
+    Avg { sum: Total, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(func: AggregateFunction) -> Self {
+        match func {
+            AggregateFunction::Count => Self::Count(0),
+            AggregateFunction::Sum => Self::Sum(None),
+            AggregateFunction::Avg => Self::Avg {
+                sum: Total::Integer(0),
+                count: 0,
+            },
+            AggregateFunction::Min => Self::Min(None),
+            AggregateFunction::Max => Self::Max(None),
+        }
+    }
+
+    /// Feeds one input value. `None` means the row itself is counted, as in `COUNT(*)`.
+    /// `NULL` inputs are ignored by every function except `COUNT(*)`.
+    fn update(&mut self, value: Option<&Value>) -> Result<(), DbError> {
+        let value = match value {
+            None => {
+                if let Self::Count(n) = self {
+                    *n += 1;
+                }
+                return Ok(());
+            }
+            Some(Value::Null) => return Ok(()),
+            Some(value) => value,
+        };
+
+        match self {
+            Self::Count(n) => *n += 1,
+            Self::Sum(total) => {
+                let sum = total.unwrap_or(Total::Integer(0));
+                *total = Some(sum.add(AggregateFunction::Sum, value)?);
+            }
+            Self::Avg { sum, count } => {
+                *sum = sum.add(AggregateFunction::Avg, value)?;
+                *count += 1;
+            }
+            Self::Min(current) => {
+                if current.as_ref().is_none_or(|c| value < c) {
+                    *current = Some(value.clone());
+                }
+            }
+            Self::Max(current) => {
+                if current.as_ref().is_none_or(|c| value > c) {
+                    *current = Some(value.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, DbError> {
+        // Results too large for an `INT` are a `BIGINT`.
+        let to_integer = |n: i64| Ok(i32::try_from(n).map_or(Value::BigInt(n), Value::Integer));
+
+        let to_real = |n: f64| {
+            Real::new(n)
+                .map(Value::Real)
+                .ok_or_else(|| DbError::TypeError("Aggregate result overflows REAL".into()))
+        };
+
+        match self {
+            Self::Count(n) => to_integer(n),
+            Self::Sum(None) => Ok(Value::Null),
+            Self::Sum(Some(Total::Integer(n))) => to_integer(n),
+            Self::Sum(Some(Total::Decimal(n))) => Ok(Value::Decimal(n)),
+            Self::Sum(Some(Total::Real(n))) => to_real(n),
+            Self::Avg { count: 0, .. } => Ok(Value::Null),
+            // The average of integers is an integer, truncated towards zero.
+            Self::Avg {
+                sum: Total::Integer(n),
+                count,
+            } => to_integer(n / count),
+            Self::Avg {
+                sum: Total::Decimal(n),
+                count,
+            } => n
+                .checked_div(Decimal::from_integer(count))
+                .map(Value::Decimal)
+                .ok_or_else(|| DbError::TypeError("AVG overflows DECIMAL".into())),
+            Self::Avg {
+                sum: Total::Real(n),
+                count,
+            } => to_real(n / count as f64),
+            Self::Min(value) | Self::Max(value) => Ok(value.unwrap_or(Value::Null)),
+        }
+    }
+}
+
+/// Where each output column of a grouped query gets its value from.
+enum Output {
+    /// Position within the group key.
+    Key(usize),
+    /// Aggregate function and the relation column it reads (`None` for `COUNT(*)`).
+    Aggregate(AggregateFunction, Option<usize>),
+}
+
+/// Hash-based grouping operator.
+///
+/// ### How it works:
+/// 1. **Key Resolution**: Maps the `GROUP BY` columns to their positions in the relation.
+/// 2. **Output Validation**: Every plain column in the select list must be one of the group
+///    keys, every aggregate argument must resolve to a column.
+/// 3. **Hashing**: Each row's key values are looked up in a `HashMap` to find (or create) its
+///    group, and the row is fed to that group's accumulators. Groups keep first-seen order.
+/// 4. **Finalization**: One output row is emitted per group, with headers taken from the
+///    select list (e.g. `COUNT(*)`, or the alias in `COUNT(*) AS total`).
+///
+/// Without `GROUP BY` columns the whole relation forms a single group, which is emitted even
+/// when there are no rows, so `SELECT COUNT(*) FROM empty` returns `0`.
+pub(crate) fn aggregate(
+    relation: Relation,
+    group_by: &[String],
+    items: &[SelectItem],
+) -> Result<Relation, DbError> {
+    let key_indices = group_by
+        .iter()
+        .map(|name| relation.column_index(name))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut outputs = Vec::new();
+    for item in items {
+        let SelectItem::Expr { expr, .. } = item else {
+            return Err(DbError::ParseError("* cannot be used with GROUP BY".into()));
+        };
+        match expr {
+            Expr::Column(name) => {
+                let idx = relation.column_index(name)?;
+                let key_pos = key_indices.iter().position(|&k| k == idx).ok_or_else(|| {
+                    DbError::ParseError(format!("Column '{}' must appear in GROUP BY", name))
+                })?;
+                outputs.push(Output::Key(key_pos));
+            }
+            Expr::Aggregate { func, arg } => {
+                let arg_idx = match arg.as_deref() {
+                    None => None,
+                    Some(Expr::Column(name)) => Some(relation.column_index(name)?),
+                    Some(other) => {
+                        return Err(DbError::ParseError(format!(
+                            "Unsupported aggregate argument: {}",
+                            other
+                        )));
+                    }
+                };
+                outputs.push(Output::Aggregate(*func, arg_idx));
+            }
+            other => {
+                return Err(DbError::ParseError(format!(
+                    "Expression {} must be an aggregate or a GROUP BY column",
+                    other
+                )));
+            }
+        }
+    }
+
+    let new_accumulators = || -> Vec<Accumulator> {
+        outputs
+            .iter()
+            .filter_map(|o| match o {
+                Output::Aggregate(func, _) => Some(Accumulator::new(*func)),
+                Output::Key(_) => None,
+            })
+            .collect()
+    };
+
+    let mut group_positions: HashMap<Vec<Value>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+    if key_indices.is_empty() {
+        group_positions.insert(Vec::new(), 0);
+        groups.push((Vec::new(), new_accumulators()));
+    }
+
+    for row in &relation.rows {
+        let key: Vec<Value> = key_indices.iter().map(|&i| row[i].clone()).collect();
+        let pos = *group_positions.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, new_accumulators()));
+            groups.len() - 1
+        });
+
+        let mut accumulators = groups[pos].1.iter_mut();
+        for output in &outputs {
+            if let Output::Aggregate(_, arg_idx) = output {
+                let accumulator = accumulators.next().expect("one accumulator per aggregate");
+                accumulator.update(arg_idx.map(|i| &row[i]))?;
+            }
+        }
+    }
+
+    let mut rows = Vec::with_capacity(groups.len());
+    for (key, accumulators) in groups {
+        let mut accumulators = accumulators.into_iter();
+        let mut out = Vec::with_capacity(outputs.len());
+        for output in &outputs {
+            match output {
+                Output::Key(pos) => out.push(key[*pos].clone()),
+                Output::Aggregate(..) => out.push(
+                    accumulators
+                        .next()
+                        .expect("one accumulator per aggregate")
+                        .finish()?,
+                ),
+            }
+        }
+        rows.push(out);
+    }
+
+    Ok(Relation {
+        headers: items.iter().map(SelectItem::header).collect(),
+        rows,
+        table: relation.table,
+    })
+}
@@ -0,0 +1,481 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use crate::{
+    Database, DbError,
+    engine::{
+        Table, Value, aggregate, decimal::DecimalType, eval::check_function, relation::Relation,
+        window,
+    },
+    parser::{
+        AggregateFunction, AlterAction, CommentTarget, ConflictAction, Expr, OnConflict,
+        SelectItem, SelectStatement, Statement, parse,
+    },
+};
+
+/// The columns a query can refer to: headers as they are at run time, without rows, and the
+/// declared type of each column where it is known (not for derived tables).
+struct Scope {
+    relation: Relation,
+    types: Vec<Option<String>>,
+}
+
+impl Scope {
+    fn new(table: &Table, known_types: bool) -> Self {
+        Self {
+            relation: Relation::empty(table),
+            types: table
+                .columns
+                .iter()
+                .map(|c| known_types.then(|| c.data_type.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl Database {
+    /// Checks `statement` against the catalog before it runs: every table, column and
+    /// function it names must exist, and `SUM`/`AVG` must not read a TEXT column.
+    ///
+    /// Only schemas are consulted, so the errors surface before any row is read or written,
+    /// whatever the size of the tables. Checks that depend on the data, like constraint
+    /// violations, still happen during execution.
+    pub fn analyze(&self, statement: &Statement) -> Result<(), DbError> {
+        match statement {
+            Statement::Select(select) | Statement::ExplainAnalyze(select) => {
+                self.analyze_select(select, &[])
+            }
+            Statement::CreateTable {
+                name,
+                if_not_exists,
+                ..
+            } => {
+                if !if_not_exists
+                    && (self.tables.contains_key(name) || self.virtual_tables.contains_key(name))
+                {
+                    return Err(DbError::TableAlreadyExists(name.clone()));
+                }
+                Ok(())
+            }
+            Statement::DropTable { name, if_exists } => match self.writable_table(name) {
+                Err(DbError::TableNotFound(_)) if *if_exists => Ok(()),
+                result => result.map(|_| ()),
+            },
+            Statement::Insert {
+                table_name,
+                rows,
+                on_conflict,
+            } => {
+                let table = self.writable_table(table_name)?;
+                for (i, row) in rows.iter().enumerate() {
+                    check_arity(table, i, row.len())?;
+                }
+                match on_conflict {
+                    Some(on_conflict) => self.analyze_conflict(table, on_conflict),
+                    None => Ok(()),
+                }
+            }
+            Statement::AlterTable { table_name, action } => {
+                let table = self.writable_table(table_name)?;
+                match action {
+                    AlterAction::DropColumn(column) => {
+                        table.column_index(column)?;
+                    }
+                    AlterAction::RenameColumn { from, to } => {
+                        table.column_index(from)?;
+                        if table.columns.iter().any(|c| c.name == *to) {
+                            return Err(DbError::ColumnAlreadyExists(to.clone()));
+                        }
+                    }
+                    AlterAction::RenameTable(to) => {
+                        if self.tables.contains_key(to) || self.virtual_tables.contains_key(to) {
+                            return Err(DbError::TableAlreadyExists(to.clone()));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Statement::CreateIndex {
+                table_name, column, ..
+            } => self
+                .writable_table(table_name)?
+                .column_index(column)
+                .map(|_| ()),
+            Statement::Comment { target, .. } => match target {
+                CommentTarget::Table(table) => self.writable_table(table).map(|_| ()),
+                CommentTarget::Column { table, column } => {
+                    self.writable_table(table)?.column_index(column).map(|_| ())
+                }
+            },
+            Statement::Analyze(Some(table)) => self.writable_table(table).map(|_| ()),
+            Statement::CreateTrigger { table_name, .. } => {
+                self.writable_table(table_name).map(|_| ())
+            }
+            Statement::Explain(inner) => self.analyze(inner),
+            Statement::ExecuteQuery(name) => {
+                let sql = self
+                    .saved_queries
+                    .get(name)
+                    .ok_or_else(|| DbError::QueryNotFound(name.clone()))?;
+                match parse(sql)? {
+                    Statement::Select(select) => self.analyze_select(&select, &[]),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// A stored table that statements may modify.
+    fn writable_table(&self, name: &str) -> Result<&Table, DbError> {
+        if self.virtual_tables.contains_key(name) {
+            return Err(DbError::ReadOnlyTable(name.to_string()));
+        }
+        self.tables
+            .get(name)
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))
+    }
+
+    /// Checks that the `ON CONFLICT` target is a unique column of `table` and that the
+    /// `DO UPDATE SET` assignments name its columns.
+    fn analyze_conflict(&self, table: &Table, on_conflict: &OnConflict) -> Result<(), DbError> {
+        if let Some(target) = &on_conflict.target
+            && !table.indexes.contains_key(&table.column_index(target)?)
+        {
+            return Err(DbError::IndexNotFound(target.clone()));
+        }
+        let ConflictAction::DoUpdate(assignments) = &on_conflict.action else {
+            return Ok(());
+        };
+        let types = table.columns.iter().map(|c| Some(c.data_type.clone()));
+        let scope = Scope {
+            relation: table.conflict_relation(),
+            types: types.clone().chain(types).collect(),
+        };
+        for (column, expr) in assignments {
+            table.column_index(column)?;
+            self.analyze_expr(expr, &[&scope])?;
+        }
+        Ok(())
+    }
+
+    /// Checks a query whose columns resolve against its own tables first and then against
+    /// `outer`, the scopes of the queries it is nested in (innermost last).
+    fn analyze_select(&self, select: &SelectStatement, outer: &[&Scope]) -> Result<(), DbError> {
+        if let Some(subquery) = &select.subquery {
+            self.analyze_select(subquery, &[])?;
+        }
+        let scope = self.select_scope(select)?;
+
+        let mut scopes = outer.to_vec();
+        scopes.push(&scope);
+        let items = select.columns.iter().filter_map(|item| match item {
+            SelectItem::Expr { expr, .. } => Some(expr),
+            SelectItem::Wildcard => None,
+        });
+        for expr in items.clone().chain(&select.where_clause) {
+            self.analyze_expr(expr, &scopes)?;
+        }
+        if select.where_clause.as_ref().is_some_and(Expr::has_window) {
+            return Err(DbError::ParseError(
+                "Window functions are only supported in the select list".into(),
+            ));
+        }
+
+        if select.is_aggregate() {
+            if items.clone().any(Expr::has_window) {
+                return Err(DbError::ParseError(
+                    "Window functions cannot be combined with GROUP BY or aggregates".into(),
+                ));
+            }
+            if items.clone().any(Expr::has_subquery) {
+                return Err(DbError::ParseError(
+                    "Subqueries in the select list of an aggregate query are not supported".into(),
+                ));
+            }
+            // Grouping an empty relation validates the select list and GROUP BY keys, and
+            // yields the headers the ORDER BY keys resolve against.
+            let mut grouped =
+                aggregate::aggregate(scope.relation.clone(), &select.group_by, &select.columns)?;
+            grouped.sort(&select.order_by)
+        } else {
+            let mut select = select.clone();
+            select.resolve_order_aliases();
+            // Computing the windows over no rows checks their keys.
+            let mut relation = scope.relation.clone();
+            window::compute_windows(&mut relation, &mut select.columns, &mut select.order_by)?;
+            relation.sort(&select.order_by)
+        }
+    }
+
+    /// The columns the select list and `WHERE` clause of `select` see: those of its source
+    /// and every joined table.
+    fn select_scope(&self, select: &SelectStatement) -> Result<Scope, DbError> {
+        let (source, known_types) = match &select.subquery {
+            Some(subquery) => {
+                let headers = self.select_headers(subquery)?;
+                let table = Table::derived(select.table_name.clone(), headers, Vec::new())?;
+                (Cow::Owned(table), false)
+            }
+            None => (
+                self.table_schema(&select.table_name, select.table_args.as_deref())?,
+                true,
+            ),
+        };
+        let mut scope = Scope::new(&source, known_types);
+        if !select.joins.is_empty() || select.table_alias.is_some() {
+            let qualifier = select.table_alias.as_deref().unwrap_or(&source.name);
+            scope.relation.qualify(qualifier);
+        }
+        for join in &select.joins {
+            let right_table = self.table_schema(&join.table_name, None)?;
+            let mut right = Scope::new(&right_table, true);
+            right
+                .relation
+                .qualify(join.alias.as_deref().unwrap_or(&right_table.name));
+            if let Some(on) = &join.on {
+                Self::join_keys(&scope.relation, &right.relation, on)?;
+            }
+            scope.relation.headers.extend(right.relation.headers);
+            scope.types.extend(right.types);
+        }
+        Ok(scope)
+    }
+
+    /// The type of each column `select` returns, as far as the schema tells: `None` for
+    /// values of unknown type, like a session variable or `NULL`.
+    pub(crate) fn select_types(
+        &self,
+        select: &SelectStatement,
+    ) -> Result<Vec<Option<String>>, DbError> {
+        let mut scope = self.select_scope(select)?;
+        if let Some(subquery) = &select.subquery {
+            let derived = self.select_types(subquery)?;
+            let len = derived.len();
+            scope.types.splice(..len, derived);
+        }
+
+        let mut types = Vec::new();
+        for item in &select.columns {
+            match item {
+                SelectItem::Wildcard => types.extend(scope.types.iter().cloned()),
+                SelectItem::Expr { expr, .. } => types.push(expr_type(expr, &scope)?),
+            }
+        }
+        Ok(types)
+    }
+
+    fn analyze_expr(&self, expr: &Expr, scopes: &[&Scope]) -> Result<(), DbError> {
+        match expr {
+            Expr::Column(name) => resolve(name, scopes).map(|_| ()),
+            Expr::Literal(_) | Expr::Variable(_) => Ok(()),
+            Expr::Function { name, args } => {
+                check_function(name, args.len())?;
+                args.iter().try_for_each(|a| self.analyze_expr(a, scopes))
+            }
+            Expr::Aggregate { func, arg } => {
+                let Some(arg) = arg else {
+                    return Ok(());
+                };
+                if let Expr::Column(name) = &**arg
+                    && matches!(func, AggregateFunction::Sum | AggregateFunction::Avg)
+                    && resolve(name, scopes)? == Some("TEXT")
+                {
+                    return Err(DbError::TypeError(format!(
+                        "{} expects numeric values, got TEXT column '{}'",
+                        func, name
+                    )));
+                }
+                self.analyze_expr(arg, scopes)
+            }
+            Expr::Binary { left, op, right } => {
+                for operand in [left, right] {
+                    let text = match &**operand {
+                        Expr::Column(name) if resolve(name, scopes)? == Some("TEXT") => {
+                            Some(format!("TEXT column '{}'", name))
+                        }
+                        Expr::Literal(Value::Text(_)) => Some(format!("TEXT {}", operand)),
+                        Expr::Cast { data_type, .. } if data_type == "TEXT" => {
+                            Some(operand.to_string())
+                        }
+                        _ => None,
+                    };
+                    if let Some(text) = text.filter(|_| op.is_arithmetic()) {
+                        return Err(DbError::TypeError(format!(
+                            "{} expects numeric values, got {}",
+                            op, text
+                        )));
+                    }
+                }
+                self.analyze_expr(left, scopes)?;
+                self.analyze_expr(right, scopes)
+            }
+            Expr::Not(inner)
+            | Expr::Negate(inner)
+            | Expr::IsNull { expr: inner, .. }
+            | Expr::Cast { expr: inner, .. } => self.analyze_expr(inner, scopes),
+            Expr::InList { expr, list, .. } => {
+                self.analyze_expr(expr, scopes)?;
+                list.iter().try_for_each(|e| self.analyze_expr(e, scopes))
+            }
+            Expr::Exists(subquery) => self.analyze_select(subquery, scopes),
+            // The window stage resolves the keys, see `analyze_select`.
+            Expr::Window { .. } => Ok(()),
+            Expr::Subquery(subquery) => {
+                let wildcard = subquery
+                    .columns
+                    .iter()
+                    .any(|c| matches!(c, SelectItem::Wildcard));
+                let columns = match wildcard {
+                    true => self.select_headers(subquery)?.len(),
+                    false => subquery.columns.len(),
+                };
+                if columns != 1 {
+                    return Err(DbError::ParseError(format!(
+                        "Scalar subquery must return one column, got {}",
+                        columns
+                    )));
+                }
+                self.analyze_select(subquery, scopes)
+            }
+        }
+    }
+}
+
+/// Rejects row `row` of an `INSERT` into `table` unless it has one value per column, naming
+/// the columns left without a value.
+fn check_arity(table: &Table, row: usize, values: usize) -> Result<(), DbError> {
+    let columns = &table.columns;
+    let problem = match values.cmp(&columns.len()) {
+        Ordering::Equal => return Ok(()),
+        Ordering::Less => {
+            let missing: Vec<&str> = columns[values..].iter().map(|c| c.name.as_str()).collect();
+            format!("no value for {}", missing.join(", "))
+        }
+        Ordering::Greater => match values - columns.len() {
+            1 => "1 value too many".to_string(),
+            extra => format!("{} values too many", extra),
+        },
+    };
+    let plural = if values == 1 { "" } else { "s" };
+    Err(DbError::ParseError(format!(
+        "Row {} of the INSERT into '{}' has {} value{} for {} columns: {}",
+        row + 1,
+        table.name,
+        values,
+        plural,
+        columns.len(),
+        problem
+    )))
+}
+
+/// The type of the values `expr` yields. Comparisons and other predicates yield `0`/`1`.
+fn expr_type(expr: &Expr, scope: &Scope) -> Result<Option<String>, DbError> {
+    let int = || Ok(Some("INT".to_string()));
+    match expr {
+        Expr::Column(name) => Ok(resolve(name, &[scope])?.map(String::from)),
+        Expr::Literal(Value::Integer(_)) => int(),
+        Expr::Literal(Value::BigInt(_)) => Ok(Some("BIGINT".into())),
+        Expr::Literal(Value::Real(_)) => Ok(Some("REAL".into())),
+        Expr::Literal(Value::Decimal(_)) => Ok(Some("DECIMAL".into())),
+        Expr::Literal(Value::Date(_)) => Ok(Some("DATE".into())),
+        Expr::Literal(Value::Timestamp(_)) => Ok(Some("TIMESTAMP".into())),
+        Expr::Literal(Value::Text(_)) => Ok(Some("TEXT".into())),
+        Expr::Literal(Value::Null) | Expr::Variable(_) => Ok(None),
+        Expr::Function { name, args } => Ok(match name.as_str() {
+            "CURRENT_TIMESTAMP" | "NOW" => Some("TIMESTAMP".into()),
+            "CURRENT_DATE" | "DATE" => Some("DATE".into()),
+            "LENGTH" | "YEAR" | "MONTH" | "DAY" => Some("INT".into()),
+            // Any argument can be the one returned, so they must share a type.
+            "COALESCE" => {
+                let mut common: Option<String> = None;
+                for arg in args {
+                    let Some(data_type) = expr_type(arg, scope)? else {
+                        continue;
+                    };
+                    common = Some(match common {
+                        None => data_type,
+                        Some(t) => common_type(&t, &data_type).ok_or_else(|| {
+                            DbError::TypeError(format!(
+                                "COALESCE arguments have no common type: {} and {}",
+                                t, data_type
+                            ))
+                        })?,
+                    });
+                }
+                common
+            }
+            "NULLIF" => expr_type(&args[0], scope)?,
+            _ => Some("TEXT".into()),
+        }),
+        Expr::Cast { data_type, .. } => Ok(Some(data_type.clone())),
+        Expr::Aggregate { func, arg } => match (func, arg) {
+            (AggregateFunction::Min | AggregateFunction::Max, Some(arg)) => expr_type(arg, scope),
+            (AggregateFunction::Sum | AggregateFunction::Avg, Some(arg)) => {
+                match expr_type(arg, scope)?.as_deref() {
+                    Some("REAL") => Ok(Some("REAL".into())),
+                    Some(t) if is_decimal(t) => Ok(Some("DECIMAL".into())),
+                    _ => int(),
+                }
+            }
+            _ => int(),
+        },
+        Expr::Binary { left, op, right } if op.is_arithmetic() => {
+            let (left, right) = (expr_type(left, scope)?, expr_type(right, scope)?);
+            let data_type = match (left.as_deref(), right.as_deref()) {
+                // The difference of two dates or timestamps is a count of days or seconds.
+                (Some("DATE" | "TIMESTAMP"), Some("DATE" | "TIMESTAMP")) => "INT",
+                (Some(t @ ("DATE" | "TIMESTAMP")), _) | (_, Some(t @ ("DATE" | "TIMESTAMP"))) => t,
+                (Some("REAL"), _) | (_, Some("REAL")) => "REAL",
+                (Some(t), _) | (_, Some(t)) if is_decimal(t) => "DECIMAL",
+                (Some("BIGINT"), _) | (_, Some("BIGINT")) => "BIGINT",
+                _ => "INT",
+            };
+            Ok(Some(data_type.into()))
+        }
+        Expr::Negate(inner) => expr_type(inner, scope),
+        Expr::Binary { .. }
+        | Expr::Not(_)
+        | Expr::IsNull { .. }
+        | Expr::InList { .. }
+        | Expr::Exists(_) => int(),
+        // Resolving the subquery's own columns needs the catalog, not just this scope.
+        Expr::Subquery(_) => Ok(None),
+        Expr::Window { .. } => int(),
+    }
+}
+
+/// The type holding values of both `a` and `b`: either one when they are the same, else the
+/// wider number, the way arithmetic widens. `None` when only one of them is a number.
+fn common_type(a: &str, b: &str) -> Option<String> {
+    let rank = |t: &str| match t {
+        "INT" => Some(0),
+        "BIGINT" => Some(1),
+        t if is_decimal(t) => Some(2),
+        "REAL" => Some(3),
+        _ => None,
+    };
+    match (rank(a), rank(b)) {
+        _ if a == b => Some(a.to_string()),
+        (Some(x), Some(y)) if x == 2 && y == 2 => Some("DECIMAL".into()),
+        (Some(x), Some(y)) => Some(if x >= y { a } else { b }.to_string()),
+        _ => None,
+    }
+}
+
+/// `DECIMAL`, with or without a precision and scale.
+fn is_decimal(data_type: &str) -> bool {
+    DecimalType::parse(data_type).is_some()
+}
+
+/// Resolves `name` in the innermost scope that has it, returning its declared type.
+fn resolve<'a>(name: &str, scopes: &[&'a Scope]) -> Result<Option<&'a str>, DbError> {
+    for scope in scopes.iter().rev() {
+        match scope.relation.column_index(name) {
+            Ok(i) => return Ok(scope.types[i].as_deref()),
+            Err(DbError::ColumnNotFound(_)) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(DbError::ColumnNotFound(name.to_string()))
+}
@@ -0,0 +1,313 @@
+//! The binary format of `COPY table FROM STDIN` style bulk loads, which ship rows without
+//! SQL text to parse.
+//!
+//! A stream is a sequence of rows. Each row is its length in bytes as a big-endian `u32`
+//! followed by its fields, and each field is a tag byte and the value:
+//!
+//! - `0`: `NULL`, nothing follows;
+//! - `1`: an integer, as a big-endian `i32`;
+//! - `2`: text, its length in bytes as a big-endian `u32` and the UTF-8 bytes;
+//! - `3`: a real, as a big-endian IEEE 754 `f64`;
+//! - `4`: a date, as days since 1970-01-01 in a big-endian `i32`;
+//! - `5`: a timestamp, as seconds since 1970-01-01 00:00:00 in a big-endian `i64`;
+//! - `6`: a 64-bit integer, as a big-endian `i64`;
+//! - `7`: a decimal, as its digits without the point in a big-endian `i128` and the number of
+//!   places after the point in a `u8`.
+
+use crate::{
+    Database, DbError,
+    engine::{Date, Decimal, Real, Timestamp, Value, session::Session, triggers::TriggerEvent},
+};
+
+const TAG_NULL: u8 = 0;
+const TAG_INTEGER: u8 = 1;
+const TAG_TEXT: u8 = 2;
+const TAG_REAL: u8 = 3;
+const TAG_DATE: u8 = 4;
+const TAG_TIMESTAMP: u8 = 5;
+const TAG_BIGINT: u8 = 6;
+const TAG_DECIMAL: u8 = 7;
+
+/// Rows larger than this are refused before they are buffered.
+const MAX_ROW_BYTES: usize = 16 << 20;
+
+/// Appends `row` to `out` in the copy format.
+pub fn encode_row(row: &[Value], out: &mut Vec<u8>) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    for value in row {
+        match value {
+            Value::Null => out.push(TAG_NULL),
+            Value::Integer(n) => {
+                out.push(TAG_INTEGER);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+            Value::BigInt(n) => {
+                out.push(TAG_BIGINT);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+            Value::Real(r) => {
+                out.push(TAG_REAL);
+                out.extend_from_slice(&r.get().to_be_bytes());
+            }
+            Value::Decimal(d) => {
+                out.push(TAG_DECIMAL);
+                out.extend_from_slice(&d.units().to_be_bytes());
+                out.push(d.scale() as u8);
+            }
+            Value::Date(d) => {
+                out.push(TAG_DATE);
+                out.extend_from_slice(&(d.days() as i32).to_be_bytes());
+            }
+            Value::Timestamp(t) => {
+                out.push(TAG_TIMESTAMP);
+                out.extend_from_slice(&t.secs().to_be_bytes());
+            }
+            Value::Text(s) => {
+                out.push(TAG_TEXT);
+                out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }
+        }
+    }
+    let len = (out.len() - start - 4) as u32;
+    out[start..start + 4].copy_from_slice(&len.to_be_bytes());
+}
+
+/// Decodes a copy stream fed in chunks of any size, as they come off the network.
+#[derive(Debug, Default)]
+pub struct CopyDecoder {
+    /// Bytes of the row not complete yet.
+    pending: Vec<u8>,
+    rows: Vec<Vec<Value>>,
+    /// Approximate size of the decoded values, as `Limits::max_bytes` counts it.
+    bytes: usize,
+    /// How many rows and bytes the target database can still take, if it is limited.
+    max_rows: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl CopyDecoder {
+    /// Decodes the rows completed by `bytes` and keeps the rest for the next chunk.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), DbError> {
+        self.pending.extend_from_slice(bytes);
+        let mut pos = 0;
+        while let Some(header) = self.pending.get(pos..pos + 4) {
+            let len = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+            if len > MAX_ROW_BYTES {
+                return Err(self.error(format!("is {} bytes long", len)));
+            }
+            let Some(body) = self.pending.get(pos + 4..pos + 4 + len) else {
+                break;
+            };
+            let row = decode_fields(body).map_err(|e| self.error(e))?;
+            self.bytes += row.iter().map(Value::approximate_size).sum::<usize>();
+            self.rows.push(row);
+            if self.max_rows.is_some_and(|max| self.rows.len() > max)
+                || self.max_bytes.is_some_and(|max| self.bytes > max)
+            {
+                return Err(DbError::StorageFull(format!(
+                    "COPY row {} does not fit in the database",
+                    self.rows.len()
+                )));
+            }
+            pos += 4 + len;
+        }
+        self.pending.drain(..pos);
+        Ok(())
+    }
+
+    /// The decoded rows, once the stream has ended. Fails if it ended inside a row.
+    pub fn finish(self) -> Result<Vec<Vec<Value>>, DbError> {
+        if !self.pending.is_empty() {
+            return Err(self.error("is cut short".into()));
+        }
+        Ok(self.rows)
+    }
+
+    fn error(&self, problem: String) -> DbError {
+        DbError::ParseError(format!("COPY row {} {}", self.rows.len() + 1, problem))
+    }
+}
+
+fn decode_fields(mut body: &[u8]) -> Result<Vec<Value>, String> {
+    fn take<'a>(body: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+        if body.len() < n {
+            return Err("is cut short".into());
+        }
+        let (taken, rest) = body.split_at(n);
+        *body = rest;
+        Ok(taken)
+    }
+
+    let mut row = Vec::new();
+    while let Some((&tag, rest)) = body.split_first() {
+        body = rest;
+        let value = match tag {
+            TAG_NULL => Value::Null,
+            TAG_INTEGER => {
+                Value::Integer(i32::from_be_bytes(take(&mut body, 4)?.try_into().unwrap()))
+            }
+            TAG_TEXT => {
+                let len = u32::from_be_bytes(take(&mut body, 4)?.try_into().unwrap()) as usize;
+                let bytes = take(&mut body, len)?;
+                let text = std::str::from_utf8(bytes).map_err(|_| "has invalid UTF-8")?;
+                Value::Text(text.to_string())
+            }
+            TAG_BIGINT => {
+                Value::BigInt(i64::from_be_bytes(take(&mut body, 8)?.try_into().unwrap()))
+            }
+            TAG_REAL => {
+                let bits = f64::from_be_bytes(take(&mut body, 8)?.try_into().unwrap());
+                Value::Real(Real::new(bits).ok_or("has a real that is not finite")?)
+            }
+            TAG_DECIMAL => {
+                let units = i128::from_be_bytes(take(&mut body, 16)?.try_into().unwrap());
+                let scale = take(&mut body, 1)?[0];
+                Value::Decimal(
+                    Decimal::new(units, scale.into()).ok_or("has a decimal out of range")?,
+                )
+            }
+            TAG_DATE => {
+                let days = i32::from_be_bytes(take(&mut body, 4)?.try_into().unwrap());
+                Value::Date(Date::from_days(days as i64).ok_or("has a date out of range")?)
+            }
+            TAG_TIMESTAMP => {
+                let secs = i64::from_be_bytes(take(&mut body, 8)?.try_into().unwrap());
+                Value::Timestamp(Timestamp::from_secs(secs).ok_or("has a timestamp out of range")?)
+            }
+            other => return Err(format!("has a field of unknown type {}", other)),
+        };
+        row.push(value);
+    }
+    Ok(row)
+}
+
+impl Database {
+    /// A decoder for a copy stream into this database, refusing the stream as soon as it
+    /// holds more rows or bytes than the database's limits leave room for.
+    pub fn copy_decoder(&self) -> CopyDecoder {
+        CopyDecoder {
+            max_rows: self
+                .limits
+                .max_rows
+                .map(|max| max.saturating_sub(self.total_rows())),
+            max_bytes: self
+                .limits
+                .max_bytes
+                .map(|max| max.saturating_sub(self.data_size())),
+            ..CopyDecoder::default()
+        }
+    }
+
+    /// Bulk-loads decoded copy rows into `table_name` on behalf of `user`. Like a multi-row
+    /// `INSERT`, all of the rows are inserted or, if one is rejected, none, and the load fires
+    /// the `INSERT` triggers of the table.
+    pub fn copy_rows(
+        &mut self,
+        user: &str,
+        table_name: &str,
+        rows: Vec<Vec<Value>>,
+    ) -> Result<usize, DbError> {
+        let sql = format!("COPY {} FROM STDIN", table_name);
+        let id = self.query_log.start(user, sql);
+        let copied =
+            self.check_unlocked(None, None)
+                .and_then(|()| match self.has_triggers(table_name) {
+                    true => self.atomically(|db| {
+                        let copied = db.insert_copied(table_name, rows)?;
+                        if copied > 0 {
+                            let session = &mut Session::new(user);
+                            db.fire_triggers(table_name, &[TriggerEvent::Insert], session)?;
+                        }
+                        Ok(copied)
+                    }),
+                    false => self.insert_copied(table_name, rows),
+                });
+        self.query_log
+            .finish(id, copied.as_ref().err().map(DbError::to_string));
+        copied
+    }
+
+    fn insert_copied(&mut self, table_name: &str, rows: Vec<Vec<Value>>) -> Result<usize, DbError> {
+        if self.virtual_tables.contains_key(table_name) {
+            return Err(DbError::ReadOnlyTable(table_name.to_string()));
+        }
+        self.check_capacity(&rows)?;
+        self.check_references(table_name, &rows)?;
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| DbError::TableNotFound(table_name.to_string()))?;
+        let copied = table.insert_rows(rows)?;
+        self.record_index_writes(table_name, copied);
+        if copied > 0 {
+            self.mark_dirty();
+        }
+        Ok(copied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_copy_rows() {
+        let rows = vec![
+            vec![Value::Integer(1), Value::Text("ada".into()), Value::Null],
+            vec![Value::BigInt(-2), Value::Null, Value::Null],
+            vec![
+                Value::Real(Real::new(0.5).unwrap()),
+                Value::Null,
+                Value::Date(Date::parse("2024-01-31").unwrap()),
+            ],
+        ];
+        let mut stream = Vec::new();
+        for row in &rows {
+            encode_row(row, &mut stream);
+        }
+        // Chunks split rows and fields anywhere.
+        let mut decoder = CopyDecoder::default();
+        for chunk in stream.chunks(3) {
+            decoder.feed(chunk).unwrap();
+        }
+        assert_eq!(decoder.finish().unwrap(), rows);
+
+        let mut decoder = CopyDecoder::default();
+        decoder.feed(&stream[..stream.len() - 1]).unwrap();
+        assert_eq!(
+            decoder.finish().unwrap_err().to_string(),
+            "Parsing error: COPY row 3 is cut short"
+        );
+
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id REAL PRIMARY, name TEXT, day DATE)").unwrap())
+            .unwrap();
+        assert_eq!(db.copy_rows("martin", "t", rows.clone()).unwrap(), 3);
+        assert!(db.is_dirty());
+        // A duplicate key rejects the whole load.
+        let more = vec![
+            vec![Value::Integer(3), Value::Null, Value::Null],
+            rows[0].clone(),
+        ];
+        assert!(matches!(
+            db.copy_rows("martin", "t", more),
+            Err(DbError::UniqueViolation(_))
+        ));
+        assert_eq!(db.tables["t"].rows, rows);
+        assert!(matches!(
+            db.copy_rows("martin", "missing", vec![]),
+            Err(DbError::TableNotFound(_))
+        ));
+
+        // Streams are refused as soon as they outgrow the limits, before the load.
+        db.limits.max_rows = Some(4);
+        let mut decoder = db.copy_decoder();
+        assert!(matches!(
+            decoder.feed(&stream),
+            Err(DbError::StorageFull(_))
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Value, schedules::unix_now};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A calendar date from 0000-01-01 to 9999-12-31, kept as days since 1970-01-01.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Date(i32);
+
+/// A date and time of day to the second, without a time zone, kept as seconds since
+/// 1970-01-01 00:00:00. Spans the same years as `Date`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp(i64);
+
+impl Date {
+    /// `None` outside of the years 0 to 9999.
+    pub fn from_days(days: i64) -> Option<Date> {
+        let range = days_from_civil(0, 1, 1)..=days_from_civil(9999, 12, 31);
+        range.contains(&days).then_some(Date(days as i32))
+    }
+
+    pub fn days(self) -> i64 {
+        self.0 as i64
+    }
+
+    /// Parses `YYYY-MM-DD`.
+    pub fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.splitn(3, '-');
+        let year = number(parts.next()?, 4)?;
+        let month = number(parts.next()?, 2)?;
+        let day = number(parts.next()?, 2)?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Date::from_days(days_from_civil(year, month as u32, day as u32))
+    }
+
+    pub fn at_midnight(self) -> Timestamp {
+        Timestamp(self.days() * SECS_PER_DAY)
+    }
+
+    /// The (year, month, day) of the date.
+    pub fn civil(self) -> (i64, u32, u32) {
+        civil_from_days(self.days())
+    }
+}
+
+impl Timestamp {
+    /// `None` outside of the years 0 to 9999.
+    pub fn from_secs(secs: i64) -> Option<Timestamp> {
+        Date::from_days(secs.div_euclid(SECS_PER_DAY))?;
+        Some(Timestamp(secs))
+    }
+
+    pub fn secs(self) -> i64 {
+        self.0
+    }
+
+    /// The current time.
+    pub fn now() -> Timestamp {
+        Timestamp(unix_now() as i64)
+    }
+
+    /// Parses `YYYY-MM-DD HH:MM:SS`, with a space or a `T` between the date and the time, or
+    /// a date alone for its midnight.
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        let Some((date, time)) = text.split_once([' ', 'T']) else {
+            return Date::parse(text).map(Date::at_midnight);
+        };
+        let date = Date::parse(date)?;
+        let mut parts = time.splitn(3, ':');
+        let hours = number(parts.next()?, 2)?;
+        let minutes = number(parts.next()?, 2)?;
+        let seconds = number(parts.next()?, 2)?;
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+        Some(Timestamp(
+            date.at_midnight().secs() + hours * 3600 + minutes * 60 + seconds,
+        ))
+    }
+
+    /// The day the timestamp falls on.
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(SECS_PER_DAY) as i32)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.civil();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_timestamp(self.0))
+    }
+}
+
+/// Written like the text form, so `Value::Date` reads `Date(2024-01-31)`.
+impl fmt::Debug for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Reads text as a `Value::Date` if it is a date, else as a `Value::Timestamp`.
+pub(crate) fn parse_temporal(text: &str) -> Option<Value> {
+    match Date::parse(text) {
+        Some(date) => Some(Value::Date(date)),
+        None => Timestamp::parse(text).map(Value::Timestamp),
+    }
+}
+
+/// A field of exactly `digits` ASCII digits.
+fn number(text: &str, digits: usize) -> Option<i64> {
+    if text.len() != digits || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Formats Unix seconds as a UTC `YYYY-MM-DD HH:MM:SS` string.
+pub fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) civil date.
+/// Uses Howard Hinnant's `civil_from_days` algorithm for the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) civil date into days since 1970-01-01, the inverse of
+/// `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_767_225_599), "2025-12-31 23:59:59");
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        for text in [
+            "1970-01-01",
+            "2000-02-29",
+            "0000-01-01",
+            "9999-12-31",
+            "1969-12-31",
+        ] {
+            assert_eq!(Date::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(Date::parse("1970-01-02").unwrap().days(), 1);
+        for text in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-1-01",
+            "24-01-01",
+            "2024-01-01x",
+        ] {
+            assert_eq!(Date::parse(text), None, "{}", text);
+        }
+
+        let ts = Timestamp::parse("2000-02-29T23:59:59").unwrap();
+        assert_eq!(ts.secs(), 951_868_799);
+        assert_eq!(ts.to_string(), "2000-02-29 23:59:59");
+        assert_eq!(ts.date(), Date::parse("2000-02-29").unwrap());
+        assert_eq!(Timestamp::parse("1970-01-02"), Timestamp::from_secs(86_400));
+        assert_eq!(Timestamp::parse("2000-01-01 24:00:00"), None);
+        assert_eq!(Date::from_days(i32::MAX as i64), None);
+    }
+
+    #[test]
+    fn test_date_queries() {
+        use crate::{Database, engine::ExecutionResult, parser::parse};
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE visits (day DATE PRIMARY, at TIMESTAMP, hits INT)",
+            "INSERT INTO visits VALUES ('2024-02-28', '2024-02-28T09:30:00', 3), \
+             ('2024-01-31', '2024-01-31', 5), ('2024-03-01', '2024-03-01T00:00:00', 1)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let mut query = |sql: &str| match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { rows, .. } => rows,
+            ExecutionResult::Message(m) => panic!("expected rows, got {}", m),
+        };
+        let date = |text: &str| Value::Date(Date::parse(text).unwrap());
+        let text = |text: &str| Value::Text(text.into());
+
+        assert_eq!(
+            query("SELECT day FROM visits WHERE day >= '2024-02-01' ORDER BY day DESC"),
+            [[date("2024-03-01")], [date("2024-02-28")]]
+        );
+        // The primary key index answers text spelling a date.
+        assert_eq!(
+            query("SELECT hits FROM visits WHERE day = '2024-01-31'"),
+            [[Value::Integer(5)]]
+        );
+        assert_eq!(
+            query(
+                "SELECT day + 1, day - DATE '2024-01-01', CAST(at AS TEXT), MONTH(at) \
+                 FROM visits WHERE at > day"
+            ),
+            [[
+                date("2024-02-29"),
+                Value::Integer(58),
+                text("2024-02-28 09:30:00"),
+                Value::Integer(2)
+            ]]
+        );
+        assert_eq!(
+            query("SELECT at FROM visits WHERE at = day ORDER BY at LIMIT 1"),
+            [[Value::Timestamp(Timestamp::parse("2024-01-31").unwrap())]]
+        );
+        assert!(matches!(
+            query("SELECT NOW(), CURRENT_DATE FROM visits LIMIT 1")[0][..],
+            [Value::Timestamp(_), Value::Date(_)]
+        ));
+
+        for sql in [
+            "INSERT INTO visits VALUES ('2024-02-30', '2024-02-30', 0)",
+            "SELECT day FROM visits WHERE day < 'yesterday'",
+            "SELECT DATE '2024-1-1' FROM visits",
+        ] {
+            let result = parse(sql).map(|statement| db.execute(statement));
+            assert!(!matches!(result, Ok(Ok(_))), "{}", sql);
+        }
+    }
+}
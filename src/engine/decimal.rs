@@ -0,0 +1,391 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Real, Value};
+
+/// The most digits a decimal holds, as in `DECIMAL(38, s)`.
+pub const MAX_PRECISION: u32 = 38;
+
+/// Digits added after the point when dividing, beyond those of the operands.
+const DIVISION_SCALE: u32 = 6;
+
+/// An exact decimal number, `units` divided by 10 to the power `scale`, such as `19.99`
+/// stored as 1999 with a scale of 2. It holds at most `MAX_PRECISION` digits. The scale is
+/// kept, so `1.50` stays `1.50`, but `1.5` and `1.50` are equal.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+fn power_of_ten(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+impl Decimal {
+    /// `None` if the number has more than `MAX_PRECISION` digits or places.
+    pub fn new(units: i128, scale: u32) -> Option<Decimal> {
+        let limit = power_of_ten(MAX_PRECISION)?;
+        (scale <= MAX_PRECISION && units.unsigned_abs() < limit.unsigned_abs())
+            .then_some(Decimal { units, scale })
+    }
+
+    pub fn from_integer(n: i64) -> Decimal {
+        Decimal {
+            units: n.into(),
+            scale: 0,
+        }
+    }
+
+    /// The decimal written the way the real prints, so `0.1` becomes exactly `0.1`.
+    pub fn from_real(real: Real) -> Option<Decimal> {
+        Decimal::parse(&format!("{}", real.get()))
+    }
+
+    /// Parses digits with an optional sign and decimal point, such as `-12.50`.
+    pub fn parse(text: &str) -> Option<Decimal> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if whole.len() + fraction.len() == 0 || !is_digits(whole) || !is_digits(fraction) {
+            return None;
+        }
+        let mut units: i128 = 0;
+        for digit in whole.bytes().chain(fraction.bytes()) {
+            units = units
+                .checked_mul(10)?
+                .checked_add(i128::from(digit - b'0'))?;
+        }
+        let units = if negative { -units } else { units };
+        Decimal::new(units, u32::try_from(fraction.len()).ok()?)
+    }
+
+    /// The decimal a number converts to, or text that is one.
+    pub fn from_value(value: &Value) -> Option<Decimal> {
+        match value {
+            Value::Integer(n) => Some(Decimal::from_integer((*n).into())),
+            Value::BigInt(n) => Some(Decimal::from_integer(*n)),
+            Value::Real(r) => Decimal::from_real(*r),
+            Value::Decimal(d) => Some(*d),
+            Value::Text(s) => Decimal::parse(s.trim()),
+            _ => None,
+        }
+    }
+
+    /// The digits without the point, so `19.99` gives 1999.
+    pub fn units(self) -> i128 {
+        self.units
+    }
+
+    pub fn scale(self) -> u32 {
+        self.scale
+    }
+
+    /// The number rounded to `scale` places, halves away from zero, or padded with zeros.
+    /// `None` if it no longer fits.
+    pub fn rescale(self, scale: u32) -> Option<Decimal> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Some(self),
+            Ordering::Greater => {
+                let factor = power_of_ten(scale - self.scale)?;
+                Decimal::new(self.units.checked_mul(factor)?, scale)
+            }
+            Ordering::Less => {
+                let factor = power_of_ten(self.scale - scale)?;
+                let (quotient, remainder) = (self.units / factor, self.units % factor);
+                let round = remainder.unsigned_abs() * 2 >= factor.unsigned_abs();
+                let units = quotient + if round { self.units.signum() } else { 0 };
+                Decimal::new(units, scale)
+            }
+        }
+    }
+
+    /// The whole part, truncated toward zero.
+    pub fn trunc(self) -> i128 {
+        self.units / 10i128.pow(self.scale)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.units == 0
+    }
+
+    /// The nearest `f64`.
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+
+    /// Compares with an integer exactly.
+    pub fn cmp_integer(self, n: i64) -> Ordering {
+        let fraction = self.units % 10i128.pow(self.scale);
+        self.trunc().cmp(&n.into()).then(fraction.cmp(&0))
+    }
+
+    /// Compares with a real as reals: the nearest `f64` is compared, so a decimal closer to a
+    /// real than any other `f64` equals it.
+    pub fn cmp_real(self, real: Real) -> Ordering {
+        self.to_f64().total_cmp(&real.get())
+    }
+
+    /// Both numbers with the larger of their scales, `None` if one no longer fits.
+    fn aligned(self, other: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((
+            self.rescale(scale)?.units,
+            other.rescale(scale)?.units,
+            scale,
+        ))
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Decimal::new(a.checked_add(b)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Decimal::new(a.checked_sub(b)?, scale)
+    }
+
+    /// The product has the places of both operands, rounded to `MAX_PRECISION` places.
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let units = self.units.checked_mul(other.units)?;
+        let scale = self.scale + other.scale;
+        if scale <= MAX_PRECISION {
+            return Decimal::new(units, scale);
+        }
+        let exact = Decimal { units, scale };
+        exact.rescale(MAX_PRECISION)
+    }
+
+    /// The quotient has `DIVISION_SCALE` more places than the more precise operand, rounded.
+    /// `None` for division by zero.
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let scale = (self.scale.max(other.scale) + DIVISION_SCALE).min(MAX_PRECISION);
+        // self / other * 10^scale, computed with one more place to round it.
+        let shift = power_of_ten(scale + 1 + other.scale - self.scale)?;
+        let tenfold = self.units.checked_mul(shift)? / other.units;
+        let round = tenfold % 10;
+        let units = tenfold / 10 + if round.abs() >= 5 { round.signum() } else { 0 };
+        Decimal::new(units, scale)
+    }
+
+    /// The remainder of truncating division, with the sign of `self`.
+    pub fn checked_rem(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Decimal::new(a.checked_rem(b)?, scale)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Whole parts first, so values with large scales aren't scaled past `i128`.
+        let scale = self.scale.max(other.scale);
+        let fraction = |d: &Decimal| (d.units % 10i128.pow(d.scale)) * 10i128.pow(scale - d.scale);
+        self.trunc()
+            .cmp(&other.trunc())
+            .then_with(|| fraction(self).cmp(&fraction(other)))
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+/// Hashes the number without its trailing zeros, so `1.5` and `1.50` hash alike.
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (mut units, mut scale) = (self.units, self.scale);
+        while scale > 0 && units % 10 == 0 {
+            units /= 10;
+            scale -= 1;
+        }
+        (units, scale).hash(state)
+    }
+}
+
+/// Written out with all its places, so `Value::Decimal` reads `Decimal(19.90)`.
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if self.units < 0 { "-" } else { "" };
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, whole)
+        } else {
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+impl From<Decimal> for String {
+    fn from(decimal: Decimal) -> String {
+        decimal.to_string()
+    }
+}
+
+impl TryFrom<String> for Decimal {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Decimal, String> {
+        Decimal::parse(&text).ok_or_else(|| format!("invalid decimal '{}'", text))
+    }
+}
+
+/// The precision and scale of a `DECIMAL` column or cast, as in `DECIMAL(10,2)`. A bare
+/// `DECIMAL` keeps the scale of each value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalType {
+    pub precision: u32,
+    pub scale: Option<u32>,
+}
+
+impl DecimalType {
+    /// Reads a type as stored in `Column::data_type`: `DECIMAL` or `DECIMAL(p,s)`. `None`
+    /// for other types.
+    pub fn parse(data_type: &str) -> Option<DecimalType> {
+        let rest = data_type.strip_prefix("DECIMAL")?;
+        if rest.is_empty() {
+            return Some(DecimalType {
+                precision: MAX_PRECISION,
+                scale: None,
+            });
+        }
+        let (precision, scale) = rest.strip_prefix('(')?.strip_suffix(')')?.split_once(',')?;
+        Some(DecimalType {
+            precision: precision.parse().ok()?,
+            scale: Some(scale.parse().ok()?),
+        })
+    }
+
+    /// `decimal` rounded to this type's scale, or `None` if it has too many digits before
+    /// the point.
+    pub fn fit(self, decimal: Decimal) -> Option<Decimal> {
+        let decimal = match self.scale {
+            Some(scale) => decimal.rescale(scale)?,
+            None => decimal,
+        };
+        let limit = power_of_ten(self.precision)?;
+        (decimal.units.unsigned_abs() < limit.unsigned_abs()).then_some(decimal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, engine::ExecutionResult, parser::parse};
+
+    fn query(db: &mut Database, sql: &str) -> (Vec<String>, Vec<Vec<Value>>) {
+        match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { rows, types, .. } => (types, rows),
+            ExecutionResult::Message(m) => panic!("expected rows, got {}", m),
+        }
+    }
+
+    #[test]
+    fn test_decimal() {
+        let d = |s: &str| Decimal::parse(s).unwrap();
+        assert_eq!(d("-12.50").to_string(), "-12.50");
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d(".5").to_string(), "0.5");
+        for text in ["", ".", "1e5", "1.2.3", "--1", "1 000"] {
+            assert!(Decimal::parse(text).is_none(), "{}", text);
+        }
+        assert!(Decimal::parse(&"9".repeat(39)).is_none());
+        assert_eq!(d("1.5"), d("1.50"));
+        assert!(d("-1.5") < d("-1.25"));
+        assert_eq!(d("2.345").rescale(2), Some(d("2.35")));
+        assert_eq!(d("-2.345").rescale(2), Some(d("-2.35")));
+        assert_eq!(d("10").checked_div(d("3")).unwrap().to_string(), "3.333333");
+        assert_eq!(
+            d("-2").checked_div(d("3")).unwrap().to_string(),
+            "-0.666667"
+        );
+        assert_eq!(d("1.10").checked_mul(d("3")).unwrap().to_string(), "3.30");
+        assert_eq!(d("2.5").cmp_integer(2), Ordering::Greater);
+        assert_eq!(d("-2.5").cmp_integer(-2), Ordering::Less);
+
+        let money = DecimalType::parse("DECIMAL(5,2)").unwrap();
+        assert_eq!(money.fit(d("999.994")), Some(d("999.99")));
+        assert_eq!(money.fit(d("999.995")), None);
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE payments (id INT PRIMARY, amount NUMERIC(8, 2), rate DECIMAL)",
+            "INSERT INTO payments VALUES (1, 0.1, 1.125), (2, 0.2, 2), (3, '19.999', 0.5)",
+            "CREATE INDEX payments_amount ON payments (amount)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        assert_eq!(db.tables["payments"].columns[1].data_type, "DECIMAL(8,2)");
+        let dec = |s: &str| Value::Decimal(d(s));
+
+        // Sums are exact, where 0.1 + 0.2 as reals is 0.30000000000000004.
+        let (types, rows) = query(
+            &mut db,
+            "SELECT SUM(amount), MAX(amount), AVG(rate) FROM payments WHERE id < 3",
+        );
+        assert_eq!(types, ["DECIMAL", "DECIMAL(8,2)", "DECIMAL"]);
+        assert_eq!(rows, [[dec("0.30"), dec("0.20"), dec("1.5625")]]);
+        let (types, rows) = query(
+            &mut db,
+            "SELECT amount * 3, amount + 1, rate * 0.5, CAST(amount AS TEXT) FROM payments \
+             WHERE amount = 20",
+        );
+        assert_eq!(types, ["DECIMAL", "DECIMAL", "REAL", "TEXT"]);
+        assert_eq!(
+            rows,
+            [[
+                dec("60.00"),
+                dec("21.00"),
+                Value::Real(Real::new(0.25).unwrap()),
+                Value::Text("20.00".into())
+            ]]
+        );
+        let (_, rows) = query(
+            &mut db,
+            "SELECT CAST(rate AS DECIMAL(4,1)), CAST('7' AS DECIMAL), CAST(rate AS INT) FROM \
+             payments WHERE id = 1",
+        );
+        assert_eq!(rows, [[dec("1.1"), dec("7"), Value::Integer(1)]]);
+
+        for sql in [
+            "INSERT INTO payments VALUES (4, 1000000, 1)",
+            "INSERT INTO payments VALUES (5, 'lots', 1)",
+            "SELECT amount / 0 FROM payments",
+            "CREATE TABLE bad (n DECIMAL(3, 4))",
+        ] {
+            let result = parse(sql).map(|statement| db.execute(statement));
+            assert!(!matches!(result, Ok(Ok(_))), "{}", sql);
+        }
+    }
+}
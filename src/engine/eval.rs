@@ -0,0 +1,571 @@
+use std::cmp::Ordering;
+
+use crate::{
+    DbError,
+    engine::{
+        Date, Decimal, Real, Timestamp, Value, datetime::parse_temporal, decimal::DecimalType,
+        relation::Relation, session::Session,
+    },
+    parser::{BinaryOperator, Expr},
+};
+
+/// Evaluates a row-level expression against one row of `relation`.
+///
+/// Columns are resolved by name through the relation headers, variables and functions read
+/// from the `session`. Aggregates are computed by the grouping operator and are rejected here.
+pub(crate) fn eval(
+    expr: &Expr,
+    relation: &Relation,
+    row: &[Value],
+    session: &Session,
+) -> Result<Value, DbError> {
+    match expr {
+        Expr::Column(name) => Ok(row[relation.column_index(name)?].clone()),
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Variable(name) => Ok(session.variable(name)),
+        Expr::Function { name, args } => {
+            let args = args
+                .iter()
+                .map(|a| eval(a, relation, row, session))
+                .collect::<Result<Vec<_>, _>>()?;
+            call_function(name, args, session)
+        }
+        Expr::Aggregate { .. } => Err(DbError::ParseError(format!(
+            "Aggregate {} cannot be mixed with row-level output",
+            expr
+        ))),
+        // Computed by the window stage, which leaves a column in their place.
+        Expr::Window { .. } => Err(DbError::ParseError(format!(
+            "Window function {} is only supported in the select list",
+            expr
+        ))),
+        // The filter and the projection run subqueries before evaluating the rest.
+        Expr::Exists(_) | Expr::Subquery(_) => Err(DbError::ParseError(format!(
+            "Subquery {} is only supported in WHERE and the select list",
+            expr
+        ))),
+        Expr::Binary { left, op, right } => {
+            let left = eval(left, relation, row, session)?;
+            let right = eval(right, relation, row, session)?;
+            apply_operator(*op, &left, &right)
+        }
+        Expr::Negate(inner) => negate(eval(inner, relation, row, session)?),
+        Expr::Not(inner) => Ok(match truth(&eval(inner, relation, row, session)?) {
+            Some(b) => boolean(!b),
+            None => Value::Null,
+        }),
+        Expr::Cast { expr, data_type } => cast(eval(expr, relation, row, session)?, data_type),
+        Expr::IsNull { expr, negated } => {
+            let is_null = eval(expr, relation, row, session)? == Value::Null;
+            Ok(boolean(is_null != *negated))
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let needle = eval(expr, relation, row, session)?;
+            let items = list
+                .iter()
+                .map(|e| eval(e, relation, row, session))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(match in_list(&needle, &items) {
+                Some(found) => boolean(found != *negated),
+                None => Value::Null,
+            })
+        }
+    }
+}
+
+/// Whether a row passes a `WHERE` predicate: only a true outcome keeps it, `NULL` does not.
+pub(crate) fn is_true(value: &Value) -> bool {
+    truth(value) == Some(true)
+}
+
+/// Interprets a value as a SQL boolean: non-zero numbers are true, `NULL` is unknown.
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Integer(n) => Some(*n != 0),
+        Value::BigInt(n) => Some(*n != 0),
+        Value::Real(r) => Some(r.get() != 0.0),
+        Value::Decimal(d) => Some(!d.is_zero()),
+        Value::Date(_) | Value::Timestamp(_) => Some(true),
+        Value::Text(s) => Some(!s.is_empty()),
+        Value::Null => None,
+    }
+}
+
+fn boolean(b: bool) -> Value {
+    Value::Integer(b as i32)
+}
+
+/// `needle IN (items)` with SQL semantics: `None` when the answer is unknown because the
+/// needle is `NULL`, or because nothing matched and the list contains a `NULL`.
+pub(crate) fn in_list(needle: &Value, items: &[Value]) -> Option<bool> {
+    if *needle == Value::Null {
+        return None;
+    }
+    if items
+        .iter()
+        .any(|item| compare(needle, item).is_ok_and(Ordering::is_eq))
+    {
+        Some(true)
+    } else if items.contains(&Value::Null) {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+/// Applies a comparison or connective using three-valued logic, or an arithmetic operator.
+fn apply_operator(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value, DbError> {
+    if op.is_arithmetic() {
+        return arithmetic(op, left, right);
+    }
+    Ok(match op {
+        BinaryOperator::And => match (truth(left), truth(right)) {
+            (Some(false), _) | (_, Some(false)) => boolean(false),
+            (Some(true), Some(true)) => boolean(true),
+            _ => Value::Null,
+        },
+        BinaryOperator::Or => match (truth(left), truth(right)) {
+            (Some(true), _) | (_, Some(true)) => boolean(true),
+            (Some(false), Some(false)) => boolean(false),
+            _ => Value::Null,
+        },
+        _ if *left == Value::Null || *right == Value::Null => Value::Null,
+        _ => {
+            let ordering = compare(left, right)?;
+            boolean(match op {
+                BinaryOperator::Eq => ordering == Ordering::Equal,
+                BinaryOperator::NotEq => ordering != Ordering::Equal,
+                BinaryOperator::Lt => ordering == Ordering::Less,
+                BinaryOperator::LtEq => ordering != Ordering::Greater,
+                BinaryOperator::Gt => ordering == Ordering::Greater,
+                BinaryOperator::GtEq => ordering != Ordering::Less,
+                _ => unreachable!("logical and arithmetic operators are handled above"),
+            })
+        }
+    })
+}
+
+/// Compares two values that aren't `NULL`. Text compared with a date or a timestamp is read
+/// as one, as in `day >= '2024-01-01'`, and must be a valid date or timestamp.
+pub(crate) fn compare(left: &Value, right: &Value) -> Result<Ordering, DbError> {
+    let temporal = |value: &Value| matches!(value, Value::Date(_) | Value::Timestamp(_));
+    match (left, right) {
+        (Value::Text(text), other) | (other, Value::Text(text)) if temporal(other) => {
+            let parsed = parse_temporal(text).ok_or_else(|| {
+                DbError::TypeError(format!("Cannot compare {:?} with '{}'", other, text))
+            })?;
+            Ok(if let Value::Text(_) = left {
+                parsed.cmp(right)
+            } else {
+                left.cmp(&parsed)
+            })
+        }
+        _ => Ok(left.cmp(right)),
+    }
+}
+
+/// Arithmetic: `NULL` if either side is `NULL`, an error for text, overflow and division by
+/// zero. Integers stay integers and division truncates toward zero: two `INT`s give an
+/// `INT`, and a `BIGINT` on either side a `BIGINT`. With a real on either side the result is
+/// a real, and otherwise with a decimal an exact decimal.
+///
+/// Days can be added to or subtracted from a date, and seconds from a timestamp. Subtracting
+/// two dates gives the days between them, and two timestamps the seconds.
+fn arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value, DbError> {
+    let (a, b, big) = match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+        (Value::Date(_) | Value::Timestamp(_), _) | (_, Value::Date(_) | Value::Timestamp(_)) => {
+            return temporal_arithmetic(op, left, right);
+        }
+        (Value::Integer(a), Value::Integer(b)) => (i64::from(*a), i64::from(*b), false),
+        (Value::Integer(a), Value::BigInt(b)) => (i64::from(*a), *b, true),
+        (Value::BigInt(a), Value::Integer(b)) => (*a, i64::from(*b), true),
+        (Value::BigInt(a), Value::BigInt(b)) => (*a, *b, true),
+        (Value::Text(_), _) | (_, Value::Text(_)) => {
+            return Err(DbError::TypeError(format!(
+                "{} expects numeric values, got TEXT",
+                op
+            )));
+        }
+        (Value::Real(_), _) | (_, Value::Real(_)) => {
+            return real_arithmetic(op, number(left), number(right));
+        }
+        _ => return decimal_arithmetic(op, left, right),
+    };
+    if b == 0 && matches!(op, BinaryOperator::Div | BinaryOperator::Mod) {
+        return Err(DbError::TypeError(format!(
+            "Division by zero in {} {} 0",
+            a, op
+        )));
+    }
+    let result = match op {
+        BinaryOperator::Add => a.checked_add(b),
+        BinaryOperator::Sub => a.checked_sub(b),
+        BinaryOperator::Mul => a.checked_mul(b),
+        BinaryOperator::Div => a.checked_div(b),
+        BinaryOperator::Mod => a.checked_rem(b),
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    let value = match result {
+        Some(n) if big => Some(Value::BigInt(n)),
+        Some(n) => i32::try_from(n).ok().map(Value::Integer),
+        None => None,
+    };
+    value.ok_or_else(|| DbError::TypeError(format!("Integer overflow in {} {} {}", a, op, b)))
+}
+
+/// `-value`: `NULL` stays `NULL`, and anything but a number is an error. Like subtraction,
+/// negating the smallest `INT` or `BIGINT` overflows.
+fn negate(value: Value) -> Result<Value, DbError> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Integer(_) | Value::BigInt(_) | Value::Real(_) | Value::Decimal(_) => {
+            arithmetic(BinaryOperator::Sub, &Value::Integer(0), &value)
+        }
+        other => Err(DbError::TypeError(format!(
+            "- expects a numeric value, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn real_arithmetic(op: BinaryOperator, a: f64, b: f64) -> Result<Value, DbError> {
+    if b == 0.0 && matches!(op, BinaryOperator::Div | BinaryOperator::Mod) {
+        return Err(DbError::TypeError(format!(
+            "Division by zero in {:?} {} 0",
+            a, op
+        )));
+    }
+    let result = match op {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Sub => a - b,
+        BinaryOperator::Mul => a * b,
+        BinaryOperator::Div => a / b,
+        BinaryOperator::Mod => a % b,
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    Real::new(result)
+        .map(Value::Real)
+        .ok_or_else(|| DbError::TypeError(format!("Real overflow in {:?} {} {:?}", a, op, b)))
+}
+
+fn decimal_arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value, DbError> {
+    let (Some(a), Some(b)) = (Decimal::from_value(left), Decimal::from_value(right)) else {
+        unreachable!("only integers and decimals are left");
+    };
+    if b.is_zero() && matches!(op, BinaryOperator::Div | BinaryOperator::Mod) {
+        return Err(DbError::TypeError(format!(
+            "Division by zero in {} {} 0",
+            a, op
+        )));
+    }
+    let result = match op {
+        BinaryOperator::Add => a.checked_add(b),
+        BinaryOperator::Sub => a.checked_sub(b),
+        BinaryOperator::Mul => a.checked_mul(b),
+        BinaryOperator::Div => a.checked_div(b),
+        BinaryOperator::Mod => a.checked_rem(b),
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    result
+        .map(Value::Decimal)
+        .ok_or_else(|| DbError::TypeError(format!("Decimal overflow in {} {} {}", a, op, b)))
+}
+
+fn temporal_arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> Result<Value, DbError> {
+    let out_of_range =
+        || DbError::TypeError(format!("{:?} {} {:?} is out of range", left, op, right));
+    let difference = |n: i64| {
+        i32::try_from(n)
+            .map(Value::Integer)
+            .map_err(|_| out_of_range())
+    };
+    match (left, op, right) {
+        (Value::Date(d), BinaryOperator::Add | BinaryOperator::Sub, Value::Integer(n)) => {
+            let n = if op == BinaryOperator::Add {
+                *n as i64
+            } else {
+                -(*n as i64)
+            };
+            Date::from_days(d.days() + n)
+                .map(Value::Date)
+                .ok_or_else(out_of_range)
+        }
+        (Value::Integer(n), BinaryOperator::Add, Value::Date(d)) => {
+            Date::from_days(d.days() + *n as i64)
+                .map(Value::Date)
+                .ok_or_else(out_of_range)
+        }
+        (Value::Timestamp(t), BinaryOperator::Add | BinaryOperator::Sub, Value::Integer(n)) => {
+            let n = if op == BinaryOperator::Add {
+                *n as i64
+            } else {
+                -(*n as i64)
+            };
+            Timestamp::from_secs(t.secs() + n)
+                .map(Value::Timestamp)
+                .ok_or_else(out_of_range)
+        }
+        (Value::Integer(n), BinaryOperator::Add, Value::Timestamp(t)) => {
+            Timestamp::from_secs(t.secs() + *n as i64)
+                .map(Value::Timestamp)
+                .ok_or_else(out_of_range)
+        }
+        (Value::Date(a), BinaryOperator::Sub, Value::Date(b)) => difference(a.days() - b.days()),
+        (Value::Timestamp(a), BinaryOperator::Sub, Value::Timestamp(b)) => {
+            difference(a.secs() - b.secs())
+        }
+        _ => Err(DbError::TypeError(format!(
+            "Cannot compute {:?} {} {:?}",
+            left, op, right
+        ))),
+    }
+}
+
+/// The value of an integer, real or decimal as an `f64`.
+fn number(value: &Value) -> f64 {
+    match value {
+        Value::Integer(n) => f64::from(*n),
+        Value::BigInt(n) => *n as f64,
+        Value::Real(r) => r.get(),
+        Value::Decimal(d) => d.to_f64(),
+        _ => unreachable!("only numbers are converted"),
+    }
+}
+
+/// Converts `value` to `INT`, `BIGINT`, `REAL`, `DECIMAL`, `DATE`, `TIMESTAMP` or `TEXT`.
+/// Text becomes a number, date or timestamp only if it is one, leading and trailing spaces
+/// aside. Numbers outside the range of a type can't be converted to it, reals and decimals
+/// become integers by truncating toward zero, and `DECIMAL(p,s)` rounds to `s` places.
+/// Timestamps become dates by dropping the time, and dates timestamps at midnight. `NULL`
+/// stays `NULL`.
+fn cast(value: Value, data_type: &str) -> Result<Value, DbError> {
+    let error =
+        |value: String| DbError::TypeError(format!("Cannot cast {} to {}", value, data_type));
+    if let Some(decimal_type) = DecimalType::parse(data_type) {
+        return match value {
+            Value::Null => Ok(Value::Null),
+            value => Decimal::from_value(&value)
+                .and_then(|d| decimal_type.fit(d))
+                .map(Value::Decimal)
+                .ok_or_else(|| match value {
+                    Value::Text(s) => error(format!("'{}'", s)),
+                    value => error(as_text(&value).unwrap_or_default()),
+                }),
+        };
+    }
+    match (value, data_type) {
+        (Value::Text(s), "INT") => s
+            .trim()
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| error(format!("'{}'", s))),
+        (Value::Text(s), "REAL") => s
+            .trim()
+            .parse()
+            .ok()
+            .and_then(Real::new)
+            .map(Value::Real)
+            .ok_or_else(|| error(format!("'{}'", s))),
+        (Value::Text(s), "BIGINT") => s
+            .trim()
+            .parse()
+            .map(Value::BigInt)
+            .map_err(|_| error(format!("'{}'", s))),
+        (Value::Real(r), "INT") => {
+            let n = r.get().trunc();
+            if n < f64::from(i32::MIN) || n > f64::from(i32::MAX) {
+                return Err(error(r.to_string()));
+            }
+            Ok(Value::Integer(n as i32))
+        }
+        (Value::Real(r), "BIGINT") => {
+            let n = r.get().trunc();
+            // [-2^63, 2^63), the range of i64.
+            if !(-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&n) {
+                return Err(error(r.to_string()));
+            }
+            Ok(Value::BigInt(n as i64))
+        }
+        (Value::Decimal(d), "INT") => i32::try_from(d.trunc())
+            .map(Value::Integer)
+            .map_err(|_| error(d.to_string())),
+        (Value::Decimal(d), "BIGINT") => i64::try_from(d.trunc())
+            .map(Value::BigInt)
+            .map_err(|_| error(d.to_string())),
+        (Value::Decimal(d), "REAL") => Ok(Value::Real(Real::new(d.to_f64()).unwrap())),
+        (Value::Integer(n), "BIGINT") => Ok(Value::BigInt(n.into())),
+        (Value::BigInt(n), "INT") => i32::try_from(n)
+            .map(Value::Integer)
+            .map_err(|_| error(n.to_string())),
+        (Value::Integer(n), "REAL") => Ok(Value::Real(Real::new(f64::from(n)).unwrap())),
+        (Value::BigInt(n), "REAL") => Ok(Value::Real(Real::new(n as f64).unwrap())),
+        (text @ Value::Text(_), "DATE") => {
+            date_of(&text).map_err(|_| error(format!("'{}'", as_text(&text).unwrap_or_default())))
+        }
+        (Value::Timestamp(t), "DATE") => Ok(Value::Date(t.date())),
+        (Value::Text(s), "TIMESTAMP") => Timestamp::parse(s.trim())
+            .map(Value::Timestamp)
+            .ok_or_else(|| error(format!("'{}'", s))),
+        (Value::Date(d), "TIMESTAMP") => Ok(Value::Timestamp(d.at_midnight())),
+        (value, "TEXT") => Ok(as_text(&value).map_or(Value::Null, Value::Text)),
+        (Value::Null, _) => Ok(Value::Null),
+        (value @ Value::Integer(_), "INT")
+        | (value @ Value::BigInt(_), "BIGINT")
+        | (value @ Value::Real(_), "REAL")
+        | (value @ Value::Date(_), "DATE")
+        | (value @ Value::Timestamp(_), "TIMESTAMP") => Ok(value),
+        // Dates don't convert to numbers or numbers to dates.
+        (value, _) => Err(error(as_text(&value).unwrap_or_default())),
+    }
+}
+
+/// Checks that `name` (uppercased) is a known scalar function taking `argc` arguments.
+pub(crate) fn check_function(name: &str, argc: usize) -> Result<(), DbError> {
+    let (min, max) = match name {
+        "CURRENT_USER" | "CURRENT_TIMESTAMP" | "CURRENT_DATE" | "NOW" => (0, Some(0)),
+        "UPPER" | "LOWER" | "LENGTH" | "DATE" | "YEAR" | "MONTH" | "DAY" => (1, Some(1)),
+        "SUBSTR" => (2, Some(3)),
+        "CONCAT" | "COALESCE" => (1, None),
+        "NULLIF" => (2, Some(2)),
+        _ => return Err(DbError::ParseError(format!("Unknown function: {}", name))),
+    };
+    if argc >= min && max.is_none_or(|max| argc <= max) {
+        return Ok(());
+    }
+    let expected = match max {
+        Some(max) if max == min => min.to_string(),
+        Some(max) => format!("{} to {}", min, max),
+        None => format!("at least {}", min),
+    };
+    Err(DbError::ParseError(format!(
+        "{} expects {} argument(s), got {}",
+        name, expected, argc
+    )))
+}
+
+/// Dispatches a scalar function call by its (uppercased) name. String functions return
+/// `NULL` for a `NULL` argument, except `CONCAT`, which skips them.
+///
+/// `COALESCE` returns its first non-`NULL` argument and `NULLIF(a, b)` returns `NULL` when
+/// `a = b`, `a` otherwise.
+fn call_function(name: &str, args: Vec<Value>, session: &Session) -> Result<Value, DbError> {
+    check_function(name, args.len())?;
+    match name {
+        "CURRENT_USER" => Ok(Value::Text(session.user.clone())),
+        "CURRENT_TIMESTAMP" | "NOW" => Ok(Value::Timestamp(Timestamp::now())),
+        "CURRENT_DATE" => Ok(Value::Date(Timestamp::now().date())),
+        "CONCAT" => Ok(Value::Text(args.iter().filter_map(as_text).collect())),
+        "COALESCE" => Ok(args
+            .into_iter()
+            .find(|a| *a != Value::Null)
+            .unwrap_or(Value::Null)),
+        "NULLIF" => {
+            let [value, other] = <[Value; 2]>::try_from(args).expect("checked by check_function");
+            Ok(if value == other { Value::Null } else { value })
+        }
+        _ if args.contains(&Value::Null) => Ok(Value::Null),
+        "UPPER" => Ok(Value::Text(
+            as_text(&args[0]).unwrap_or_default().to_uppercase(),
+        )),
+        "LOWER" => Ok(Value::Text(
+            as_text(&args[0]).unwrap_or_default().to_lowercase(),
+        )),
+        "LENGTH" => {
+            let length = as_text(&args[0]).unwrap_or_default().chars().count();
+            Ok(Value::Integer(length.min(i32::MAX as usize) as i32))
+        }
+        "SUBSTR" => substr(&args),
+        "DATE" => date_of(&args[0]),
+        "YEAR" | "MONTH" | "DAY" => {
+            let Value::Date(date) = date_of(&args[0])? else {
+                unreachable!("date_of returns dates");
+            };
+            let (year, month, day) = date.civil();
+            Ok(Value::Integer(match name {
+                "YEAR" => year as i32,
+                "MONTH" => month as i32,
+                _ => day as i32,
+            }))
+        }
+        _ => unreachable!("checked by check_function"),
+    }
+}
+
+/// The day of a date, a timestamp or text holding either, for `DATE()` and the functions
+/// reading a part of a date.
+fn date_of(value: &Value) -> Result<Value, DbError> {
+    let date = match value {
+        Value::Date(d) => Some(*d),
+        Value::Timestamp(t) => Some(t.date()),
+        Value::Text(s) => match parse_temporal(s.trim()) {
+            Some(Value::Date(d)) => Some(d),
+            Some(Value::Timestamp(t)) => Some(t.date()),
+            _ => None,
+        },
+        _ => None,
+    };
+    date.map(Value::Date)
+        .ok_or_else(|| DbError::TypeError(format!("Expected a date or timestamp, got {:?}", value)))
+}
+
+/// The text form of a value for the string functions: numbers are written out in decimal,
+/// dates as `YYYY-MM-DD` and timestamps as `YYYY-MM-DD HH:MM:SS`.
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Text(s) => Some(s.clone()),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::BigInt(n) => Some(n.to_string()),
+        Value::Real(r) => Some(r.to_string()),
+        Value::Decimal(d) => Some(d.to_string()),
+        Value::Date(d) => Some(d.to_string()),
+        Value::Timestamp(t) => Some(t.to_string()),
+        Value::Null => None,
+    }
+}
+
+/// `SUBSTR(text, start [, length])`: the characters from position `start` (counting from 1)
+/// on, at most `length` of them. Positions before the first character count toward
+/// `length`, as in standard SQL.
+fn substr(args: &[Value]) -> Result<Value, DbError> {
+    let text = as_text(&args[0]).unwrap_or_default();
+    let integer = |value: &Value, what: &str| match value {
+        Value::Integer(n) => Ok(*n as i64),
+        Value::BigInt(n) => Ok(*n),
+        Value::Real(_) => Err(DbError::TypeError(format!(
+            "SUBSTR expects an integer {}, got REAL",
+            what
+        ))),
+        Value::Decimal(_) => Err(DbError::TypeError(format!(
+            "SUBSTR expects an integer {}, got DECIMAL",
+            what
+        ))),
+        _ => Err(DbError::TypeError(format!(
+            "SUBSTR expects an integer {}, got TEXT",
+            what
+        ))),
+    };
+    let start = integer(&args[1], "start")?;
+    let end = match args.get(2) {
+        Some(length) => match integer(length, "length")? {
+            length if length < 0 => {
+                return Err(DbError::TypeError(format!(
+                    "SUBSTR expects a non-negative length, got {}",
+                    length
+                )));
+            }
+            length => start + length,
+        },
+        None => i64::MAX,
+    };
+    let result = (1..)
+        .zip(text.chars())
+        .filter(|(position, _)| *position >= start && *position < end)
+        .map(|(_, c)| c)
+        .collect();
+    Ok(Value::Text(result))
+}
@@ -0,0 +1,356 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Database, DbError,
+    engine::{Column, Table, Value},
+    parser::Expr,
+};
+
+/// `FOREIGN KEY (column) REFERENCES parent_table (parent_column)`: every non-NULL value of
+/// `column` must be held by a row of the parent table, whose `parent_column` is a
+/// `PRIMARY KEY` or `UNIQUE` column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub column: String,
+    pub parent_table: String,
+    pub parent_column: String,
+    #[serde(default)]
+    pub on_delete: DeleteAction,
+}
+
+/// What becomes of the rows referencing a parent row when it is deleted, declared with
+/// `ON DELETE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeleteAction {
+    /// `NO ACTION` or `RESTRICT`: the parent row can't be deleted while it is referenced.
+    #[default]
+    NoAction,
+    /// `CASCADE`: the referencing rows are deleted along with it.
+    Cascade,
+    /// `SET NULL`: the referencing rows keep going with their reference set to `NULL`.
+    SetNull,
+}
+
+impl fmt::Display for DeleteAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteAction::NoAction => write!(f, "NO ACTION"),
+            DeleteAction::Cascade => write!(f, "CASCADE"),
+            DeleteAction::SetNull => write!(f, "SET NULL"),
+        }
+    }
+}
+
+impl Database {
+    /// Checks the foreign keys of the table `name` about to be created with `columns`: the
+    /// parent must be a stored table, or the new table itself, with a unique `parent_column` of
+    /// the same type as `column`; `INT` and `BIGINT` may reference one another. `CASCADE` and `SET NULL` are refused, as no statement deletes rows to act upon.
+    pub(crate) fn check_foreign_key_definitions(
+        &self,
+        name: &str,
+        columns: &[Column],
+        foreign_keys: &[ForeignKey],
+    ) -> Result<(), DbError> {
+        for key in foreign_keys {
+            if key.on_delete != DeleteAction::NoAction {
+                return Err(DbError::ParseError(format!(
+                    "ON DELETE {} is not supported; rows are never deleted",
+                    key.on_delete
+                )));
+            }
+            let column = columns
+                .iter()
+                .find(|c| c.name == key.column)
+                .ok_or_else(|| DbError::ColumnNotFound(key.column.clone()))?;
+            let parent_columns = if key.parent_table == name {
+                columns
+            } else {
+                if self.virtual_tables.contains_key(&key.parent_table) {
+                    return Err(DbError::ReadOnlyTable(key.parent_table.clone()));
+                }
+                let parent = self
+                    .tables
+                    .get(&key.parent_table)
+                    .ok_or_else(|| DbError::TableNotFound(key.parent_table.clone()))?;
+                &parent.columns
+            };
+            let parent_column = parent_columns
+                .iter()
+                .find(|c| c.name == key.parent_column)
+                .ok_or_else(|| DbError::ColumnNotFound(key.parent_column.clone()))?;
+            if !parent_column.is_primary && !parent_column.is_unique {
+                return Err(DbError::ForeignKeyViolation(format!(
+                    "'{}.{}' is not a PRIMARY KEY or UNIQUE column",
+                    key.parent_table, key.parent_column
+                )));
+            }
+            if key_type(&column.data_type) != key_type(&parent_column.data_type) {
+                return Err(DbError::TypeMismatch {
+                    column: key.column.clone(),
+                    expected: parent_column.declared_type(),
+                    got: column.declared_type(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects `rows` about to be inserted into `table_name` if one of them references a
+    /// parent row that does not exist. A table referencing itself may reference the rows
+    /// inserted along with it.
+    pub(crate) fn check_references(
+        &self,
+        table_name: &str,
+        rows: &[Vec<Value>],
+    ) -> Result<(), DbError> {
+        let Some(table) = self.tables.get(table_name) else {
+            return Ok(());
+        };
+        for key in &table.foreign_keys {
+            let column = table.column_index(&key.column)?;
+            let parent = self.parent_keys(key)?;
+            let inserted: HashSet<&Value> = match key.parent_table == table_name {
+                true => {
+                    let i = table.column_index(&key.parent_column)?;
+                    rows.iter().filter_map(|row| row.get(i)).collect()
+                }
+                false => HashSet::new(),
+            };
+            let missing = rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .find(|v| **v != Value::Null && !parent.contains(*v) && !inserted.contains(v));
+            if let Some(value) = missing {
+                return Err(missing_parent(key, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks every reference from and into `table_name`, for changes that can break them
+    /// after the fact, like an `ON CONFLICT DO UPDATE` rewriting a key.
+    pub(crate) fn check_table_references(&self, table_name: &str) -> Result<(), DbError> {
+        let children = self
+            .tables
+            .values()
+            .flat_map(|child| child.foreign_keys.iter().map(move |key| (child, key)))
+            .filter(|(child, key)| child.name == table_name || key.parent_table == table_name);
+        for (child, key) in children {
+            let column = child.column_index(&key.column)?;
+            let parent = self.parent_keys(key)?;
+            let missing = child
+                .rows
+                .iter()
+                .map(|row| &row[column])
+                .find(|v| **v != Value::Null && !parent.contains(*v));
+            if let Some(value) = missing {
+                return Err(missing_parent(key, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `table_name` has foreign keys or is referenced by one.
+    pub(crate) fn has_references(&self, table_name: &str) -> bool {
+        self.tables.values().any(|table| {
+            table
+                .foreign_keys
+                .iter()
+                .any(|key| table.name == table_name || key.parent_table == table_name)
+        })
+    }
+
+    /// Rejects removing the table `table_name` or, when given, its `column` while another
+    /// table still references it. References from the table to itself go with it.
+    pub(crate) fn check_unreferenced(
+        &self,
+        table_name: &str,
+        column: Option<&str>,
+    ) -> Result<(), DbError> {
+        for child in self.tables.values() {
+            for key in &child.foreign_keys {
+                let own_key =
+                    child.name == table_name && column.is_none_or(|column| column == key.column);
+                if key.parent_table == table_name
+                    && column.is_none_or(|column| column == key.parent_column)
+                    && !own_key
+                {
+                    return Err(DbError::ForeignKeyViolation(format!(
+                        "'{}.{}' is referenced by '{}.{}'",
+                        table_name, key.parent_column, child.name, key.column
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Points the foreign keys into the table `from` at its new name `to`.
+    pub(crate) fn rename_referenced_table(&mut self, from: &str, to: &str) {
+        for table in self.tables.values_mut() {
+            for key in &mut table.foreign_keys {
+                if key.parent_table == from {
+                    key.parent_table = to.to_string();
+                }
+            }
+        }
+    }
+
+    /// Follows the rename of the column `from` of `table_name` to `to` in the foreign keys
+    /// that name it.
+    pub(crate) fn rename_referenced_column(&mut self, table_name: &str, from: &str, to: &str) {
+        for table in self.tables.values_mut() {
+            for key in &mut table.foreign_keys {
+                if table.name == table_name && key.column == from {
+                    key.column = to.to_string();
+                }
+                if key.parent_table == table_name && key.parent_column == from {
+                    key.parent_column = to.to_string();
+                }
+            }
+        }
+    }
+
+    /// The values of the parent column of `key`, from its unique index.
+    fn parent_keys(&self, key: &ForeignKey) -> Result<&HashSet<Value>, DbError> {
+        let parent: &Table = self
+            .tables
+            .get(&key.parent_table)
+            .ok_or_else(|| DbError::TableNotFound(key.parent_table.clone()))?;
+        let i = parent.column_index(&key.parent_column)?;
+        parent
+            .indexes
+            .get(&i)
+            .ok_or_else(|| DbError::IndexNotFound(key.parent_column.clone()))
+    }
+}
+
+fn missing_parent(key: &ForeignKey, value: &Value) -> DbError {
+    DbError::ForeignKeyViolation(format!(
+        "no row of '{}' has {} = {}",
+        key.parent_table,
+        key.parent_column,
+        Expr::Literal(value.clone())
+    ))
+}
+
+/// The type a key column is compared by: `INT` and `BIGINT` values are equal to one another,
+/// as are `TEXT` and `VARCHAR` values.
+fn key_type(data_type: &str) -> &str {
+    match data_type {
+        "INT" | "INTEGER" | "BIGINT" => "BIGINT",
+        "VARCHAR" => "TEXT",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn run(db: &mut Database, sql: &str) -> Result<(), DbError> {
+        db.execute(parse(sql).unwrap()).map(|_| ())
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let mut db = Database::new();
+        run(&mut db, "CREATE TABLE teams (id INT PRIMARY, name TEXT)").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT, FOREIGN KEY (team_id) REFERENCES teams (id))",
+        )
+        .unwrap();
+        let fails = |db: &mut Database, sql: &str| {
+            matches!(run(db, sql), Err(DbError::ForeignKeyViolation(_)))
+        };
+        assert!(fails(
+            &mut db,
+            "CREATE TABLE x (team TEXT REFERENCES teams (name))"
+        ));
+        // A key holds the type of the one it references, or a wider or narrower integer.
+        assert_eq!(
+            run(&mut db, "CREATE TABLE x (team TEXT REFERENCES teams (id))")
+                .unwrap_err()
+                .to_string(),
+            "Column 'team' expects INT, got TEXT"
+        );
+        run(
+            &mut db,
+            "CREATE TABLE x (team BIGINT REFERENCES teams (id))",
+        )
+        .unwrap();
+        run(&mut db, "DROP TABLE x").unwrap();
+
+        run(&mut db, "INSERT INTO teams VALUES (1, 'core')").unwrap();
+        run(&mut db, "INSERT INTO devs VALUES (1, 1)").unwrap();
+        assert_eq!(
+            run(&mut db, "INSERT INTO devs VALUES (2, 1), (3, 9)")
+                .unwrap_err()
+                .to_string(),
+            "Foreign key violation: no row of 'teams' has id = 9"
+        );
+        assert_eq!(db.tables["devs"].rows.len(), 1);
+        assert!(
+            db.copy_rows(
+                "martin",
+                "devs",
+                vec![vec![Value::Integer(4), Value::Integer(9)]]
+            )
+            .is_err()
+        );
+
+        // Rewriting a referenced key is undone.
+        let sql = "INSERT INTO teams VALUES (1, 'x') ON CONFLICT (id) DO UPDATE SET id = 2";
+        assert!(fails(&mut db, sql));
+        assert_eq!(db.tables["teams"].rows[0][0], Value::Integer(1));
+
+        // The parent can't go while it is referenced, and renames carry over.
+        assert!(fails(&mut db, "DROP TABLE teams"));
+        assert!(fails(&mut db, "ALTER TABLE teams DROP COLUMN id"));
+        run(&mut db, "ALTER TABLE teams RENAME TO squads").unwrap();
+        run(&mut db, "ALTER TABLE squads RENAME COLUMN id TO squad_id").unwrap();
+        assert_eq!(
+            db.tables["devs"].foreign_keys,
+            [ForeignKey {
+                column: "team_id".into(),
+                parent_table: "squads".into(),
+                parent_column: "squad_id".into(),
+                on_delete: DeleteAction::NoAction,
+            }]
+        );
+        run(&mut db, "ALTER TABLE devs DROP COLUMN team_id").unwrap();
+        run(&mut db, "DROP TABLE squads").unwrap();
+
+        // A table may reference itself, including rows inserted along with the referencing one.
+        run(
+            &mut db,
+            "CREATE TABLE emp (id INT PRIMARY, manager INT REFERENCES emp (id))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO emp VALUES (2, 1), (1, 1)").unwrap();
+        run(&mut db, "DROP TABLE emp").unwrap();
+
+        for action in ["SET NULL", "CASCADE"] {
+            let sql = format!(
+                "CREATE TABLE tasks (id INT PRIMARY, owner INT REFERENCES devs (id) ON DELETE {})",
+                action
+            );
+            assert!(matches!(run(&mut db, &sql), Err(DbError::ParseError(_))));
+        }
+        run(
+            &mut db,
+            "CREATE TABLE tasks (id INT PRIMARY, owner INT REFERENCES devs (id) ON DELETE RESTRICT)",
+        )
+        .unwrap();
+        assert_eq!(
+            db.tables["tasks"].foreign_keys[0].on_delete,
+            DeleteAction::NoAction
+        );
+        assert!(parse("CREATE TABLE x (a INT REFERENCES devs (id) ON DELETE LATER)").is_err());
+    }
+}
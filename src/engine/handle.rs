@@ -0,0 +1,196 @@
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Table, Value},
+    parser::Statement,
+};
+
+/// A stored table, checked against the catalog when the handle is made.
+///
+/// Handles are plain names underneath: they stay valid across inserts and updates, and
+/// using one after its table was dropped or renamed fails with `TableNotFound`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableRef {
+    name: String,
+}
+
+impl TableRef {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A column of a stored table, resolved to its position once so that reads through it
+/// skip the lookup by name.
+///
+/// A schema change that moves the column (`DROP COLUMN` of an earlier one, `RENAME COLUMN`)
+/// makes the handle stale: using it then fails with `ColumnNotFound` instead of reading
+/// another column.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColumnRef {
+    table: TableRef,
+    name: String,
+    position: usize,
+    data_type: String,
+}
+
+impl ColumnRef {
+    pub fn table(&self) -> &TableRef {
+        &self.table
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The position of the column in the rows of its table.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The declared type, e.g. `INT` or `TEXT`.
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
+}
+
+impl Database {
+    /// Resolves the stored table `name`. Virtual tables have no handles: their rows are only
+    /// reachable through queries.
+    pub fn table_ref(&self, name: &str) -> Result<TableRef, DbError> {
+        if self.virtual_tables.contains_key(name) {
+            return Err(DbError::ReadOnlyTable(name.to_string()));
+        }
+        self.get_table(name.to_string())?;
+        Ok(TableRef {
+            name: name.to_string(),
+        })
+    }
+
+    /// Resolves column `name` of `table`.
+    pub fn column_ref(&self, table: &TableRef, name: &str) -> Result<ColumnRef, DbError> {
+        let stored = self.resolve_table(table)?;
+        let position = stored.column_index(name)?;
+        Ok(ColumnRef {
+            table: table.clone(),
+            name: name.to_string(),
+            position,
+            data_type: stored.columns[position].data_type.clone(),
+        })
+    }
+
+    /// Inserts `rows` into `table`, with the same checks, limits and stats as an `INSERT`.
+    pub fn insert(
+        &mut self,
+        table: &TableRef,
+        rows: Vec<Vec<Value>>,
+    ) -> Result<ExecutionResult, DbError> {
+        self.execute(Statement::Insert {
+            table_name: table.name.clone(),
+            rows,
+            on_conflict: None,
+        })
+    }
+
+    /// The value of `column` in every row, in storage order.
+    pub fn column_values(&self, column: &ColumnRef) -> Result<Vec<&Value>, DbError> {
+        let table = self.resolve_column(column)?;
+        Ok(table.rows.iter().map(|row| &row[column.position]).collect())
+    }
+
+    /// The rows whose `column` equals `value`, through its secondary index when it has one.
+    pub fn rows_where(&self, column: &ColumnRef, value: &Value) -> Result<Vec<&[Value]>, DbError> {
+        let table = self.resolve_column(column)?;
+        Ok(match table.indexed_rows(column.position, value) {
+            Some(positions) => positions
+                .iter()
+                .map(|&pos| table.rows[pos].as_slice())
+                .collect(),
+            None => table
+                .rows
+                .iter()
+                .filter(|row| row[column.position] == *value)
+                .map(Vec::as_slice)
+                .collect(),
+        })
+    }
+
+    fn resolve_table(&self, table: &TableRef) -> Result<&Table, DbError> {
+        self.get_table(table.name.clone())
+    }
+
+    /// The table of `column`, as long as the column is still where the handle says.
+    fn resolve_column(&self, column: &ColumnRef) -> Result<&Table, DbError> {
+        let table = self.resolve_table(&column.table)?;
+        match table.columns.get(column.position) {
+            Some(c) if c.name == column.name => Ok(table),
+            _ => Err(DbError::ColumnNotFound(column.name.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database, DbError,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_handles() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY KEY, team TEXT, name TEXT)",
+            "CREATE INDEX devs_team ON devs (team)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let devs = db.table_ref("devs").unwrap();
+        let team = db.column_ref(&devs, "team").unwrap();
+        let name = db.column_ref(&devs, "name").unwrap();
+        assert_eq!((team.position(), team.data_type()), (1, "TEXT"));
+        assert!(matches!(
+            db.table_ref("missing"),
+            Err(DbError::TableNotFound(_))
+        ));
+        assert!(matches!(
+            db.column_ref(&devs, "nmae"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+
+        let text = |s: &str| Value::Text(s.into());
+        let rows = vec![
+            vec![Value::Integer(1), text("core"), text("ada")],
+            vec![Value::Integer(2), text("web"), text("grace")],
+            vec![Value::Integer(3), text("core"), text("linus")],
+        ];
+        let result = db.insert(&devs, rows).unwrap();
+        assert!(matches!(result, ExecutionResult::Message(m) if m == "3 rows inserted."));
+        assert!(matches!(
+            db.insert(&devs, vec![vec![Value::Integer(1), text("x"), text("y")]]),
+            Err(DbError::UniqueViolation(_))
+        ));
+
+        assert_eq!(
+            db.column_values(&name).unwrap(),
+            [&text("ada"), &text("grace"), &text("linus")]
+        );
+        let core = db.rows_where(&team, &text("core")).unwrap();
+        assert_eq!(core.len(), 2);
+        assert_eq!(core[1][2], text("linus"));
+        assert_eq!(db.rows_where(&name, &text("grace")).unwrap().len(), 1);
+
+        // Dropping `team` moves `name` to its position: the old handle no longer resolves.
+        db.execute(parse("ALTER TABLE devs DROP COLUMN team").unwrap())
+            .unwrap();
+        assert!(matches!(
+            db.column_values(&name),
+            Err(DbError::ColumnNotFound(_))
+        ));
+        db.execute(parse("DROP TABLE devs").unwrap()).unwrap();
+        assert!(matches!(
+            db.insert(&devs, vec![]),
+            Err(DbError::TableNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    DbError,
+    engine::{Table, Value, datetime::parse_temporal, relation::Relation},
+    parser::{BinaryOperator, Expr, Hint, SelectStatement},
+};
+
+/// A lookup that can answer part of a `WHERE` clause from an index instead of checking every
+/// row.
+///
+/// The probe first discards the requested values that are absent from the index. If none
+/// remain the table is never scanned. PRIMARY/UNIQUE indexes only record which values exist,
+/// so the scan stops as soon as every remaining value has been found, since each one occurs in
+/// exactly one row. Secondary indexes (`CREATE INDEX`) know the rows holding each value, which
+/// are read directly.
+#[derive(Debug)]
+pub(crate) struct IndexProbe {
+    pub column: usize,
+    pub values: Vec<Value>,
+}
+
+impl IndexProbe {
+    /// Picks the probe answering `predicate`, the (rewritten) `WHERE` clause of a select over
+    /// `table`, if any.
+    ///
+    /// Joined queries are always scanned. The `NO_INDEX` hint disables the probe and
+    /// `USE INDEX (<column>)` restricts it to that column, which must be indexed.
+    pub fn for_select(
+        table: &Table,
+        select: &SelectStatement,
+        predicate: Option<&Expr>,
+    ) -> Result<Option<Self>, DbError> {
+        let Some(predicate) = predicate else {
+            return Ok(None);
+        };
+        if !select.joins.is_empty() || select.has_hint(Hint::NoIndex) {
+            return Ok(None);
+        }
+
+        let qualifier = select.table_alias.as_deref().unwrap_or(&table.name);
+        let only = match &select.use_index {
+            Some(name) => {
+                let column = table
+                    .column_index(unqualified(name, qualifier))
+                    .ok()
+                    .filter(|i| table.is_indexed(*i))
+                    .ok_or_else(|| DbError::IndexNotFound(name.clone()))?;
+                Some(column)
+            }
+            None => None,
+        };
+        Ok(Self::find(table, qualifier, predicate, only))
+    }
+
+    /// Finds an indexed `column IN (literals)` or `column = literal` condition in `predicate`,
+    /// either at the top level or as one side of an `AND`. The column may be qualified with
+    /// `qualifier`, the name the query refers to the table by. With `only` set, other indexed
+    /// columns are ignored. When both sides of an `AND` have a probe, the one returning fewer
+    /// rows wins.
+    fn find(table: &Table, qualifier: &str, predicate: &Expr, only: Option<usize>) -> Option<Self> {
+        let (column, candidates) = match predicate {
+            Expr::Binary {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => {
+                return [left, right]
+                    .into_iter()
+                    .filter_map(|side| Self::find(table, qualifier, side, only))
+                    .min_by_key(|probe| probe.estimated_rows(table));
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated: false,
+            } => (expr.as_ref(), list.iter().collect::<Vec<_>>()),
+            Expr::Binary {
+                left,
+                op: BinaryOperator::Eq,
+                right,
+            } => (left.as_ref(), vec![right.as_ref()]),
+            _ => return None,
+        };
+
+        let Expr::Column(name) = column else {
+            return None;
+        };
+        let column = table.column_index(unqualified(name, qualifier)).ok()?;
+        if only.is_some_and(|only| only != column) {
+            return None;
+        }
+        if !table.is_indexed(column) {
+            return None;
+        }
+
+        let mut values = Vec::new();
+        for candidate in candidates {
+            let Expr::Literal(value) = candidate else {
+                return None;
+            };
+            // Text compares with dates as the date it spells; see `eval::compare`.
+            let temporal = matches!(
+                table.columns[column].data_type.as_str(),
+                "DATE" | "TIMESTAMP"
+            );
+            let parsed = match value {
+                Value::Text(text) if temporal => Some(parse_temporal(text)?),
+                _ => None,
+            };
+            let value = parsed.as_ref().unwrap_or(value);
+            let exists = match table.indexes.get(&column) {
+                Some(index) => index.contains(value),
+                None => !table.indexed_rows(column, value)?.is_empty(),
+            };
+            if exists && !values.contains(value) {
+                values.push(value.clone());
+            }
+        }
+        Some(Self { column, values })
+    }
+
+    /// Collects the rows holding one of the probed values. The result still has to be
+    /// filtered by the full predicate.
+    pub fn scan(&self, table: &Table) -> Relation {
+        let mut relation = Relation::empty(table);
+        if self.values.is_empty() {
+            return relation;
+        }
+
+        if !table.indexes.contains_key(&self.column) {
+            let mut positions = self.positions(table);
+            // Keep the table's row order, like a scan would.
+            positions.sort_unstable();
+            relation.rows = positions
+                .into_iter()
+                .map(|p| table.rows[p].clone())
+                .collect();
+            return relation;
+        }
+
+        for row in &table.rows {
+            if self.values.contains(&row[self.column]) {
+                relation.rows.push(row.clone());
+                if relation.rows.len() == self.values.len() {
+                    break;
+                }
+            }
+        }
+        relation
+    }
+
+    /// How many rows the probe returns: one per value for a PRIMARY/UNIQUE index.
+    pub fn estimated_rows(&self, table: &Table) -> usize {
+        if table.indexes.contains_key(&self.column) {
+            self.values.len()
+        } else {
+            self.positions(table).len()
+        }
+    }
+
+    /// The positions of the rows holding the probed values, from a secondary index.
+    fn positions(&self, table: &Table) -> Vec<usize> {
+        self.values
+            .iter()
+            .filter_map(|value| table.indexed_rows(self.column, value))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// Strips the `qualifier.` prefix a column reference may carry.
+fn unqualified<'a>(name: &'a str, qualifier: &str) -> &'a str {
+    name.strip_prefix(qualifier)
+        .and_then(|n| n.strip_prefix('.'))
+        .unwrap_or(name)
+}
@@ -0,0 +1,145 @@
+use crate::{
+    Database,
+    engine::{ExecutionResult, Timestamp, Value, schedules::unix_now, trace::IndexLookup},
+};
+
+/// How much an index has been used since startup. An index that is written on every insert
+/// but never looked up only slows the database down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexUsage {
+    /// Queries answered (partly) from the index.
+    pub lookups: u64,
+    /// Rows those lookups returned.
+    pub rows_fetched: u64,
+    /// Inserts that had to update the index.
+    pub writes: u64,
+    /// Unix time of the last lookup.
+    pub last_used: Option<u64>,
+}
+
+impl Database {
+    /// Counts a query's use of an index.
+    pub(crate) fn record_index_lookup(&mut self, lookup: IndexLookup) {
+        let usage = self
+            .stats
+            .index_usage
+            .entry((lookup.table, lookup.column))
+            .or_default();
+        usage.lookups += 1;
+        usage.rows_fetched += lookup.rows as u64;
+        usage.last_used = Some(unix_now());
+    }
+
+    /// Counts the index maintenance of `rows` rows inserted into `table_name`.
+    pub(crate) fn record_index_writes(&mut self, table_name: &str, rows: usize) {
+        let Some(table) = self.tables.get(table_name) else {
+            return;
+        };
+        for i in table.indexed_columns() {
+            let key = (table.name.clone(), table.columns[i].name.clone());
+            self.stats.index_usage.entry(key).or_default().writes += rows as u64;
+        }
+    }
+
+    /// Lists every index with its usage as a
+    /// `table | column | kind | lookups | rows_fetched | writes | last_used` result set,
+    /// sorted by table and column. Indexes never looked up report zero lookups.
+    pub fn show_index_stats(&self) -> ExecutionResult {
+        let count = |n: u64| Value::Integer(n.min(i32::MAX as u64) as i32);
+
+        let mut rows = Vec::new();
+        for table in self.tables.values() {
+            for i in table.indexed_columns() {
+                let column = &table.columns[i];
+                let usage = self
+                    .stats
+                    .index_usage
+                    .get(&(table.name.clone(), column.name.clone()))
+                    .copied()
+                    .unwrap_or_default();
+                let kind = if column.is_primary {
+                    "PRIMARY"
+                } else if column.is_unique {
+                    "UNIQUE"
+                } else {
+                    "INDEX"
+                };
+
+                rows.push(vec![
+                    Value::Text(table.name.clone()),
+                    Value::Text(column.name.clone()),
+                    Value::Text(kind.into()),
+                    count(usage.lookups),
+                    count(usage.rows_fetched),
+                    count(usage.writes),
+                    usage
+                        .last_used
+                        .and_then(|t| Timestamp::from_secs(t as i64))
+                        .map_or(Value::Null, Value::Timestamp),
+                ]);
+            }
+        }
+        rows.sort();
+
+        ExecutionResult::listing(
+            &[
+                ("table", "TEXT"),
+                ("column", "TEXT"),
+                ("kind", "TEXT"),
+                ("lookups", "INT"),
+                ("rows_fetched", "INT"),
+                ("writes", "INT"),
+                ("last_used", "TIMESTAMP"),
+            ],
+            rows,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_index_stats() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, email TEXT UNIQUE, name TEXT)",
+            "INSERT INTO users VALUES (1, 'a@x.io', 'Ada')",
+            "INSERT INTO users VALUES (2, 'b@x.io', 'Bob')",
+            "SELECT * FROM users WHERE id IN (1, 2, 3)",
+            "SELECT * FROM users WHERE id = 2",
+            "SELECT * FROM users WHERE name = 'Ada'",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(parse("SHOW INDEX STATS").unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers[3], "lookups");
+        assert_eq!(rows.len(), 2);
+
+        // Sorted by column: `email` is written but never read.
+        assert_eq!(rows[0][1], Value::Text("email".into()));
+        assert_eq!(
+            rows[0][3..6],
+            [Value::Integer(0), Value::Integer(0), Value::Integer(2)]
+        );
+        assert_eq!(rows[0][6], Value::Null);
+
+        assert_eq!(rows[1][1], Value::Text("id".into()));
+        assert_eq!(rows[1][2], Value::Text("PRIMARY".into()));
+        assert_eq!(
+            rows[1][3..6],
+            [Value::Integer(2), Value::Integer(3), Value::Integer(2)]
+        );
+        assert!(matches!(rows[1][6], Value::Timestamp(_)));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{Database, DbError, engine::Value};
+
+/// Caps on the amount of data a database may hold. Writes that would exceed a cap are rejected
+/// with `DbError::StorageFull` instead of silently filling memory and disk. `None` means
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of rows across all tables.
+    pub max_rows: Option<usize>,
+    /// Maximum size of the stored values in memory, in bytes, as `Value::approximate_size`
+    /// estimates it. This is not the size of the database file, which depends on the
+    /// persistence format.
+    pub max_bytes: Option<usize>,
+    /// Maximum size of a database file to load, in bytes. Larger files are refused with
+    /// `DbError::StorageFull` before they are read into memory.
+    pub max_load_bytes: Option<usize>,
+}
+
+impl Limits {
+    /// Parses a byte size such as `500000`, `64k`, `10m` or `2g` (binary multiples).
+    pub fn parse_size(s: &str) -> Result<usize, String> {
+        let invalid = || format!("Invalid size: {}", s);
+        let lower = s.trim().to_lowercase();
+        let (digits, multiplier) = match lower.char_indices().last() {
+            Some((i, 'k')) => (&lower[..i], 1 << 10),
+            Some((i, 'm')) => (&lower[..i], 1 << 20),
+            Some((i, 'g')) => (&lower[..i], 1 << 30),
+            _ => (lower.as_str(), 1),
+        };
+        let amount: usize = digits.parse().map_err(|_| invalid())?;
+        amount.checked_mul(multiplier).ok_or_else(invalid)
+    }
+}
+
+impl Database {
+    /// Number of rows stored across all tables.
+    pub fn total_rows(&self) -> usize {
+        self.tables.values().map(|t| t.rows.len()).sum()
+    }
+
+    /// Approximate size of all stored values, in bytes.
+    pub fn data_size(&self) -> usize {
+        self.tables.values().map(|t| t.approx_bytes).sum()
+    }
+
+    /// How many more bytes of values fit under `max_bytes`, if it is set.
+    pub(crate) fn bytes_left(&self) -> Option<usize> {
+        let max_bytes = self.limits.max_bytes?;
+        Some(max_bytes.saturating_sub(self.data_size()))
+    }
+
+    /// Rejects new rows that would together push the database past its limits.
+    pub(crate) fn check_capacity(&self, rows: &[Vec<Value>]) -> Result<(), DbError> {
+        if let Some(max_rows) = self.limits.max_rows
+            && self.total_rows() + rows.len() > max_rows
+        {
+            return Err(DbError::StorageFull(format!(
+                "row limit of {} reached",
+                max_rows
+            )));
+        }
+
+        if let Some(max_bytes) = self.limits.max_bytes {
+            let rows_bytes: usize = rows.iter().flatten().map(Value::approximate_size).sum();
+            if self.data_size() + rows_bytes > max_bytes {
+                return Err(DbError::StorageFull(format!(
+                    "size limit of {} bytes reached ({} bytes used)",
+                    max_bytes,
+                    self.data_size()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::ExecutionResult, parser::parse};
+
+    #[test]
+    fn test_storage_limits() {
+        assert_eq!(Limits::parse_size("64k"), Ok(65536));
+        assert!(Limits::parse_size("lots").is_err());
+
+        let mut db = Database::new();
+        db.limits.max_rows = Some(2);
+        db.execute(parse("CREATE TABLE t (id INT)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO t VALUES (1)").unwrap())
+            .unwrap();
+        // A batch that doesn't fit is rejected as a whole.
+        let full = db.execute(parse("INSERT INTO t VALUES (2), (3)").unwrap());
+        assert!(matches!(full, Err(DbError::StorageFull(_))));
+        assert_eq!(db.total_rows(), 1);
+        db.execute(parse("INSERT INTO t VALUES (2)").unwrap())
+            .unwrap();
+        let full = db.execute(parse("INSERT INTO t VALUES (3)").unwrap());
+        assert!(matches!(full, Err(DbError::StorageFull(_))));
+        assert_eq!(db.total_rows(), 2);
+
+        db.limits = Limits {
+            max_bytes: Some(db.data_size()),
+            ..Limits::default()
+        };
+        let full = db.execute(parse("INSERT INTO t VALUES (3)").unwrap());
+        assert!(matches!(full, Err(DbError::StorageFull(_))));
+
+        let ExecutionResult::Data { rows, .. } = db.show_status() else {
+            panic!("expected data");
+        };
+        assert!(rows.contains(&vec![Value::Text("total_rows".into()), Value::Integer(2)]));
+        assert!(rows.contains(&vec![Value::Text("max_rows".into()), Value::Null]));
+
+        // An upsert's new row fits, but not what `DO UPDATE` makes of the stored one.
+        db.limits = Limits::default();
+        db.execute(parse("CREATE TABLE s (id INT PRIMARY, name TEXT)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO s VALUES (1, 'aaaaaaaaaaaaaaaaaaaa')").unwrap())
+            .unwrap();
+        let bytes = db.data_size();
+        db.limits.max_bytes = Some(bytes + 30);
+        let sql = "INSERT INTO s VALUES (1, 'b') ON CONFLICT (id) DO UPDATE SET name = CONCAT(name, name)";
+        let full = db.execute(parse(sql).unwrap());
+        assert!(matches!(full, Err(DbError::StorageFull(_))));
+        assert_eq!(db.data_size(), bytes);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+
+use crate::{
+    Database,
+    engine::{Table, Value, relation::Relation},
+    parser::{AggregateFunction, Expr, SelectItem},
+};
+
+/// What the values of a `MASKED` column read as in query results.
+pub const MASK: &str = "****";
+
+/// The headers under which the masked columns of `table` appear once scanned, prefixed with
+/// `qualifier` when the relation is qualified.
+pub(crate) fn masked_headers<'a>(
+    table: &'a Table,
+    qualifier: Option<&'a str>,
+) -> impl Iterator<Item = String> + 'a {
+    table
+        .columns
+        .iter()
+        .filter(|c| c.is_masked)
+        .map(move |c| match qualifier {
+            Some(qualifier) => format!("{}.{}", qualifier, c.name),
+            None => c.name.clone(),
+        })
+}
+
+/// Which outputs of projecting `relation` with `items`, laid out as `Relation::project` does,
+/// are computed from a `masked` header. `db` finds the outer columns subqueries refer to.
+pub(crate) fn masked_outputs(
+    db: &Database,
+    relation: &Relation,
+    items: &[SelectItem],
+    masked: &HashSet<String>,
+) -> Vec<bool> {
+    let mut outputs = Vec::new();
+    for item in items {
+        match item {
+            SelectItem::Wildcard => {
+                outputs.extend(relation.headers.iter().map(|h| masked.contains(h)));
+            }
+            SelectItem::Expr { expr, .. } => outputs.push(reveals(db, expr, relation, masked)),
+        }
+    }
+    outputs
+}
+
+/// Replaces the values of the `masked` outputs of `relation`. `NULL` stays `NULL`.
+pub(crate) fn mask(relation: &mut Relation, masked: &[bool]) {
+    for row in &mut relation.rows {
+        for (value, _) in row.iter_mut().zip(masked).filter(|(_, m)| **m) {
+            if *value != Value::Null {
+                *value = Value::Text(MASK.into());
+            }
+        }
+    }
+}
+
+/// Whether `expr` depends on the value of a masked column. `COUNT` does not, nor does
+/// `EXISTS`, which only filters. A subquery masks the columns of its own tables, but not
+/// those it takes from `relation`; when they can't be told, it is masked.
+fn reveals(db: &Database, expr: &Expr, relation: &Relation, masked: &HashSet<String>) -> bool {
+    let reveals = |e: &Expr| reveals(db, e, relation, masked);
+    match expr {
+        Expr::Column(name) => relation
+            .column_index(name)
+            .is_ok_and(|i| masked.contains(&relation.headers[i])),
+        Expr::Subquery(subquery) => db
+            .outer_references(subquery, relation)
+            .map_or(true, |refs| {
+                refs.iter().any(|&i| masked.contains(&relation.headers[i]))
+            }),
+        Expr::Literal(_) | Expr::Variable(_) | Expr::Exists(_) => false,
+        Expr::Aggregate {
+            func: AggregateFunction::Count,
+            ..
+        } => false,
+        Expr::Aggregate { arg, .. } => arg.as_deref().is_some_and(reveals),
+        Expr::Function { args, .. } => args.iter().any(reveals),
+        Expr::Binary { left, right, .. } => reveals(left) || reveals(right),
+        Expr::Not(inner)
+        | Expr::Negate(inner)
+        | Expr::IsNull { expr: inner, .. }
+        | Expr::Cast { expr: inner, .. } => reveals(inner),
+        Expr::InList { expr, list, .. } => reveals(expr) || list.iter().any(reveals),
+        // Ranks give away the order of the values they are computed from.
+        Expr::Window {
+            partition_by,
+            order_by,
+            ..
+        } => partition_by
+            .iter()
+            .chain(order_by.iter().map(|o| &o.column))
+            .any(|c| reveals(&Expr::Column(c.clone()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Database,
+        engine::{ExecutionResult, session::Session},
+        parser::parse,
+    };
+
+    fn rows(db: &mut Database, sql: &str, session: &mut Session) -> Vec<Vec<Value>> {
+        let ExecutionResult::Data { rows, .. } = db
+            .execute_with_session(parse(sql).unwrap(), session)
+            .unwrap()
+        else {
+            panic!("expected data");
+        };
+        rows
+    }
+
+    #[test]
+    fn test_masked_columns() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, email TEXT MASKED, team_id INT)",
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'ada@example.com', 1)",
+            "INSERT INTO teams VALUES (1, 'core')",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let users = db.tables.get_mut("users").unwrap();
+        users
+            .insert_row(vec![Value::Integer(2), Value::Null, Value::Integer(1)])
+            .unwrap();
+        let mask = || Value::Text(MASK.into());
+        let mut session = Session::new("martin");
+
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM users WHERE id = 1", &mut session),
+            [[Value::Integer(1), mask(), Value::Integer(1)]]
+        );
+        // Filters see the real value, NULL stays NULL and counting reveals nothing.
+        let sql = "SELECT u.email, t.name FROM users u JOIN teams t ON u.team_id = t.id \
+                   WHERE u.email = 'ada@example.com'";
+        assert_eq!(
+            rows(&mut db, sql, &mut session),
+            [[mask(), Value::Text("core".into())]]
+        );
+        assert_eq!(
+            rows(
+                &mut db,
+                "SELECT email FROM users WHERE id = 2",
+                &mut session
+            ),
+            [[Value::Null]]
+        );
+        let sql = "SELECT COUNT(email), MAX(email) FROM users";
+        assert_eq!(
+            rows(&mut db, sql, &mut session),
+            [[Value::Integer(1), mask()]]
+        );
+
+        // A correlated subquery can't hand out the outer masked column either.
+        let sql = "SELECT (SELECT u.email FROM teams t LIMIT 1), \
+                   (SELECT t.name FROM teams t WHERE t.id = u.team_id) FROM users u WHERE id = 1";
+        assert_eq!(
+            rows(&mut db, sql, &mut session),
+            [[mask(), Value::Text("core".into())]]
+        );
+
+        session.unmask = true;
+        assert_eq!(
+            rows(
+                &mut db,
+                "SELECT email FROM users WHERE id = 1",
+                &mut session
+            ),
+            [[Value::Text("ada@example.com".into())]]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{
+    Database, DbError,
+    engine::{Column, Value, datetime::format_timestamp, schedules::unix_now},
+    parser::parse_script,
+};
+
+/// The table recording which migrations were applied to a database, and when.
+pub const MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// A named SQL script that moves the database from one version of its schema to the next.
+/// Statements are separated by `;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub name: String,
+    pub sql: String,
+}
+
+impl Migration {
+    pub fn new(name: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            sql: sql.into(),
+        }
+    }
+
+    /// Reads the `.sql` files of `dir` in file name order, e.g. `001_create_users.sql`, each
+    /// named after its file without the extension.
+    pub fn load_dir(dir: &Path) -> Result<Vec<Migration>, DbError> {
+        let read_failed = |e: std::io::Error| {
+            DbError::IoError(format!(
+                "Could not read migrations in {}: {}",
+                dir.display(),
+                e
+            ))
+        };
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_failed)? {
+            let path = entry.map_err(read_failed)?.path();
+            if path.extension().is_some_and(|ext| ext == "sql") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let sql = std::fs::read_to_string(&path).map_err(read_failed)?;
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                Ok(Migration::new(name, sql))
+            })
+            .collect()
+    }
+}
+
+impl Database {
+    /// Adds a migration to apply after the ones registered before it. Registering the same
+    /// migration again does nothing, so the registrations can run on every start.
+    pub fn register_migration(&mut self, migration: Migration) -> Result<(), DbError> {
+        match self.migrations.iter().find(|m| m.name == migration.name) {
+            Some(existing) if *existing == migration => Ok(()),
+            Some(_) => Err(DbError::MigrationAlreadyExists(migration.name)),
+            None => {
+                self.migrations.push(migration);
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies the registered migrations not yet recorded in `schema_migrations`, in order,
+    /// and returns their names.
+    ///
+    /// Each migration is all-or-nothing: if one of its statements fails, every change it made
+    /// is undone and the remaining migrations are not attempted. Migrations applied before it
+    /// stay applied.
+    pub fn migrate(&mut self) -> Result<Vec<String>, DbError> {
+        if !self.tables.contains_key(MIGRATIONS_TABLE) {
+            let column = |name: &str, is_primary| Column {
+                name: name.into(),
+                data_type: "TEXT".into(),
+                is_primary,
+                is_unique: false,
+                comment: None,
+                is_masked: false,
+                max_length: None,
+                is_not_null: false,
+            };
+            let columns = vec![column("name", true), column("applied_at", false)];
+            self.create_table(MIGRATIONS_TABLE.into(), columns)?;
+            self.mark_dirty();
+        }
+
+        let applied: HashSet<&Value> = self.tables[MIGRATIONS_TABLE]
+            .rows
+            .iter()
+            .map(|row| &row[0])
+            .collect();
+        let pending: Vec<Migration> = self
+            .migrations
+            .iter()
+            .filter(|m| !applied.contains(&Value::Text(m.name.clone())))
+            .cloned()
+            .collect();
+
+        let mut names = Vec::with_capacity(pending.len());
+        for migration in pending {
+            self.atomically(|db| db.apply_migration(&migration))
+                .map_err(|err| DbError::MigrationFailed(migration.name.clone(), err.to_string()))?;
+            names.push(migration.name);
+        }
+        Ok(names)
+    }
+
+    fn apply_migration(&mut self, migration: &Migration) -> Result<(), DbError> {
+        for statement in parse_script(&migration.sql)? {
+            self.execute(statement)?;
+        }
+        let record = vec![
+            Value::Text(migration.name.clone()),
+            Value::Text(format_timestamp(unix_now() as i64)),
+        ];
+        self.tables
+            .get_mut(MIGRATIONS_TABLE)
+            .ok_or_else(|| DbError::TableNotFound(MIGRATIONS_TABLE.into()))?
+            .insert_row(record)?;
+        self.mark_dirty();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let mut db = Database::new();
+        let initial = Migration::new(
+            "001_users",
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT);\n\
+             INSERT INTO users VALUES (1, 'semi;colon');",
+        );
+        db.register_migration(initial.clone()).unwrap();
+        db.register_migration(initial).unwrap();
+        assert!(matches!(
+            db.register_migration(Migration::new("001_users", "SELECT 1")),
+            Err(DbError::MigrationAlreadyExists(_))
+        ));
+        assert_eq!(db.migrate().unwrap(), ["001_users"]);
+        assert!(db.migrate().unwrap().is_empty());
+        assert_eq!(
+            db.tables["users"].rows[0][1],
+            Value::Text("semi;colon".into())
+        );
+
+        // The failing INSERT takes the table created before it down with it.
+        db.register_migration(Migration::new(
+            "002_teams",
+            "CREATE TABLE teams (id INT); INSERT INTO users VALUES (1, 'dup')",
+        ))
+        .unwrap();
+        let failed = db.migrate();
+        assert!(matches!(failed, Err(DbError::MigrationFailed(name, _)) if name == "002_teams"));
+        assert!(!db.tables.contains_key("teams"));
+        assert_eq!(db.tables[MIGRATIONS_TABLE].rows.len(), 1);
+    }
+}
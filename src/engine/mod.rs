@@ -1,20 +1,187 @@
-use axum::extract::State;
+mod aggregate;
+mod analyze;
+pub mod copy;
+pub mod datetime;
+pub mod decimal;
+mod eval;
+pub mod foreign_keys;
+pub mod handle;
+mod index_scan;
+pub mod index_stats;
+pub mod limits;
+pub mod masking;
+pub mod migrations;
+pub mod plan;
+pub mod query_log;
+pub mod real;
+mod relation;
+mod rewrite;
+pub mod saved_queries;
+pub mod schedules;
+pub mod schema_diff;
+pub mod secondary_index;
+pub mod session;
+pub mod statistics;
+pub mod status;
+mod subquery;
+pub mod table_functions;
+pub mod trace;
+pub mod transaction;
+pub mod triggers;
+pub mod type_export;
+pub mod upsert;
+pub mod virtual_table;
+mod window;
+
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::{
     DbError,
-    parser::{JoinDefinition, Statement},
+    error::syntax_error::SyntaxError,
+    parser::{
+        AlterAction, CommentTarget, Hint, JoinCondition, JoinDefinition, JoinKind,
+        PreparedStatement, SelectStatement, Statement, StatementCache, is_column_reference,
+    },
+    storage::{JsonStyle, Storage},
 };
+pub use datetime::{Date, Timestamp};
+pub use decimal::Decimal;
+use decimal::DecimalType;
+use foreign_keys::ForeignKey;
+use index_scan::IndexProbe;
+use limits::Limits;
+use migrations::Migration;
+use query_log::QueryLog;
+pub use real::Real;
+use relation::Relation;
+use schedules::Schedule;
+use session::Session;
+use statistics::ColumnStats;
+use status::Stats;
+use trace::{IndexLookup, QueryMemory, QueryTrace};
+use triggers::{Trigger, TriggerEvent};
+use upsert::UpsertCounts;
+use virtual_table::VirtualTable;
 
 /// Supported primitive data types for database values.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Integer(i32),
+    /// A 64-bit integer, for values beyond the range of `Integer` and `BIGINT` columns.
+    BigInt(i64),
+    Real(Real),
+    /// An exact decimal, as stored in `DECIMAL` columns.
+    Decimal(Decimal),
+    Date(Date),
+    Timestamp(Timestamp),
     Text(String),
     Null,
 }
 
+/// Values of different types are ordered `Null` < numbers < dates and timestamps < `Text`,
+/// so sorting a column with mixed contents is always deterministic. Integers, reals and
+/// decimals compare as numbers, a date as the timestamp of its midnight, and within a type the
+/// natural ordering applies.
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn rank(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Integer(_) | Value::BigInt(_) | Value::Real(_) | Value::Decimal(_) => 1,
+                Value::Date(_) | Value::Timestamp(_) => 2,
+                Value::Text(_) => 3,
+            }
+        }
+
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::BigInt(a), Value::BigInt(b)) => a.cmp(b),
+            (Value::Integer(a), Value::BigInt(b)) => i64::from(*a).cmp(b),
+            (Value::BigInt(a), Value::Integer(b)) => a.cmp(&i64::from(*b)),
+            (Value::Real(a), Value::Real(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Real(b)) => b.cmp_integer(i64::from(*a)).reverse(),
+            (Value::Real(a), Value::Integer(b)) => a.cmp_integer(i64::from(*b)),
+            (Value::BigInt(a), Value::Real(b)) => b.cmp_integer(*a).reverse(),
+            (Value::Real(a), Value::BigInt(b)) => a.cmp_integer(*b),
+            (Value::Decimal(a), Value::Decimal(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Decimal(b)) => b.cmp_integer(i64::from(*a)).reverse(),
+            (Value::Decimal(a), Value::Integer(b)) => a.cmp_integer(i64::from(*b)),
+            (Value::BigInt(a), Value::Decimal(b)) => b.cmp_integer(*a).reverse(),
+            (Value::Decimal(a), Value::BigInt(b)) => a.cmp_integer(*b),
+            (Value::Real(a), Value::Decimal(b)) => b.cmp_real(*a).reverse(),
+            (Value::Decimal(a), Value::Real(b)) => a.cmp_real(*b),
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::Date(a), Value::Timestamp(b)) => a.at_midnight().cmp(b),
+            (Value::Timestamp(a), Value::Date(b)) => a.cmp(&b.at_midnight()),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+}
+
+/// Equal when they compare equal, so `1` and `1.0` are the same key in an index.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+/// Numbers hash as their `f64` and dates as the timestamp of their midnight, following
+/// `PartialEq`.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Integer(n) => (1u8, f64::from(*n).to_bits()).hash(state),
+            Value::BigInt(n) => (1u8, (*n as f64).to_bits()).hash(state),
+            Value::Real(r) => (1u8, r.get().to_bits()).hash(state),
+            Value::Decimal(d) => (1u8, d.to_f64().to_bits()).hash(state),
+            Value::Date(d) => (2u8, d.at_midnight()).hash(state),
+            Value::Timestamp(t) => (2u8, t).hash(state),
+            Value::Text(s) => (3u8, s).hash(state),
+            Value::Null => 0u8.hash(state),
+        }
+    }
+}
+
+impl Value {
+    /// The name of the value's type, as in a column declaration: `INT`, `TEXT` and so on.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "INT",
+            Value::BigInt(_) => "BIGINT",
+            Value::Real(_) => "REAL",
+            Value::Decimal(_) => "DECIMAL",
+            Value::Date(_) => "DATE",
+            Value::Timestamp(_) => "TIMESTAMP",
+            Value::Text(_) => "TEXT",
+            Value::Null => "NULL",
+        }
+    }
+
+    /// Rough heap + inline footprint of the value, used for reporting and size limits.
+    pub fn approximate_size(&self) -> usize {
+        match self {
+            Value::Text(s) => std::mem::size_of::<Value>() + s.capacity(),
+            _ => std::mem::size_of::<Value>(),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Defines the schema of a table column including constraints.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Column {
@@ -22,6 +189,45 @@ pub struct Column {
     pub data_type: String,
     pub is_primary: bool,
     pub is_unique: bool,
+    // Set by `COMMENT ON COLUMN`.
+    #[serde(default)]
+    pub comment: Option<String>,
+    // Declared `MASKED`: selected values read as `masking::MASK` unless the session unmasks.
+    #[serde(default)]
+    pub is_masked: bool,
+    // Declared `VARCHAR(n)`: longer text is rejected on write.
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    // Declared `NOT NULL`. Primary key columns reject `NULL` as well.
+    #[serde(default)]
+    pub is_not_null: bool,
+}
+
+impl Column {
+    /// Whether `value` may be stored in the column, once converted by `Table::convert_row`.
+    /// `NULL` fits every column.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match (self.data_type.as_str(), value) {
+            (_, Value::Null) => true,
+            ("INT" | "INTEGER", Value::Integer(_))
+            | ("BIGINT", Value::BigInt(_))
+            | ("REAL", Value::Real(_))
+            | ("DATE", Value::Date(_))
+            | ("TIMESTAMP", Value::Timestamp(_))
+            | ("TEXT" | "VARCHAR", Value::Text(_)) => true,
+            (data_type, value) => {
+                DecimalType::parse(data_type).is_some() && matches!(value, Value::Decimal(_))
+            }
+        }
+    }
+
+    /// The type as written in `CREATE TABLE`, such as `VARCHAR(255)`.
+    pub fn declared_type(&self) -> String {
+        match self.max_length {
+            Some(n) => format!("{}({})", self.data_type, n),
+            None => self.data_type.clone(),
+        }
+    }
 }
 
 /// The core data structure for storing records and managing indexes.
@@ -31,10 +237,35 @@ pub struct Table {
     pub columns: Vec<Column>,
     pub rows: Vec<Vec<Value>>,
 
+    // Set by `COMMENT ON TABLE`.
+    #[serde(default)]
+    pub comment: Option<String>,
+
     // We will use this for fast lookups(indexing) for now. it maps columnIndex -> Hashset of
     // existing values
     #[serde(skip)]
     pub indexes: HashMap<usize, HashSet<Value>>,
+
+    // Indexes added with `CREATE INDEX`, by name, and the column each one covers.
+    #[serde(default)]
+    pub secondary_indexes: BTreeMap<String, String>,
+
+    // Declared with `REFERENCES` or `FOREIGN KEY`.
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
+
+    // Collected by `ANALYZE`, by column name; a column missing here has not been analyzed.
+    #[serde(default)]
+    pub statistics: BTreeMap<String, ColumnStats>,
+
+    // The entries of the secondary indexes: column position -> value -> positions of the rows
+    // holding it, in ascending order. Rebuilt from `secondary_indexes` after loading.
+    #[serde(skip)]
+    pub(crate) row_index: HashMap<usize, HashMap<Value, Vec<usize>>>,
+
+    // Approximate size of the stored rows, kept up to date for the storage limits.
+    #[serde(skip)]
+    pub(crate) approx_bytes: usize,
 }
 
 impl Table {
@@ -50,27 +281,76 @@ impl Table {
             name,
             columns,
             rows: Vec::new(),
+            comment: None,
             indexes,
+            secondary_indexes: BTreeMap::new(),
+            foreign_keys: Vec::new(),
+            statistics: BTreeMap::new(),
+            row_index: HashMap::new(),
+            approx_bytes: 0,
+        }
+    }
+
+    /// Wraps the result of a subquery in `FROM (SELECT ...) AS name` as a table without
+    /// indexes. Qualified headers like `users.id` become plain column names, and each column
+    /// takes the type of its first non-NULL value. Two columns ending up with the same name,
+    /// as `a.id` and `b.id` do, are rejected: neither could be referred to.
+    pub(crate) fn derived(
+        name: String,
+        headers: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    ) -> Result<Self, DbError> {
+        let columns: Vec<Column> = headers
+            .into_iter()
+            .enumerate()
+            .map(|(i, header)| {
+                let data_type = value_type(&rows, i);
+                let name = match header.rsplit_once('.') {
+                    Some((_, column)) if is_column_reference(&header) => column.to_string(),
+                    _ => header,
+                };
+                Column {
+                    name,
+                    data_type: data_type.into(),
+                    is_primary: false,
+                    is_unique: false,
+                    comment: None,
+                    is_masked: false,
+                    max_length: None,
+                    is_not_null: false,
+                }
+            })
+            .collect();
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.name == column.name) {
+                return Err(DbError::ColumnAlreadyExists(column.name.clone()));
+            }
         }
+        let mut table = Table::new(name, columns);
+        table.approx_bytes = rows.iter().flatten().map(Value::approximate_size).sum();
+        table.rows = rows;
+        Ok(table)
     }
 
     /// Inserts a new row into the table after validating constraints.
     /// Returns DbError::UniqueViolation if a PRIMARY or UNIQUE constraint is broken.
-    pub fn insert_row(&mut self, row: Vec<Value>) -> Result<(), DbError> {
+    pub fn insert_row(&mut self, mut row: Vec<Value>) -> Result<(), DbError> {
         //validate columns count
         if row.len() != self.columns.len() {
             return Err(DbError::ParseError("Columns count mismatch".into()));
         }
+        self.convert_row(&mut row)?;
 
         //check constraints primary or unique
         //If the column has an index (i.e. it is primary or unique)
         // Check if the value already exists
         // If yes → reject the insert
         for (i, value) in row.iter().enumerate() {
-            if let Some(index) = self.indexes.get_mut(&i) {
-                if index.contains(value) {
-                    return Err(DbError::UniqueViolation(self.columns[i].name.clone()));
-                }
+            if let Some(index) = self.indexes.get(&i)
+                && *value != Value::Null
+                && index.contains(value)
+            {
+                return Err(DbError::UniqueViolation(self.columns[i].name.clone()));
             }
         }
 
@@ -81,14 +361,152 @@ impl Table {
             }
         }
 
+        self.approx_bytes += row.iter().map(Value::approximate_size).sum::<usize>();
         self.rows.push(row);
+        self.index_row(self.rows.len() - 1);
+        Ok(())
+    }
+
+    /// Converts text written into `DATE` and `TIMESTAMP` columns, such as `'2024-01-31'`, to
+    /// dates and timestamps, dates written into `TIMESTAMP` columns to their midnight, and
+    /// integers written into `BIGINT` and `REAL` columns to those types. Numbers and numeric
+    /// text written into `DECIMAL(p,s)` columns become decimals rounded to `s` places, and
+    /// must have at most `p` digits. Text longer than a `VARCHAR(n)` column allows is
+    /// rejected, and so is any value left of another type than its column, or `NULL` in a
+    /// `NOT NULL` or primary key column.
+    pub(crate) fn convert_row(&self, row: &mut [Value]) -> Result<(), DbError> {
+        for (column, value) in self.columns.iter().zip(row.iter_mut()) {
+            if let (Some(max_length), Value::Text(text)) = (column.max_length, &*value) {
+                let length = text.chars().count();
+                if length > max_length {
+                    return Err(DbError::ValueTooLong {
+                        column: column.name.clone(),
+                        max_length,
+                        length,
+                    });
+                }
+            }
+            let converted = match (column.data_type.as_str(), &*value) {
+                (_, Value::Null) => continue,
+                ("DATE", Value::Text(text)) => Date::parse(text).map(Value::Date),
+                ("TIMESTAMP", Value::Text(text)) => Timestamp::parse(text).map(Value::Timestamp),
+                ("TIMESTAMP", Value::Date(date)) => Some(Value::Timestamp(date.at_midnight())),
+                ("BIGINT", Value::Integer(n)) => Some(Value::BigInt((*n).into())),
+                ("REAL", Value::Integer(n)) => Real::new(f64::from(*n)).map(Value::Real),
+                ("REAL", Value::BigInt(n)) => Real::new(*n as f64).map(Value::Real),
+                (data_type, value) => match DecimalType::parse(data_type) {
+                    Some(decimal_type) => Decimal::from_value(value)
+                        .and_then(|d| decimal_type.fit(d))
+                        .map(Value::Decimal),
+                    None => continue,
+                },
+            };
+            *value = converted.ok_or_else(|| {
+                DbError::TypeError(format!(
+                    "Invalid {} for column '{}': {:?}",
+                    column.data_type, column.name, value
+                ))
+            })?;
+        }
+        for (column, value) in self.columns.iter().zip(row.iter()) {
+            if *value == Value::Null && (column.is_not_null || column.is_primary) {
+                return Err(DbError::NotNullViolation(column.name.clone()));
+            }
+            if !column.accepts(value) {
+                return Err(DbError::TypeMismatch {
+                    column: column.name.clone(),
+                    expected: column.declared_type(),
+                    got: value.type_name().to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts several rows as one change: if any of them is rejected, the rows inserted
+    /// before it are removed again, so the table is left exactly as it was.
+    pub fn insert_rows(&mut self, rows: Vec<Vec<Value>>) -> Result<usize, DbError> {
+        let start = self.rows.len();
+        for row in rows {
+            if let Err(err) = self.insert_row(row) {
+                self.truncate_rows(start);
+                return Err(err);
+            }
+        }
+        Ok(self.rows.len() - start)
+    }
+
+    /// Removes the rows from position `len` on, with their index entries. Only used to undo
+    /// `insert_row`, whose values are unique in each index.
+    fn truncate_rows(&mut self, len: usize) {
+        let removed: Vec<Vec<Value>> = self.rows.drain(len..).collect();
+        for (pos, row) in (len..).zip(removed) {
+            for (i, value) in row.iter().enumerate() {
+                if let Some(index) = self.indexes.get_mut(&i) {
+                    index.remove(value);
+                }
+            }
+            self.unindex_row(pos, &row);
+            self.approx_bytes -= row.iter().map(Value::approximate_size).sum::<usize>();
+        }
+    }
+
+    /// Removes a column from the schema and from every stored row.
+    /// Indexes are keyed by column position, so they are rebuilt afterwards.
+    pub fn drop_column(&mut self, name: &str) -> Result<(), DbError> {
+        let idx = self.column_index(name)?;
+
+        self.columns.remove(idx);
+        for row in &mut self.rows {
+            row.remove(idx);
+        }
+        // Secondary indexes and foreign keys go with their column.
+        self.secondary_indexes.retain(|_, column| column != name);
+        self.foreign_keys.retain(|key| key.column != name);
+        self.statistics.remove(name);
+
+        self.rebuild_indexes();
         Ok(())
     }
 
-    /// Reconstructs the in-memory HashSet indexes from the existing rows.
-    /// This is called after loading the database from JSON.
+    /// Renames a column, rejecting the change if the new name is already taken.
+    pub fn rename_column(&mut self, from: &str, to: String) -> Result<(), DbError> {
+        let idx = self.column_index(from)?;
+        if self.columns.iter().any(|c| c.name == to) {
+            return Err(DbError::ColumnAlreadyExists(to));
+        }
+
+        for column in self.secondary_indexes.values_mut() {
+            if column == from {
+                *column = to.clone();
+            }
+        }
+        if let Some(stats) = self.statistics.remove(from) {
+            self.statistics.insert(to.clone(), stats);
+        }
+        self.columns[idx].name = to;
+        Ok(())
+    }
+
+    /// Finds the position of a column by name.
+    pub fn column_index(&self, name: &str) -> Result<usize, DbError> {
+        self.columns
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| DbError::ColumnNotFound(name.to_string()))
+    }
+
+    /// Reconstructs the in-memory HashSet indexes, the entries of the secondary indexes and
+    /// the size estimate from the existing rows. This is called after loading the database
+    /// from JSON.
     pub fn rebuild_indexes(&mut self) {
         self.indexes.clear();
+        self.approx_bytes = self
+            .rows
+            .iter()
+            .flatten()
+            .map(Value::approximate_size)
+            .sum();
 
         //init empty sets for columns that need indexing
         for (i, col) in self.columns.iter().enumerate() {
@@ -105,12 +523,69 @@ impl Table {
                 }
             }
         }
+        self.rebuild_row_index();
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Database {
     pub tables: HashMap<String, Table>,
+
+    // Named SELECT statements, stored as SQL text and persisted with the tables.
+    #[serde(default)]
+    pub saved_queries: BTreeMap<String, String>,
+
+    // Statements run periodically by the web server, persisted with the tables.
+    #[serde(default)]
+    pub schedules: BTreeMap<String, Schedule>,
+
+    // Statements run after changes to a table, by trigger name, persisted with the tables.
+    #[serde(default)]
+    pub triggers: BTreeMap<String, Trigger>,
+
+    // External data sources registered at runtime. They are not part of the persisted state.
+    #[serde(skip)]
+    pub virtual_tables: HashMap<String, Arc<dyn VirtualTable>>,
+
+    // Caps on the amount of stored data, configured by the embedding application.
+    #[serde(skip)]
+    pub limits: Limits,
+
+    // Layout of JSON database files, configured by the embedding application.
+    #[serde(skip)]
+    pub json_style: JsonStyle,
+
+    // How long a SELECT may run before it is stopped, configured by the embedding application.
+    #[serde(skip)]
+    pub statement_timeout: Option<Duration>,
+
+    // Runtime counters reported by `SHOW STATUS`.
+    #[serde(skip)]
+    pub(crate) stats: Stats,
+
+    // Migrations registered by the embedding application, applied by `migrate`.
+    #[serde(skip)]
+    migrations: Vec<Migration>,
+
+    // Parsed statements reused by `parse_cached`.
+    #[serde(skip)]
+    statement_cache: StatementCache,
+
+    // Running and recent statements, listed by `system.queries`.
+    #[serde(skip)]
+    query_log: QueryLog,
+
+    // Set by every change to persisted state and cleared by `flush`.
+    #[serde(skip)]
+    dirty: bool,
+
+    // How many triggers are firing one inside the other right now.
+    #[serde(skip)]
+    trigger_depth: usize,
+
+    // The session whose transaction is open. No other session may write until it ends.
+    #[serde(skip)]
+    pub(crate) transaction_owner: Option<u64>,
 }
 
 /// Possible return values from an executed SQL statement.
@@ -119,24 +594,148 @@ pub enum ExecutionResult {
     Data {
         headers: Vec<String>,
         rows: Vec<Vec<Value>>,
+        /// The type of each column, such as `INT`, `TEXT` or a declared `TIMESTAMP`.
+        types: Vec<String>,
     },
 }
 
+impl ExecutionResult {
+    /// Rows whose column types are inferred from the values themselves.
+    pub fn data(headers: Vec<String>, rows: Vec<Vec<Value>>) -> Self {
+        let types = (0..headers.len())
+            .map(|i| value_type(&rows, i).to_string())
+            .collect();
+        ExecutionResult::Data {
+            headers,
+            rows,
+            types,
+        }
+    }
+
+    /// Rows of a catalog listing, whose columns are given as `(name, type)` pairs.
+    pub(crate) fn listing(columns: &[(&str, &str)], rows: Vec<Vec<Value>>) -> Self {
+        ExecutionResult::Data {
+            headers: columns.iter().map(|(name, _)| name.to_string()).collect(),
+            rows,
+            types: columns.iter().map(|(_, t)| t.to_string()).collect(),
+        }
+    }
+}
+
+/// The type of the first non-NULL value in column `i`, `TEXT` when there is none.
+fn value_type(rows: &[Vec<Value>], i: usize) -> &'static str {
+    rows.iter()
+        .map(|row| &row[i])
+        .find(|v| **v != Value::Null)
+        .map_or("TEXT", Value::type_name)
+}
+
 impl Database {
     pub fn new() -> Self {
         Self {
             tables: HashMap::new(),
+            saved_queries: BTreeMap::new(),
+            schedules: BTreeMap::new(),
+            triggers: BTreeMap::new(),
+            virtual_tables: HashMap::new(),
+            limits: Limits::default(),
+            json_style: JsonStyle::default(),
+            statement_timeout: None,
+            stats: Stats::default(),
+            migrations: Vec::new(),
+            statement_cache: StatementCache::default(),
+            query_log: QueryLog::default(),
+            dirty: false,
+            trigger_depth: 0,
+            transaction_owner: None,
         }
     }
 
+    /// Whether persisted state changed since the last successful `flush`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Records that persisted state changed and needs to be written by the next `flush`.
+    pub(crate) fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Writes the database to `storage` if anything changed since the last flush.
+    pub fn flush(&mut self, storage: &Storage) -> Result<(), DbError> {
+        if self.dirty {
+            storage.save(self)?;
+            self.dirty = false;
+            self.stats.last_save = Some(schedules::unix_now());
+        }
+        Ok(())
+    }
+
     pub fn create_table(&mut self, name: String, columns: Vec<Column>) -> Result<(), DbError> {
-        if self.tables.contains_key(&name) {
+        if self.tables.contains_key(&name) || self.virtual_tables.contains_key(&name) {
             return Err(DbError::TableAlreadyExists(name));
         }
 
         let table = Table::new(name.clone(), columns);
 
         self.tables.insert(name, table);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes a stored table with all its rows.
+    pub fn drop_table(&mut self, name: &str) -> Result<(), DbError> {
+        if self.virtual_tables.contains_key(name) {
+            return Err(DbError::ReadOnlyTable(name.to_string()));
+        }
+        self.tables
+            .remove(name)
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))?;
+        // Its triggers go with it.
+        self.triggers.retain(|_, trigger| trigger.table != name);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Moves a stored table to a new name, rejecting the change if the name is already taken.
+    pub fn rename_table(&mut self, from: &str, to: String) -> Result<(), DbError> {
+        if self.tables.contains_key(&to) || self.virtual_tables.contains_key(&to) {
+            return Err(DbError::TableAlreadyExists(to));
+        }
+        let mut table = self
+            .tables
+            .remove(from)
+            .ok_or_else(|| DbError::TableNotFound(from.to_string()))?;
+        table.name = to.clone();
+        self.tables.insert(to, table);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Sets or, with `None`, removes the comment documenting a stored table or column.
+    pub fn set_comment(
+        &mut self,
+        target: &CommentTarget,
+        comment: Option<String>,
+    ) -> Result<(), DbError> {
+        let table_name = match target {
+            CommentTarget::Table(table) | CommentTarget::Column { table, .. } => table,
+        };
+        if self.virtual_tables.contains_key(table_name) {
+            return Err(DbError::ReadOnlyTable(table_name.clone()));
+        }
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| DbError::TableNotFound(table_name.clone()))?;
+        match target {
+            CommentTarget::Table(_) => table.comment = comment,
+            CommentTarget::Column { column, .. } => {
+                let i = table.column_index(column)?;
+                table.columns[i].comment = comment;
+            }
+        }
+        self.mark_dirty();
         Ok(())
     }
 
@@ -146,145 +745,911 @@ impl Database {
             .ok_or_else(|| DbError::TableNotFound(name.to_string()))
     }
 
-    /// Dispatches a parsed Statement to the appropriate internal execution logic.
+    /// Registers an external data source under `name` so it can be queried like a table.
+    pub fn register_virtual_table(
+        &mut self,
+        name: String,
+        table: impl VirtualTable + 'static,
+    ) -> Result<(), DbError> {
+        if self.tables.contains_key(&name) || self.virtual_tables.contains_key(&name) {
+            return Err(DbError::TableAlreadyExists(name));
+        }
+        self.virtual_tables.insert(name, Arc::new(table));
+        Ok(())
+    }
+
+    /// Removes a previously registered virtual table.
+    pub fn unregister_virtual_table(&mut self, name: &str) -> Result<(), DbError> {
+        self.virtual_tables
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))
+    }
+
+    /// Resolves a table name for reading, materializing virtual tables and table functions
+    /// into a temporary `Table` so the select and join logic can treat every source the same way.
+    ///
+    /// Stored tables shadow virtual tables, which in turn shadow the built-in table functions.
+    /// Passing `args` always calls a table function.
+    fn scan_table(
+        &self,
+        name: String,
+        args: Option<&[Value]>,
+        session: &Session,
+    ) -> Result<Cow<'_, Table>, DbError> {
+        if let Some(args) = args {
+            return table_functions::call(self, &name, args, session).map(Cow::Owned);
+        }
+
+        if let Some(table) = self.tables.get(&name) {
+            return Ok(Cow::Borrowed(table));
+        }
+
+        let Some(source) = self.virtual_tables.get(&name) else {
+            return table_functions::call(self, &name, &[], session).map(Cow::Owned);
+        };
+
+        let mut table = Table::new(name, source.columns());
+        for row in source.rows()? {
+            if row.len() != table.columns.len() {
+                return Err(DbError::IoError(format!(
+                    "Virtual table '{}' produced a row with {} values, expected {}",
+                    table.name,
+                    row.len(),
+                    table.columns.len()
+                )));
+            }
+            table.rows.push(row);
+        }
+        Ok(Cow::Owned(table))
+    }
+
+    /// Resolves a table name like `scan_table`, but only for its columns: virtual tables and
+    /// table functions come back without rows, so nothing is read or computed.
+    pub(crate) fn table_schema(
+        &self,
+        name: &str,
+        args: Option<&[Value]>,
+    ) -> Result<Cow<'_, Table>, DbError> {
+        if args.is_none() {
+            if let Some(table) = self.tables.get(name) {
+                return Ok(Cow::Borrowed(table));
+            }
+            if let Some(source) = self.virtual_tables.get(name) {
+                return Ok(Cow::Owned(Table::new(name.to_string(), source.columns())));
+            }
+        }
+        table_functions::columns(name)
+            .map(|columns| Cow::Owned(Table::new(name.to_lowercase(), columns)))
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))
+    }
+
+    /// How many rows `scan_table` would produce for `name`, from the row counts of stored
+    /// tables and the arguments of table functions. Only virtual tables that can't tell are read.
+    pub(crate) fn row_count(&self, name: &str, args: Option<&[Value]>) -> Result<usize, DbError> {
+        if args.is_none() {
+            if let Some(table) = self.tables.get(name) {
+                return Ok(table.rows.len());
+            }
+            if let Some(source) = self.virtual_tables.get(name) {
+                return match source.row_count() {
+                    Some(count) => Ok(count),
+                    None => Ok(source.rows()?.count()),
+                };
+            }
+        }
+        table_functions::row_count(self, name, args.unwrap_or_default())
+    }
+
+    /// Parses `sql` through the statement cache, so repeated queries that differ only in
+    /// their literals are parsed once. Hits and misses feed the `cache_hit_rate_pct` status.
+    pub fn parse_cached(&mut self, sql: &str) -> Result<Statement, SyntaxError> {
+        let (statement, hit) = self.statement_cache.parse(sql)?;
+        if hit {
+            self.stats.cache_hits += 1;
+        } else {
+            self.stats.cache_misses += 1;
+        }
+        Ok(statement)
+    }
+
+    /// Parses `sql` once with each `?` standing for a value, to run it again and again with
+    /// `execute_prepared`. Preparing the same SQL again reuses the cached statement.
+    pub fn prepare(&mut self, sql: &str) -> Result<PreparedStatement, String> {
+        let (prepared, hit) = self.statement_cache.prepare(sql)?;
+        if hit {
+            self.stats.cache_hits += 1;
+        } else {
+            self.stats.cache_misses += 1;
+        }
+        Ok(prepared)
+    }
+
+    /// Runs a prepared statement with `params` bound to its placeholders, in order.
+    pub fn execute_prepared(
+        &mut self,
+        prepared: &PreparedStatement,
+        params: &[Value],
+    ) -> Result<ExecutionResult, DbError> {
+        let statement = prepared.bind(params).map_err(DbError::ParseError)?;
+        self.execute(statement)
+    }
+
+    /// Dispatches a parsed Statement to the appropriate internal execution logic,
+    /// using a fresh anonymous session.
     pub fn execute(&mut self, statement: Statement) -> Result<ExecutionResult, DbError> {
+        self.execute_with_session(statement, &mut Session::default())
+    }
+
+    /// Like `execute`, but reads and updates the caller's session state
+    /// (`SET @var`, `CURRENT_USER()`).
+    pub fn execute_with_session(
+        &mut self,
+        statement: Statement,
+        session: &mut Session,
+    ) -> Result<ExecutionResult, DbError> {
+        let sql = query_log::describe(&statement);
+        self.execute_logged(statement, session, sql)
+    }
+
+    /// The statements running now and the last ones that finished. The log can be kept
+    /// (it is shared by its clones) to watch this database from another thread.
+    pub fn query_log(&self) -> &QueryLog {
+        &self.query_log
+    }
+
+    /// Runs `statement` as an entry of the query log, listed under `sql`.
+    pub(crate) fn execute_logged(
+        &mut self,
+        statement: Statement,
+        session: &mut Session,
+        sql: String,
+    ) -> Result<ExecutionResult, DbError> {
+        let id = self.query_log.start(&session.user, sql);
+        let result = self
+            .check_unlocked(Some(session), Some(&statement))
+            .and_then(|()| self.run_statement(statement, session));
+        let error = result.as_ref().err().map(ToString::to_string);
+        self.query_log.finish(id, error);
+        result
+    }
+
+    fn run_statement(
+        &mut self,
+        statement: Statement,
+        session: &mut Session,
+    ) -> Result<ExecutionResult, DbError> {
+        self.stats.queries_executed += 1;
+        self.stats.last_rows_written = 0;
+        self.analyze(&statement)?;
         match statement {
-            Statement::CreateTable { name, columns } => {
-                let engine_colums = columns
+            Statement::CreateTable {
+                name,
+                if_not_exists: true,
+                ..
+            } if self.tables.contains_key(&name) || self.virtual_tables.contains_key(&name) => Ok(
+                ExecutionResult::Message(format!("Table '{}' already exists, skipped", name)),
+            ),
+            Statement::CreateTable {
+                name,
+                columns,
+                foreign_keys,
+                ..
+            } => {
+                let engine_colums: Vec<Column> = columns
                     .into_iter()
                     .map(|c| Column {
                         name: c.name,
                         data_type: c.data_type,
                         is_primary: c.is_primary,
                         is_unique: c.is_unique,
+                        comment: None,
+                        is_masked: c.is_masked,
+                        max_length: c.max_length,
+                        is_not_null: c.is_not_null,
+                    })
+                    .collect();
+                let foreign_keys: Vec<ForeignKey> = foreign_keys
+                    .into_iter()
+                    .map(|k| ForeignKey {
+                        column: k.column,
+                        parent_table: k.parent_table,
+                        parent_column: k.parent_column,
+                        on_delete: k.on_delete,
                     })
                     .collect();
+                self.check_foreign_key_definitions(&name, &engine_colums, &foreign_keys)?;
                 self.create_table(name.clone(), engine_colums)?;
+                self.tables.get_mut(&name).unwrap().foreign_keys = foreign_keys;
                 Ok(ExecutionResult::Message(format!(
                     "Table '{}' created",
                     name
                 )))
             }
-            Statement::Insert { table_name, values } => {
+            Statement::Insert {
+                table_name,
+                rows,
+                on_conflict,
+            } => {
+                if self.virtual_tables.contains_key(&table_name) {
+                    return Err(DbError::ReadOnlyTable(table_name));
+                }
+                // Upserts are checked as if every row were new, and for the growth of the rows
+                // they update as they run.
+                self.check_capacity(&rows)?;
+                let bytes_left = self.bytes_left();
+                // Triggers run after the rows are written; if one fails, the rows go too.
+                let before_triggers = self.has_triggers(&table_name).then(|| self.snapshot());
+                // An upsert can rewrite keys, so its references are checked once it ran, against
+                // a copy of the table to go back to.
+                let upsert_backup = match &on_conflict {
+                    Some(_) if self.has_references(&table_name) => {
+                        self.tables.get(&table_name).cloned()
+                    }
+                    Some(_) => None,
+                    None => {
+                        self.check_references(&table_name, &rows)?;
+                        None
+                    }
+                };
                 let table = self
                     .tables
                     .get_mut(&table_name)
-                    .ok_or_else(|| DbError::TableNotFound(table_name))?;
-                table.insert_row(values)?;
-                Ok(ExecutionResult::Message("1 row inserted.".into()))
+                    .ok_or_else(|| DbError::TableNotFound(table_name.clone()))?;
+                let count = |n: usize| match n {
+                    1 => "1 row".to_string(),
+                    n => format!("{} rows", n),
+                };
+                let (counts, message) = match on_conflict {
+                    None => {
+                        let inserted = table.insert_rows(rows)?;
+                        let counts = UpsertCounts {
+                            inserted,
+                            ..Default::default()
+                        };
+                        (counts, format!("{} inserted.", count(inserted)))
+                    }
+                    Some(on_conflict) => {
+                        let counts = table.upsert_rows(rows, &on_conflict, session, bytes_left)?;
+                        let message = format!(
+                            "{} inserted, {} updated, {} skipped.",
+                            count(counts.inserted),
+                            counts.updated,
+                            counts.skipped
+                        );
+                        (counts, message)
+                    }
+                };
+                let written = counts.inserted + counts.updated;
+                if let Some(backup) = upsert_backup
+                    && let Err(err) = self.check_table_references(&table_name)
+                {
+                    self.tables.insert(table_name, backup);
+                    return Err(err);
+                }
+                self.record_index_writes(&table_name, written);
+                if written > 0 {
+                    self.mark_dirty();
+                }
+                if let Some(snapshot) = before_triggers {
+                    let events = [
+                        (counts.inserted > 0).then_some(TriggerEvent::Insert),
+                        (counts.updated > 0).then_some(TriggerEvent::Update),
+                    ];
+                    let events: Vec<TriggerEvent> = events.into_iter().flatten().collect();
+                    if let Err(err) = self.fire_triggers(&table_name, &events, session) {
+                        self.restore(snapshot);
+                        return Err(err);
+                    }
+                }
+                // Set last, as the statements of the triggers count their own rows.
+                self.stats.last_rows_written = written;
+                Ok(ExecutionResult::Message(message))
             }
 
-            Statement::Select {
-                table_name,
-                columns,
-                join,
-            } => self.handle_select(table_name, columns, join),
-        }
-    }
-
-    /// The core execution engine logic for retrieving and combining data.
-    ///
-    /// ### How it works:
-    /// This function branches into two distinct execution paths based on the query:
-    ///
-    /// #### Path A: Standard Selection (No Join)
-    /// 1. **Column Mapping**: Maps requested column names to their numerical indices in the table.
-    /// 2. **Projection**: Iterates through `table.rows` and creates a new vector containing only
-    ///    the data from the requested indices. This is a linear $O(N)$ operation.
-    ///
-    /// #### Path B: Inner Join (Nested Loop Join)
-    /// 1. **Left/Right Resolution**: Loads both the primary (left) and join (right) tables.
-    /// 2. **Index Lookup**: Finds the indices of the columns specified in the `ON` clause.
-    /// 3. **Join Algorithm**: Implements a **Nested Loop Join**:
-    ///    - Outer Loop: Iterates through every row in the Left Table.
-    ///    - Inner Loop: Iterates through every row in the Right Table.
-    ///    - Comparison: If `left_row[key] == right_row[key]`, the rows are merged.
-    ///    - Complexity: $O(N \times M)$ where $N$ and $M$ are the row counts.
-    /// 4. **Header Merging**: Dynamically generates new headers in the format `table.column`
-    ///    to prevent naming collisions between joined tables.//
-    pub fn handle_select(
-        &self,
-        table_name: String,
-        columns: Vec<String>,
-        join: Option<JoinDefinition>,
-    ) -> Result<ExecutionResult, DbError> {
-        let table = self.get_table(table_name)?;
-
-        //basic select
-        if join.is_none() {
-            let col_indices: Vec<usize> = if columns.contains(&"*".to_string()) {
-                (0..table.columns.len()).collect()
-            } else {
-                columns
-                    .iter()
-                    .map(|name| {
-                        table
-                            .columns
-                            .iter()
-                            .position(|c| &c.name == name)
-                            .ok_or_else(|| DbError::ColumnNotFound(name.clone()))
-                    })
-                    .collect::<Result<Vec<_>, _>>()?
-            };
-
-            let headers = col_indices
-                .iter()
-                .map(|&i| table.columns[i].name.clone())
-                .collect();
-            let rows = table
-                .rows
-                .iter()
-                .map(|row| col_indices.iter().map(|&i| row[i].clone()).collect())
-                .collect();
-            return Ok(ExecutionResult::Data { headers, rows });
-        }
-
-        let join_info = join.unwrap();
-        let left_table = table;
-        let right_table = self.get_table(join_info.table_name)?;
-
-        let left_col_idx = left_table
-            .columns
-            .iter()
-            .position(|c| c.name == join_info.left_column)
-            .ok_or_else(|| DbError::ColumnNotFound(join_info.left_column))?;
-        let right_col_idx = right_table
-            .columns
-            .iter()
-            .position(|c| c.name == join_info.right_column)
-            .ok_or_else(|| DbError::ColumnNotFound(join_info.right_column))?;
-
-        let mut joined_rows = Vec::new();
-        let mut headers = Vec::new();
-
-        // Build headers
-        for c in &left_table.columns {
-            headers.push(format!("{}.{}", left_table.name, c.name));
-        }
-        for c in &right_table.columns {
-            headers.push(format!("{}.{}", right_table.name, c.name));
-        }
-
-        // NESTED LOOP JOIN LOGIC
-        for l_row in &left_table.rows {
-            for r_row in &right_table.rows {
-                if l_row[left_col_idx] == r_row[right_col_idx] {
-                    let mut combined = l_row.clone();
-                    combined.extend(r_row.clone());
-                    joined_rows.push(combined);
+            Statement::DropTable { name, if_exists } => {
+                if !if_exists || self.tables.contains_key(&name) {
+                    self.check_unreferenced(&name, None)?;
+                    self.drop_table(&name)?;
+                    return Ok(ExecutionResult::Message(format!(
+                        "Table '{}' dropped",
+                        name
+                    )));
                 }
+                Ok(ExecutionResult::Message(format!(
+                    "Table '{}' does not exist, skipped",
+                    name
+                )))
             }
-        }
 
-        Ok(ExecutionResult::Data {
-            headers,
-            rows: joined_rows,
-        })
-    }
-}
+            Statement::Select(select) => self.run_select(*select, session),
+            Statement::SetVariable { name, value } => {
+                session.variables.insert(name.clone(), value);
+                Ok(ExecutionResult::Message(format!("@{} set", name)))
+            }
 
-#[cfg(test)]
-mod tests {
+            Statement::CreateIndex {
+                name,
+                table_name,
+                column,
+            } => {
+                self.create_index(name.clone(), &table_name, &column)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Index '{}' created on {}.{}",
+                    name, table_name, column
+                )))
+            }
+            Statement::DropIndex(name) => {
+                self.drop_index(&name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Index '{}' dropped",
+                    name
+                )))
+            }
+
+            Statement::CreateQuery { name, sql } => {
+                self.save_query(name.clone(), sql)?;
+                Ok(ExecutionResult::Message(format!("Query '{}' saved", name)))
+            }
+            Statement::ExecuteQuery(name) => self.run_saved_query(&name, session),
+            Statement::DropQuery(name) => {
+                self.drop_query(&name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Query '{}' dropped",
+                    name
+                )))
+            }
+            Statement::ShowQueries => Ok(self.list_saved_queries()),
+            Statement::CreateSchedule {
+                name,
+                sql,
+                interval_secs,
+            } => {
+                self.create_schedule(name.clone(), sql, interval_secs)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Schedule '{}' created",
+                    name
+                )))
+            }
+            Statement::DropSchedule(name) => {
+                self.drop_schedule(&name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Schedule '{}' dropped",
+                    name
+                )))
+            }
+            Statement::ShowSchedules => Ok(self.list_schedules()),
+            Statement::CreateTrigger {
+                name,
+                table_name,
+                event,
+                sql,
+            } => {
+                let trigger = Trigger {
+                    table: table_name,
+                    event,
+                    sql,
+                };
+                self.create_trigger(name.clone(), trigger)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Trigger '{}' created",
+                    name
+                )))
+            }
+            Statement::DropTrigger(name) => {
+                self.drop_trigger(&name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Trigger '{}' dropped",
+                    name
+                )))
+            }
+            Statement::ShowTriggers => Ok(self.list_triggers()),
+            Statement::ShowStatus => Ok(self.show_status()),
+            Statement::ShowIndexStats => Ok(self.show_index_stats()),
+            Statement::Comment { target, comment } => {
+                let removed = comment.is_none();
+                self.set_comment(&target, comment)?;
+                let object = match target {
+                    CommentTarget::Table(table) => format!("table '{}'", table),
+                    CommentTarget::Column { table, column } => {
+                        format!("column '{}.{}'", table, column)
+                    }
+                };
+                let action = if removed { "removed from" } else { "set on" };
+                Ok(ExecutionResult::Message(format!(
+                    "Comment {} {}",
+                    action, object
+                )))
+            }
+            Statement::Analyze(table) => {
+                let analyzed = self.collect_statistics(table.as_deref())?;
+                Ok(ExecutionResult::Message(match table {
+                    Some(table) => format!("Table '{}' analyzed", table),
+                    None => format!("{} tables analyzed", analyzed),
+                }))
+            }
+            Statement::Begin => {
+                self.begin(session)?;
+                Ok(ExecutionResult::Message("Transaction started".into()))
+            }
+            Statement::Commit => {
+                self.commit(session)?;
+                Ok(ExecutionResult::Message("Transaction committed".into()))
+            }
+            Statement::Rollback => {
+                self.rollback(session)?;
+                Ok(ExecutionResult::Message("Transaction rolled back".into()))
+            }
+            Statement::Savepoint(name) => {
+                self.savepoint(session, name.clone())?;
+                Ok(ExecutionResult::Message(format!(
+                    "Savepoint '{}' set",
+                    name
+                )))
+            }
+            Statement::RollbackToSavepoint(name) => {
+                self.rollback_to_savepoint(session, &name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Rolled back to savepoint '{}'",
+                    name
+                )))
+            }
+            Statement::ReleaseSavepoint(name) => {
+                self.release_savepoint(session, &name)?;
+                Ok(ExecutionResult::Message(format!(
+                    "Savepoint '{}' released",
+                    name
+                )))
+            }
+            Statement::Explain(inner) => Ok(self.plan(&inner)?.to_result()),
+            Statement::ExplainAnalyze(select) => self.explain_analyze(*select, session),
+
+            Statement::AlterTable { table_name, action } => {
+                if self.virtual_tables.contains_key(&table_name) {
+                    return Err(DbError::ReadOnlyTable(table_name));
+                }
+                if let AlterAction::RenameTable(to) = action {
+                    self.rename_table(&table_name, to.clone())?;
+                    self.rename_referenced_table(&table_name, &to);
+                    self.rename_triggered_table(&table_name, &to);
+                    return Ok(ExecutionResult::Message(format!(
+                        "Table '{}' renamed to '{}'",
+                        table_name, to
+                    )));
+                }
+                if let AlterAction::DropColumn(column) = &action {
+                    self.check_unreferenced(&table_name, Some(column))?;
+                }
+                let table = self
+                    .tables
+                    .get_mut(&table_name)
+                    .ok_or_else(|| DbError::TableNotFound(table_name.clone()))?;
+
+                let message = match action {
+                    AlterAction::DropColumn(column) => {
+                        table.drop_column(&column)?;
+                        format!("Column '{}' dropped", column)
+                    }
+                    AlterAction::RenameColumn { from, to } => {
+                        table.rename_column(&from, to.clone())?;
+                        self.rename_referenced_column(&table_name, &from, &to);
+                        format!("Column '{}' renamed to '{}'", from, to)
+                    }
+                    AlterAction::RenameTable(_) => unreachable!("handled above"),
+                };
+                self.mark_dirty();
+                Ok(ExecutionResult::Message(message))
+            }
+        }
+    }
+
+    /// The core execution engine logic for retrieving and combining data.
+    ///
+    /// ### How it works:
+    /// The query runs as a small pipeline over an intermediate `Relation` (headers + rows):
+    ///
+    /// #### Stage 1: Source
+    /// - **Standard Selection (No Join)**: The rows of the source table are used as-is.
+    /// - **Derived Tables**: `FROM (SELECT ...) AS u` runs the subquery first and treats its
+    ///   result as a table named `u`, without indexes.
+    /// - **Joins (Nested Loop Join)**: Each joined table is combined with the result so far, so
+    ///   `A JOIN B ON ... JOIN C ON ...` joins `C` against the rows of `A JOIN B`:
+    ///    - Outer Loop: Iterates through every row of the result so far.
+    ///    - Inner Loop: Iterates through every row in the joined table.
+    ///    - Comparison: If `left_row[key] == right_row[key]`, the rows are merged. A `CROSS JOIN`
+    ///      merges every pair.
+    ///    - A `LEFT JOIN` also emits each left row without a match, with `NULL` right columns.
+    ///    - Complexity: $O(N \times M)$ per join where $N$ and $M$ are the row counts.
+    ///    - With the `/*+ HASH_JOIN */` hint, equi-joins instead build a hash table over the
+    ///      joined table's key column and probe it once per left row, in $O(N + M)$.
+    ///    - Once `ANALYZE` has collected statistics for every table, chains of `INNER JOIN ...
+    ///      ON` run smallest filtered table first, as far as the `ON` conditions allow. The
+    ///      columns keep the order of the query, the rows may not.
+    ///    - Headers are generated in the format `table.column` to prevent naming collisions,
+    ///      using the alias instead of the table name when one is given. Aliases make
+    ///      self-joins possible: `FROM emp e JOIN emp m ON e.manager = m.id`.
+    ///
+    /// #### Stage 2: Filtering
+    /// A `WHERE` predicate drops every row for which it is not true (`NULL` counts as false).
+    /// `[NOT] EXISTS (SELECT ...)` runs the subquery, once per row when it refers to the
+    /// outer row's columns: `WHERE NOT EXISTS (SELECT * FROM orders WHERE user_id = u.id)`.
+    /// It is rewritten first: constant subexpressions are folded, a predicate that is always
+    /// true is dropped and one that is always false skips reading the tables. With joins, each
+    /// `AND`ed part that only needs one input filters that input before the join, except the
+    /// right side of a `LEFT JOIN`. The filtered inputs then drop the columns no later stage
+    /// refers to, so joins don't copy them into every combined row.
+    /// Without a join, an `IN` list or equality on an indexed column (PRIMARY/UNIQUE or
+    /// `CREATE INDEX`) is first answered from the index, so values missing from the table never
+    /// trigger a scan. `/*+ NO_INDEX */` forces the scan and `USE INDEX (<column>)` limits the
+    /// lookup to that column's index.
+    ///
+    /// #### Stage 3: Grouping
+    /// With a `GROUP BY` clause or aggregate calls in the select list, rows are folded into one
+    /// row per distinct key (or a single row for the whole table) by the hash aggregation
+    /// operator, which also computes the select list. Later stages work on the grouped output.
+    ///
+    /// #### Stage 3b: Windows
+    /// Without grouping, window functions such as `RANK() OVER (PARTITION BY team_id ORDER BY
+    /// score DESC)` are computed over the filtered rows, each into a column of its own that
+    /// the later stages read like any other.
+    ///
+    /// #### Stage 4: Ordering
+    /// If an `ORDER BY` clause is present, rows are stably sorted by each key in turn using
+    /// the total ordering defined on `Value` (`NULL` < integers < text). A key may name a
+    /// column alias from the select list.
+    ///
+    /// #### Stage 5: Projection
+    /// Maps requested column names to their positions in the relation and keeps only those
+    /// values; any other expression is evaluated per row. This is a linear $O(N)$ operation.
+    ///
+    /// #### Stage 6: Deduplication
+    /// `SELECT DISTINCT` drops projected rows already seen, keeping the first occurrence so
+    /// the sort order survives.
+    ///
+    /// #### Stage 7: Pagination
+    /// `OFFSET` drops rows from the front of the final result and `LIMIT` caps the rest.
+    ///
+    /// Every materialized intermediate result, and the input of joins and sorts, is accounted
+    /// in `trace.memory`; index lookups are listed in `trace.index_lookups`.
+    pub fn handle_select(
+        &self,
+        select: SelectStatement,
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<ExecutionResult, DbError> {
+        let types = self.select_types(&select)?;
+        let relation = self.select_relation(select, session, trace)?;
+        // Columns whose type the schema doesn't tell take that of their values.
+        let types = types
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| t.unwrap_or_else(|| value_type(&relation.rows, i).to_string()))
+            .collect();
+        Ok(ExecutionResult::Data {
+            headers: relation.headers,
+            rows: relation.rows,
+            types,
+        })
+    }
+
+    /// Runs the pipeline described on `handle_select`, returning the final relation.
+    fn select_relation(
+        &self,
+        mut select: SelectStatement,
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<Relation, DbError> {
+        let is_aggregate = select.is_aggregate();
+        if !is_aggregate {
+            // Plain queries sort before projecting, under the original column names.
+            select.resolve_order_aliases();
+        }
+        // Fold constants and drop whatever the predicate already decides.
+        select.where_clause = select
+            .where_clause
+            .take()
+            .map(rewrite::simplify_predicate)
+            .filter(|predicate| rewrite::known(predicate) != Some(true));
+        let always_false = select.where_clause.as_ref().and_then(rewrite::known) == Some(false);
+        let load = |table: &Table| {
+            if always_false {
+                Relation::empty(table)
+            } else {
+                Relation::from_table(table)
+            }
+        };
+
+        let table = match select.subquery.take() {
+            Some(subquery) => {
+                let started = Instant::now();
+                let derived = self.select_relation(*subquery, session, trace)?;
+                trace.record(
+                    "Derived Table",
+                    || select.table_name.clone(),
+                    &derived,
+                    started,
+                )?;
+                Cow::Owned(Table::derived(
+                    select.table_name.clone(),
+                    derived.headers,
+                    derived.rows,
+                )?)
+            }
+            None => self.scan_table(
+                select.table_name.clone(),
+                select.table_args.as_deref(),
+                session,
+            )?,
+        };
+        let probe = IndexProbe::for_select(&table, &select, select.where_clause.as_ref())?
+            .filter(|_| !always_false);
+        let hash_join = select.has_hint(Hint::HashJoin);
+        let started = Instant::now();
+        let mut relation = match probe {
+            Some(probe) => {
+                let relation = probe.scan(&table);
+                let column = table.columns[probe.column].name.clone();
+                trace.record(
+                    "Index Lookup",
+                    || format!("{}.{}", table.name, column),
+                    &relation,
+                    started,
+                )?;
+                trace.index_lookups.push(IndexLookup {
+                    table: table.name.clone(),
+                    column,
+                    rows: relation.rows.len(),
+                });
+                relation
+            }
+            None => {
+                let relation = load(&table);
+                trace.record("Full Scan", || table.name.clone(), &relation, started)?;
+                relation
+            }
+        };
+        let qualifier = (!select.joins.is_empty() || select.table_alias.is_some())
+            .then(|| select.table_alias.as_deref().unwrap_or(&table.name));
+        if let Some(qualifier) = qualifier {
+            relation.qualify(qualifier);
+        }
+        trace.memory.materialize(&relation);
+        let mut masked: HashSet<String> = masking::masked_headers(&table, qualifier).collect();
+
+        let mut analyzed = table.has_statistics();
+        let mut rights = Vec::with_capacity(select.joins.len());
+        for join_info in &select.joins {
+            let started = Instant::now();
+            let right_table = self.scan_table(join_info.table_name.clone(), None, session)?;
+            analyzed &= right_table.has_statistics();
+            let mut right = load(&right_table);
+            trace.record("Full Scan", || right_table.name.clone(), &right, started)?;
+            let qualifier = join_info.alias.as_deref().unwrap_or(&right_table.name);
+            right.qualify(qualifier);
+            masked.extend(masking::masked_headers(&right_table, Some(qualifier)));
+            rights.push(right);
+        }
+
+        let inputs: Vec<Vec<String>> = std::iter::once(&relation)
+            .chain(&rights)
+            .map(|r| r.headers.clone())
+            .collect();
+        let kinds: Vec<JoinKind> = select.joins.iter().map(|j| j.kind).collect();
+        let pushed = rewrite::push_down(select.where_clause.take(), &inputs, &kinds);
+        // Without joins there is nothing to save, the projection runs on the filtered rows.
+        let mut needed = (!select.joins.is_empty())
+            .then(|| rewrite::needed_columns(&select, pushed.residual.as_ref(), &inputs))
+            .flatten()
+            .map(Vec::into_iter);
+        if let Some(predicate) = &pushed.source {
+            let started = Instant::now();
+            relation.filter(predicate, session)?;
+            trace.record("Filter", || predicate.to_string(), &relation, started)?;
+        }
+        if let Some(columns) = needed.as_mut().and_then(Iterator::next) {
+            relation.retain_columns(&columns);
+        }
+        for (right, predicate) in rights.iter_mut().zip(pushed.joins) {
+            if let Some(predicate) = &predicate {
+                let started = Instant::now();
+                right.filter(predicate, session)?;
+                trace.record("Filter", || predicate.to_string(), right, started)?;
+            }
+            if let Some(columns) = needed.as_mut().and_then(Iterator::next) {
+                right.retain_columns(&columns);
+            }
+        }
+
+        let sizes: Vec<usize> = rights.iter().map(|r| r.rows.len()).collect();
+        let order = plan::join_order(&select.joins, &inputs, analyzed.then_some(&sizes));
+        let widths: Vec<usize> = std::iter::once(&relation)
+            .chain(&rights)
+            .map(|r| r.headers.len())
+            .collect();
+        let mut steps: Vec<_> = select.joins.into_iter().zip(rights).enumerate().collect();
+        steps.sort_by_key(|(i, _)| order.iter().position(|o| o == i));
+        for (_, (join_info, right)) in steps {
+            let started = Instant::now();
+            let operator = plan::join_operator(&join_info, hash_join);
+            let detail = plan::join_detail(&join_info);
+            relation = self.join(relation, right, join_info, hash_join, &mut trace.memory)?;
+            trace.record(operator, || detail, &relation, started)?;
+        }
+        // Joined out of order, the columns go back to the order of the tables in the query.
+        if order.iter().enumerate().any(|(i, o)| i != *o) {
+            let mut starts = vec![0; widths.len()];
+            let mut offset = widths[0];
+            for &i in &order {
+                starts[i + 1] = offset;
+                offset += widths[i + 1];
+            }
+            let columns: Vec<usize> = (0..widths.len())
+                .flat_map(|i| starts[i]..starts[i] + widths[i])
+                .collect();
+            relation.retain_columns(&columns);
+        }
+        if let Some(predicate) = &pushed.residual {
+            let started = Instant::now();
+            self.filter_rows(&mut relation, predicate, session, trace)?;
+            trace.record("Filter", || predicate.to_string(), &relation, started)?;
+        }
+
+        // Masking happens on the way out: filters, joins and sorts still see the real values.
+        let masks = (!session.unmask && !masked.is_empty())
+            .then(|| masking::masked_outputs(self, &relation, &select.columns, &masked));
+        // The select list as written, before the window stage rewrites it.
+        let mut written_columns = None;
+        if !is_aggregate && select.columns.iter().any(|c| c.has_window()) {
+            let started = Instant::now();
+            let detail = plan::window_detail(&select.columns);
+            written_columns = Some(plan::project_detail(&select.columns));
+            window::compute_windows(&mut relation, &mut select.columns, &mut select.order_by)?;
+            trace.memory.sort_bytes += relation.approximate_size();
+            trace.record("Window", || detail, &relation, started)?;
+        }
+        let order_keys = || plan::sort_detail(&select.order_by);
+        relation = if is_aggregate {
+            let started = Instant::now();
+            let mut grouped = aggregate::aggregate(relation, &select.group_by, &select.columns)?;
+            trace.memory.materialize(&grouped);
+            trace.record(
+                "Hash Aggregate",
+                || select.group_by.join(", "),
+                &grouped,
+                started,
+            )?;
+            if !select.order_by.is_empty() {
+                trace.memory.sort_bytes += grouped.approximate_size();
+                let started = Instant::now();
+                grouped.sort(&select.order_by)?;
+                trace.record("Sort", order_keys, &grouped, started)?;
+            }
+            grouped
+        } else {
+            if !select.order_by.is_empty() {
+                trace.memory.sort_bytes += relation.approximate_size();
+                let started = Instant::now();
+                relation.sort(&select.order_by)?;
+                trace.record("Sort", order_keys, &relation, started)?;
+            }
+            let started = Instant::now();
+            let projected = self.project_rows(relation, &select.columns, session, trace)?;
+            trace.memory.materialize(&projected);
+            let detail = || {
+                written_columns
+                    .clone()
+                    .unwrap_or_else(|| plan::project_detail(&select.columns))
+            };
+            trace.record("Project", detail, &projected, started)?;
+            projected
+        };
+        if let Some(masks) = &masks {
+            masking::mask(&mut relation, masks);
+        }
+
+        if select.distinct {
+            let started = Instant::now();
+            relation.dedup();
+            trace.record("Distinct", || "hash".into(), &relation, started)?;
+        }
+        if select.limit.is_some() || select.offset.is_some() {
+            let started = Instant::now();
+            relation.paginate(select.limit, select.offset);
+            let detail = || plan::limit_detail(select.limit, select.offset);
+            trace.record("Limit", detail, &relation, started)?;
+        }
+        Ok(relation)
+    }
+
+    /// Combines the running result with the rows of the joined table, using a nested loop or,
+    /// for `hash_join` with an `ON` condition, a hash table of the right rows keyed on the join
+    /// column. Both produce the same rows in the same order.
+    fn join(
+        &self,
+        left: Relation,
+        right: Relation,
+        join_info: JoinDefinition,
+        hash_join: bool,
+        memory: &mut QueryMemory,
+    ) -> Result<Relation, DbError> {
+        memory.join_bytes += memory.materialize(&right);
+
+        // A cross join has no condition: every pair of rows matches.
+        let keys = match join_info.on {
+            None => None,
+            Some(on) => Some(Self::join_keys(&left, &right, &on)?),
+        };
+
+        // HASH JOIN BUILD SIDE: right row positions grouped by key, in table order
+        let buckets = match keys {
+            Some((_, r)) if hash_join => {
+                let mut buckets: HashMap<&Value, Vec<usize>> = HashMap::new();
+                for (i, r_row) in right.rows.iter().enumerate() {
+                    buckets.entry(&r_row[r]).or_default().push(i);
+                }
+                memory.join_bytes += right.rows.len() * std::mem::size_of::<usize>();
+                Some(buckets)
+            }
+            _ => None,
+        };
+
+        let mut joined_rows = Vec::new();
+        let mut headers = left.headers.clone();
+        headers.extend(right.headers.iter().cloned());
+
+        for l_row in &left.rows {
+            let matches: Vec<&Vec<Value>> = match (&buckets, keys) {
+                (Some(buckets), Some((l, _))) => buckets
+                    .get(&l_row[l])
+                    .into_iter()
+                    .flatten()
+                    .map(|&i| &right.rows[i])
+                    .collect(),
+                // NESTED LOOP JOIN LOGIC
+                _ => right
+                    .rows
+                    .iter()
+                    .filter(|r_row| keys.is_none_or(|(l, r)| l_row[l] == r_row[r]))
+                    .collect(),
+            };
+
+            // LEFT JOIN keeps unmatched left rows, padding the right side with NULLs
+            if matches.is_empty() && join_info.kind == JoinKind::Left {
+                let mut padded = l_row.clone();
+                padded.resize(l_row.len() + right.headers.len(), Value::Null);
+                joined_rows.push(padded);
+            }
+            for r_row in matches {
+                let mut combined = l_row.clone();
+                combined.extend(r_row.iter().cloned());
+                joined_rows.push(combined);
+            }
+        }
+
+        let joined = Relation {
+            headers,
+            rows: joined_rows,
+            table: None,
+        };
+        memory.join_bytes += memory.materialize(&joined);
+        Ok(joined)
+    }
+
+    /// Resolves the `ON` columns to positions in the left and right relations. The condition
+    /// may name the two sides in either order, e.g. `teams.id = devs.team_id`.
+    fn join_keys(
+        left: &Relation,
+        right: &Relation,
+        on: &JoinCondition,
+    ) -> Result<(usize, usize), DbError> {
+        let resolve = |l: &str, r: &str| Ok((left.column_index(l)?, right.column_index(r)?));
+        resolve(&on.left_column, &on.right_column)
+            .or_else(|err: DbError| resolve(&on.right_column, &on.left_column).map_err(|_| err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
 
     #[test]
@@ -296,12 +1661,20 @@ mod tests {
                 data_type: "INT".into(),
                 is_primary: true,
                 is_unique: false,
+                comment: None,
+                is_masked: false,
+                max_length: None,
+                is_not_null: false,
             },
             Column {
                 name: "name".into(),
                 data_type: "TEXT".into(),
                 is_primary: false,
                 is_unique: false,
+                comment: None,
+                is_masked: false,
+                max_length: None,
+                is_not_null: false,
             },
         ];
         db.create_table("users".into(), cols).unwrap();
@@ -315,4 +1688,1723 @@ mod tests {
         let badres = table.insert_row(vec![Value::Integer(1), Value::Text("Dup".into())]);
         assert!(badres.is_err());
     }
+
+    #[test]
+    fn test_alter_table_columns() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT, email TEXT UNIQUE, name TEXT)",
+            "INSERT INTO users VALUES (1, 'a@x.io', 'Alice')",
+            "ALTER TABLE users DROP COLUMN id",
+            "ALTER TABLE users RENAME COLUMN name TO full_name",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let table = db.tables.get_mut("users").unwrap();
+        assert_eq!(table.columns[1].name, "full_name");
+        assert_eq!(
+            table.rows[0],
+            vec![Value::Text("a@x.io".into()), Value::Text("Alice".into())]
+        );
+
+        // the UNIQUE index moved from position 1 to position 0 with the data
+        let dup = table.insert_row(vec![Value::Text("a@x.io".into()), Value::Text("B".into())]);
+        assert!(matches!(dup, Err(DbError::UniqueViolation(_))));
+        assert!(table.rename_column("email", "full_name".into()).is_err());
+    }
+
+    #[test]
+    fn test_order_by() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE scores (name TEXT, points INT)",
+            "INSERT INTO scores VALUES ('bob', 7)",
+            "INSERT INTO scores VALUES ('amy', 9)",
+            "INSERT INTO scores VALUES ('cat', 7)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        db.tables
+            .get_mut("scores")
+            .unwrap()
+            .insert_row(vec![Value::Text("dan".into()), Value::Null])
+            .unwrap();
+
+        let sql = "SELECT name FROM scores ORDER BY points DESC, name";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        let names: Vec<_> = rows.into_iter().map(|r| r[0].clone()).collect();
+        assert_eq!(
+            names,
+            ["amy", "bob", "cat", "dan"].map(|n| Value::Text(n.into()))
+        );
+        assert!(Value::Null < Value::Integer(-5));
+        assert!(Value::Integer(100) < Value::Text("1".into()));
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        let mut db = Database::new();
+        let sql = "SELECT value FROM generate_series(1, 100) ORDER BY value DESC LIMIT 3 OFFSET 2";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(98)],
+                vec![Value::Integer(97)],
+                vec![Value::Integer(96)]
+            ]
+        );
+
+        let sql = "SELECT value FROM generate_series(1, 5) OFFSET 10";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT, score INT)",
+            "INSERT INTO devs VALUES (1, 1, 10)",
+            "INSERT INTO devs VALUES (2, 2, 5)",
+            "INSERT INTO devs VALUES (3, 1, 7)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql = "SELECT team_id, COUNT(*), SUM(score), MAX(score) FROM devs GROUP BY team_id ORDER BY team_id";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["team_id", "COUNT(*)", "SUM(score)", "MAX(score)"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Integer(2),
+                    Value::Integer(17),
+                    Value::Integer(10)
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Integer(1),
+                    Value::Integer(5),
+                    Value::Integer(5)
+                ],
+            ]
+        );
+
+        let bad = "SELECT id, COUNT(*) FROM devs GROUP BY team_id";
+        assert!(db.execute(crate::parser::parse(bad).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_aggregates_without_group_by() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, age INT)",
+            "INSERT INTO users VALUES (1, 30)",
+            "INSERT INTO users VALUES (2, 41)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let table = db.tables.get_mut("users").unwrap();
+        table
+            .insert_row(vec![Value::Integer(3), Value::Null])
+            .unwrap();
+
+        let sql = "SELECT COUNT(*), COUNT(age), SUM(age), AVG(age), MIN(age) FROM users";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(3),
+                Value::Integer(2),
+                Value::Integer(71),
+                Value::Integer(35),
+                Value::Integer(30)
+            ]]
+        );
+
+        db.execute(crate::parser::parse("CREATE TABLE empty (x INT)").unwrap())
+            .unwrap();
+        let sql = "SELECT COUNT(*), SUM(x), AVG(x) FROM empty";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            vec![vec![Value::Integer(0), Value::Null, Value::Null]]
+        );
+    }
+
+    #[test]
+    fn test_session_variables() {
+        let mut db = Database::new();
+        let mut session = Session::new("martin");
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO devs VALUES (1, 3)",
+            "SET @team = 3",
+        ] {
+            db.execute_with_session(crate::parser::parse(sql).unwrap(), &mut session)
+                .unwrap();
+        }
+
+        let sql = "SELECT @team, CURRENT_USER(), id, @missing FROM devs";
+        let ExecutionResult::Data { headers, rows, .. } = db
+            .execute_with_session(crate::parser::parse(sql).unwrap(), &mut session)
+            .unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["@team", "CURRENT_USER()", "id", "@missing"]);
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(3),
+                Value::Text("martin".into()),
+                Value::Integer(1),
+                Value::Null
+            ]]
+        );
+
+        let sql = "SELECT CURRENT_TIMESTAMP";
+        let ExecutionResult::Data { rows, .. } = db
+            .execute_with_session(crate::parser::parse(sql).unwrap(), &mut session)
+            .unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert!(matches!(rows[0][0], Value::Timestamp(_)));
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO devs VALUES (1, 2)",
+            "INSERT INTO devs VALUES (2, 1)",
+            "INSERT INTO devs VALUES (3, 2)",
+            "INSERT INTO devs VALUES (4, 3)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql = "SELECT DISTINCT team_id FROM devs ORDER BY team_id DESC LIMIT 2";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Integer(3)], vec![Value::Integer(2)]]);
+    }
+
+    #[test]
+    fn test_where_in() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO devs VALUES (1, 2)",
+            "INSERT INTO devs VALUES (2, 1)",
+            "INSERT INTO devs VALUES (3, 2)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        db.tables
+            .get_mut("devs")
+            .unwrap()
+            .insert_row(vec![Value::Integer(4), Value::Null])
+            .unwrap();
+
+        let ids = |db: &mut Database, sql: &str| -> Vec<Value> {
+            let ExecutionResult::Data { rows, .. } =
+                db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+            else {
+                panic!("expected data");
+            };
+            rows.into_iter().map(|r| r[0].clone()).collect()
+        };
+
+        // indexed column, including values missing from the table
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM devs WHERE id IN (3, 1, 99) ORDER BY id"
+            ),
+            [Value::Integer(1), Value::Integer(3)]
+        );
+        assert!(ids(&mut db, "SELECT id FROM devs WHERE id IN (7, 8)").is_empty());
+        // unindexed column, combined with another condition
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM devs WHERE team_id IN (2) AND id > 1"
+            ),
+            [Value::Integer(3)]
+        );
+        // NULL never matches IN or NOT IN
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM devs WHERE team_id NOT IN (2)"),
+            [Value::Integer(2)]
+        );
+        assert!(ids(&mut db, "SELECT id FROM devs WHERE id NOT IN (1, @unset)").is_empty());
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT COUNT(*) FROM devs WHERE id IN (1, 2) OR team_id = 2"
+            ),
+            [Value::Integer(3)]
+        );
+    }
+
+    #[test]
+    fn test_where_is_null() {
+        let mut db = Database::new();
+        db.execute(
+            crate::parser::parse("CREATE TABLE devs (id INT PRIMARY, team_id INT)").unwrap(),
+        )
+        .unwrap();
+        let table = db.tables.get_mut("devs").unwrap();
+        table
+            .insert_row(vec![Value::Integer(1), Value::Integer(2)])
+            .unwrap();
+        table
+            .insert_row(vec![Value::Integer(2), Value::Null])
+            .unwrap();
+
+        let sql =
+            "SELECT id, team_id IS NULL FROM devs WHERE team_id IS NULL OR id = 1 ORDER BY id";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["id", "team_id IS NULL"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Integer(0)],
+                vec![Value::Integer(2), Value::Integer(1)]
+            ]
+        );
+
+        let sql = "SELECT COUNT(*) FROM devs WHERE NOT team_id IS NOT NULL";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Integer(1)]]);
+        assert!(crate::parser::parse("SELECT * FROM devs WHERE id IS 3").is_err());
+    }
+
+    #[test]
+    fn test_left_join() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO teams VALUES (1, 'core')",
+            "INSERT INTO devs VALUES (1, 1)",
+            "INSERT INTO devs VALUES (2, 7)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql =
+            "SELECT devs.id, teams.name FROM devs LEFT JOIN teams ON team_id = id ORDER BY devs.id";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Text("core".into())],
+                vec![Value::Integer(2), Value::Null]
+            ]
+        );
+
+        // devs without a team
+        let sql =
+            "SELECT devs.id FROM devs LEFT OUTER JOIN teams ON team_id = id WHERE teams.id IS NULL";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Integer(2)]]);
+    }
+
+    #[test]
+    fn test_cross_join() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE sizes (size TEXT)",
+            "CREATE TABLE colors (color TEXT)",
+            "INSERT INTO sizes VALUES ('S')",
+            "INSERT INTO sizes VALUES ('M')",
+            "INSERT INTO colors VALUES ('red')",
+            "INSERT INTO colors VALUES ('blue')",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        for sql in [
+            "SELECT * FROM sizes CROSS JOIN colors ORDER BY size, color",
+            "SELECT * FROM sizes, colors ORDER BY size, color",
+        ] {
+            let ExecutionResult::Data { headers, rows, .. } =
+                db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+            else {
+                panic!("expected data");
+            };
+            assert_eq!(headers, ["sizes.size", "colors.color"]);
+            assert_eq!(rows.len(), 4);
+            assert_eq!(
+                rows[0],
+                vec![Value::Text("M".into()), Value::Text("blue".into())]
+            );
+        }
+        let sql = "SELECT * FROM sizes, colors, sizes";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows.len(), 8);
+    }
+
+    #[test]
+    fn test_multi_table_join() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE orgs (org_id INT PRIMARY, org TEXT)",
+            "CREATE TABLE teams (team_id INT PRIMARY, team_org INT, team TEXT)",
+            "CREATE TABLE devs (id INT PRIMARY, dev_team INT, name TEXT)",
+            "INSERT INTO orgs VALUES (1, 'acme')",
+            "INSERT INTO teams VALUES (10, 1, 'core')",
+            "INSERT INTO teams VALUES (20, 1, 'web')",
+            "INSERT INTO devs VALUES (1, 10, 'Alice')",
+            "INSERT INTO devs VALUES (2, 20, 'Bob')",
+            "INSERT INTO devs VALUES (3, 30, 'Cat')",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql = "SELECT name, team, org FROM devs JOIN teams ON dev_team = team_id JOIN orgs ON org_id = team_org ORDER BY name";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["devs.name", "teams.team", "orgs.org"]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[1],
+            vec![
+                Value::Text("Bob".into()),
+                Value::Text("web".into()),
+                Value::Text("acme".into())
+            ]
+        );
+
+        let sql = "SELECT name, org FROM devs LEFT JOIN teams ON dev_team = team_id LEFT JOIN orgs ON team_org = org_id WHERE org IS NULL";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Text("Cat".into()), Value::Null]]);
+    }
+
+    #[test]
+    fn test_table_aliases() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE emp (id INT PRIMARY, name TEXT, manager INT)",
+            "INSERT INTO emp VALUES (1, 'Ada', 0)",
+            "INSERT INTO emp VALUES (2, 'Bob', 1)",
+            "INSERT INTO emp VALUES (3, 'Cy', 1)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql =
+            "SELECT e.name, m.name FROM emp AS e JOIN emp m ON e.manager = m.id ORDER BY e.name";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["e.name", "m.name"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Text("Bob".into()), Value::Text("Ada".into())],
+                vec![Value::Text("Cy".into()), Value::Text("Ada".into())],
+            ]
+        );
+
+        let sql = "SELECT e.name FROM emp e WHERE e.id = 3";
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Text("Cy".into())]]);
+
+        // Unqualified names are ambiguous in a self-join.
+        let sql = "SELECT name FROM emp e JOIN emp m ON e.manager = m.id";
+        assert!(db.execute(crate::parser::parse(sql).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_column_aliases() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT, team INT)",
+            "INSERT INTO users VALUES (1, 'Bob', 1)",
+            "INSERT INTO users VALUES (2, 'Ada', 1)",
+            "INSERT INTO users VALUES (3, 'Cy', 2)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql =
+            "SELECT name AS display_name, id user_id FROM users ORDER BY display_name LIMIT 1";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["display_name", "user_id"]);
+        assert_eq!(
+            rows,
+            vec![vec![Value::Text("Ada".into()), Value::Integer(2)]]
+        );
+
+        let sql = "SELECT team, COUNT(*) AS members FROM users GROUP BY team ORDER BY members DESC";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["team", "members"]);
+        assert_eq!(rows[0], vec![Value::Integer(1), Value::Integer(2)]);
+    }
+
+    #[test]
+    fn test_qualified_columns() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE users (id INT PRIMARY, name TEXT, team_id INT)",
+            "INSERT INTO teams VALUES (1, 'core')",
+            "INSERT INTO teams VALUES (2, 'web')",
+            "INSERT INTO users VALUES (1, 'Ada', 2)",
+            "INSERT INTO users VALUES (2, 'Bob', 1)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+
+        let sql = "SELECT users.id, teams.id, teams.name FROM users JOIN teams ON teams.id = users.team_id WHERE users.id = 1";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["users.id", "teams.id", "teams.name"]);
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Text("web".into())
+            ]]
+        );
+
+        // A single table answers to its own name too.
+        let sql = "SELECT users.name FROM users WHERE users.id = 2";
+        let ExecutionResult::Data { headers, rows, .. } =
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["name"]);
+        assert_eq!(rows, vec![vec![Value::Text("Bob".into())]]);
+
+        let sql = "SELECT id FROM users JOIN teams ON team_id = teams.id";
+        assert!(matches!(
+            db.execute(crate::parser::parse(sql).unwrap()),
+            Err(DbError::AmbiguousColumn(_))
+        ));
+        let sql = "SELECT teams.name FROM users";
+        assert!(db.execute(crate::parser::parse(sql).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_optimizer_hints() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE users (id INT PRIMARY, email TEXT UNIQUE, team_id INT)",
+            "INSERT INTO teams VALUES (1, 'core')",
+            "INSERT INTO teams VALUES (2, 'web')",
+            "INSERT INTO users VALUES (1, 'a@x.io', 2)",
+            "INSERT INTO users VALUES (2, 'b@x.io', 9)",
+            "INSERT INTO users VALUES (3, 'c@x.io', 2)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let join = "users LEFT JOIN teams ON team_id = teams.id";
+        let nested = run(&format!("SELECT * FROM {}", join)).unwrap();
+        let hashed = run(&format!("SELECT /*+ HASH_JOIN */ * FROM {}", join)).unwrap();
+        let (
+            ExecutionResult::Data { rows: nested, .. },
+            ExecutionResult::Data { rows: hashed, .. },
+        ) = (nested, hashed)
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(nested.len(), 3);
+        assert_eq!(nested, hashed);
+
+        let plan = db
+            .plan(
+                &crate::parser::parse(
+                    "SELECT /*+HASH_JOIN*/ * FROM users JOIN teams ON team_id = teams.id",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(plan.children[0].operator, "Hash Join");
+
+        let plan = |sql: &str| db.plan(&crate::parser::parse(sql).unwrap());
+        // The scan below the filter and projection shows which index answered the WHERE clause.
+        let scan = |sql: &str| plan(sql).unwrap().children[0].children[0].detail.clone();
+        let where_clause = "WHERE id = 1 AND email = 'a@x.io'";
+        assert_eq!(
+            scan(&format!("SELECT * FROM users {}", where_clause)),
+            "users.id"
+        );
+        assert_eq!(
+            scan(&format!(
+                "SELECT * FROM users USE INDEX (email) {}",
+                where_clause
+            )),
+            "users.email"
+        );
+        assert_eq!(
+            scan(&format!(
+                "SELECT /*+ NO_INDEX */ * FROM users {}",
+                where_clause
+            )),
+            "users"
+        );
+        assert!(matches!(
+            plan("SELECT * FROM users USE INDEX (team_id) WHERE team_id = 2"),
+            Err(DbError::IndexNotFound(_))
+        ));
+        assert!(crate::parser::parse("SELECT /*+ FAST */ * FROM users").is_err());
+    }
+
+    #[test]
+    fn test_derived_tables() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT, team_id INT)",
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Ada', 1)",
+            "INSERT INTO users VALUES (2, 'Bob', 2)",
+            "INSERT INTO users VALUES (3, 'Cy', 1)",
+            "INSERT INTO teams VALUES (1, 'core')",
+            "INSERT INTO teams VALUES (2, 'web')",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let query = |db: &mut Database, sql: &str| {
+            let ExecutionResult::Data { headers, rows, .. } =
+                db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+            else {
+                panic!("expected data");
+            };
+            (headers, rows)
+        };
+
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT u.name, t.name FROM (SELECT name, team_id FROM users WHERE id > 1) AS u \
+             JOIN teams t ON u.team_id = t.id ORDER BY u.name",
+        );
+        assert_eq!(headers, ["u.name", "t.name"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Text("Bob".into()), Value::Text("web".into())],
+                vec![Value::Text("Cy".into()), Value::Text("core".into())],
+            ]
+        );
+
+        // Aggregates and qualified subquery headers become plain columns of the derived table.
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT team, n FROM (SELECT users.team_id AS team, COUNT(*) AS n FROM users \
+             GROUP BY team_id) s WHERE n > 1",
+        );
+        assert_eq!(headers, ["team", "n"]);
+        assert_eq!(rows, vec![vec![Value::Integer(1), Value::Integer(2)]]);
+
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT * FROM (SELECT * FROM (SELECT id FROM users) a WHERE id = 2) b",
+        );
+        assert_eq!(headers, ["id"]);
+        assert_eq!(rows, vec![vec![Value::Integer(2)]]);
+
+        // `u.id` and `t.id` would both be the column `id` of the derived table.
+        let clash = "SELECT * FROM (SELECT u.id, t.id FROM users u JOIN teams t \
+                     ON u.team_id = t.id) AS pairs";
+        for sql in [clash.to_string(), format!("EXPLAIN {}", clash)] {
+            assert!(matches!(
+                db.execute(crate::parser::parse(&sql).unwrap()),
+                Err(DbError::ColumnAlreadyExists(name)) if name == "id"
+            ));
+        }
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT * FROM (SELECT u.id, t.id AS team FROM users u JOIN teams t \
+             ON u.team_id = t.id) AS pairs WHERE id = 2",
+        );
+        assert_eq!(headers, ["id", "team"]);
+        assert_eq!(rows, vec![vec![Value::Integer(2), Value::Integer(2)]]);
+    }
+
+    #[test]
+    fn test_exists() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE orders (id INT PRIMARY, user_id INT)",
+            "INSERT INTO users VALUES (1, 'Ada')",
+            "INSERT INTO users VALUES (2, 'Bob')",
+            "INSERT INTO users VALUES (3, 'Cy')",
+            "INSERT INTO orders VALUES (10, 1)",
+            "INSERT INTO orders VALUES (11, 1)",
+            "INSERT INTO orders VALUES (12, 3)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let names = |db: &mut Database, sql: &str| {
+            let ExecutionResult::Data { rows, .. } =
+                db.execute(crate::parser::parse(sql).unwrap()).unwrap()
+            else {
+                panic!("expected data");
+            };
+            rows.into_iter()
+                .map(|row| match &row[0] {
+                    Value::Text(name) => name.clone(),
+                    other => panic!("expected a name, got {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Correlated: `u.id` is bound to each outer row.
+        let sql = "SELECT name FROM users u WHERE EXISTS (SELECT * FROM orders WHERE user_id = u.id) ORDER BY name";
+        assert_eq!(names(&mut db, sql), ["Ada", "Cy"]);
+
+        // Anti-join; an unqualified outer column works too when the subquery lacks it.
+        let sql = "SELECT name FROM users WHERE NOT EXISTS \
+                   (SELECT 1 FROM orders WHERE user_id = users.id AND name <> 'Cy') ORDER BY name";
+        assert_eq!(names(&mut db, sql), ["Bob", "Cy"]);
+
+        // Uncorrelated subqueries decide for every row at once.
+        let sql =
+            "SELECT name FROM users WHERE id < 3 AND EXISTS (SELECT * FROM orders WHERE id = 12)";
+        assert_eq!(names(&mut db, sql), ["Ada", "Bob"]);
+        let sql = "SELECT name FROM users WHERE EXISTS (SELECT * FROM orders WHERE id = 99)";
+        assert!(names(&mut db, sql).is_empty());
+
+        // `id` resolves to orders.id inside the subquery, not to the outer users.id.
+        let sql = "SELECT name FROM users WHERE EXISTS (SELECT * FROM orders WHERE id = 1)";
+        assert!(names(&mut db, sql).is_empty());
+
+        // Alongside a join, the subquery sees the columns of every joined table.
+        let sql = "SELECT u.name FROM users u JOIN orders o ON u.id = o.user_id \
+                   WHERE NOT EXISTS (SELECT * FROM orders p WHERE p.user_id = u.id AND p.id > o.id)";
+        assert_eq!(names(&mut db, sql), ["Ada", "Cy"]);
+    }
+
+    #[test]
+    fn test_scalar_subquery() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO teams VALUES (1, 'core'), (2, 'web'), (3, 'ops')",
+            "INSERT INTO devs VALUES (10, 1), (11, 1), (12, 2)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let sql = "SELECT name, (SELECT COUNT(*) FROM devs WHERE devs.team_id = teams.id) \
+                   FROM teams ORDER BY id";
+        let Ok(ExecutionResult::Data { headers, rows, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            headers,
+            [
+                "name",
+                "(SELECT COUNT(*) FROM devs WHERE devs.team_id = teams.id)"
+            ]
+        );
+        let counts: Vec<Value> = rows.iter().map(|r| r[1].clone()).collect();
+        assert_eq!(counts, [2, 1, 0].map(Value::Integer));
+
+        // No row gives NULL; an uncorrelated subquery runs once for every row.
+        let sql = "SELECT (SELECT id FROM devs WHERE team_id = t.id) AS dev, \
+                   (SELECT MAX(id) FROM devs) AS newest FROM teams t WHERE id > 1 ORDER BY id";
+        let Ok(ExecutionResult::Data { headers, rows, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["dev", "newest"]);
+        assert_eq!(
+            rows,
+            [
+                [Value::Integer(12), Value::Integer(12)],
+                [Value::Null, Value::Integer(12)],
+            ]
+        );
+
+        // Also usable as a value in WHERE.
+        let sql =
+            "SELECT name FROM teams t WHERE (SELECT COUNT(*) FROM devs WHERE team_id = t.id) > 1";
+        let Ok(ExecutionResult::Data { rows, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Text("core".into())]]);
+
+        assert!(matches!(
+            run("SELECT (SELECT id FROM devs WHERE team_id = teams.id) FROM teams"),
+            Err(DbError::SubqueryTooManyRows(2))
+        ));
+        assert!(matches!(
+            run("SELECT (SELECT id, team_id FROM devs) FROM teams"),
+            Err(DbError::ParseError(_))
+        ));
+        assert!(matches!(
+            run("SELECT COUNT(*), (SELECT 1 FROM devs) FROM teams"),
+            Err(DbError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT, name TEXT)",
+            "CREATE TABLE orders (user_id INT, total INT)",
+        ] {
+            db.execute(crate::parser::parse(sql).unwrap()).unwrap();
+        }
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        // The tables are empty, so none of these would ever evaluate a row.
+        assert!(matches!(
+            run("SELECT * FROM users WHERE nmae = 'x'"),
+            Err(DbError::ColumnNotFound(c)) if c == "nmae"
+        ));
+        assert!(matches!(
+            run("SELECT SUM(name) FROM users"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT TODAY() FROM users"),
+            Err(DbError::ParseError(e)) if e == "Unknown function: TODAY"
+        ));
+        assert!(matches!(
+            run("SELECT * FROM users JOIN orders ON users.id = orders.uid"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+        assert!(matches!(
+            run("ALTER TABLE users RENAME COLUMN id TO name"),
+            Err(DbError::ColumnAlreadyExists(_))
+        ));
+        assert!(matches!(
+            run("INSERT INTO users VALUES (1, 'ada'), (2)"),
+            Err(DbError::ParseError(e))
+                if e == "Row 2 of the INSERT into 'users' has 1 value for 2 columns: no value for name"
+        ));
+        assert!(matches!(
+            run("INSERT INTO orders VALUES (1, 2, 3, 4)"),
+            Err(DbError::ParseError(e)) if e.ends_with("2 values too many")
+        ));
+
+        // Outer columns resolve inside EXISTS, aliases in ORDER BY, aggregates by header.
+        for sql in [
+            "SELECT name FROM users u WHERE EXISTS (SELECT * FROM orders WHERE user_id = u.id)",
+            "SELECT name AS n FROM users ORDER BY n",
+            "SELECT user_id, SUM(total) AS spent FROM orders GROUP BY user_id ORDER BY spent",
+            "SELECT t.value FROM (SELECT * FROM generate_series(1, 3)) AS t",
+        ] {
+            assert!(run(sql).is_ok(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_multi_row_insert_is_atomic() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run("INSERT INTO users VALUES (1, 'Ann')").unwrap();
+
+        // The third row collides with the first, so none of them is kept.
+        let dup = run("INSERT INTO users VALUES (2, 'Bob'), (3, 'Cy'), (2, 'Dup')");
+        assert!(matches!(dup, Err(DbError::UniqueViolation(_))));
+        let Ok(ExecutionResult::Message(message)) =
+            run("INSERT INTO users VALUES (2, 'Bob'), (3, 'Cy')")
+        else {
+            panic!("expected the rows to be accepted");
+        };
+        assert_eq!(message, "2 rows inserted.");
+
+        let table = &db.tables["users"];
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.indexes[&0].len(), 3);
+        let bytes: usize = table
+            .rows
+            .iter()
+            .flatten()
+            .map(Value::approximate_size)
+            .sum();
+        assert_eq!(table.approx_bytes, bytes);
+    }
+
+    #[test]
+    fn test_prepared_statements() {
+        let mut db = Database::new();
+        db.execute(
+            crate::parser::parse("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)").unwrap(),
+        )
+        .unwrap();
+        let insert = db.prepare("INSERT INTO users VALUES (?, ?)").unwrap();
+        assert_eq!(insert.param_count(), 2);
+        for (id, name) in [(1, "ann"), (2, "o'brien")] {
+            db.execute_prepared(&insert, &[Value::Integer(id), Value::Text(name.into())])
+                .unwrap();
+        }
+        assert!(db.execute_prepared(&insert, &[Value::Integer(3)]).is_err());
+
+        let select = db
+            .prepare("SELECT name FROM users WHERE id = ? LIMIT ?")
+            .unwrap();
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            db.execute_prepared(&select, &[Value::Integer(2), Value::Integer(1)])
+        else {
+            panic!("expected rows");
+        };
+        assert_eq!(rows, [[Value::Text("o'brien".into())]]);
+
+        // Preparing the same SQL again is answered by the statement cache.
+        let hits = db.stats.cache_hits;
+        db.prepare("INSERT INTO users VALUES (?, ?)").unwrap();
+        assert_eq!(db.stats.cache_hits, hits + 1);
+        assert!(db.prepare("SELECT * FROM ?").is_err());
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE stock (sku INT PRIMARY KEY, name TEXT UNIQUE, qty INT)").unwrap();
+        run("INSERT INTO stock VALUES (1, 'bolt', 5), (2, 'nut', 3)").unwrap();
+
+        let message = |result: Result<ExecutionResult, DbError>| match result {
+            Ok(ExecutionResult::Message(message)) => message,
+            _ => panic!("expected a message"),
+        };
+        assert_eq!(
+            message(run(
+                "INSERT INTO stock VALUES (1, 'bolt', 9), (3, 'gear', 1) ON CONFLICT DO NOTHING"
+            )),
+            "1 row inserted, 0 updated, 1 skipped."
+        );
+        assert_eq!(
+            message(run(
+                "INSERT INTO stock VALUES (2, 'nut', 4) ON CONFLICT (sku) DO UPDATE SET qty = excluded.qty, name = 'hex_nut'"
+            )),
+            "0 rows inserted, 1 updated, 0 skipped."
+        );
+
+        // Renaming sku 3 to an existing name fails and undoes the whole statement.
+        let clash = run(
+            "INSERT INTO stock VALUES (4, 'washer', 1), (3, 'x', 0) ON CONFLICT (sku) DO UPDATE SET name = 'bolt'",
+        );
+        assert!(matches!(clash, Err(DbError::UniqueViolation(_))));
+        // So does one that first updates a row inserted by the same statement.
+        let clash = run(
+            "INSERT INTO stock VALUES (4, 'w', 1), (4, 'x', 2), (5, 'bolt', 0) ON CONFLICT (sku) DO UPDATE SET name = excluded.name",
+        );
+        assert!(matches!(clash, Err(DbError::UniqueViolation(_))));
+        assert!(matches!(
+            run("INSERT INTO stock VALUES (5, 'pin', 1) ON CONFLICT (qty) DO NOTHING"),
+            Err(DbError::IndexNotFound(_))
+        ));
+        assert!(matches!(
+            run(
+                "INSERT INTO stock VALUES (5, 'pin', 1) ON CONFLICT DO UPDATE SET qty = excluded.amount"
+            ),
+            Err(DbError::ColumnNotFound(_))
+        ));
+
+        let table = &db.tables["stock"];
+        assert_eq!(
+            table.rows,
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("bolt".into()),
+                    Value::Integer(5)
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Text("hex_nut".into()),
+                    Value::Integer(4)
+                ],
+                vec![
+                    Value::Integer(3),
+                    Value::Text("gear".into()),
+                    Value::Integer(1)
+                ],
+            ]
+        );
+        assert!(table.indexes[&1].contains(&Value::Text("hex_nut".into())));
+        assert!(!table.indexes[&1].contains(&Value::Text("nut".into())));
+    }
+
+    #[test]
+    fn test_if_exists() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        let create = "CREATE TABLE IF NOT EXISTS t (id INT PRIMARY KEY)";
+        run(create).unwrap();
+        run("INSERT INTO t VALUES (1)").unwrap();
+        // Re-running the script keeps the table and its rows.
+        run(create).unwrap();
+        assert!(matches!(
+            run("CREATE TABLE t (id INT)"),
+            Err(DbError::TableAlreadyExists(_))
+        ));
+        assert_eq!(db.tables["t"].rows.len(), 1);
+
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("DROP TABLE t").unwrap();
+        assert!(matches!(
+            run("DROP TABLE t"),
+            Err(DbError::TableNotFound(_))
+        ));
+        run("DROP TABLE IF EXISTS t").unwrap();
+        assert!(!db.tables.contains_key("t"));
+        assert!(crate::parser::parse("DROP TABLE IF t").is_err());
+    }
+
+    #[test]
+    fn test_rename_table() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE people (id INT PRIMARY KEY)").unwrap();
+        run("CREATE TABLE teams (id INT)").unwrap();
+        run("INSERT INTO people VALUES (1)").unwrap();
+        assert!(matches!(
+            run("ALTER TABLE people RENAME TO teams"),
+            Err(DbError::TableAlreadyExists(_))
+        ));
+        run("ALTER TABLE people RENAME TO users").unwrap();
+        assert!(matches!(
+            run("SELECT * FROM people"),
+            Err(DbError::TableNotFound(_))
+        ));
+        // The rows and indexes move with the table.
+        assert!(matches!(
+            run("INSERT INTO users VALUES (1)"),
+            Err(DbError::UniqueViolation(_))
+        ));
+        assert_eq!(db.tables["users"].name, "users");
+    }
+
+    #[test]
+    fn test_result_types() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE events (id INT PRIMARY KEY, name TEXT, at TIMESTAMP)").unwrap();
+        run("INSERT INTO events VALUES (1, 'launch', '2024-01-01T00:00:00')").unwrap();
+        let mut types = |sql: &str| match run(sql).unwrap() {
+            ExecutionResult::Data { types, .. } => types,
+            ExecutionResult::Message(_) => panic!("expected data"),
+        };
+
+        assert_eq!(types("SELECT * FROM events"), ["INT", "TEXT", "TIMESTAMP"]);
+        assert_eq!(
+            types("SELECT at, id = 1, 'x', @unset, CURRENT_USER() FROM events"),
+            ["TIMESTAMP", "INT", "TEXT", "TEXT", "TEXT"]
+        );
+        assert_eq!(
+            types("SELECT name, COUNT(*), MAX(at) FROM events GROUP BY name"),
+            ["TEXT", "INT", "TIMESTAMP"]
+        );
+        assert_eq!(
+            types("SELECT e.at FROM (SELECT at FROM events) AS e"),
+            ["TIMESTAMP"]
+        );
+        assert_eq!(types("SHOW SCHEDULES")[2], "INT");
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY KEY, name TEXT, email TEXT)").unwrap();
+        run("INSERT INTO users VALUES (1, 'ada', 'a@x'), (2, 'alan', 'b@x'), (3, 'ada', 'a@x')")
+            .unwrap();
+        run("INSERT INTO users VALUES (4, 'ada', 'c@x'), (5, 'alan', 'b@x'), (6, 'ada', 'a@x')")
+            .unwrap();
+
+        let ExecutionResult::Data { headers, rows, .. } =
+            run("FIND DUPLICATES IN users (name, email)").unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["name", "email", "occurrences"]);
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(
+            rows,
+            [
+                vec![text("ada"), text("a@x"), Value::Integer(3)],
+                vec![text("alan"), text("b@x"), Value::Integer(2)],
+            ]
+        );
+        assert!(matches!(
+            run("FIND DUPLICATES IN users (missing)"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+        assert!(crate::parser::parse("FIND DUPLICATES IN users name").is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run("COMMENT ON TABLE users IS 'People who can log in'").unwrap();
+        run("COMMENT ON COLUMN users.name IS 'display_name'").unwrap();
+        run("ALTER TABLE users RENAME COLUMN name TO nickname").unwrap();
+        assert!(matches!(
+            run("COMMENT ON COLUMN users.missing IS 'x'"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+
+        let Ok(ExecutionResult::Data { headers, rows, .. }) = run("DESCRIBE users") else {
+            panic!("expected data");
+        };
+        assert_eq!(headers.last().unwrap(), "comment");
+        assert_eq!(rows[0][5], Value::Null);
+        assert_eq!(rows[1][1], Value::Text("nickname".into()));
+        assert_eq!(rows[1][5], Value::Text("display_name".into()));
+        run("CREATE TABLE \"Bob's Items\" (id INT)").unwrap();
+        let Ok(ExecutionResult::Data { rows, .. }) = run("DESCRIBE \"Bob's Items\"") else {
+            panic!("expected data");
+        };
+        assert_eq!(rows[0][1], Value::Text("id".into()));
+
+        let bytes = crate::storage::Format::Json.encode(&db).unwrap();
+        let loaded = crate::storage::Format::Json.decode(&bytes).unwrap();
+        let users = &loaded.tables["users"];
+        assert_eq!(users.comment.as_deref(), Some("People who can log in"));
+
+        db.execute(crate::parser::parse("COMMENT ON TABLE users IS NULL").unwrap())
+            .unwrap();
+        assert_eq!(db.tables["users"].comment, None);
+        assert!(crate::parser::parse("COMMENT ON users IS 'x'").is_err());
+        assert!(crate::parser::parse("COMMENT ON TABLE users IS x").is_err());
+        for sql in [
+            "COMMENT ON TABLE users IS 'a' 'b'",
+            "COMMENT ON TABLE users IS 'a' || 'b'",
+            "COMMENT ON TABLE users IS NULL 'x'",
+            "COMMENT ON TABLE users IS 'a'b'",
+        ] {
+            assert!(crate::parser::parse(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE orders (id INT PRIMARY KEY, price INT, qty INT, note TEXT)").unwrap();
+        run("INSERT INTO orders VALUES (1, 10, 3, 'a'), (3, 4, 5, 'c')").unwrap();
+        let row = vec![
+            Value::Integer(2),
+            Value::Integer(7),
+            Value::Null,
+            Value::Null,
+        ];
+        db.tables
+            .get_mut("orders")
+            .unwrap()
+            .insert_row(row)
+            .unwrap();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let sql = "SELECT id, price * qty + 1 AS total FROM orders WHERE price - qty > 2";
+        let Ok(ExecutionResult::Data { rows, types, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Integer(1), Value::Integer(31)]]);
+        assert_eq!(types, ["INT", "INT"]);
+
+        let Ok(ExecutionResult::Data { rows, .. }) = run("SELECT price * qty FROM orders") else {
+            panic!("expected data");
+        };
+        assert_eq!(rows[2], [Value::Null]);
+        assert!(matches!(
+            run("SELECT price / 0 FROM orders"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT note + 1 FROM orders"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT 2147483647 + price FROM orders"),
+            Err(DbError::TypeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE devs (id INT, name TEXT)").unwrap();
+        run("INSERT INTO devs VALUES (1, 'Ada'), (2, 'Grace')").unwrap();
+        let result = run(
+            "SELECT UPPER(name), LOWER(name), LENGTH(name), SUBSTR(name, 2, 3), \
+             SUBSTR(name, 3), CONCAT(name, '-', id) FROM devs WHERE LENGTH(name) > 3",
+        )
+        .unwrap();
+        let ExecutionResult::Data {
+            headers,
+            rows,
+            types,
+        } = result
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers[0], "UPPER(name)");
+        assert_eq!(types[2], "INT");
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(
+            rows,
+            [[
+                text("GRACE"),
+                text("grace"),
+                Value::Integer(5),
+                text("rac"),
+                text("ace"),
+                text("Grace-2"),
+            ]]
+        );
+
+        db.tables
+            .get_mut("devs")
+            .unwrap()
+            .insert_row(vec![Value::Integer(3), Value::Null])
+            .unwrap();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        let result =
+            run("SELECT UPPER(name), CONCAT('#', name), SUBSTR(name, 0, 2) FROM devs WHERE id = 3");
+        let Ok(ExecutionResult::Data { rows, .. }) = result else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Null, text("#"), Value::Null]]);
+        // Position 0 comes before the first character and uses up one of the two.
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            run("SELECT SUBSTR(name, 0, 2) FROM devs WHERE id = 1")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[text("A")]]);
+        assert!(matches!(
+            run("SELECT UPPER() FROM devs"),
+            Err(DbError::ParseError(_))
+        ));
+        assert!(matches!(
+            run("SELECT SUBSTR(name, 1, 2, 3) FROM devs"),
+            Err(DbError::ParseError(_))
+        ));
+        assert!(matches!(
+            run("SELECT SUBSTR(name, 'x') FROM devs"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT SUBSTR(name, 1, -1) FROM devs"),
+            Err(DbError::TypeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_coalesce_nullif() {
+        let mut db = Database::new();
+        db.execute(
+            crate::parser::parse("CREATE TABLE devs (id INT, nickname TEXT, name TEXT)").unwrap(),
+        )
+        .unwrap();
+        let devs = db.tables.get_mut("devs").unwrap();
+        let text = |s: &str| Value::Text(s.into());
+        devs.insert_row(vec![Value::Integer(1), text("ada"), text("Ada")])
+            .unwrap();
+        devs.insert_row(vec![Value::Integer(2), Value::Null, text("Grace")])
+            .unwrap();
+        devs.insert_row(vec![Value::Integer(3), Value::Null, Value::Null])
+            .unwrap();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let result = run(
+            "SELECT COALESCE(nickname, name, 'anonymous'), NULLIF(id, 2), \
+             COALESCE(NULLIF(id, 2), 2.5) FROM devs ORDER BY id",
+        )
+        .unwrap();
+        let ExecutionResult::Data { rows, types, .. } = result else {
+            panic!("expected data");
+        };
+        assert_eq!(types, ["TEXT", "INT", "REAL"]);
+        let real = |r: f64| Value::Real(Real::new(r).unwrap());
+        assert_eq!(
+            rows,
+            [
+                [text("ada"), Value::Integer(1), Value::Integer(1)],
+                [text("Grace"), Value::Null, real(2.5)],
+                [text("anonymous"), Value::Integer(3), Value::Integer(3)],
+            ]
+        );
+        assert!(matches!(
+            run("SELECT COALESCE(nickname, id) FROM devs"),
+            Err(DbError::TypeError(_))
+        ));
+
+        let result = run("SELECT id FROM devs WHERE NULLIF(nickname, 'ada') IS NULL").unwrap();
+        let ExecutionResult::Data { rows, .. } = result else {
+            panic!("expected data");
+        };
+        assert_eq!(rows.len(), 3);
+        assert!(matches!(
+            run("SELECT NULLIF(id) FROM devs"),
+            Err(DbError::ParseError(_))
+        ));
+        assert!(matches!(
+            run("SELECT COALESCE() FROM devs"),
+            Err(DbError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE items (id INT, code TEXT)").unwrap();
+        run("INSERT INTO items VALUES (1, '42'), (2, '7'), (3, 'x7')").unwrap();
+
+        let result =
+            run("SELECT CAST(id AS TEXT), CAST(code AS INT) * 2 FROM items WHERE id < 3").unwrap();
+        let ExecutionResult::Data { rows, types, .. } = result else {
+            panic!("expected data");
+        };
+        assert_eq!(types, ["TEXT", "INT"]);
+        assert_eq!(
+            rows,
+            [
+                [Value::Text("1".into()), Value::Integer(84)],
+                [Value::Text("2".into()), Value::Integer(14)],
+            ]
+        );
+        let result = run("SELECT id FROM items WHERE CAST(code AS TEXT) = '7'").unwrap();
+        let ExecutionResult::Data { rows, .. } = result else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Integer(2)]]);
+
+        assert!(matches!(
+            run("SELECT CAST(code AS INT) FROM items"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT CAST(id AS TEXT) + 1 FROM items"),
+            Err(DbError::TypeError(_))
+        ));
+
+        run("CREATE TABLE events (d DATE, ts TIMESTAMP, r REAL)").unwrap();
+        run("INSERT INTO events VALUES ('2024-01-31', '2024-01-31T12:00:00', 1.5)").unwrap();
+        for sql in [
+            "SELECT CAST(d AS INT) FROM events",
+            "SELECT CAST(ts AS BIGINT) FROM events",
+            "SELECT CAST(d AS REAL) FROM events",
+            "SELECT CAST(r AS DATE) FROM events",
+        ] {
+            assert!(matches!(run(sql), Err(DbError::TypeError(_))), "{}", sql);
+        }
+        let result = run("SELECT CAST(d AS DATE), CAST(NULL AS INT) FROM events").unwrap();
+        let ExecutionResult::Data { rows, .. } = result else {
+            panic!("expected data");
+        };
+        assert_eq!(rows[0][1], Value::Null);
+    }
+
+    #[test]
+    fn test_bigint() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE events (id BIGINT PRIMARY, at_ms BIGINT, n INT)").unwrap();
+        run("INSERT INTO events VALUES (1, 1700000000123, 2147483647), (7, 5, 1)").unwrap();
+        // `INT` values written to a `BIGINT` column are widened, and still equal to `INT`s.
+        assert_eq!(db.tables["events"].rows[1][0], Value::BigInt(7));
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let result = run("SELECT id, at_ms + 1000, n + id FROM events WHERE at_ms > 2147483648");
+        let Ok(ExecutionResult::Data { rows, types, .. }) = result else {
+            panic!("expected data");
+        };
+        assert_eq!(types, ["BIGINT", "BIGINT", "BIGINT"]);
+        assert_eq!(
+            rows,
+            [[
+                Value::Integer(1),
+                Value::BigInt(1_700_000_001_123),
+                Value::BigInt(2_147_483_648)
+            ]]
+        );
+        // Sums outgrowing `INT` become `BIGINT`.
+        let Ok(ExecutionResult::Data { rows, .. }) = run("SELECT SUM(n) FROM events") else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::BigInt(2_147_483_648)]]);
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            run("SELECT CAST(at_ms AS TEXT), CAST(id AS INT) FROM events WHERE id = 1")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            [[Value::Text("1700000000123".into()), Value::Integer(1)]]
+        );
+
+        assert!(matches!(
+            run("SELECT CAST(at_ms AS INT) FROM events"),
+            Err(DbError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("SELECT at_ms * 9223372036854775807 FROM events"),
+            Err(DbError::TypeError(_))
+        ));
+
+        // The smallest `BIGINT` is written as its digits with a minus sign.
+        run("INSERT INTO events VALUES (-9223372036854775808, -1, -1)").unwrap();
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            run("SELECT at_ms FROM events WHERE id = -9223372036854775808")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::BigInt(-1)]]);
+        assert_eq!(db.tables["events"].rows[2][0], Value::BigInt(i64::MIN));
+    }
+
+    #[test]
+    fn test_varchar_length() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (code VARCHAR(3) PRIMARY, name VARCHAR, city TEXT)").unwrap();
+        run("INSERT INTO users VALUES ('abc', 'a_long_name', 'x'), ('é€ö', 'b', 'y')").unwrap();
+        let Err(err) = run("INSERT INTO users VALUES ('abcd', 'c', 'z')") else {
+            panic!("expected an error");
+        };
+        assert!(matches!(
+            &err,
+            DbError::ValueTooLong {
+                column,
+                max_length: 3,
+                length: 4
+            } if column == "code"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Value too long for column 'code': 4 characters, at most 3"
+        );
+        // The columns after a `VARCHAR(n)` are kept.
+        let columns = &db.tables["users"].columns;
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns[0].declared_type(), "VARCHAR(3)");
+        assert_eq!(columns[1].declared_type(), "VARCHAR");
+        assert_eq!(db.tables["users"].rows.len(), 2);
+        for sql in [
+            "CREATE TABLE t (c VARCHAR(0))",
+            "CREATE TABLE t (c VARCHAR(big))",
+        ] {
+            assert!(crate::parser::parse(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY, name TEXT, score REAL)").unwrap();
+        let Err(err) = run("INSERT INTO users VALUES ('abc', 1, 2)") else {
+            panic!("expected an error");
+        };
+        assert!(matches!(
+            &err,
+            DbError::TypeMismatch { column, expected, got }
+                if column == "id" && expected == "INT" && got == "TEXT"
+        ));
+        assert_eq!(err.to_string(), "Column 'id' expects INT, got TEXT");
+        assert!(matches!(
+            run("INSERT INTO users VALUES (1, 2, 3)"),
+            Err(DbError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            run("INSERT INTO users VALUES (1, 'ada', 'high')"),
+            Err(DbError::TypeMismatch { .. })
+        ));
+
+        // Integers are widened to `REAL`.
+        run("INSERT INTO users VALUES (1, 'ada', 2)").unwrap();
+        assert!(matches!(db.tables["users"].rows[0][2], Value::Real(_)));
+    }
+
+    #[test]
+    fn test_null_literal() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY, email TEXT UNIQUE, name TEXT NOT NULL, age INT)")
+            .unwrap();
+        // Several rows may leave a `UNIQUE` column `NULL`.
+        run("INSERT INTO users VALUES (1, NULL, 'ada', null), (2, NULL, 'NULL', 30)").unwrap();
+        let rows = &db.tables["users"].rows;
+        assert_eq!(rows[0][1], Value::Null);
+        assert_eq!(rows[0][3], Value::Null);
+        assert_eq!(rows[1][2], Value::Text("NULL".into()));
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            run("SELECT id, NULL FROM users WHERE age IS NULL")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Integer(1), Value::Null]]);
+        run(
+            "INSERT INTO users VALUES (2, 'b', 'bob', 1) ON CONFLICT (id) DO UPDATE SET age = NULL",
+        )
+        .unwrap();
+        assert_eq!(db.tables["users"].rows[1][3], Value::Null);
+
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        for (sql, column) in [
+            ("INSERT INTO users VALUES (3, 'c', NULL, 1)", "name"),
+            ("INSERT INTO users VALUES (NULL, 'c', 'cy', 1)", "id"),
+            (
+                "INSERT INTO users VALUES (1, 'a', 'ada', 1) ON CONFLICT (id) DO UPDATE SET name = NULL",
+                "name",
+            ),
+        ] {
+            assert!(
+                matches!(run(sql), Err(DbError::NotNullViolation(c)) if c == column),
+                "{}",
+                sql
+            );
+        }
+        assert!(db.tables["users"].columns[2].is_not_null);
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE people (id INT PRIMARY, name TEXT)").unwrap();
+        run("INSERT INTO people VALUES (1, 'O''Brien'), (2, ''''), (3, '')").unwrap();
+        run("COMMENT ON COLUMN people.name IS 'the person''s name'").unwrap();
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            run("SELECT id, CONCAT(name, '''s') FROM people WHERE name = 'O''Brien'")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Integer(1), Value::Text("O'Brien's".into())]]);
+
+        // Stored values and the SQL written back out keep the quotes.
+        let json = serde_json::to_string(&db).unwrap();
+        let restored: Database = serde_json::from_str(&json).unwrap();
+        let names: Vec<&Value> = restored.tables["people"]
+            .rows
+            .iter()
+            .map(|r| &r[1])
+            .collect();
+        assert_eq!(
+            names,
+            [
+                &Value::Text("O'Brien".into()),
+                &Value::Text("'".into()),
+                &Value::Text("".into())
+            ]
+        );
+        let diff = Database::new().schema_diff(&db);
+        assert!(
+            diff.statements
+                .contains(&"COMMENT ON COLUMN people.name IS 'the person''s name'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_quoted_identifiers() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run(r#"CREATE TABLE Users (Id INT PRIMARY, "Select" TEXT, "first name" TEXT)"#).unwrap();
+        run("INSERT INTO USERS VALUES (1, 'a', 'Ann'), (2, 'b', 'Bob')").unwrap();
+        let Ok(ExecutionResult::Data { headers, rows, .. }) =
+            run(r#"SELECT "Select", Users."first name" AS "Name" FROM users WHERE ID = 1"#)
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["Select", "Name"]);
+        assert_eq!(rows, [[Value::Text("a".into()), Value::Text("Ann".into())]]);
+        // Quoted names keep their case, so "select" is a different column.
+        assert!(run(r#"SELECT "select" FROM users"#).is_err());
+        assert!(run(r#"SELECT "ID" FROM users"#).is_err());
+        run("ANALYZE USERS").unwrap();
+        assert!(run(r#"ANALYZE "Users""#).is_err());
+
+        let diff = Database::new().schema_diff(&db);
+        assert_eq!(
+            diff.statements,
+            [r#"CREATE TABLE users (id INT PRIMARY KEY, "Select" TEXT, "first name" TEXT)"#]
+        );
+    }
+
+    #[test]
+    fn test_negative_numbers() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(crate::parser::parse(sql).unwrap());
+        run("CREATE TABLE temps (id INT PRIMARY, celsius INT, delta REAL)").unwrap();
+        run("INSERT INTO temps VALUES (1, -5, -0.5), (2,- 3, - .25), (3, 0, +1.5)").unwrap();
+        let Ok(ExecutionResult::Data { rows, .. }) = run("SELECT celsius, delta FROM temps") else {
+            panic!("expected data");
+        };
+        let real = |r: f64| Value::Real(crate::engine::real::Real::new(r).unwrap());
+        assert_eq!(
+            rows,
+            [
+                [Value::Integer(-5), real(-0.5)],
+                [Value::Integer(-3), real(-0.25)],
+                [Value::Integer(0), real(1.5)]
+            ]
+        );
+
+        // However it is spaced, a minus after a value subtracts.
+        for sql in [
+            "SELECT id FROM temps WHERE celsius = - 5",
+            "SELECT id FROM temps WHERE celsius -1 = -6",
+            "SELECT id FROM temps WHERE celsius - -1 = -4",
+            "SELECT id FROM temps WHERE - 5 = celsius",
+        ] {
+            let Ok(ExecutionResult::Data { rows, .. }) = run(sql) else {
+                panic!("expected data for {}", sql);
+            };
+            assert_eq!(rows, [[Value::Integer(1)]], "{}", sql);
+        }
+        let Ok(ExecutionResult::Data { rows, .. }) = run("SELECT - 2, 3 -1, -2147483648") else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            [[
+                Value::Integer(-2),
+                Value::Integer(2),
+                Value::Integer(i32::MIN)
+            ]]
+        );
+
+        // Columns and parenthesized expressions are negated too.
+        let sql = "SELECT -celsius, - -celsius, -(celsius + 1) * 2, -delta, -NULL \
+                   FROM temps WHERE -celsius = 5";
+        let Ok(ExecutionResult::Data { rows, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            [[
+                Value::Integer(5),
+                Value::Integer(-5),
+                Value::Integer(8),
+                real(0.5),
+                Value::Null
+            ]]
+        );
+        for sql in [
+            "SELECT -'cold' FROM temps",
+            "SELECT -CAST(-2147483648 AS INT) FROM temps",
+        ] {
+            assert!(matches!(run(sql), Err(DbError::TypeError(_))), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_dirty_tracking() {
+        let mut db = Database::new();
+        assert!(!db.is_dirty());
+
+        db.execute(crate::parser::parse("SELECT * FROM generate_series(1, 3)").unwrap())
+            .unwrap();
+        assert!(!db.is_dirty());
+        db.execute(crate::parser::parse("CREATE TABLE t (id INT PRIMARY)").unwrap())
+            .unwrap();
+        assert!(db.is_dirty());
+
+        db.flush(&Storage::Memory).unwrap();
+        assert!(!db.is_dirty());
+
+        // a rejected insert leaves nothing to persist
+        db.execute(crate::parser::parse("INSERT INTO t VALUES (1)").unwrap())
+            .unwrap();
+        db.flush(&Storage::Memory).unwrap();
+        assert!(
+            db.execute(crate::parser::parse("INSERT INTO t VALUES (1)").unwrap())
+                .is_err()
+        );
+        assert!(!db.is_dirty());
+    }
 }
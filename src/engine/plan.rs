@@ -0,0 +1,734 @@
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+use crate::{
+    Database, DbError,
+    engine::{
+        ExecutionResult, Table, Value,
+        index_scan::IndexProbe,
+        relation::Relation,
+        rewrite,
+        session::Session,
+        statistics::{ColumnStats, selectivity},
+    },
+    parser::{
+        CommentTarget, Expr, Hint, JoinDefinition, JoinKind, OrderByDefinition, SelectItem,
+        SelectStatement, Statement, parse,
+    },
+};
+
+/// One operator of an execution plan. The root is the final stage of the query and
+/// `children` are the inputs it consumes.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanNode {
+    pub operator: String,
+    pub detail: String,
+    pub estimated_rows: usize,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    fn new(operator: &str, detail: String, estimated_rows: usize, children: Vec<PlanNode>) -> Self {
+        Self {
+            operator: operator.into(),
+            detail,
+            estimated_rows,
+            children,
+        }
+    }
+
+    /// Lists the operators of the plan, parents before their inputs, as the result of an
+    /// `EXPLAIN`. Operators are indented two spaces per level below the root.
+    pub fn to_result(&self) -> ExecutionResult {
+        let mut rows = Vec::new();
+        self.push_rows(0, &mut rows);
+        ExecutionResult::listing(
+            &[
+                ("operator", "TEXT"),
+                ("detail", "TEXT"),
+                ("estimated_rows", "INT"),
+            ],
+            rows,
+        )
+    }
+
+    fn push_rows(&self, depth: usize, rows: &mut Vec<Vec<Value>>) {
+        rows.push(vec![
+            Value::Text(format!("{}{}", "  ".repeat(depth), self.operator)),
+            Value::Text(self.detail.clone()),
+            Value::Integer(self.estimated_rows.min(i32::MAX as usize) as i32),
+        ]);
+        for child in &self.children {
+            child.push_rows(depth + 1, rows);
+        }
+    }
+}
+
+impl Database {
+    /// How many rows `statement` would return, or touch for a write, from the row counts and
+    /// indexes of its tables. Nothing runs. Filters are estimated from the statistics of
+    /// `ANALYZE`; on tables without them, filters that cannot use an index are assumed to keep
+    /// every row, so the figure is an upper bound a UI can warn about before executing.
+    pub fn estimate_rows(&self, statement: &Statement) -> Result<usize, DbError> {
+        Ok(self.plan(statement)?.estimated_rows)
+    }
+
+    /// Describes how `statement` would be executed without running it.
+    pub fn plan(&self, statement: &Statement) -> Result<PlanNode, DbError> {
+        match statement {
+            Statement::Select(select) | Statement::ExplainAnalyze(select) => {
+                self.plan_select(select)
+            }
+            Statement::Explain(inner) => self.plan(inner),
+            Statement::CreateTable { name, .. } => {
+                Ok(PlanNode::new("Create Table", name.clone(), 0, vec![]))
+            }
+            Statement::DropTable { name, .. } => {
+                let rows = self.tables.get(name).map_or(0, |t| t.rows.len());
+                Ok(PlanNode::new("Drop Table", name.clone(), rows, vec![]))
+            }
+            Statement::Insert {
+                table_name, rows, ..
+            } => Ok(PlanNode::new(
+                "Insert",
+                table_name.clone(),
+                rows.len(),
+                vec![],
+            )),
+            Statement::ExecuteQuery(name) => {
+                let sql = self
+                    .saved_queries
+                    .get(name)
+                    .ok_or_else(|| DbError::QueryNotFound(name.clone()))?;
+                let stmt = parse(sql)?;
+                let inner = self.plan(&stmt)?;
+                let rows = inner.estimated_rows;
+                Ok(PlanNode::new(
+                    "Saved Query",
+                    name.clone(),
+                    rows,
+                    vec![inner],
+                ))
+            }
+            Statement::CreateIndex { table_name, .. } => {
+                let rows = self.get_table(table_name.clone())?.rows.len();
+                Ok(PlanNode::new(
+                    "Create Index",
+                    table_name.clone(),
+                    rows,
+                    vec![],
+                ))
+            }
+            Statement::Comment { target, .. } => {
+                let detail = match target {
+                    CommentTarget::Table(table) => table.clone(),
+                    CommentTarget::Column { table, column } => format!("{}.{}", table, column),
+                };
+                Ok(PlanNode::new("Catalog Update", detail, 0, vec![]))
+            }
+            Statement::Analyze(table) => {
+                let rows = match table {
+                    Some(table) => self.get_table(table.clone())?.rows.len(),
+                    None => self.tables.values().map(|t| t.rows.len()).sum(),
+                };
+                let detail = table.clone().unwrap_or_else(|| "all tables".into());
+                Ok(PlanNode::new("Analyze", detail, rows, vec![]))
+            }
+            Statement::CreateQuery { name, .. }
+            | Statement::DropIndex(name)
+            | Statement::DropQuery(name)
+            | Statement::CreateSchedule { name, .. }
+            | Statement::DropSchedule(name)
+            | Statement::CreateTrigger { name, .. }
+            | Statement::DropTrigger(name) => {
+                Ok(PlanNode::new("Catalog Update", name.clone(), 0, vec![]))
+            }
+            Statement::ShowQueries => Ok(PlanNode::new(
+                "Catalog Scan",
+                "saved queries".into(),
+                self.saved_queries.len(),
+                vec![],
+            )),
+            Statement::SetVariable { name, .. } => Ok(PlanNode::new(
+                "Set Variable",
+                format!("@{}", name),
+                0,
+                vec![],
+            )),
+            Statement::ShowStatus => Ok(PlanNode::new(
+                "Catalog Scan",
+                "status".into(),
+                self.status().len(),
+                vec![],
+            )),
+            Statement::ShowIndexStats => Ok(PlanNode::new(
+                "Catalog Scan",
+                "index stats".into(),
+                self.tables.values().map(|t| t.indexes.len()).sum(),
+                vec![],
+            )),
+            Statement::ShowSchedules => Ok(PlanNode::new(
+                "Catalog Scan",
+                "schedules".into(),
+                self.schedules.len(),
+                vec![],
+            )),
+            Statement::ShowTriggers => Ok(PlanNode::new(
+                "Catalog Scan",
+                "triggers".into(),
+                self.triggers.len(),
+                vec![],
+            )),
+            Statement::Begin | Statement::Commit | Statement::Rollback => Ok(PlanNode::new(
+                "Transaction",
+                super::query_log::describe(statement),
+                0,
+                vec![],
+            )),
+            Statement::Savepoint(name)
+            | Statement::RollbackToSavepoint(name)
+            | Statement::ReleaseSavepoint(name) => {
+                Ok(PlanNode::new("Savepoint", name.clone(), 0, vec![]))
+            }
+            Statement::AlterTable { table_name, .. } => {
+                let rows = self.get_table(table_name.clone())?.rows.len();
+                Ok(PlanNode::new(
+                    "Alter Table",
+                    table_name.clone(),
+                    rows,
+                    vec![],
+                ))
+            }
+        }
+    }
+
+    fn plan_select(&self, select: &SelectStatement) -> Result<PlanNode, DbError> {
+        let (left, operator, source_rows, derived) = match &select.subquery {
+            Some(subquery) => {
+                let inner = self.plan_select(subquery)?;
+                let table = Table::derived(
+                    select.table_name.clone(),
+                    self.select_headers(subquery)?,
+                    Vec::new(),
+                )?;
+                (
+                    Cow::Owned(table),
+                    "Derived Table",
+                    inner.estimated_rows,
+                    vec![inner],
+                )
+            }
+            None => {
+                let args = select.table_args.as_deref();
+                let table = self.table_schema(&select.table_name, args)?;
+                let operator = if select.table_args.is_some() {
+                    "Table Function"
+                } else if self.virtual_tables.contains_key(&select.table_name) {
+                    "Virtual Scan"
+                } else {
+                    "Full Scan"
+                };
+                let rows = self.row_count(&select.table_name, args)?;
+                (table, operator, rows, vec![])
+            }
+        };
+        // The same rewrite the executor applies before filtering.
+        let predicate = select
+            .where_clause
+            .clone()
+            .map(rewrite::simplify_predicate)
+            .filter(|predicate| rewrite::known(predicate) != Some(true));
+        if predicate.as_ref().and_then(rewrite::known) == Some(false) {
+            let node = PlanNode::new("Empty Result", "WHERE is always false".into(), 0, vec![]);
+            return Ok(plan_output(select, node));
+        }
+
+        let mut node = PlanNode::new(
+            operator,
+            scan_detail(&left.name, select.table_alias.as_deref()),
+            source_rows,
+            derived,
+        );
+        if let Some(probe) = IndexProbe::for_select(&left, select, predicate.as_ref())? {
+            node = PlanNode::new(
+                "Index Lookup",
+                format!("{}.{}", left.name, left.columns[probe.column].name),
+                probe.estimated_rows(&left),
+                vec![],
+            );
+        }
+
+        let rights = select
+            .joins
+            .iter()
+            .map(|join| self.table_schema(&join.table_name, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let right_rows = select
+            .joins
+            .iter()
+            .map(|join| self.row_count(&join.table_name, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let qualified = |table: &Table, alias: Option<&str>| -> Vec<String> {
+            let qualifier = alias.unwrap_or(&table.name);
+            table
+                .columns
+                .iter()
+                .map(|c| format!("{}.{}", qualifier, c.name))
+                .collect()
+        };
+        let mut inputs = vec![qualified(&left, select.table_alias.as_deref())];
+        for (join, right) in select.joins.iter().zip(&rights) {
+            inputs.push(qualified(right, join.alias.as_deref()));
+        }
+        let mut scanned = vec![(select.table_alias.as_deref().unwrap_or(&left.name), &*left)];
+        for (join, right) in select.joins.iter().zip(&rights) {
+            scanned.push((join.alias.as_deref().unwrap_or(&right.name), &**right));
+        }
+        let stats = |column: &str| column_stats(&scanned, column);
+        let kinds: Vec<JoinKind> = select.joins.iter().map(|j| j.kind).collect();
+        let pushed = rewrite::push_down(predicate, &inputs, &kinds);
+        let needed = (!select.joins.is_empty())
+            .then(|| rewrite::needed_columns(select, pushed.residual.as_ref(), &inputs))
+            .flatten();
+        let prune = |node: PlanNode, input: usize| match &needed {
+            Some(needed) => {
+                let kept: Vec<&str> = needed[input]
+                    .iter()
+                    .map(|&i| inputs[input][i].as_str())
+                    .collect();
+                let rows = node.estimated_rows;
+                PlanNode::new("Prune Columns", kept.join(", "), rows, vec![node])
+            }
+            None => node,
+        };
+        node = prune(filter(node, pushed.source, source_rows, &stats), 0);
+
+        let right_scans: Vec<PlanNode> = select
+            .joins
+            .iter()
+            .zip(&rights)
+            .zip(right_rows)
+            .zip(pushed.joins)
+            .enumerate()
+            .map(|(i, (((join, right), rows), pushed))| {
+                let scan = PlanNode::new(
+                    "Full Scan",
+                    scan_detail(&right.name, join.alias.as_deref()),
+                    rows,
+                    vec![],
+                );
+                prune(filter(scan, pushed, rows, &stats), i + 1)
+            })
+            .collect();
+        let analyzed = scanned.iter().all(|(_, table)| table.has_statistics());
+        let sizes: Vec<usize> = right_scans.iter().map(|s| s.estimated_rows).collect();
+        let order = join_order(&select.joins, &inputs, analyzed.then_some(&sizes));
+        let mut steps: Vec<_> = select
+            .joins
+            .iter()
+            .zip(&rights)
+            .zip(right_scans)
+            .enumerate()
+            .collect();
+        steps.sort_by_key(|(i, _)| order.iter().position(|o| o == i));
+
+        for (_, ((join, right), right_scan)) in steps {
+            // When the right key is PRIMARY/UNIQUE every left row matches at most once.
+            let right_unique = join.on.as_ref().is_some_and(|on| {
+                let column = on.right_column.rsplit('.').next().unwrap_or_default();
+                right
+                    .columns
+                    .iter()
+                    .position(|c| c.name == column)
+                    .is_some_and(|i| right.indexes.contains_key(&i))
+            });
+            // Otherwise each key value is assumed to be shared by as many rows of either side,
+            // if `ANALYZE` counted the distinct keys.
+            let distinct_keys = join.on.as_ref().and_then(|on| {
+                [&on.left_column, &on.right_column]
+                    .into_iter()
+                    .filter_map(|column| stats(column))
+                    .map(|s| s.distinct)
+                    .max()
+            });
+            let (left_rows, right_rows) = (node.estimated_rows, right_scan.estimated_rows);
+            let mut estimated_rows = match distinct_keys {
+                _ if right_unique => left_rows,
+                Some(distinct) => (left_rows * right_rows).div_ceil(distinct.max(1)),
+                None => left_rows * right_rows,
+            };
+            // A LEFT JOIN emits every left row at least once.
+            if join.kind == JoinKind::Left {
+                estimated_rows = estimated_rows.max(left_rows);
+            }
+            let operator = join_operator(join, select.has_hint(Hint::HashJoin));
+            node = PlanNode::new(
+                operator,
+                join_detail(join),
+                estimated_rows,
+                vec![node, right_scan],
+            );
+        }
+
+        let rows = node.estimated_rows;
+        node = filter(node, pushed.residual, rows, &stats);
+        Ok(plan_output(select, node))
+    }
+
+    /// The headers `select` produces, worked out from the catalog without running it.
+    pub(super) fn select_headers(&self, select: &SelectStatement) -> Result<Vec<String>, DbError> {
+        if select.is_aggregate() {
+            return Ok(select.columns.iter().map(SelectItem::header).collect());
+        }
+        let relation = self.source_relation(select)?;
+        Ok(relation
+            .project(&select.columns, &Session::default())?
+            .headers)
+    }
+
+    /// An empty relation with the headers the `WHERE` clause of `select` sees: those of the
+    /// source and every joined table.
+    pub(super) fn source_relation(&self, select: &SelectStatement) -> Result<Relation, DbError> {
+        let source = match &select.subquery {
+            Some(subquery) => Cow::Owned(Table::derived(
+                select.table_name.clone(),
+                self.select_headers(subquery)?,
+                Vec::new(),
+            )?),
+            None => self.table_schema(&select.table_name, select.table_args.as_deref())?,
+        };
+        let mut relation = Relation::empty(&source);
+        if !select.joins.is_empty() || select.table_alias.is_some() {
+            relation.qualify(select.table_alias.as_deref().unwrap_or(&source.name));
+        }
+        for join in &select.joins {
+            let right_table = self.table_schema(&join.table_name, None)?;
+            let mut right = Relation::empty(&right_table);
+            right.qualify(join.alias.as_deref().unwrap_or(&right_table.name));
+            relation.headers.extend(right.headers);
+        }
+        Ok(relation)
+    }
+}
+
+/// Adds the stages after filtering (grouping, sorting, projection, ...) on top of `node`.
+fn plan_output(select: &SelectStatement, mut node: PlanNode) -> PlanNode {
+    if !select.group_by.is_empty() {
+        let rows = node.estimated_rows;
+        node = PlanNode::new(
+            "Hash Aggregate",
+            select.group_by.join(", "),
+            rows,
+            vec![node],
+        );
+    }
+
+    if select.group_by.is_empty() && select.columns.iter().any(SelectItem::has_window) {
+        let rows = node.estimated_rows;
+        node = PlanNode::new("Window", window_detail(&select.columns), rows, vec![node]);
+    }
+
+    if !select.order_by.is_empty() {
+        let rows = node.estimated_rows;
+        node = PlanNode::new("Sort", sort_detail(&select.order_by), rows, vec![node]);
+    }
+
+    let rows = node.estimated_rows;
+    node = PlanNode::new("Project", project_detail(&select.columns), rows, vec![node]);
+
+    if select.distinct {
+        let rows = node.estimated_rows;
+        node = PlanNode::new("Distinct", "hash".into(), rows, vec![node]);
+    }
+
+    if select.limit.is_some() || select.offset.is_some() {
+        let offset = select.offset.unwrap_or(0);
+        let mut rows = node.estimated_rows.saturating_sub(offset);
+        if let Some(limit) = select.limit {
+            rows = rows.min(limit);
+        }
+        let detail = limit_detail(select.limit, select.offset);
+        node = PlanNode::new("Limit", detail, rows, vec![node]);
+    }
+    node
+}
+
+/// The operator name of `join`, shared with the runtime statistics of `EXPLAIN ANALYZE`.
+pub(super) fn join_operator(join: &JoinDefinition, hash_hint: bool) -> &'static str {
+    match (join.kind, hash_hint && join.on.is_some()) {
+        (JoinKind::Inner, false) => "Nested Loop Join",
+        (JoinKind::Inner, true) => "Hash Join",
+        (JoinKind::Left, false) => "Nested Loop Left Join",
+        (JoinKind::Left, true) => "Hash Left Join",
+        (JoinKind::Cross, _) => "Nested Loop Cross Join",
+    }
+}
+
+pub(super) fn join_detail(join: &JoinDefinition) -> String {
+    match &join.on {
+        Some(on) => format!("{} = {}", on.left_column, on.right_column),
+        None => "cartesian product".into(),
+    }
+}
+
+pub(super) fn sort_detail(order_by: &[OrderByDefinition]) -> String {
+    let keys: Vec<String> = order_by
+        .iter()
+        .map(|o| format!("{} {}", o.column, if o.descending { "DESC" } else { "ASC" }))
+        .collect();
+    keys.join(", ")
+}
+
+/// The select list items computing window functions.
+pub(super) fn window_detail(columns: &[SelectItem]) -> String {
+    let items: Vec<String> = columns
+        .iter()
+        .filter(|c| c.has_window())
+        .map(|c| c.to_string())
+        .collect();
+    items.join(", ")
+}
+
+pub(super) fn project_detail(columns: &[SelectItem]) -> String {
+    let items: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+    items.join(", ")
+}
+
+pub(super) fn limit_detail(limit: Option<usize>, offset: Option<usize>) -> String {
+    let offset = offset.unwrap_or(0);
+    match limit {
+        Some(limit) => format!("LIMIT {} OFFSET {}", limit, offset),
+        None => format!("OFFSET {}", offset),
+    }
+}
+
+/// The order to run the joins of a query in, as positions in `joins`. `inputs` holds the
+/// qualified headers of the source and then of each joined table.
+///
+/// The joins run as written unless `sizes`, the rows each joined table is estimated to
+/// contribute, are known and every join is an `INNER JOIN ... ON`. Then each step joins the
+/// smallest table whose `ON` condition only needs the tables joined so far, which keeps the
+/// intermediate results small.
+pub(super) fn join_order(
+    joins: &[JoinDefinition],
+    inputs: &[Vec<String>],
+    sizes: Option<&[usize]>,
+) -> Vec<usize> {
+    let as_written: Vec<usize> = (0..joins.len()).collect();
+    let Some(sizes) = sizes else {
+        return as_written;
+    };
+    // The input each join needs joined before it, besides its own table.
+    let mut needs = Vec::with_capacity(joins.len());
+    for (i, join) in joins.iter().enumerate() {
+        let Some(on) = join.on.as_ref().filter(|_| join.kind == JoinKind::Inner) else {
+            return as_written;
+        };
+        let sides = (
+            input_of(inputs, &on.left_column),
+            input_of(inputs, &on.right_column),
+        );
+        match sides {
+            (Some(l), Some(r)) if r == i + 1 && l != r => needs.push(l),
+            (Some(l), Some(r)) if l == i + 1 && l != r => needs.push(r),
+            _ => return as_written,
+        }
+    }
+
+    let mut joined = vec![0];
+    let mut order = Vec::with_capacity(joins.len());
+    while order.len() < joins.len() {
+        let next = (0..joins.len())
+            .filter(|i| !order.contains(i) && joined.contains(&needs[*i]))
+            .min_by_key(|&i| sizes[i]);
+        let Some(next) = next else {
+            return as_written;
+        };
+        order.push(next);
+        joined.push(next + 1);
+    }
+    order
+}
+
+/// The input whose headers hold `column`, qualified or not, if exactly one does.
+fn input_of(inputs: &[Vec<String>], column: &str) -> Option<usize> {
+    let holds = |header: &String| {
+        header == column || (!column.contains('.') && header.rsplit('.').next() == Some(column))
+    };
+    let mut found = (0..inputs.len()).filter(|&i| inputs[i].iter().any(holds));
+    match (found.next(), found.next()) {
+        (Some(i), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// Wraps `node` in a filter on `predicate`, if there is one. The filter is estimated to keep
+/// its share of the `input_rows` it applies to, and no more rows than `node` yields.
+fn filter<'a>(
+    node: PlanNode,
+    predicate: Option<Expr>,
+    input_rows: usize,
+    stats: &dyn Fn(&str) -> Option<&'a ColumnStats>,
+) -> PlanNode {
+    match predicate {
+        Some(predicate) => {
+            let kept = (input_rows as f64 * selectivity(&predicate, stats)).ceil() as usize;
+            let rows = node.estimated_rows.min(kept);
+            PlanNode::new("Filter", predicate.to_string(), rows, vec![node])
+        }
+        None => node,
+    }
+}
+
+/// The statistics of `column`, qualified or not, among the `scanned` tables and the names
+/// they go by in the query.
+fn column_stats<'a>(scanned: &[(&str, &'a Table)], column: &str) -> Option<&'a ColumnStats> {
+    let (qualifier, name) = match column.split_once('.') {
+        Some((qualifier, name)) => (Some(qualifier), name),
+        None => (None, column),
+    };
+    scanned
+        .iter()
+        .filter(|(scanned_as, _)| qualifier.is_none_or(|q| q == *scanned_as))
+        .find_map(|(_, table)| table.column_statistics(name))
+}
+
+/// Names a scanned table, with its alias when the query gives one.
+fn scan_detail(table_name: &str, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => format!("{} AS {}", table_name, alias),
+        None => table_name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_estimate_rows() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id INT PRIMARY, n INT)").unwrap())
+            .unwrap();
+        for i in 0..20 {
+            db.execute(parse(&format!("INSERT INTO t VALUES ({}, {})", i, i % 3)).unwrap())
+                .unwrap();
+        }
+        let estimate = |sql: &str| db.estimate_rows(&parse(sql).unwrap());
+        assert_eq!(estimate("SELECT * FROM t").unwrap(), 20);
+        assert_eq!(estimate("SELECT * FROM t WHERE id IN (1, 2)").unwrap(), 2);
+        assert_eq!(
+            estimate("SELECT * FROM t a JOIN t b ON a.n = b.n").unwrap(),
+            400
+        );
+        assert_eq!(estimate("SELECT * FROM t LIMIT 5").unwrap(), 5);
+        assert_eq!(
+            estimate("INSERT INTO t VALUES (20, 0), (21, 0)").unwrap(),
+            2
+        );
+        assert!(estimate("SELECT * FROM missing").is_err());
+
+        // Table functions are sized from their arguments, never run.
+        assert_eq!(
+            estimate("SELECT * FROM generate_series(1, 2000000000)").unwrap(),
+            2_000_000_000
+        );
+        assert_eq!(
+            estimate("SELECT * FROM generate_series(10, 1, -3)").unwrap(),
+            4
+        );
+        assert_eq!(estimate("SELECT * FROM generate_series(5, 1)").unwrap(), 0);
+        assert_eq!(estimate("SELECT * FROM pragma_table_info('t')").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_plan_join() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT)",
+            "INSERT INTO teams VALUES (1, 'core')",
+            "INSERT INTO devs VALUES (1, 1)",
+            "INSERT INTO devs VALUES (2, 1)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+
+        let stmt = parse("SELECT * FROM devs JOIN teams ON team_id = id ORDER BY id").unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        assert_eq!(plan.operator, "Project");
+        let sort = &plan.children[0];
+        assert_eq!(sort.operator, "Sort");
+        let join = &sort.children[0];
+        assert_eq!(join.operator, "Nested Loop Join");
+        assert_eq!(join.estimated_rows, 2);
+        assert_eq!(join.children.len(), 2);
+
+        let stmt = parse("SELECT * FROM devs WHERE id IN (2, 9) AND team_id = 1").unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        let filter = &plan.children[0];
+        assert_eq!(filter.operator, "Filter");
+        assert_eq!(filter.detail, "id IN (2, 9) AND team_id = 1");
+        assert_eq!(filter.children[0].operator, "Index Lookup");
+        assert_eq!(filter.children[0].estimated_rows, 1);
+
+        // The team filter runs on the teams scan, below the join.
+        let stmt = parse(
+            "SELECT * FROM devs d JOIN teams t ON d.team_id = t.id WHERE t.name = 'core' AND 1 = 1",
+        )
+        .unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        let join = &plan.children[0];
+        assert_eq!(join.operator, "Nested Loop Join");
+        assert_eq!(join.children[0].operator, "Full Scan");
+        assert_eq!(join.children[1].operator, "Filter");
+        assert_eq!(join.children[1].detail, "t.name = 'core'");
+
+        let stmt = parse("SELECT COUNT(*) FROM devs WHERE id = 1 AND 2 < 1").unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        assert_eq!(plan.children[0].operator, "Empty Result");
+
+        let stmt =
+            parse("SELECT * FROM (SELECT id FROM devs WHERE id = 1) d JOIN teams t ON d.id = t.id")
+                .unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        let derived = &plan.children[0].children[0];
+        assert_eq!(derived.operator, "Derived Table");
+        assert_eq!(derived.detail, "d");
+        assert_eq!(derived.estimated_rows, 1);
+        assert_eq!(derived.children[0].operator, "Project");
+
+        // Only the columns used after the join survive the scans.
+        let stmt =
+            parse("SELECT t.name FROM devs d JOIN teams t ON d.team_id = t.id WHERE d.id > 1")
+                .unwrap();
+        let plan = db.plan(&stmt).unwrap();
+        let join = &plan.children[0];
+        assert_eq!(join.children[0].operator, "Prune Columns");
+        assert_eq!(join.children[0].detail, "d.team_id");
+        assert_eq!(join.children[0].children[0].operator, "Filter");
+        assert_eq!(join.children[1].operator, "Prune Columns");
+        assert_eq!(join.children[1].detail, "t.id, t.name");
+
+        let stmt = parse("EXPLAIN SELECT * FROM devs WHERE id = 2").unwrap();
+        let ExecutionResult::Data { rows, .. } = db.execute(stmt).unwrap() else {
+            panic!("expected data");
+        };
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(rows[0][0], text("Project"));
+        assert_eq!(rows[1][0], text("  Filter"));
+        assert_eq!(
+            rows[2],
+            [text("    Index Lookup"), text("devs.id"), Value::Integer(1)]
+        );
+        // The explained statement doesn't run.
+        db.execute(parse("EXPLAIN INSERT INTO devs VALUES (3, 1)").unwrap())
+            .unwrap();
+        assert_eq!(db.tables["devs"].rows.len(), 2);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{engine::schedules::unix_now, parser::Statement};
+
+/// How many finished queries the log keeps.
+const RECENT_QUERIES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryState {
+    Running,
+    Finished,
+    Failed,
+}
+
+impl QueryState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryState::Running => "running",
+            QueryState::Finished => "finished",
+            QueryState::Failed => "failed",
+        }
+    }
+}
+
+/// A statement that is running or ran recently.
+#[derive(Debug, Clone)]
+pub struct QueryInfo {
+    pub id: u64,
+    pub user: String,
+    pub sql: String,
+    pub state: QueryState,
+    /// Unix time at which the statement started.
+    pub started_at: u64,
+    /// How long the statement ran, or has been running so far.
+    pub duration: Duration,
+    pub error: Option<String>,
+    started: Instant,
+}
+
+/// The statements a database is running and the last ones it finished, queryable as
+/// `system.queries`.
+///
+/// Clones share the same log, so a front end can keep one and list the running statements
+/// while the database itself is busy running them.
+#[derive(Debug, Clone, Default)]
+pub struct QueryLog {
+    inner: Arc<Mutex<LogEntries>>,
+}
+
+#[derive(Debug, Default)]
+struct LogEntries {
+    next_id: u64,
+    running: BTreeMap<u64, QueryInfo>,
+    recent: VecDeque<QueryInfo>,
+}
+
+impl QueryLog {
+    /// Records that `user` started running `sql` and returns the id of the entry.
+    pub(crate) fn start(&self, user: &str, sql: String) -> u64 {
+        let mut entries = self.inner.lock().unwrap();
+        entries.next_id += 1;
+        let id = entries.next_id;
+        let info = QueryInfo {
+            id,
+            user: user.to_string(),
+            sql,
+            state: QueryState::Running,
+            started_at: unix_now(),
+            duration: Duration::ZERO,
+            error: None,
+            started: Instant::now(),
+        };
+        entries.running.insert(id, info);
+        id
+    }
+
+    /// Moves the entry `id` to the finished statements, failed if there is an `error`.
+    pub(crate) fn finish(&self, id: u64, error: Option<String>) {
+        let mut entries = self.inner.lock().unwrap();
+        let Some(mut info) = entries.running.remove(&id) else {
+            return;
+        };
+        info.duration = info.started.elapsed();
+        info.state = match error {
+            Some(_) => QueryState::Failed,
+            None => QueryState::Finished,
+        };
+        info.error = error;
+        if entries.recent.len() == RECENT_QUERIES {
+            entries.recent.pop_back();
+        }
+        entries.recent.push_front(info);
+    }
+
+    /// The running statements, oldest first, then the finished ones, most recent first.
+    pub fn queries(&self) -> Vec<QueryInfo> {
+        let entries = self.inner.lock().unwrap();
+        let running = entries.running.values().map(|info| QueryInfo {
+            duration: info.started.elapsed(),
+            ..info.clone()
+        });
+        running.chain(entries.recent.iter().cloned()).collect()
+    }
+}
+
+/// The text logged for a statement run without its SQL at hand: queries are written back in
+/// full, other statements as their leading keywords and target.
+pub(crate) fn describe(statement: &Statement) -> String {
+    match statement {
+        Statement::Select(select) => select.to_string(),
+        Statement::ExplainAnalyze(select) => format!("EXPLAIN ANALYZE {}", select),
+        Statement::Explain(inner) => format!("EXPLAIN {}", describe(inner)),
+        Statement::CreateTable { name, .. } => format!("CREATE TABLE {} ...", name),
+        Statement::DropTable { name, .. } => format!("DROP TABLE {}", name),
+        Statement::CreateIndex {
+            name, table_name, ..
+        } => format!("CREATE INDEX {} ON {} ...", name, table_name),
+        Statement::DropIndex(name) => format!("DROP INDEX {}", name),
+        Statement::Insert { table_name, .. } => format!("INSERT INTO {} ...", table_name),
+        Statement::AlterTable { table_name, .. } => format!("ALTER TABLE {} ...", table_name),
+        Statement::CreateQuery { name, .. } => format!("CREATE QUERY {} ...", name),
+        Statement::ExecuteQuery(name) => format!("EXECUTE {}", name),
+        Statement::DropQuery(name) => format!("DROP QUERY {}", name),
+        Statement::ShowQueries => "SHOW QUERIES".into(),
+        Statement::CreateSchedule { name, .. } => format!("CREATE SCHEDULE {} ...", name),
+        Statement::DropSchedule(name) => format!("DROP SCHEDULE {}", name),
+        Statement::ShowSchedules => "SHOW SCHEDULES".into(),
+        Statement::CreateTrigger { name, .. } => format!("CREATE TRIGGER {} ...", name),
+        Statement::DropTrigger(name) => format!("DROP TRIGGER {}", name),
+        Statement::ShowTriggers => "SHOW TRIGGERS".into(),
+        Statement::ShowStatus => "SHOW STATUS".into(),
+        Statement::ShowIndexStats => "SHOW INDEX STATS".into(),
+        Statement::SetVariable { name, .. } => format!("SET @{} ...", name),
+        Statement::Comment { .. } => "COMMENT ON ...".into(),
+        Statement::Analyze(Some(table)) => format!("ANALYZE {}", table),
+        Statement::Analyze(None) => "ANALYZE".into(),
+        Statement::Begin => "BEGIN".into(),
+        Statement::Commit => "COMMIT".into(),
+        Statement::Rollback => "ROLLBACK".into(),
+        Statement::Savepoint(name) => format!("SAVEPOINT {}", name),
+        Statement::RollbackToSavepoint(name) => format!("ROLLBACK TO SAVEPOINT {}", name),
+        Statement::ReleaseSavepoint(name) => format!("RELEASE SAVEPOINT {}", name),
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A finite `f64` that can be compared, hashed and used as an index key like the other
+/// values. NaN and the infinities are never stored, and `-0.0` is kept as `0.0` so that equal
+/// numbers hash alike.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Real(f64);
+
+impl Real {
+    /// `None` for NaN and the infinities.
+    pub fn new(value: f64) -> Option<Real> {
+        value.is_finite().then_some(Real(value + 0.0))
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+
+    /// Compares with an integer exactly, even where `n as f64` would round.
+    pub fn cmp_integer(self, n: i64) -> Ordering {
+        // 2^63: every i64 is in [-2^63, 2^63), and both bounds are exact as f64.
+        const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+        if self.0 >= LIMIT {
+            return Ordering::Greater;
+        }
+        if self.0 < -LIMIT {
+            return Ordering::Less;
+        }
+        (self.0.trunc() as i64)
+            .cmp(&n)
+            .then(self.0.fract().total_cmp(&0.0))
+    }
+}
+
+impl Ord for Real {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for Real {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Real {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Real {}
+
+impl Hash for Real {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
+/// Written like the `f64`, so `Value::Real` reads `Real(1.5)`.
+impl fmt::Debug for Real {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Real {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// Parses a decimal literal such as `1.5`, `-0.25` or `3.`: digits with a single point and an
+/// optional sign. Integers, exponents and words like `inf` are not decimal literals.
+pub(crate) fn parse_decimal(token: &str) -> Option<Real> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    let (whole, fraction) = digits.split_once('.')?;
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.len() + fraction.len() == 0 || !is_digits(whole) || !is_digits(fraction) {
+        return None;
+    }
+    Real::new(token.parse().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Database,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    fn query(db: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+        match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { rows, .. } => rows,
+            ExecutionResult::Message(m) => panic!("expected rows, got {}", m),
+        }
+    }
+
+    #[test]
+    fn test_real() {
+        assert_eq!(parse_decimal("1.5").map(Real::get), Some(1.5));
+        assert_eq!(parse_decimal("-.25").map(Real::get), Some(-0.25));
+        assert_eq!(parse_decimal("3.").map(Real::get), Some(3.0));
+        for token in ["1", "1e5", "inf", "NaN", ".", "-", "1.2.3", "a.b"] {
+            assert_eq!(parse_decimal(token), None, "{}", token);
+        }
+
+        assert!(Real::new(f64::NAN).is_none());
+        assert!(Real::new(f64::INFINITY).is_none());
+        assert_eq!(Real::new(-0.0), Real::new(0.0));
+        assert_eq!(format!("{:?}", Real::new(2.0).unwrap()), "2.0");
+        let big = Real::new(9_007_199_254_740_992.0).unwrap();
+        assert_eq!(big.cmp_integer(9_007_199_254_740_993), Ordering::Less);
+        assert_eq!(Real::new(-1.5).unwrap().cmp_integer(-1), Ordering::Less);
+        assert_eq!(
+            Real::new(1e19).unwrap().cmp_integer(i64::MAX),
+            Ordering::Greater
+        );
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE items (id INT PRIMARY, price FLOAT)",
+            "INSERT INTO items VALUES (1, 2.5), (2, 10), (3, -0.25), (4, 1.)",
+            "CREATE INDEX items_price ON items (price)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        assert_eq!(db.tables["items"].columns[1].data_type, "REAL");
+        let real = |r: f64| Value::Real(Real::new(r).unwrap());
+
+        // Integers and reals compare as numbers, and 1 finds 1.0 in an index.
+        assert_eq!(
+            query(
+                &mut db,
+                "SELECT id FROM items WHERE price > 1 ORDER BY price"
+            ),
+            [[Value::Integer(1)], [Value::Integer(2)]]
+        );
+        assert_eq!(
+            query(
+                &mut db,
+                "SELECT price * 2, price + 1 FROM items WHERE price = 1"
+            ),
+            [[real(2.0), real(2.0)]]
+        );
+        assert_eq!(
+            query(
+                &mut db,
+                "SELECT SUM(price), AVG(price), MIN(price), MAX(price) FROM items"
+            ),
+            [[
+                real(13.25),
+                real(13.25 / 4.0),
+                real(-0.25),
+                Value::Integer(10)
+            ]]
+        );
+        assert_eq!(
+            query(
+                &mut db,
+                "SELECT CAST(price AS INT), CAST(price AS TEXT) FROM items WHERE id = 3"
+            ),
+            [[Value::Integer(0), Value::Text("-0.25".into())]]
+        );
+        assert!(
+            db.execute(parse("SELECT price / 0 FROM items").unwrap())
+                .is_err()
+        );
+    }
+}
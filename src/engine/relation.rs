@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::{
+    DbError,
+    engine::{
+        Table, Value,
+        eval::{eval, is_true},
+        session::Session,
+    },
+    parser::{Expr, OrderByDefinition, SelectItem},
+};
+
+/// An intermediate result flowing between the stages of a SELECT: headers plus rows.
+#[derive(Debug, Clone)]
+pub(crate) struct Relation {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// The table whose bare column names are the headers, so `table.column` resolves too.
+    /// `None` once the headers are qualified.
+    pub table: Option<String>,
+}
+
+impl Relation {
+    /// Copies the rows of a table, using the bare column names as headers.
+    pub fn from_table(table: &Table) -> Self {
+        Self {
+            headers: table.columns.iter().map(|c| c.name.clone()).collect(),
+            rows: table.rows.clone(),
+            table: Some(table.name.clone()),
+        }
+    }
+
+    /// A relation with the table's headers but no rows.
+    pub fn empty(table: &Table) -> Self {
+        Self {
+            headers: table.columns.iter().map(|c| c.name.clone()).collect(),
+            rows: Vec::new(),
+            table: Some(table.name.clone()),
+        }
+    }
+
+    /// Prefixes every header with `qualifier.` (the table name or its alias) so columns stay
+    /// unambiguous once other tables are joined in.
+    pub fn qualify(&mut self, qualifier: &str) {
+        for header in &mut self.headers {
+            *header = format!("{}.{}", qualifier, header);
+        }
+        self.table = None;
+    }
+
+    /// Resolves a column name against the headers.
+    ///
+    /// An exact match always wins, and `table.column` matches the bare header `column` of a
+    /// single-table relation. Otherwise an unqualified name like `id` matches a joined header
+    /// `table.id`, as long as only one table provides that column.
+    pub fn column_index(&self, name: &str) -> Result<usize, DbError> {
+        if let Some(i) = self.headers.iter().position(|h| h == name) {
+            return Ok(i);
+        }
+        if let Some(column) = self
+            .table
+            .as_deref()
+            .and_then(|table| name.strip_prefix(table)?.strip_prefix('.'))
+            && let Some(i) = self.headers.iter().position(|h| h == column)
+        {
+            return Ok(i);
+        }
+
+        let suffix = format!(".{}", name);
+        let mut matches = self
+            .headers
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.ends_with(&suffix));
+
+        match (matches.next(), matches.next()) {
+            (Some((i, _)), None) => Ok(i),
+            (Some(_), Some(_)) => Err(DbError::AmbiguousColumn(name.to_string())),
+            _ => Err(DbError::ColumnNotFound(name.to_string())),
+        }
+    }
+
+    /// Rough footprint of the rows, see `Value::approximate_size`.
+    pub fn approximate_size(&self) -> usize {
+        self.rows
+            .iter()
+            .flatten()
+            .map(Value::approximate_size)
+            .sum()
+    }
+
+    /// Stably sorts the rows by the given keys, the first key being the most significant.
+    pub fn sort(&mut self, order_by: &[OrderByDefinition]) -> Result<(), DbError> {
+        if order_by.is_empty() {
+            return Ok(());
+        }
+
+        let keys = order_by
+            .iter()
+            .map(|o| Ok((self.column_index(&o.column)?, o.descending)))
+            .collect::<Result<Vec<_>, DbError>>()?;
+
+        self.rows.sort_by(|a, b| {
+            keys.iter()
+                .map(|&(i, descending)| {
+                    let ordering = a[i].cmp(&b[i]);
+                    if descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                })
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(())
+    }
+
+    /// Keeps only the rows for which `predicate` evaluates to true.
+    pub fn filter(&mut self, predicate: &Expr, session: &Session) -> Result<(), DbError> {
+        let mut keep = Vec::with_capacity(self.rows.len());
+        for row in &self.rows {
+            keep.push(is_true(&eval(predicate, self, row, session)?));
+        }
+
+        let mut keep = keep.into_iter();
+        self.rows.retain(|_| keep.next().unwrap_or(false));
+        Ok(())
+    }
+
+    /// Removes duplicate rows, keeping the first occurrence of each.
+    pub fn dedup(&mut self) {
+        let mut seen = HashSet::new();
+        self.rows.retain(|row| seen.insert(row.clone()));
+    }
+
+    /// Keeps only the columns at `indexes`, in that order.
+    pub fn retain_columns(&mut self, indexes: &[usize]) {
+        self.headers = indexes.iter().map(|&i| self.headers[i].clone()).collect();
+        for row in &mut self.rows {
+            *row = indexes.iter().map(|&i| row[i].clone()).collect();
+        }
+    }
+
+    /// Skips the first `offset` rows and keeps at most `limit` of the remaining ones.
+    pub fn paginate(&mut self, limit: Option<usize>, offset: Option<usize>) {
+        let offset = offset.unwrap_or(0).min(self.rows.len());
+        self.rows.drain(..offset);
+        if let Some(limit) = limit {
+            self.rows.truncate(limit);
+        }
+    }
+
+    /// Keeps only the requested columns, in the requested order. `*` expands to every column
+    /// and any other expression is evaluated per row. Aliased items take the alias as header.
+    pub fn project(self, items: &[SelectItem], session: &Session) -> Result<Relation, DbError> {
+        enum Output<'a> {
+            Column(usize),
+            Computed(&'a Expr),
+        }
+
+        let mut headers = Vec::new();
+        let mut outputs = Vec::new();
+        for item in items {
+            match item {
+                SelectItem::Wildcard => {
+                    headers.extend(self.headers.iter().cloned());
+                    outputs.extend((0..self.headers.len()).map(Output::Column));
+                }
+                SelectItem::Expr {
+                    expr: Expr::Column(name),
+                    alias,
+                } => {
+                    let i = self.column_index(name)?;
+                    headers.push(alias.clone().unwrap_or_else(|| self.headers[i].clone()));
+                    outputs.push(Output::Column(i));
+                }
+                SelectItem::Expr { expr, .. } => {
+                    headers.push(item.header());
+                    outputs.push(Output::Computed(expr));
+                }
+            }
+        }
+
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                outputs
+                    .iter()
+                    .map(|output| match output {
+                        Output::Column(i) => Ok(row[*i].clone()),
+                        Output::Computed(expr) => eval(expr, &self, row, session),
+                    })
+                    .collect::<Result<Vec<_>, DbError>>()
+            })
+            .collect::<Result<Vec<_>, DbError>>()?;
+
+        Ok(Relation {
+            headers,
+            rows,
+            table: self.table.clone(),
+        })
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    engine::{
+        Value,
+        eval::{eval, is_true},
+        relation::Relation,
+        session::Session,
+    },
+    parser::{BinaryOperator, Expr, JoinKind, SelectItem, SelectStatement},
+};
+
+/// Folds every subexpression built only from literals into its value, e.g. `2 > 1` into `1`.
+/// Columns, variables and function calls are left alone, so the result doesn't depend on the
+/// rows or the session.
+pub(crate) fn fold_constants(expr: Expr) -> Expr {
+    let folded = match expr {
+        Expr::Binary { left, op, right } => Expr::Binary {
+            left: Box::new(fold_constants(*left)),
+            op,
+            right: Box::new(fold_constants(*right)),
+        },
+        Expr::Not(inner) => Expr::Not(Box::new(fold_constants(*inner))),
+        Expr::Negate(inner) => Expr::Negate(Box::new(fold_constants(*inner))),
+        Expr::Cast { expr, data_type } => Expr::Cast {
+            expr: Box::new(fold_constants(*expr)),
+            data_type,
+        },
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: Box::new(fold_constants(*expr)),
+            negated,
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => Expr::InList {
+            expr: Box::new(fold_constants(*expr)),
+            list: list.into_iter().map(fold_constants).collect(),
+            negated,
+        },
+        other => return other,
+    };
+
+    if !is_constant(&folded) {
+        return folded;
+    }
+    let empty = Relation {
+        headers: Vec::new(),
+        rows: Vec::new(),
+        table: None,
+    };
+    match eval(&folded, &empty, &[], &Session::default()) {
+        Ok(value) => Expr::Literal(value),
+        Err(_) => folded,
+    }
+}
+
+/// Folds the constants of a `WHERE` predicate and drops the `AND`/`OR` operands whose outcome
+/// is already known, e.g. `id = 1 AND 1 = 1` becomes `id = 1` and `id = 1 OR 2 > 1` becomes
+/// `1`.
+///
+/// A `WHERE` clause only keeps rows for which it is true, so here `NULL` behaves like false.
+/// That only holds at the top of the predicate: below a `NOT` the operands are merely folded.
+pub(crate) fn simplify_predicate(expr: Expr) -> Expr {
+    let (left, op, right) = match expr {
+        Expr::Binary {
+            left,
+            op: op @ (BinaryOperator::And | BinaryOperator::Or),
+            right,
+        } => (simplify_predicate(*left), op, simplify_predicate(*right)),
+        other => return fold_constants(other),
+    };
+
+    match (op, known(&left), known(&right)) {
+        (BinaryOperator::And, Some(false), _) | (BinaryOperator::And, _, Some(false)) => {
+            Expr::Literal(Value::Integer(0))
+        }
+        (BinaryOperator::Or, Some(true), _) | (BinaryOperator::Or, _, Some(true)) => {
+            Expr::Literal(Value::Integer(1))
+        }
+        (BinaryOperator::And, Some(true), _) | (BinaryOperator::Or, Some(false), _) => right,
+        (BinaryOperator::And, _, Some(true)) | (BinaryOperator::Or, _, Some(false)) => left,
+        _ => Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+    }
+}
+
+/// Whether a simplified predicate keeps every row (`Some(true)`), no row (`Some(false)`), or
+/// depends on the row.
+pub(crate) fn known(predicate: &Expr) -> Option<bool> {
+    match predicate {
+        Expr::Literal(value) => Some(is_true(value)),
+        _ => None,
+    }
+}
+
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Column(_)
+        | Expr::Variable(_)
+        | Expr::Function { .. }
+        | Expr::Aggregate { .. }
+        | Expr::Exists(_)
+        | Expr::Subquery(_)
+        | Expr::Window { .. } => false,
+        Expr::Binary { left, right, .. } => is_constant(left) && is_constant(right),
+        Expr::Not(inner)
+        | Expr::Negate(inner)
+        | Expr::IsNull { expr: inner, .. }
+        | Expr::Cast { expr: inner, .. } => is_constant(inner),
+        Expr::InList { expr, list, .. } => is_constant(expr) && list.iter().all(is_constant),
+    }
+}
+
+/// A `WHERE` clause split by the earliest point of a join pipeline at which each part can be
+/// evaluated.
+#[derive(Debug, Default)]
+pub(crate) struct PushedPredicates {
+    /// Filters the source table before any join.
+    pub source: Option<Expr>,
+    /// Filters the right side of the join at the same position before joining it.
+    pub joins: Vec<Option<Expr>>,
+    /// Needs the joined rows.
+    pub residual: Option<Expr>,
+}
+
+/// Pushes the `AND`ed parts of `predicate` below the joins.
+///
+/// `inputs` holds the headers of the source followed by those of each joined table, in the
+/// order of `kinds`. A part moves to the single input providing all its columns, as resolved
+/// against the joined headers, unless that input is the right side of a `LEFT JOIN`: filtering
+/// there would turn dropped matches into `NULL`-padded rows. Parts without columns filter the
+/// source, except `EXISTS` subqueries, which may refer to any joined column. Anything else
+/// stays in the residual predicate, as does the whole predicate of a query without joins.
+pub(crate) fn push_down(
+    predicate: Option<Expr>,
+    inputs: &[Vec<String>],
+    kinds: &[JoinKind],
+) -> PushedPredicates {
+    let mut pushed = PushedPredicates {
+        joins: vec![None; kinds.len()],
+        ..Default::default()
+    };
+    let predicate = match predicate {
+        Some(predicate) if !kinds.is_empty() => predicate,
+        residual => {
+            pushed.residual = residual;
+            return pushed;
+        }
+    };
+    let joined = Relation {
+        headers: inputs.concat(),
+        rows: Vec::new(),
+        table: None,
+    };
+    let input_of = |column: usize| {
+        let mut end = 0;
+        inputs.iter().position(|headers| {
+            end += headers.len();
+            column < end
+        })
+    };
+
+    let mut conjuncts = Vec::new();
+    split_conjuncts(predicate, &mut conjuncts);
+    for conjunct in conjuncts {
+        let mut columns = Vec::new();
+        collect_columns(&conjunct, &mut columns);
+
+        let mut target = Some(0);
+        if conjunct.is_aggregate() || conjunct.has_subquery() {
+            target = None;
+        } else if !columns.is_empty() {
+            let resolved: Option<Vec<usize>> = columns
+                .iter()
+                .map(|name| joined.column_index(name).ok().and_then(input_of))
+                .collect();
+            target = match resolved.as_deref() {
+                Some([first, rest @ ..]) if rest.iter().all(|i| i == first) => Some(*first),
+                _ => None,
+            };
+        }
+
+        let slot = match target {
+            Some(0) => &mut pushed.source,
+            Some(i) if kinds[i - 1] != JoinKind::Left => &mut pushed.joins[i - 1],
+            _ => &mut pushed.residual,
+        };
+        *slot = Some(match slot.take() {
+            Some(existing) => Expr::Binary {
+                left: Box::new(existing),
+                op: BinaryOperator::And,
+                right: Box::new(conjunct),
+            },
+            None => conjunct,
+        });
+    }
+    pushed
+}
+
+/// Picks the columns of each join input that the query still needs once the pushed-down
+/// filters ran: those named by the select list, the `ON` conditions, `GROUP BY`, `ORDER BY`
+/// and the `residual` filter. `inputs` is laid out as for `push_down`.
+///
+/// Returns `None` when nothing can be pruned: for `SELECT *`, `EXISTS` subqueries (which may
+/// refer to any column), or a name that doesn't resolve and must fail later as usual.
+pub(crate) fn needed_columns(
+    select: &SelectStatement,
+    residual: Option<&Expr>,
+    inputs: &[Vec<String>],
+) -> Option<Vec<Vec<usize>>> {
+    let mut names = Vec::new();
+    for item in &select.columns {
+        match item {
+            SelectItem::Wildcard => return None,
+            SelectItem::Expr { expr, .. } if expr.has_subquery() => return None,
+            SelectItem::Expr { expr, .. } => collect_columns(expr, &mut names),
+        }
+    }
+    if let Some(residual) = residual {
+        if residual.has_subquery() {
+            return None;
+        }
+        collect_columns(residual, &mut names);
+    }
+    for on in select.joins.iter().filter_map(|j| j.on.as_ref()) {
+        names.extend([on.left_column.as_str(), on.right_column.as_str()]);
+    }
+    names.extend(select.group_by.iter().map(String::as_str));
+    names.extend(select.order_by.iter().map(|o| o.column.as_str()));
+
+    let joined = Relation {
+        headers: inputs.concat(),
+        rows: Vec::new(),
+        table: None,
+    };
+    let mut needed = vec![false; joined.headers.len()];
+    for name in names {
+        needed[joined.column_index(name).ok()?] = true;
+    }
+    if needed.iter().all(|n| *n) {
+        return None;
+    }
+
+    let mut start = 0;
+    let kept = inputs
+        .iter()
+        .map(|headers| {
+            let kept = (0..headers.len()).filter(|i| needed[start + i]).collect();
+            start += headers.len();
+            kept
+        })
+        .collect();
+    Some(kept)
+}
+
+fn split_conjuncts(expr: Expr, out: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            split_conjuncts(*left, out);
+            split_conjuncts(*right, out);
+        }
+        other => out.push(other),
+    }
+}
+
+fn collect_columns<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Column(name) => out.push(name),
+        // Columns of a subquery aren't ours; its conjunct stays in the residual anyway.
+        Expr::Literal(_) | Expr::Variable(_) | Expr::Exists(_) | Expr::Subquery(_) => {}
+        Expr::Function { args, .. } => args.iter().for_each(|a| collect_columns(a, out)),
+        Expr::Aggregate { arg, .. } => arg.iter().for_each(|a| collect_columns(a, out)),
+        Expr::Binary { left, right, .. } => {
+            collect_columns(left, out);
+            collect_columns(right, out);
+        }
+        Expr::Not(inner)
+        | Expr::Negate(inner)
+        | Expr::IsNull { expr: inner, .. }
+        | Expr::Cast { expr: inner, .. } => collect_columns(inner, out),
+        Expr::InList { expr, list, .. } => {
+            collect_columns(expr, out);
+            list.iter().for_each(|e| collect_columns(e, out));
+        }
+        Expr::Window {
+            partition_by,
+            order_by,
+            ..
+        } => {
+            out.extend(partition_by.iter().map(String::as_str));
+            out.extend(order_by.iter().map(|o| o.column.as_str()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{SelectStatement, Statement, parse};
+
+    fn predicate(sql_where: &str) -> Expr {
+        let Statement::Select(select) =
+            parse(&format!("SELECT * FROM t WHERE {}", sql_where)).unwrap()
+        else {
+            panic!("expected select");
+        };
+        let SelectStatement { where_clause, .. } = *select;
+        where_clause.unwrap()
+    }
+
+    #[test]
+    fn test_simplify_predicate() {
+        let simplified = |sql: &str| simplify_predicate(predicate(sql)).to_string();
+        assert_eq!(simplified("id = 1 AND 1 = 1"), "id = 1");
+        assert_eq!(simplified("id = 1 OR 2 > 1"), "1");
+        assert_eq!(simplified("id = 1 AND 1 = 2"), "0");
+        assert_eq!(simplified("id = 1 OR 1 IN (2, 3)"), "id = 1");
+        assert_eq!(simplified("id = 1 AND @unset = 1"), "id = 1 AND @unset = 1");
+        // `NOT (x AND NULL)` may be true, so the NULL is kept below the NOT.
+        assert_eq!(
+            simplified("NOT (id = 1 AND @unset IS NULL)"),
+            "NOT (id = 1 AND @unset IS NULL)"
+        );
+    }
+
+    #[test]
+    fn test_push_down() {
+        let inputs = [
+            vec!["u.id".to_string(), "u.team_id".to_string()],
+            vec!["t.id".to_string(), "t.name".to_string()],
+            vec!["o.team".to_string(), "o.name".to_string()],
+        ];
+        let kinds = [JoinKind::Inner, JoinKind::Left];
+        let pushed = push_down(
+            Some(predicate(
+                "u.id > 1 AND t.name = 'web' AND o.team = 2 AND u.id = t.id AND 1 = @x",
+            )),
+            &inputs,
+            &kinds,
+        );
+        assert_eq!(pushed.source.unwrap().to_string(), "u.id > 1 AND 1 = @x");
+        assert_eq!(
+            pushed.joins[0].as_ref().unwrap().to_string(),
+            "t.name = 'web'"
+        );
+        assert!(pushed.joins[1].is_none());
+        assert_eq!(
+            pushed.residual.unwrap().to_string(),
+            "o.team = 2 AND u.id = t.id"
+        );
+
+        // `name` is ambiguous once joined, so it is left for the residual filter to report.
+        let pushed = push_down(Some(predicate("name = 'web'")), &inputs, &kinds);
+        assert!(pushed.residual.is_some());
+    }
+
+    #[test]
+    fn test_needed_columns() {
+        let inputs = [
+            vec![
+                "u.id".to_string(),
+                "u.team_id".to_string(),
+                "u.bio".to_string(),
+            ],
+            vec!["t.id".to_string(), "t.name".to_string()],
+        ];
+        let select = |sql: &str| {
+            let Statement::Select(select) = parse(sql).unwrap() else {
+                panic!("expected select");
+            };
+            *select
+        };
+
+        let query = select("SELECT t.name FROM u JOIN t ON u.team_id = t.id ORDER BY u.id");
+        assert_eq!(
+            needed_columns(&query, None, &inputs),
+            Some(vec![vec![0, 1], vec![0, 1]])
+        );
+        // With `bio` needed by the residual filter too, nothing is left to prune.
+        let residual = predicate("bio = 'x'");
+        assert_eq!(needed_columns(&query, Some(&residual), &inputs), None);
+
+        let query = select("SELECT * FROM u JOIN t ON u.team_id = t.id");
+        assert_eq!(needed_columns(&query, None, &inputs), None);
+        // `id` is ambiguous: keep everything so the projection reports it.
+        let query = select("SELECT id FROM u JOIN t ON u.team_id = t.id");
+        assert_eq!(needed_columns(&query, None, &inputs), None);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Value, session::Session},
+    parser::{Statement, parse},
+};
+
+impl Database {
+    /// Stores a named SELECT so it can later be run with `EXECUTE <name>`.
+    pub fn save_query(&mut self, name: String, sql: String) -> Result<(), DbError> {
+        if self.saved_queries.contains_key(&name) {
+            return Err(DbError::QueryAlreadyExists(name));
+        }
+        match parse(&sql)? {
+            Statement::Select(_) => {}
+            _ => {
+                return Err(DbError::ParseError(
+                    "Saved queries must be SELECT statements".into(),
+                ));
+            }
+        }
+
+        self.saved_queries.insert(name, sql);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes a saved query.
+    pub fn drop_query(&mut self, name: &str) -> Result<(), DbError> {
+        self.saved_queries
+            .remove(name)
+            .ok_or_else(|| DbError::QueryNotFound(name.to_string()))?;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Parses the stored SQL against the current schema and runs it.
+    pub fn run_saved_query(
+        &mut self,
+        name: &str,
+        session: &Session,
+    ) -> Result<ExecutionResult, DbError> {
+        let sql = self
+            .saved_queries
+            .get(name)
+            .ok_or_else(|| DbError::QueryNotFound(name.to_string()))?;
+
+        match parse(sql)? {
+            Statement::Select(select) => self.run_select(*select, session),
+            _ => Err(DbError::ParseError(
+                "Saved queries must be SELECT statements".into(),
+            )),
+        }
+    }
+
+    /// Lists saved queries as a `name | sql` result set, sorted by name.
+    pub fn list_saved_queries(&self) -> ExecutionResult {
+        let rows = self
+            .saved_queries
+            .iter()
+            .map(|(name, sql)| vec![Value::Text(name.clone()), Value::Text(sql.clone())])
+            .collect();
+        ExecutionResult::listing(&[("name", "TEXT"), ("sql", "TEXT")], rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database, DbError,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_saved_queries() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE devs (id INT PRIMARY, name TEXT)",
+            "INSERT INTO devs VALUES (2, 'Bob')",
+            "INSERT INTO devs VALUES (1, 'Alice')",
+            "CREATE QUERY by_id AS SELECT name FROM devs ORDER BY id LIMIT 1",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+
+        let ExecutionResult::Data { rows, .. } =
+            db.execute(parse("EXECUTE by_id").unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, vec![vec![Value::Text("Alice".into())]]);
+
+        assert!(parse("CREATE QUERY bad AS INSERT INTO devs VALUES (3, 'x')").is_err());
+        let dup = db.execute(parse("CREATE QUERY by_id AS SELECT * FROM devs").unwrap());
+        assert!(matches!(dup, Err(DbError::QueryAlreadyExists(_))));
+
+        // saved queries survive a JSON round trip
+        let json = serde_json::to_string(&db).unwrap();
+        let mut restored: Database = serde_json::from_str(&json).unwrap();
+        restored
+            .execute(parse("DROP QUERY by_id").unwrap())
+            .unwrap();
+        assert!(
+            restored
+                .run_saved_query("by_id", &Default::default())
+                .is_err()
+        );
+    }
+}
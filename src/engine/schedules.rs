@@ -0,0 +1,145 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Value},
+    parser::parse,
+};
+
+/// A statement that is re-run on a fixed interval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub sql: String,
+    pub interval_secs: u64,
+    /// Unix timestamp (seconds) at which the statement is next due.
+    pub next_run: u64,
+}
+
+/// Current Unix time in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Database {
+    /// Registers a statement to run every `interval_secs`, first due one interval from now.
+    pub fn create_schedule(
+        &mut self,
+        name: String,
+        sql: String,
+        interval_secs: u64,
+    ) -> Result<(), DbError> {
+        if self.schedules.contains_key(&name) {
+            return Err(DbError::ScheduleAlreadyExists(name));
+        }
+        parse(&sql)?;
+        let next_run = unix_now()
+            .checked_add(interval_secs)
+            .ok_or_else(|| DbError::ParseError(format!("Interval too long: {}s", interval_secs)))?;
+
+        self.schedules.insert(
+            name,
+            Schedule {
+                sql,
+                interval_secs,
+                next_run,
+            },
+        );
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes a schedule so it no longer runs.
+    pub fn drop_schedule(&mut self, name: &str) -> Result<(), DbError> {
+        self.schedules
+            .remove(name)
+            .ok_or_else(|| DbError::ScheduleNotFound(name.to_string()))?;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Names of the schedules whose next run is at or before `now`.
+    pub fn due_schedules(&self, now: u64) -> Vec<String> {
+        self.schedules
+            .iter()
+            .filter(|(_, s)| s.next_run <= now)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Executes a schedule's statement and moves its next run one interval past `now`.
+    /// The next run is advanced even if the statement fails, so a broken schedule does not
+    /// retry in a tight loop.
+    pub fn run_schedule(&mut self, name: &str, now: u64) -> Result<ExecutionResult, DbError> {
+        let schedule = self
+            .schedules
+            .get_mut(name)
+            .ok_or_else(|| DbError::ScheduleNotFound(name.to_string()))?;
+        schedule.next_run = now.saturating_add(schedule.interval_secs);
+        let sql = schedule.sql.clone();
+        self.mark_dirty();
+
+        let stmt = parse(&sql)?;
+        self.execute(stmt)
+    }
+
+    /// Lists schedules as a `name | sql | interval_secs | next_run` result set.
+    pub fn list_schedules(&self) -> ExecutionResult {
+        let rows = self
+            .schedules
+            .iter()
+            .map(|(name, s)| {
+                vec![
+                    Value::Text(name.clone()),
+                    Value::Text(s.sql.clone()),
+                    Value::Integer(s.interval_secs.min(i32::MAX as u64) as i32),
+                    Value::Integer(s.next_run.min(i32::MAX as u64) as i32),
+                ]
+            })
+            .collect();
+        ExecutionResult::listing(
+            &[
+                ("name", "TEXT"),
+                ("sql", "TEXT"),
+                ("interval_secs", "INT"),
+                ("next_run", "INT"),
+            ],
+            rows,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, parser::parse};
+
+    #[test]
+    fn test_schedules() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE logs (id INT, msg TEXT)",
+            "CREATE SCHEDULE heartbeat AS INSERT INTO logs VALUES (1, 'ping') EVERY '1h'",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        for interval in ["'soon'", "'5é'", "'é'", "''", "'18446744073709551615d'"] {
+            let sql = format!("CREATE SCHEDULE bad AS SELECT 1 EVERY {}", interval);
+            assert!(parse(&sql).is_err(), "{}", sql);
+        }
+        let sql = "CREATE SCHEDULE far AS SELECT 1 EVERY '18446744073709551615s'";
+        assert!(db.execute(parse(sql).unwrap()).is_err());
+
+        let created = db.schedules["heartbeat"].next_run;
+        assert!(db.due_schedules(created - 1).is_empty());
+        assert_eq!(db.due_schedules(created), vec!["heartbeat".to_string()]);
+
+        db.run_schedule("heartbeat", created).unwrap();
+        assert_eq!(db.tables["logs"].rows.len(), 1);
+        assert_eq!(db.schedules["heartbeat"].next_run, created + 3600);
+        assert!(db.due_schedules(created).is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    Database,
+    engine::{Column, Table, foreign_keys::DeleteAction},
+    parser::{quote, quote_identifier},
+};
+
+/// What it takes to give one database the schema of another; see `Database::schema_diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// The statements to run, in order.
+    pub statements: Vec<String>,
+    /// Differences no statement can apply, such as a column to add or a changed type, left
+    /// for a person to handle.
+    pub unsupported: Vec<String>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty() && self.unsupported.is_empty()
+    }
+}
+
+impl Database {
+    /// Compares the stored tables, their indexes and comments, the saved queries and the
+    /// triggers with those of `target`, and lists the statements that migrate this database to
+    /// its schema. Rows are not compared. Tables are dropped before the tables they reference and created
+    /// after them.
+    pub fn schema_diff(&self, target: &Database) -> SchemaDiff {
+        let mut diff = SchemaDiff::default();
+        let changed_query =
+            |name: &String| target.saved_queries.get(name) != self.saved_queries.get(name);
+        for name in self.saved_queries.keys().filter(|n| changed_query(n)) {
+            diff.statements
+                .push(format!("DROP QUERY {}", quote_identifier(name)));
+        }
+        let changed_trigger = |name: &String| target.triggers.get(name) != self.triggers.get(name);
+        for name in self.triggers.keys().filter(|n| changed_trigger(n)) {
+            diff.statements
+                .push(format!("DROP TRIGGER {}", quote_identifier(name)));
+        }
+
+        let dropped: Vec<&Table> = self
+            .tables
+            .values()
+            .filter(|t| !target.tables.contains_key(&t.name))
+            .collect();
+        for table in dependency_order(dropped, true) {
+            diff.statements
+                .push(format!("DROP TABLE {}", quote_identifier(&table.name)));
+        }
+
+        let mut kept: Vec<&String> = self
+            .tables
+            .keys()
+            .filter(|name| target.tables.contains_key(*name))
+            .collect();
+        kept.sort();
+        for name in kept {
+            diff_table(&self.tables[name], &target.tables[name], &mut diff);
+        }
+
+        let created: Vec<&Table> = target
+            .tables
+            .values()
+            .filter(|t| !self.tables.contains_key(&t.name))
+            .collect();
+        for table in dependency_order(created, false) {
+            diff.statements.push(create_table(table));
+            for (index, column) in &table.secondary_indexes {
+                diff.statements.push(create_index(table, index, column));
+            }
+            diff.statements.extend(comments(table, None));
+        }
+
+        for (name, sql) in &target.saved_queries {
+            if self.saved_queries.get(name) != Some(sql) {
+                diff.statements.push(format!(
+                    "CREATE QUERY {} AS {}",
+                    quote_identifier(name),
+                    sql
+                ));
+            }
+        }
+        for (name, trigger) in &target.triggers {
+            if self.triggers.get(name) != Some(trigger) {
+                diff.statements.push(format!(
+                    "CREATE TRIGGER {} AFTER {} ON {} AS {}",
+                    quote_identifier(name),
+                    trigger.event,
+                    quote_identifier(&trigger.table),
+                    trigger.sql
+                ));
+            }
+        }
+        diff
+    }
+}
+
+fn diff_table(from: &Table, to: &Table, diff: &mut SchemaDiff) {
+    let name = &to.name;
+    for (index, column) in &from.secondary_indexes {
+        if to.secondary_indexes.get(index) != Some(column) {
+            diff.statements
+                .push(format!("DROP INDEX {}", quote_identifier(index)));
+        }
+    }
+    for column in &from.columns {
+        if to.column_index(&column.name).is_err() {
+            diff.statements.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                quote_identifier(name),
+                quote_identifier(&column.name)
+            ));
+        }
+    }
+    for column in &to.columns {
+        match from.columns.iter().find(|c| c.name == column.name) {
+            None => diff.unsupported.push(format!(
+                "column {}.{} must be added: {}",
+                name,
+                column.name,
+                column_definition(to, column)
+            )),
+            Some(old) => {
+                let (old, new) = (column_definition(from, old), column_definition(to, column));
+                if old != new {
+                    diff.unsupported.push(format!(
+                        "column {}.{} changes from {} to {}",
+                        name, column.name, old, new
+                    ));
+                }
+            }
+        }
+    }
+    for (index, column) in &to.secondary_indexes {
+        if from.secondary_indexes.get(index) != Some(column) {
+            diff.statements.push(create_index(to, index, column));
+        }
+    }
+    diff.statements.extend(comments(to, Some(from)));
+}
+
+/// The `COMMENT ON` statements that give `table` its comments, skipping those `before` already
+/// has.
+fn comments(table: &Table, before: Option<&Table>) -> Vec<String> {
+    let comment = |comment: &Option<String>| match comment {
+        Some(text) => quote(text),
+        None => "NULL".into(),
+    };
+    let mut statements = Vec::new();
+    if before.map_or(table.comment.is_some(), |before| {
+        before.comment != table.comment
+    }) {
+        statements.push(format!(
+            "COMMENT ON TABLE {} IS {}",
+            quote_identifier(&table.name),
+            comment(&table.comment)
+        ));
+    }
+    for column in &table.columns {
+        let old = before.and_then(|b| b.columns.iter().find(|c| c.name == column.name));
+        let changed = match old {
+            Some(old) => old.comment != column.comment,
+            // Dropped columns lose their comment, and new ones can't be added.
+            None => before.is_none() && column.comment.is_some(),
+        };
+        if changed {
+            statements.push(format!(
+                "COMMENT ON COLUMN {}.{} IS {}",
+                quote_identifier(&table.name),
+                quote_identifier(&column.name),
+                comment(&column.comment)
+            ));
+        }
+    }
+    statements
+}
+
+fn create_table(table: &Table) -> String {
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {}",
+                quote_identifier(&c.name),
+                column_definition(table, c)
+            )
+        })
+        .collect();
+    format!(
+        "CREATE TABLE {} ({})",
+        quote_identifier(&table.name),
+        columns.join(", ")
+    )
+}
+
+fn create_index(table: &Table, index: &str, column: &str) -> String {
+    format!(
+        "CREATE INDEX {} ON {} ({})",
+        quote_identifier(index),
+        quote_identifier(&table.name),
+        quote_identifier(column)
+    )
+}
+
+/// The type and constraints of `column`, as written after its name in `CREATE TABLE`.
+fn column_definition(table: &Table, column: &Column) -> String {
+    let mut definition = column.declared_type();
+    if column.is_primary {
+        definition.push_str(" PRIMARY KEY");
+    }
+    if column.is_unique {
+        definition.push_str(" UNIQUE");
+    }
+    if column.is_not_null {
+        definition.push_str(" NOT NULL");
+    }
+    if column.is_masked {
+        definition.push_str(" MASKED");
+    }
+    for key in table
+        .foreign_keys
+        .iter()
+        .filter(|k| k.column == column.name)
+    {
+        definition.push_str(&format!(
+            " REFERENCES {} ({})",
+            quote_identifier(&key.parent_table),
+            quote_identifier(&key.parent_column)
+        ));
+        if key.on_delete != DeleteAction::NoAction {
+            definition.push_str(&format!(" ON DELETE {}", key.on_delete));
+        }
+    }
+    definition
+}
+
+/// Sorts `tables` by name, then so that a table referencing another one comes after it, or
+/// before it when `referencing_first`.
+fn dependency_order(mut tables: Vec<&Table>, referencing_first: bool) -> Vec<&Table> {
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut pending: BTreeSet<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+    let mut ordered = Vec::with_capacity(tables.len());
+    while !pending.is_empty() {
+        let blocked = |table: &Table| {
+            if referencing_first {
+                // Still referenced by another pending table.
+                tables.iter().any(|other| {
+                    other.name != table.name
+                        && pending.contains(other.name.as_str())
+                        && other
+                            .foreign_keys
+                            .iter()
+                            .any(|k| k.parent_table == table.name)
+                })
+            } else {
+                // Still waiting for a pending parent.
+                table.foreign_keys.iter().any(|k| {
+                    k.parent_table != table.name && pending.contains(k.parent_table.as_str())
+                })
+            }
+        };
+        let next = tables
+            .iter()
+            .find(|t| pending.contains(t.name.as_str()) && !blocked(t))
+            // References can't form a cycle, but a hand-edited file could; take them in order.
+            .or_else(|| tables.iter().find(|t| pending.contains(t.name.as_str())))
+            .copied()
+            .expect("a pending table");
+        pending.remove(next.name.as_str());
+        ordered.push(next);
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn database(statements: &[&str]) -> Database {
+        let mut db = Database::new();
+        for sql in statements {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_schema_diff() {
+        let mut dev = database(&[
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT REFERENCES teams (id), nick TEXT)",
+            "CREATE TABLE old (id INT)",
+            "CREATE INDEX devs_nick ON devs (nick)",
+            "CREATE QUERY q AS SELECT * FROM teams",
+        ]);
+        let prod = database(&[
+            "CREATE TABLE teams (id INT PRIMARY, name TEXT, region TEXT)",
+            "CREATE TABLE orgs (id INT PRIMARY)",
+            "CREATE TABLE units (id INT PRIMARY, org_id INT REFERENCES orgs (id))",
+            "CREATE TABLE devs (id INT PRIMARY, team_id INT REFERENCES teams (id))",
+            "CREATE INDEX teams_name ON teams (name)",
+            "COMMENT ON COLUMN teams.name IS 'display name'",
+            "CREATE QUERY q AS SELECT id FROM teams",
+            "CREATE TRIGGER new_team AFTER INSERT ON teams AS SELECT id FROM orgs",
+        ]);
+
+        let diff = dev.schema_diff(&prod);
+        assert_eq!(
+            diff.statements,
+            [
+                "DROP QUERY q",
+                "DROP TABLE old",
+                "DROP INDEX devs_nick",
+                "ALTER TABLE devs DROP COLUMN nick",
+                "CREATE INDEX teams_name ON teams (name)",
+                "COMMENT ON COLUMN teams.name IS 'display name'",
+                "CREATE TABLE orgs (id INT PRIMARY KEY)",
+                "CREATE TABLE units (id INT PRIMARY KEY, org_id INT REFERENCES orgs (id))",
+                "CREATE QUERY q AS SELECT id FROM teams",
+                "CREATE TRIGGER new_team AFTER INSERT ON teams AS SELECT id FROM orgs",
+            ]
+        );
+        assert_eq!(
+            diff.unsupported,
+            ["column teams.region must be added: TEXT"]
+        );
+
+        for sql in &diff.statements {
+            dev.execute(parse(sql).unwrap()).unwrap();
+        }
+        let diff = dev.schema_diff(&prod);
+        assert!(diff.statements.is_empty());
+        assert_eq!(diff.unsupported.len(), 1);
+        // Dropping goes the other way round.
+        let empty = Database::new();
+        assert_eq!(
+            prod.schema_diff(&empty).statements,
+            [
+                "DROP QUERY q",
+                "DROP TRIGGER new_team",
+                "DROP TABLE devs",
+                "DROP TABLE teams",
+                "DROP TABLE units",
+                "DROP TABLE orgs",
+            ]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    Database, DbError,
+    engine::{Table, Value},
+};
+
+impl Table {
+    /// Whether column `i` has a PRIMARY/UNIQUE index or one added with `CREATE INDEX`.
+    pub fn is_indexed(&self, i: usize) -> bool {
+        self.indexes.contains_key(&i) || self.row_index.contains_key(&i)
+    }
+
+    /// The positions of the columns with any kind of index, in no particular order.
+    pub(crate) fn indexed_columns(&self) -> impl Iterator<Item = usize> + '_ {
+        let secondary = self
+            .row_index
+            .keys()
+            .filter(|i| !self.indexes.contains_key(i));
+        self.indexes.keys().chain(secondary).copied()
+    }
+
+    /// The positions of the rows holding `value` in column `i`, in ascending order, or `None`
+    /// if the column has no secondary index.
+    pub(crate) fn indexed_rows(&self, i: usize, value: &Value) -> Option<&[usize]> {
+        let entries = self.row_index.get(&i)?;
+        Some(entries.get(value).map_or(&[], Vec::as_slice))
+    }
+
+    /// Adds the row at `pos` to the secondary indexes. Rows are added in ascending order, so
+    /// the positions of each value stay sorted. A row too short for a column, which only a
+    /// damaged file holds, is left out of its index until the integrity check removes it.
+    pub(crate) fn index_row(&mut self, pos: usize) {
+        let row = &self.rows[pos];
+        for (i, entries) in &mut self.row_index {
+            if let Some(value) = row.get(*i) {
+                entries.entry(value.clone()).or_default().push(pos);
+            }
+        }
+    }
+
+    /// Removes `row`, stored at `pos`, from the secondary indexes.
+    pub(crate) fn unindex_row(&mut self, pos: usize, row: &[Value]) {
+        for (i, entries) in &mut self.row_index {
+            if let Some(positions) = entries.get_mut(&row[*i]) {
+                positions.retain(|p| *p != pos);
+                if positions.is_empty() {
+                    entries.remove(&row[*i]);
+                }
+            }
+        }
+    }
+
+    /// Reconstructs the entries of every secondary index from the rows.
+    pub(crate) fn rebuild_row_index(&mut self) {
+        self.row_index.clear();
+        for column in self.secondary_indexes.values() {
+            if let Ok(i) = self.column_index(column) {
+                self.row_index.entry(i).or_default();
+            }
+        }
+        for pos in 0..self.rows.len() {
+            self.index_row(pos);
+        }
+    }
+}
+
+impl Database {
+    /// Adds a secondary index named `name` on `column` of `table_name`, so equality and `IN`
+    /// conditions on the column read only the matching rows. Index names are unique across
+    /// all tables.
+    pub fn create_index(
+        &mut self,
+        name: String,
+        table_name: &str,
+        column: &str,
+    ) -> Result<(), DbError> {
+        if self.index_table(&name).is_some() {
+            return Err(DbError::IndexAlreadyExists(name));
+        }
+        if self.virtual_tables.contains_key(table_name) {
+            return Err(DbError::ReadOnlyTable(table_name.to_string()));
+        }
+        let table = self
+            .tables
+            .get_mut(table_name)
+            .ok_or_else(|| DbError::TableNotFound(table_name.to_string()))?;
+        table.column_index(column)?;
+
+        table.secondary_indexes.insert(name, column.to_string());
+        table.rebuild_row_index();
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes the secondary index named `name` and returns the table it was on.
+    pub fn drop_index(&mut self, name: &str) -> Result<String, DbError> {
+        let table_name = self
+            .index_table(name)
+            .ok_or_else(|| DbError::IndexNameNotFound(name.to_string()))?
+            .to_string();
+        let table = self
+            .tables
+            .get_mut(&table_name)
+            .expect("index_table returns a stored table");
+        table.secondary_indexes.remove(name);
+        table.rebuild_row_index();
+        self.mark_dirty();
+        Ok(table_name)
+    }
+
+    /// The table holding the secondary index named `name`.
+    fn index_table(&self, name: &str) -> Option<&str> {
+        self.tables
+            .values()
+            .find(|t| t.secondary_indexes.contains_key(name))
+            .map(|t| t.name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database, DbError,
+        engine::{ExecutionResult, Value, session::Session, trace::QueryTrace},
+        parser::{Statement, parse},
+        storage::Format,
+    };
+
+    #[test]
+    fn test_secondary_index() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(parse(sql).unwrap());
+        run("CREATE TABLE orders (id INT PRIMARY KEY, customer TEXT, qty INT)").unwrap();
+        run("INSERT INTO orders VALUES (1, 'ada', 2), (2, 'alan', 1), (3, 'ada', 5)").unwrap();
+        run("CREATE INDEX by_customer ON orders (customer)").unwrap();
+        assert!(matches!(
+            run("CREATE INDEX by_customer ON orders (qty)"),
+            Err(DbError::IndexAlreadyExists(_))
+        ));
+        assert!(matches!(
+            run("CREATE INDEX by_missing ON orders (missing)"),
+            Err(DbError::ColumnNotFound(_))
+        ));
+        run("INSERT INTO orders VALUES (4, 'ada', 1)").unwrap();
+        // A failed batch leaves no entries behind.
+        assert!(run("INSERT INTO orders VALUES (5, 'grace', 1), (1, 'dup', 1)").is_err());
+
+        let table = &db.tables["orders"];
+        let ada = Value::Text("ada".into());
+        assert_eq!(table.indexed_rows(1, &ada), Some(&[0, 2, 3][..]));
+        assert_eq!(
+            table.indexed_rows(1, &Value::Text("grace".into())),
+            Some(&[][..])
+        );
+
+        let select = "SELECT id FROM orders WHERE customer = 'ada' AND qty > 1";
+        let Statement::Select(select) = parse(select).unwrap() else {
+            panic!("expected a select");
+        };
+        let mut trace = QueryTrace::default();
+        let result = db.handle_select(*select, &Session::default(), &mut trace);
+        let Ok(ExecutionResult::Data { rows, .. }) = result else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Integer(1)], [Value::Integer(3)]]);
+        assert_eq!(trace.index_lookups[0].column, "customer");
+        assert_eq!(trace.index_lookups[0].rows, 3);
+
+        // An upsert moves the updated row to its new value.
+        let upsert = "INSERT INTO orders VALUES (2, 'ada', 9) \
+                      ON CONFLICT (id) DO UPDATE SET customer = excluded.customer";
+        db.execute(parse(upsert).unwrap()).unwrap();
+        let table = &db.tables["orders"];
+        assert_eq!(table.indexed_rows(1, &ada), Some(&[0, 1, 2, 3][..]));
+        assert_eq!(
+            table.indexed_rows(1, &Value::Text("alan".into())),
+            Some(&[][..])
+        );
+
+        // The definition is persisted and the entries rebuilt on load.
+        let bytes = Format::Json.encode(&db).unwrap();
+        let mut loaded = Format::Json.decode(&bytes).unwrap();
+        for table in loaded.tables.values_mut() {
+            table.rebuild_indexes();
+        }
+        assert_eq!(
+            loaded.tables["orders"].indexed_rows(1, &ada),
+            Some(&[0, 1, 2, 3][..])
+        );
+
+        let mut run = |sql: &str| db.execute(parse(sql).unwrap());
+        run("DROP INDEX by_customer").unwrap();
+        assert!(matches!(
+            run("DROP INDEX by_customer"),
+            Err(DbError::IndexNameNotFound(_))
+        ));
+        assert!(!db.tables["orders"].is_indexed(1));
+    }
+}
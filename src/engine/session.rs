@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Value, query_log::describe, transaction::Snapshot},
+    parser::Statement,
+};
+
+/// Identifies the next session created, to tell whose transaction holds a database.
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Per-connection state that lives outside the database: who is connected, the variables
+/// they have `SET`, their open transaction and its savepoints. The REPL keeps one for its whole lifetime, while
+/// callers of `Database::execute` get a fresh anonymous session for every statement.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub user: String,
+    pub variables: HashMap<String, Value>,
+    /// Whether `MASKED` columns show their values. Off unless the front end grants it.
+    pub unmask: bool,
+    /// Whether `system.queries` lists the statements of every user rather than only the
+    /// session's own. Off unless the front end grants it.
+    pub admin: bool,
+    /// Shared by the copies of the session, which continue the same transaction.
+    pub(crate) id: u64,
+    /// The state of the database when the open transaction began, to go back to on
+    /// `ROLLBACK`.
+    pub(crate) transaction: Option<Arc<Snapshot>>,
+    /// Savepoints of the open transaction by name, oldest first. A name can repeat, the latest one wins. Shared, so
+    /// copying a session doesn't copy the database once per savepoint.
+    pub(crate) savepoints: Vec<(String, Arc<Snapshot>)>,
+}
+
+impl Session {
+    pub fn new(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            variables: HashMap::new(),
+            unmask: false,
+            admin: false,
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            transaction: None,
+            savepoints: Vec::new(),
+        }
+    }
+
+    /// Reads a variable; unset variables evaluate to `NULL`.
+    pub fn variable(&self, name: &str) -> Value {
+        self.variables.get(name).cloned().unwrap_or(Value::Null)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new("anonymous")
+    }
+}
+
+/// What a statement runs with: the principal an authentication layer vouched for, the
+/// session it continues and, when the caller has it, the SQL text it was parsed from.
+///
+/// The principal takes precedence over `Session::user`, so every check made while the
+/// statement runs, and `CURRENT_USER()`, sees the authenticated identity even when the
+/// session was opened by someone else.
+pub struct ExecutionContext<'a> {
+    principal: &'a str,
+    session: &'a mut Session,
+    sql: Option<&'a str>,
+}
+
+impl<'a> ExecutionContext<'a> {
+    pub fn new(principal: &'a str, session: &'a mut Session) -> Self {
+        Self {
+            principal,
+            session,
+            sql: None,
+        }
+    }
+
+    /// Lists the statements run in this context under `sql` in `system.queries`, instead of
+    /// text rebuilt from the parsed statement.
+    pub fn with_sql(mut self, sql: &'a str) -> Self {
+        self.sql = Some(sql);
+        self
+    }
+
+    pub fn principal(&self) -> &str {
+        self.principal
+    }
+
+    pub fn session(&mut self) -> &mut Session {
+        self.session
+    }
+}
+
+impl Database {
+    /// Like `execute_with_session`, as the principal of `context`.
+    pub fn execute_in(
+        &mut self,
+        statement: Statement,
+        context: &mut ExecutionContext,
+    ) -> Result<ExecutionResult, DbError> {
+        if context.session.user != context.principal {
+            context.session.user = context.principal.to_string();
+        }
+        let sql = match context.sql {
+            Some(sql) => sql.to_string(),
+            None => describe(&statement),
+        };
+        self.execute_logged(statement, context.session, sql)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_execution_context() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id INT)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO t VALUES (1)").unwrap())
+            .unwrap();
+
+        let mut session = Session::new("web");
+        let mut context = ExecutionContext::new("ada", &mut session);
+        db.execute_in(parse("SET @team = 'core'").unwrap(), &mut context)
+            .unwrap();
+        let result = db.execute_in(
+            parse("SELECT CURRENT_USER(), @team FROM t").unwrap(),
+            &mut context,
+        );
+        let Ok(ExecutionResult::Data { rows, .. }) = result else {
+            panic!("expected data");
+        };
+        assert_eq!(
+            rows,
+            [[Value::Text("ada".into()), Value::Text("core".into())]]
+        );
+        assert_eq!(context.principal(), "ada");
+        assert_eq!(session.user, "ada");
+    }
+}
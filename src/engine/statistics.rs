@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Database, DbError,
+    engine::{Table, Value},
+    parser::{BinaryOperator, Expr},
+};
+
+/// Buckets of the histogram `ANALYZE` builds per column.
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// The share of rows a predicate is assumed to keep when the statistics can't tell.
+const UNKNOWN_SELECTIVITY: f64 = 1.0;
+
+/// How much a table may grow or shrink, as a share of the rows `ANALYZE` saw, before its
+/// statistics are considered stale and ignored.
+const STALE_CHANGE: f64 = 0.2;
+
+/// What `ANALYZE` found in a column, for the planner's estimates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub rows: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    /// The bounds of an equi-depth histogram of the non-NULL values: each of the buckets
+    /// between two consecutive bounds holds about as many values. Empty for a column of NULLs.
+    pub histogram: Vec<Value>,
+}
+
+impl ColumnStats {
+    fn build<'a>(values: impl Iterator<Item = &'a Value>) -> Self {
+        let mut rows = 0;
+        let mut sorted: Vec<&Value> = values
+            .inspect(|_| rows += 1)
+            .filter(|v| **v != Value::Null)
+            .collect();
+        sorted.sort();
+        let nulls = rows - sorted.len();
+        let mut distinct = sorted.len().min(1);
+        distinct += sorted.windows(2).filter(|w| w[0] != w[1]).count();
+        let histogram = match sorted.len() {
+            0 => Vec::new(),
+            n => {
+                let buckets = HISTOGRAM_BUCKETS.min(n);
+                (0..=buckets)
+                    .map(|i| sorted[(i * n / buckets).min(n - 1)].clone())
+                    .collect()
+            }
+        };
+        ColumnStats {
+            rows,
+            nulls,
+            distinct,
+            histogram,
+        }
+    }
+
+    /// Whether the statistics still describe a table that now holds `rows` rows. The
+    /// estimates scale their shares by the current row count, which only holds while the
+    /// table is about the size it was analyzed at.
+    fn is_fresh(&self, rows: usize) -> bool {
+        (rows as f64 - self.rows as f64).abs() <= self.rows as f64 * STALE_CHANGE
+    }
+
+    fn non_null(&self) -> f64 {
+        match self.rows {
+            0 => 0.0,
+            rows => (rows - self.nulls) as f64 / rows as f64,
+        }
+    }
+
+    /// The share of rows equal to `value`, assuming the values are spread evenly.
+    fn equal(&self, value: &Value) -> f64 {
+        let (Some(min), Some(max)) = (self.histogram.first(), self.histogram.last()) else {
+            return 0.0;
+        };
+        if value < min || value > max {
+            return 0.0;
+        }
+        self.non_null() / self.distinct.max(1) as f64
+    }
+
+    /// The share of rows below `value`, read off the histogram. Integers are interpolated
+    /// within their bucket, anything else counts as halfway through it.
+    fn below(&self, value: &Value) -> f64 {
+        let bounds = &self.histogram;
+        let (Some(min), Some(max)) = (bounds.first(), bounds.last()) else {
+            return 0.0;
+        };
+        if value <= min {
+            return 0.0;
+        }
+        if value > max {
+            return self.non_null();
+        }
+        let i = bounds.windows(2).position(|w| value <= &w[1]).unwrap_or(0);
+        let number = |value: &Value| match value {
+            Value::Integer(n) => Some(f64::from(*n)),
+            Value::BigInt(n) => Some(*n as f64),
+            Value::Real(r) => Some(r.get()),
+            Value::Decimal(d) => Some(d.to_f64()),
+            Value::Date(d) => Some(d.at_midnight().secs() as f64),
+            Value::Timestamp(t) => Some(t.secs() as f64),
+            _ => None,
+        };
+        let within = match (number(&bounds[i]), number(&bounds[i + 1]), number(value)) {
+            (Some(low), Some(high), Some(v)) if high > low => (v - low) / (high - low),
+            _ => 0.5,
+        };
+        let buckets = (bounds.len() - 1) as f64;
+        self.non_null() * (i as f64 + within) / buckets
+    }
+}
+
+impl Table {
+    /// Rebuilds the statistics of every column from the current rows.
+    pub fn collect_statistics(&mut self) {
+        self.statistics = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let stats = ColumnStats::build(self.rows.iter().filter_map(|row| row.get(i)));
+                (column.name.clone(), stats)
+            })
+            .collect();
+    }
+
+    /// The statistics of the column `name`, unless there are none or they are stale.
+    pub fn column_statistics(&self, name: &str) -> Option<&ColumnStats> {
+        self.statistics
+            .get(name)
+            .filter(|stats| stats.is_fresh(self.rows.len()))
+    }
+
+    /// Whether `ANALYZE` collected statistics for the table that are not stale.
+    pub fn has_statistics(&self) -> bool {
+        self.statistics
+            .values()
+            .next()
+            .is_some_and(|stats| stats.is_fresh(self.rows.len()))
+    }
+}
+
+impl Database {
+    /// `ANALYZE [table]`: collects the statistics of one stored table, or of all of them.
+    /// Returns how many tables were analyzed.
+    pub fn collect_statistics(&mut self, table_name: Option<&str>) -> Result<usize, DbError> {
+        let tables: Vec<&mut Table> = match table_name {
+            Some(name) => vec![
+                self.tables
+                    .get_mut(name)
+                    .ok_or_else(|| DbError::TableNotFound(name.to_string()))?,
+            ],
+            None => self.tables.values_mut().collect(),
+        };
+        let analyzed = tables.len();
+        for table in tables {
+            table.collect_statistics();
+        }
+        if analyzed > 0 {
+            self.mark_dirty();
+        }
+        Ok(analyzed)
+    }
+}
+
+/// The share of rows `predicate` is estimated to keep, with `stats` giving the statistics of
+/// the columns it names. Comparisons of a column with a literal are estimated from the
+/// column's histogram; `AND`, `OR` and `NOT` combine their operands as if independent, and
+/// anything else keeps every row.
+pub(crate) fn selectivity<'a>(
+    predicate: &Expr,
+    stats: &dyn Fn(&str) -> Option<&'a ColumnStats>,
+) -> f64 {
+    let column_stats = |expr: &Expr| match expr {
+        Expr::Column(name) => stats(name),
+        _ => None,
+    };
+    match predicate {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => selectivity(left, stats) * selectivity(right, stats),
+        Expr::Binary {
+            left,
+            op: BinaryOperator::Or,
+            right,
+        } => {
+            let (a, b) = (selectivity(left, stats), selectivity(right, stats));
+            a + b - a * b
+        }
+        Expr::Not(inner) => 1.0 - selectivity(inner, stats),
+        Expr::IsNull { expr, negated } => match column_stats(expr) {
+            Some(s) if *negated => s.non_null(),
+            Some(s) => 1.0 - s.non_null(),
+            None => UNKNOWN_SELECTIVITY,
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let Some(s) = column_stats(expr) else {
+                return UNKNOWN_SELECTIVITY;
+            };
+            let mut matched = 0.0;
+            for item in list {
+                let Expr::Literal(value) = item else {
+                    return UNKNOWN_SELECTIVITY;
+                };
+                matched += s.equal(value);
+            }
+            let matched = matched.min(s.non_null());
+            if *negated {
+                s.non_null() - matched
+            } else {
+                matched
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let (s, value, op) = match (column_stats(left), &**right, column_stats(right), &**left)
+            {
+                (Some(s), Expr::Literal(value), _, _) => (s, value, *op),
+                (_, _, Some(s), Expr::Literal(value)) => (s, value, flip(*op)),
+                _ => return UNKNOWN_SELECTIVITY,
+            };
+            let (equal, below) = (s.equal(value), s.below(value));
+            match op {
+                BinaryOperator::Eq => equal,
+                BinaryOperator::NotEq => s.non_null() - equal,
+                BinaryOperator::Lt => below,
+                BinaryOperator::LtEq => below + equal,
+                BinaryOperator::Gt => s.non_null() - below - equal,
+                BinaryOperator::GtEq => s.non_null() - below,
+                _ => UNKNOWN_SELECTIVITY,
+            }
+            .clamp(0.0, 1.0)
+        }
+        _ => UNKNOWN_SELECTIVITY,
+    }
+}
+
+/// The operator that keeps the meaning when its operands swap sides, e.g. `5 < a` as `a > 5`.
+fn flip(op: BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::ExecutionResult, parser::parse};
+
+    #[test]
+    fn test_statistics() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id INT PRIMARY, grp INT)").unwrap())
+            .unwrap();
+        let table = db.tables.get_mut("t").unwrap();
+        for i in 0..1000 {
+            let grp = if i % 10 == 0 {
+                Value::Null
+            } else {
+                Value::Integer(i % 4)
+            };
+            table.insert_row(vec![Value::Integer(i), grp]).unwrap();
+        }
+        let estimate = |db: &Database, sql: &str| db.estimate_rows(&parse(sql).unwrap()).unwrap();
+        // Without statistics filters are assumed to keep every row.
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE id < 100"), 1000);
+
+        db.execute(parse("ANALYZE t").unwrap()).unwrap();
+        let stats = &db.tables["t"].statistics["grp"];
+        assert_eq!((stats.rows, stats.nulls, stats.distinct), (1000, 100, 4));
+        assert_eq!(stats.histogram.len(), HISTOGRAM_BUCKETS + 1);
+
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE id < 100"), 100);
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE 900 <= id"), 100);
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE grp = 1"), 225);
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE grp IS NULL"), 100);
+        assert_eq!(estimate(&db, "SELECT * FROM t WHERE grp = 9"), 0);
+        assert_eq!(
+            estimate(&db, "SELECT * FROM t WHERE id < 500 AND grp IN (1, 2)"),
+            225
+        );
+        // Each group is assumed to hold as many rows on both sides of the join.
+        assert_eq!(
+            estimate(&db, "SELECT * FROM t a JOIN t b ON a.grp = b.grp"),
+            250_000
+        );
+        assert!(db.execute(parse("ANALYZE missing").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_statistics_guide_the_plan() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(parse(sql).unwrap()).unwrap();
+        run("CREATE TABLE e (v INT)");
+        run("ANALYZE e");
+        for i in 0..6 {
+            run(&format!("INSERT INTO e VALUES ({})", i));
+        }
+        run("CREATE TABLE big (id INT PRIMARY, grp INT)");
+        run("CREATE INDEX big_grp ON big (grp)");
+        run("CREATE TABLE mid (big_id INT)");
+        run("CREATE TABLE small (big_id INT)");
+        for i in 0..100 {
+            run(&format!("INSERT INTO big VALUES ({}, {})", i, i % 2));
+        }
+        for i in 0..10 {
+            run(&format!("INSERT INTO mid VALUES ({})", i));
+        }
+        run("INSERT INTO small VALUES (3), (4)");
+
+        let estimate = |db: &Database, sql: &str| db.estimate_rows(&parse(sql).unwrap()).unwrap();
+        // Statistics of the empty table no longer describe its rows.
+        assert_eq!(estimate(&db, "SELECT * FROM e WHERE v > 1"), 6);
+        db.execute(parse("ANALYZE e").unwrap()).unwrap();
+        assert_eq!(estimate(&db, "SELECT * FROM e WHERE v > 1"), 4);
+
+        // The probe on the primary key returns one row, the one on `grp` fifty.
+        let plan = db
+            .plan(&parse("SELECT * FROM big WHERE grp = 1 AND id = 5").unwrap())
+            .unwrap();
+        let scan = &plan.children[0].children[0];
+        assert_eq!(
+            (scan.operator.as_str(), scan.detail.as_str()),
+            ("Index Lookup", "big.id")
+        );
+
+        let sql = "SELECT * FROM big b JOIN mid m ON b.id = m.big_id \
+                   JOIN small s ON s.big_id = b.id";
+        let last_join = |db: &Database| {
+            let plan = db.plan(&parse(sql).unwrap()).unwrap();
+            plan.children[0].detail.clone()
+        };
+        let select = |db: &mut Database| match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { headers, rows, .. } => (headers, rows),
+            ExecutionResult::Message(m) => panic!("expected rows, got {}", m),
+        };
+        // Without statistics the joins run as written.
+        assert_eq!(last_join(&db), "s.big_id = b.id");
+        let as_written = select(&mut db);
+        db.execute(parse("ANALYZE").unwrap()).unwrap();
+        assert_eq!(last_join(&db), "b.id = m.big_id");
+        assert_eq!(select(&mut db), as_written);
+    }
+}
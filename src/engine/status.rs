@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::{
+    Database,
+    engine::{
+        ExecutionResult, Value, datetime::format_timestamp, index_stats::IndexUsage,
+        schedules::unix_now, trace::QueryMemory,
+    },
+};
+
+/// Runtime counters of a database. They describe the current process and are not persisted.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    /// Unix time at which the database was created or loaded.
+    pub started_at: u64,
+    pub queries_executed: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Unix time of the last successful flush to storage.
+    pub last_save: Option<u64>,
+    /// Rows the most recent statement inserted or updated.
+    pub last_rows_written: usize,
+    /// Intermediate results of the most recent SELECT.
+    pub last_query_memory: QueryMemory,
+    /// Largest intermediate result of any SELECT since startup.
+    pub peak_query_bytes: usize,
+    /// Usage of each index, keyed by `(table, column)`.
+    pub index_usage: HashMap<(String, String), IndexUsage>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started_at: unix_now(),
+            queries_executed: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            last_save: None,
+            last_rows_written: 0,
+            last_query_memory: QueryMemory::default(),
+            peak_query_bytes: 0,
+            index_usage: HashMap::new(),
+        }
+    }
+}
+
+impl Database {
+    /// Engine statistics as `(name, value)` pairs. Unknown values (unset limits, a hit rate
+    /// before any cache lookup, a database never saved) are reported as `NULL`.
+    ///
+    /// This backs `SHOW STATUS` as well as the web server's metrics endpoint.
+    pub fn status(&self) -> Vec<(String, Value)> {
+        let int = |n: usize| Value::Integer(n.min(i32::MAX as usize) as i32);
+        let limit = |n: Option<usize>| n.map_or(Value::Null, int);
+
+        let lookups = self.stats.cache_hits + self.stats.cache_misses;
+        let hit_rate = match lookups {
+            0 => Value::Null,
+            n => int((self.stats.cache_hits * 100 / n) as usize),
+        };
+        let memory = &self.stats.last_query_memory;
+        let last_save = self
+            .stats
+            .last_save
+            .map_or(Value::Null, |t| Value::Text(format_timestamp(t as i64)));
+
+        vec![
+            ("tables".into(), int(self.tables.len())),
+            ("total_rows".into(), int(self.total_rows())),
+            (
+                "indexes".into(),
+                int(self.tables.values().map(|t| t.indexes.len()).sum()),
+            ),
+            ("max_rows".into(), limit(self.limits.max_rows)),
+            ("data_bytes".into(), int(self.data_size())),
+            ("max_bytes".into(), limit(self.limits.max_bytes)),
+            ("max_load_bytes".into(), limit(self.limits.max_load_bytes)),
+            (
+                "uptime_secs".into(),
+                int(unix_now().saturating_sub(self.stats.started_at) as usize),
+            ),
+            (
+                "queries_executed".into(),
+                int(self.stats.queries_executed as usize),
+            ),
+            ("cache_hit_rate_pct".into(), hit_rate),
+            ("cached_statements".into(), int(self.statement_cache.len())),
+            ("last_save".into(), last_save),
+            (
+                "last_query_rows_materialized".into(),
+                int(memory.rows_materialized),
+            ),
+            (
+                "last_query_bytes_materialized".into(),
+                int(memory.bytes_materialized),
+            ),
+            ("last_query_join_bytes".into(), int(memory.join_bytes)),
+            ("last_query_sort_bytes".into(), int(memory.sort_bytes)),
+            ("last_query_peak_bytes".into(), int(memory.peak_bytes)),
+            ("peak_query_bytes".into(), int(self.stats.peak_query_bytes)),
+        ]
+    }
+
+    /// Lists `status()` as a `name | value` result set.
+    pub fn show_status(&self) -> ExecutionResult {
+        let rows = self
+            .status()
+            .into_iter()
+            .map(|(name, value)| vec![Value::Text(name), value])
+            .collect();
+        // Values are mostly counts, but some settings are text.
+        ExecutionResult::data(vec!["name".into(), "value".into()], rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+        storage::Storage,
+    };
+
+    #[test]
+    fn test_show_status() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, email TEXT UNIQUE)",
+            "INSERT INTO users VALUES (1, 'a@x.io')",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        db.flush(&Storage::Memory).unwrap();
+
+        let ExecutionResult::Data { rows, .. } = db.execute(parse("SHOW STATUS").unwrap()).unwrap()
+        else {
+            panic!("expected data");
+        };
+        let get = |name: &str| {
+            rows.iter()
+                .find(|r| r[0] == Value::Text(name.into()))
+                .map(|r| r[1].clone())
+                .unwrap()
+        };
+        assert_eq!(get("indexes"), Value::Integer(2));
+        assert_eq!(get("queries_executed"), Value::Integer(3));
+        assert_eq!(get("cache_hit_rate_pct"), Value::Null);
+        assert_eq!(get("cached_statements"), Value::Integer(0));
+        assert!(matches!(get("last_save"), Value::Text(_)));
+    }
+}
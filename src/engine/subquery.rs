@@ -0,0 +1,265 @@
+use crate::{
+    Database, DbError,
+    engine::{
+        Value,
+        eval::{eval, is_true},
+        relation::Relation,
+        session::Session,
+        trace::QueryTrace,
+    },
+    parser::{Expr, SelectItem, SelectStatement},
+};
+
+impl Database {
+    /// Keeps the rows of `relation` for which `predicate` is true, like `Relation::filter`,
+    /// but also evaluates `EXISTS` subqueries.
+    ///
+    /// A subquery that refers to no column of `relation` runs once. A correlated one runs once
+    /// per row, with each outer column it refers to replaced by that row's value. A column the
+    /// subquery's own tables provide always refers to those, as in standard SQL.
+    pub(crate) fn filter_rows(
+        &self,
+        relation: &mut Relation,
+        predicate: &Expr,
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<(), DbError> {
+        let predicate = self.run_subqueries(predicate, relation, None, session, trace)?;
+        if !predicate.has_subquery() {
+            return relation.filter(&predicate, session);
+        }
+
+        let mut keep = Vec::with_capacity(relation.rows.len());
+        for row in &relation.rows {
+            let bound = self.run_subqueries(&predicate, relation, Some(row), session, trace)?;
+            keep.push(is_true(&eval(&bound, relation, row, session)?));
+        }
+        let mut keep = keep.into_iter();
+        relation.rows.retain(|_| keep.next().unwrap_or(false));
+        Ok(())
+    }
+
+    /// Projects `relation` like `Relation::project`, but also evaluates the scalar subqueries
+    /// of the select list, once if they refer to no column of `relation` and once per row
+    /// otherwise. Their items keep the header the subquery gives them.
+    pub(crate) fn project_rows(
+        &self,
+        relation: Relation,
+        items: &[SelectItem],
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<Relation, DbError> {
+        let items = self.bind_items(items, &relation, None, session, trace)?;
+        if !items.iter().any(item_has_subquery) {
+            return relation.project(&items, session);
+        }
+
+        let mut rows = Vec::with_capacity(relation.rows.len());
+        for row in &relation.rows {
+            let bound = self.bind_items(&items, &relation, Some(row), session, trace)?;
+            let single = Relation {
+                headers: relation.headers.clone(),
+                rows: vec![row.clone()],
+                table: relation.table.clone(),
+            };
+            rows.extend(single.project(&bound, session)?.rows);
+        }
+        let mut projected = Relation {
+            rows: Vec::new(),
+            ..relation
+        }
+        .project(&items, session)?;
+        projected.rows = rows;
+        Ok(projected)
+    }
+
+    /// Runs the subqueries of the select list items that have one, as `run_subqueries` does,
+    /// aliasing each such item to its original header.
+    fn bind_items(
+        &self,
+        items: &[SelectItem],
+        relation: &Relation,
+        row: Option<&[Value]>,
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<Vec<SelectItem>, DbError> {
+        items
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr { expr, alias } if expr.has_subquery() => Ok(SelectItem::Expr {
+                    expr: self.run_subqueries(expr, relation, row, session, trace)?,
+                    alias: Some(alias.clone().unwrap_or_else(|| item.header())),
+                }),
+                other => Ok(other.clone()),
+            })
+            .collect()
+    }
+
+    /// Replaces each `EXISTS` in `expr` by its outcome, `1` or `0`, and each scalar subquery
+    /// by its value. Without a `row`,
+    /// correlated subqueries can't run yet and are left in place.
+    fn run_subqueries(
+        &self,
+        expr: &Expr,
+        outer: &Relation,
+        row: Option<&[Value]>,
+        session: &Session,
+        trace: &mut QueryTrace,
+    ) -> Result<Expr, DbError> {
+        let mut run = |e: &Expr| self.run_subqueries(e, outer, row, session, trace);
+        Ok(match expr {
+            Expr::Exists(subquery) => {
+                let mut subquery = (**subquery).clone();
+                let mut refs = Vec::new();
+                self.bind_outer(&mut subquery, &mut Vec::new(), outer, row, &mut refs)?;
+                if !refs.is_empty() && row.is_none() {
+                    return Ok(expr.clone());
+                }
+                let result = self.select_relation(subquery, session, trace)?;
+                Expr::Literal(Value::Integer(!result.rows.is_empty() as i32))
+            }
+            Expr::Subquery(subquery) => {
+                let mut subquery = (**subquery).clone();
+                let mut refs = Vec::new();
+                self.bind_outer(&mut subquery, &mut Vec::new(), outer, row, &mut refs)?;
+                if !refs.is_empty() && row.is_none() {
+                    return Ok(expr.clone());
+                }
+                let result = self.select_relation(subquery, session, trace)?;
+                match result.rows.as_slice() {
+                    [] => Expr::Literal(Value::Null),
+                    [row] => Expr::Literal(row[0].clone()),
+                    rows => return Err(DbError::SubqueryTooManyRows(rows.len())),
+                }
+            }
+            Expr::Binary { left, op, right } => Expr::Binary {
+                left: Box::new(run(left)?),
+                op: *op,
+                right: Box::new(run(right)?),
+            },
+            Expr::Not(inner) => Expr::Not(Box::new(run(inner)?)),
+            Expr::Negate(inner) => Expr::Negate(Box::new(run(inner)?)),
+            Expr::Cast { expr, data_type } => Expr::Cast {
+                expr: Box::new(run(expr)?),
+                data_type: data_type.clone(),
+            },
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: Box::new(run(expr)?),
+                negated: *negated,
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Expr::InList {
+                expr: Box::new(run(expr)?),
+                list: list.iter().map(run).collect::<Result<_, _>>()?,
+                negated: *negated,
+            },
+            Expr::Function { name, args } => Expr::Function {
+                name: name.clone(),
+                args: args.iter().map(run).collect::<Result<_, _>>()?,
+            },
+            other => other.clone(),
+        })
+    }
+
+    /// The positions of the columns of `outer` that `subquery` refers to, so that its
+    /// result is computed from them.
+    pub(crate) fn outer_references(
+        &self,
+        subquery: &SelectStatement,
+        outer: &Relation,
+    ) -> Result<Vec<usize>, DbError> {
+        let mut refs = Vec::new();
+        self.bind_outer(
+            &mut subquery.clone(),
+            &mut Vec::new(),
+            outer,
+            None,
+            &mut refs,
+        )?;
+        Ok(refs)
+    }
+
+    /// Finds the columns of `outer` that `subquery` refers to, adding their positions to
+    /// `refs`, and, given a `row`, replaces them by that row's values. `scopes` holds the
+    /// sources of the subqueries enclosing this one, whose columns shadow the outer ones.
+    fn bind_outer(
+        &self,
+        subquery: &mut SelectStatement,
+        scopes: &mut Vec<Relation>,
+        outer: &Relation,
+        row: Option<&[Value]>,
+        refs: &mut Vec<usize>,
+    ) -> Result<(), DbError> {
+        scopes.push(self.source_relation(subquery)?);
+        let items = subquery.columns.iter_mut().filter_map(|item| match item {
+            SelectItem::Expr { expr, .. } => Some(expr),
+            SelectItem::Wildcard => None,
+        });
+        for expr in items.chain(subquery.where_clause.as_mut()) {
+            self.bind_expr(expr, scopes, outer, row, refs)?;
+        }
+        scopes.pop();
+        Ok(())
+    }
+
+    fn bind_expr(
+        &self,
+        expr: &mut Expr,
+        scopes: &mut Vec<Relation>,
+        outer: &Relation,
+        row: Option<&[Value]>,
+        refs: &mut Vec<usize>,
+    ) -> Result<(), DbError> {
+        let mut bind = |e: &mut Expr| self.bind_expr(e, scopes, outer, row, refs);
+        match expr {
+            Expr::Column(name) => {
+                let shadowed = scopes.iter().any(|scope| {
+                    !matches!(scope.column_index(name), Err(DbError::ColumnNotFound(_)))
+                });
+                let Some(i) = outer.column_index(name).ok().filter(|_| !shadowed) else {
+                    return Ok(());
+                };
+                if let Some(row) = row {
+                    *expr = Expr::Literal(row[i].clone());
+                }
+                refs.push(i);
+            }
+            Expr::Literal(_) | Expr::Variable(_) | Expr::Window { .. } => {}
+            Expr::Exists(subquery) | Expr::Subquery(subquery) => {
+                self.bind_outer(subquery, scopes, outer, row, refs)?
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    bind(arg)?;
+                }
+            }
+            Expr::Aggregate { arg, .. } => {
+                if let Some(arg) = arg {
+                    bind(arg)?;
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                bind(left)?;
+                bind(right)?;
+            }
+            Expr::Not(inner)
+            | Expr::Negate(inner)
+            | Expr::IsNull { expr: inner, .. }
+            | Expr::Cast { expr: inner, .. } => bind(inner)?,
+            Expr::InList { expr, list, .. } => {
+                bind(expr)?;
+                for item in list {
+                    bind(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn item_has_subquery(item: &SelectItem) -> bool {
+    matches!(item, SelectItem::Expr { expr, .. } if expr.has_subquery())
+}
@@ -0,0 +1,366 @@
+use crate::{
+    Database, DbError,
+    engine::{Column, Table, Timestamp, Value, session::Session},
+};
+
+/// The most rows `generate_series` produces, unless `Limits::max_rows` is lower.
+const MAX_SERIES_ROWS: usize = 10_000_000;
+
+/// Built-in table-valued functions that can appear in a `FROM` clause.
+///
+/// - `generate_series(start, stop[, step])` produces a single `value` column, refusing
+///   series longer than `MAX_SERIES_ROWS` or the database's row limit.
+/// - `pragma_table_info('table')` describes the columns of a table, comments included.
+/// - `memory_stats` reports row counts and approximate memory usage per table.
+/// - `dual()` has one row and no columns; it backs `SELECT` statements without `FROM`.
+/// - `system.queries` lists the running statements, then the last ones that finished. Only
+///   those of the session's user, unless the session is an admin one.
+pub fn call(
+    db: &Database,
+    name: &str,
+    args: &[Value],
+    session: &Session,
+) -> Result<Table, DbError> {
+    match name.to_lowercase().as_str() {
+        "dual" => {
+            let mut table = empty("dual");
+            table.rows.push(vec![]);
+            Ok(table)
+        }
+        "generate_series" => generate_series(db, args),
+        "pragma_table_info" => pragma_table_info(db, args),
+        "memory_stats" => memory_stats(db),
+        "system.queries" => system_queries(db, session),
+        _ => Err(DbError::TableNotFound(name.to_string())),
+    }
+}
+
+/// How many rows `call` would return, worked out from the arguments and the catalog.
+pub fn row_count(db: &Database, name: &str, args: &[Value]) -> Result<usize, DbError> {
+    match name.to_lowercase().as_str() {
+        "dual" => Ok(1),
+        "generate_series" => {
+            let (start, stop, step) = series(args)?;
+            Ok(series_len(start, stop, step))
+        }
+        "pragma_table_info" => Ok(db.table_schema(table_arg(args)?, None)?.columns.len()),
+        "memory_stats" => Ok(db.tables.len()),
+        "system.queries" => Ok(db.query_log().queries().len()),
+        _ => Err(DbError::TableNotFound(name.to_string())),
+    }
+}
+
+/// The columns `name` produces, without computing any rows. `None` for unknown functions.
+pub fn columns(name: &str) -> Option<Vec<Column>> {
+    let columns = match name.to_lowercase().as_str() {
+        "dual" => vec![],
+        "generate_series" => vec![column("value", "INT")],
+        "pragma_table_info" => vec![
+            column("cid", "INT"),
+            column("name", "TEXT"),
+            column("type", "TEXT"),
+            column("pk", "INT"),
+            column("unique", "INT"),
+            column("comment", "TEXT"),
+        ],
+        "memory_stats" => vec![
+            column("table_name", "TEXT"),
+            column("rows", "INT"),
+            column("columns", "INT"),
+            column("indexes", "INT"),
+            column("approx_bytes", "INT"),
+        ],
+        "system.queries" => vec![
+            column("id", "INT"),
+            column("user", "TEXT"),
+            column("sql", "TEXT"),
+            column("state", "TEXT"),
+            column("started_at", "TIMESTAMP"),
+            column("duration_ms", "INT"),
+            column("error", "TEXT"),
+        ],
+        _ => return None,
+    };
+    Some(columns)
+}
+
+/// An empty table with the columns of the function `name`.
+fn empty(name: &str) -> Table {
+    Table::new(name.into(), columns(name).unwrap_or_default())
+}
+
+fn column(name: &str, data_type: &str) -> Column {
+    Column {
+        name: name.into(),
+        data_type: data_type.into(),
+        is_primary: false,
+        is_unique: false,
+        comment: None,
+        is_masked: false,
+        max_length: None,
+        is_not_null: false,
+    }
+}
+
+fn integer_arg(args: &[Value], i: usize, function: &str) -> Result<i32, DbError> {
+    match args.get(i) {
+        Some(Value::Integer(n)) => Ok(*n),
+        _ => Err(DbError::ParseError(format!(
+            "{} expects an integer for argument {}",
+            function,
+            i + 1
+        ))),
+    }
+}
+
+/// The `start`, `stop` and `step` arguments of `generate_series`.
+fn series(args: &[Value]) -> Result<(i64, i64, i64), DbError> {
+    if !(2..=3).contains(&args.len()) {
+        return Err(DbError::ParseError(
+            "generate_series expects (start, stop[, step])".into(),
+        ));
+    }
+
+    let start = integer_arg(args, 0, "generate_series")?;
+    let stop = integer_arg(args, 1, "generate_series")?;
+    let step = if args.len() == 3 {
+        integer_arg(args, 2, "generate_series")?
+    } else {
+        1
+    };
+    if step == 0 {
+        return Err(DbError::ParseError(
+            "generate_series step cannot be zero".into(),
+        ));
+    }
+    Ok((start as i64, stop as i64, step as i64))
+}
+
+/// How many values `generate_series(start, stop, step)` produces.
+fn series_len(start: i64, stop: i64, step: i64) -> usize {
+    let span = if step > 0 { stop - start } else { start - stop };
+    if span < 0 {
+        0
+    } else {
+        (span / step.abs() + 1) as usize
+    }
+}
+
+fn generate_series(db: &Database, args: &[Value]) -> Result<Table, DbError> {
+    let (start, stop, step) = series(args)?;
+    let max_rows = db
+        .limits
+        .max_rows
+        .map_or(MAX_SERIES_ROWS, |max| max.min(MAX_SERIES_ROWS));
+    let len = series_len(start, stop, step);
+    if len > max_rows {
+        return Err(DbError::StorageFull(format!(
+            "generate_series would produce {} rows, more than the limit of {}",
+            len, max_rows
+        )));
+    }
+    let mut table = empty("generate_series");
+    let mut current = start;
+    while (step > 0 && current <= stop) || (step < 0 && current >= stop) {
+        table.rows.push(vec![Value::Integer(current as i32)]);
+        current += step;
+    }
+    Ok(table)
+}
+
+/// The table named by the argument of `pragma_table_info`.
+fn table_arg(args: &[Value]) -> Result<&str, DbError> {
+    match args.first() {
+        Some(Value::Text(target)) => Ok(target),
+        _ => Err(DbError::ParseError(
+            "pragma_table_info expects a table name".into(),
+        )),
+    }
+}
+
+fn pragma_table_info(db: &Database, args: &[Value]) -> Result<Table, DbError> {
+    let source = db.table_schema(table_arg(args)?, None)?;
+
+    let mut table = empty("pragma_table_info");
+    for (i, col) in source.columns.iter().enumerate() {
+        table.rows.push(vec![
+            Value::Integer(i as i32),
+            Value::Text(col.name.clone()),
+            Value::Text(col.declared_type()),
+            Value::Integer(col.is_primary as i32),
+            Value::Integer(col.is_unique as i32),
+            col.comment.clone().map_or(Value::Null, Value::Text),
+        ]);
+    }
+    Ok(table)
+}
+
+fn memory_stats(db: &Database) -> Result<Table, DbError> {
+    let mut table = empty("memory_stats");
+
+    let mut names: Vec<&String> = db.tables.keys().collect();
+    names.sort();
+    for name in names {
+        let source = &db.tables[name];
+        let data_bytes = source.approx_bytes;
+        let index_bytes: usize = source
+            .indexes
+            .values()
+            .flatten()
+            .map(Value::approximate_size)
+            .sum();
+
+        table.rows.push(vec![
+            Value::Text(name.clone()),
+            Value::Integer(source.rows.len() as i32),
+            Value::Integer(source.columns.len() as i32),
+            Value::Integer(source.indexes.len() as i32),
+            Value::Integer((data_bytes + index_bytes).min(i32::MAX as usize) as i32),
+        ]);
+    }
+    Ok(table)
+}
+
+fn system_queries(db: &Database, session: &Session) -> Result<Table, DbError> {
+    let mut table = empty("system.queries");
+    let int = |n: u128| Value::Integer(n.min(i32::MAX as u128) as i32);
+    let visible = db
+        .query_log()
+        .queries()
+        .into_iter()
+        .filter(|info| session.admin || info.user == session.user);
+    for info in visible {
+        table.rows.push(vec![
+            int(info.id as u128),
+            Value::Text(info.user),
+            Value::Text(info.sql),
+            Value::Text(info.state.as_str().into()),
+            Timestamp::from_secs(info.started_at as i64).map_or(Value::Null, Value::Timestamp),
+            int(info.duration.as_millis()),
+            info.error.map_or(Value::Null, Value::Text),
+        ]);
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database, DbError,
+        engine::{ExecutionResult, Value, session::Session},
+        parser::parse,
+    };
+
+    fn query(db: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+        match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { rows, .. } => rows,
+            _ => panic!("expected data"),
+        }
+    }
+
+    #[test]
+    fn test_table_functions() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE users (id INT PRIMARY, name TEXT)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO users VALUES (1, 'Alice')").unwrap())
+            .unwrap();
+
+        let series = query(&mut db, "SELECT * FROM generate_series(1, 1000)");
+        assert_eq!(series.len(), 1000);
+        assert_eq!(series[999], vec![Value::Integer(1000)]);
+        assert_eq!(
+            query(&mut db, "SELECT value FROM generate_series(10, 1, -3)").len(),
+            4
+        );
+        let huge = parse("SELECT * FROM generate_series(1, 2000000000)").unwrap();
+        assert!(matches!(db.execute(huge), Err(DbError::StorageFull(_))));
+        db.limits.max_rows = Some(100);
+        let series = parse("SELECT * FROM generate_series(1, 101)").unwrap();
+        assert!(matches!(db.execute(series), Err(DbError::StorageFull(_))));
+        db.limits.max_rows = None;
+        // Arguments are separated by exactly one comma.
+        for sql in [
+            "SELECT * FROM generate_series(1,,3)",
+            "SELECT * FROM generate_series(1 3)",
+            "SELECT * FROM generate_series(1,)",
+            "SELECT * FROM generate_series(,1)",
+            "SELECT * FROM generate_series(1, 3",
+        ] {
+            assert!(parse(sql).is_err(), "{}", sql);
+        }
+
+        let info = query(&mut db, "SELECT name, pk FROM pragma_table_info('users')");
+        assert_eq!(
+            info,
+            vec![
+                vec![Value::Text("id".into()), Value::Integer(1)],
+                vec![Value::Text("name".into()), Value::Integer(0)],
+            ]
+        );
+
+        let stats = query(&mut db, "SELECT table_name, rows FROM memory_stats");
+        assert_eq!(
+            stats,
+            vec![vec![Value::Text("users".into()), Value::Integer(1)]]
+        );
+    }
+
+    #[test]
+    fn test_system_queries() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE users (id INT)").unwrap())
+            .unwrap();
+        assert!(db.execute(parse("SELECT * FROM missing").unwrap()).is_err());
+
+        // The query reading the log is still running, so it comes first.
+        let text = |s: &str| Value::Text(s.into());
+        let rows = query(&mut db, "SELECT id, sql, state, error FROM system.queries");
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Integer(3),
+                    text("SELECT id, sql, state, error FROM system.queries"),
+                    text("running"),
+                    Value::Null,
+                ],
+                vec![
+                    Value::Integer(2),
+                    text("SELECT * FROM missing"),
+                    text("failed"),
+                    text("Table 'missing' not found"),
+                ],
+                vec![
+                    Value::Integer(1),
+                    text("CREATE TABLE users ..."),
+                    text("finished"),
+                    Value::Null,
+                ],
+            ]
+        );
+        let finished = query(
+            &mut db,
+            "SELECT user FROM system.queries WHERE state = 'finished'",
+        );
+        assert_eq!(finished.len(), 2);
+        assert_eq!(finished[0], vec![text("anonymous")]);
+
+        // Other users' statements are only listed for admin sessions.
+        let mut ada = Session::new("ada");
+        db.execute_with_session(parse("SELECT 1").unwrap(), &mut ada)
+            .unwrap();
+        let users = "SELECT DISTINCT user FROM system.queries";
+        assert_eq!(query(&mut db, users), vec![vec![text("anonymous")]]);
+        let mut admin = Session::new("root");
+        admin.admin = true;
+        let Ok(ExecutionResult::Data { rows, .. }) =
+            db.execute_with_session(parse(users).unwrap(), &mut admin)
+        else {
+            panic!("expected data");
+        };
+        assert!(rows.contains(&vec![text("ada")]));
+        let started = query(&mut db, "SELECT started_at FROM system.queries");
+        assert!(matches!(started[0][0], Value::Timestamp(_)));
+    }
+}
@@ -0,0 +1,256 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Value, relation::Relation, session::Session},
+    parser::SelectStatement,
+};
+
+/// Memory used by the intermediate results of a single query. Sizes are estimates based on
+/// `Value::approximate_size`, like the rest of the engine's accounting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryMemory {
+    /// Rows materialized across all stages, counting a row once per stage that copies it.
+    pub rows_materialized: usize,
+    /// Bytes of every intermediate result, summed over the stages.
+    pub bytes_materialized: usize,
+    /// Bytes of join inputs read from the right-hand tables plus the joined output.
+    pub join_bytes: usize,
+    /// Bytes of the relations that had to be sorted.
+    pub sort_bytes: usize,
+    /// Largest single intermediate result.
+    pub peak_bytes: usize,
+}
+
+/// An index consulted while answering a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexLookup {
+    pub table: String,
+    pub column: String,
+    /// Rows the lookup handed to the rest of the query.
+    pub rows: usize,
+}
+
+/// The work of one operator of a profiled query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorStats {
+    /// Named like the operators of `EXPLAIN`.
+    pub operator: String,
+    pub detail: String,
+    /// Rows the operator produced.
+    pub rows: usize,
+    /// Approximate size of those rows, as `QueryMemory` counts it.
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+/// What a single query did along the way, collected while it runs and folded into the
+/// database's stats afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct QueryTrace {
+    pub memory: QueryMemory,
+    pub index_lookups: Vec<IndexLookup>,
+    /// Whether to fill `operators`, which costs a clock read per operator.
+    pub profile: bool,
+    /// The operators in the order they finished, when profiling.
+    pub operators: Vec<OperatorStats>,
+    /// When the query has to finish by, and the statement timeout that set it.
+    pub deadline: Option<(Instant, Duration)>,
+}
+
+impl QueryTrace {
+    /// Records an operator that started at `started` and produced `output`. `detail` is only
+    /// built when profiling. Fails once the deadline has passed, so queries are stopped
+    /// between operators.
+    pub(crate) fn record(
+        &mut self,
+        operator: &str,
+        detail: impl FnOnce() -> String,
+        output: &Relation,
+        started: Instant,
+    ) -> Result<(), DbError> {
+        if let Some((deadline, timeout)) = self.deadline
+            && Instant::now() > deadline
+        {
+            return Err(DbError::StatementTimeout(timeout));
+        }
+        if self.profile {
+            self.operators.push(OperatorStats {
+                operator: operator.to_string(),
+                detail: detail(),
+                rows: output.rows.len(),
+                bytes: output.approximate_size(),
+                elapsed: started.elapsed(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl QueryMemory {
+    /// Accounts for a relation produced by one stage and returns its size in bytes.
+    pub(crate) fn materialize(&mut self, relation: &Relation) -> usize {
+        let bytes = relation.approximate_size();
+        self.rows_materialized += relation.rows.len();
+        self.bytes_materialized += bytes;
+        self.peak_bytes = self.peak_bytes.max(bytes);
+        bytes
+    }
+}
+
+impl Database {
+    /// Runs a SELECT and records the memory its intermediate results used and the indexes
+    /// it consulted in the stats.
+    pub(crate) fn run_select(
+        &mut self,
+        select: SelectStatement,
+        session: &Session,
+    ) -> Result<ExecutionResult, DbError> {
+        let mut trace = QueryTrace {
+            deadline: self.deadline(),
+            ..QueryTrace::default()
+        };
+        let result = self.handle_select(select, session, &mut trace);
+        self.record_trace(&mut trace);
+        result
+    }
+
+    /// Runs a SELECT like `run_select` but returns, instead of its rows, one row per
+    /// operator with the rows it produced, their approximate size in bytes and the
+    /// microseconds it took, in execution order.
+    pub(crate) fn explain_analyze(
+        &mut self,
+        select: SelectStatement,
+        session: &Session,
+    ) -> Result<ExecutionResult, DbError> {
+        let mut trace = QueryTrace {
+            profile: true,
+            deadline: self.deadline(),
+            ..QueryTrace::default()
+        };
+        let result = self.handle_select(select, session, &mut trace);
+        self.record_trace(&mut trace);
+        result?;
+
+        let rows = trace
+            .operators
+            .into_iter()
+            .map(|op| {
+                vec![
+                    Value::Text(op.operator),
+                    Value::Text(op.detail),
+                    Value::Integer(op.rows.min(i32::MAX as usize) as i32),
+                    Value::Integer(op.bytes.min(i32::MAX as usize) as i32),
+                    Value::Integer(op.elapsed.as_micros().min(i32::MAX as u128) as i32),
+                ]
+            })
+            .collect();
+        Ok(ExecutionResult::listing(
+            &[
+                ("operator", "TEXT"),
+                ("detail", "TEXT"),
+                ("rows", "INT"),
+                ("bytes", "INT"),
+                ("time_us", "INT"),
+            ],
+            rows,
+        ))
+    }
+
+    /// The deadline of a query starting now under `statement_timeout`.
+    fn deadline(&self) -> Option<(Instant, Duration)> {
+        self.statement_timeout
+            .map(|timeout| (Instant::now() + timeout, timeout))
+    }
+
+    fn record_trace(&mut self, trace: &mut QueryTrace) {
+        let memory = trace.memory;
+        self.stats.last_query_memory = memory;
+        self.stats.peak_query_bytes = self.stats.peak_query_bytes.max(memory.peak_bytes);
+        for lookup in trace.index_lookups.drain(..) {
+            self.record_index_lookup(lookup);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        Database, DbError,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_query_memory() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE a (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE b (id INT PRIMARY, a_id INT)",
+            "INSERT INTO a VALUES (1, 'x')",
+            "INSERT INTO a VALUES (2, 'y')",
+            "INSERT INTO b VALUES (10, 1)",
+            "INSERT INTO b VALUES (20, 1)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+
+        db.execute(parse("SELECT * FROM a WHERE id = 2").unwrap())
+            .unwrap();
+        let point = db.stats.last_query_memory;
+        assert_eq!(point.join_bytes, 0);
+        assert_eq!(point.sort_bytes, 0);
+
+        db.execute(parse("SELECT * FROM a JOIN b ON id = a_id ORDER BY b.id").unwrap())
+            .unwrap();
+        let joined = db.stats.last_query_memory;
+        assert!(joined.join_bytes > 0);
+        assert!(joined.sort_bytes > 0);
+        assert!(joined.rows_materialized > point.rows_materialized);
+        assert!(joined.peak_bytes <= joined.bytes_materialized);
+        assert_eq!(db.stats.peak_query_bytes, joined.peak_bytes);
+
+        db.statement_timeout = Some(Duration::ZERO);
+        let slow = db.execute(parse("SELECT * FROM a JOIN b ON id = a_id").unwrap());
+        assert!(matches!(slow, Err(DbError::StatementTimeout(_))));
+    }
+
+    #[test]
+    fn test_explain_analyze() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(parse(sql).unwrap());
+        run("CREATE TABLE a (id INT PRIMARY, name TEXT)").unwrap();
+        run("CREATE TABLE b (id INT PRIMARY, a_id INT)").unwrap();
+        run("INSERT INTO a VALUES (1, 'x'), (2, 'y'), (3, 'z')").unwrap();
+        run("INSERT INTO b VALUES (10, 1), (20, 1), (30, 2)").unwrap();
+
+        let sql = "EXPLAIN ANALYZE SELECT name FROM a JOIN b ON a.id = a_id \
+                   WHERE b.id > 10 ORDER BY name LIMIT 1";
+        let Ok(ExecutionResult::Data { headers, rows, .. }) = run(sql) else {
+            panic!("expected data");
+        };
+        assert_eq!(headers, ["operator", "detail", "rows", "bytes", "time_us"]);
+        let stages: Vec<(&Value, &Value)> = rows.iter().map(|r| (&r[0], &r[2])).collect();
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(
+            stages,
+            [
+                (&text("Full Scan"), &Value::Integer(3)),
+                (&text("Full Scan"), &Value::Integer(3)),
+                (&text("Filter"), &Value::Integer(2)),
+                (&text("Nested Loop Join"), &Value::Integer(2)),
+                (&text("Sort"), &Value::Integer(2)),
+                (&text("Project"), &Value::Integer(2)),
+                (&text("Limit"), &Value::Integer(1)),
+            ]
+        );
+        assert_eq!(rows[3][1], text("a.id = a_id"));
+        // The projection keeps one column of the joined rows, the limit one of those rows.
+        let bytes: Vec<&Value> = rows.iter().map(|r| &r[3]).collect();
+        assert!(bytes[3] > bytes[5] && bytes[5] > bytes[6] && *bytes[6] > Value::Integer(0));
+
+        assert!(parse("EXPLAIN ANALYZE DROP TABLE a").is_err());
+    }
+}
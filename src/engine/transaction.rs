@@ -0,0 +1,361 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::{
+    Database, DbError,
+    engine::{
+        ExecutionResult, Table,
+        schedules::Schedule,
+        session::{ExecutionContext, Session},
+        triggers::Trigger,
+    },
+    parser::Statement,
+};
+
+/// The most savepoints a transaction holds at once. Each one is a copy of the database.
+pub const MAX_SAVEPOINTS: usize = 32;
+
+/// The persisted state of a database at one point in time, to go back to when a group of
+/// statements fails halfway.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    tables: HashMap<String, Table>,
+    saved_queries: BTreeMap<String, String>,
+    schedules: BTreeMap<String, Schedule>,
+    triggers: BTreeMap<String, Trigger>,
+    dirty: bool,
+    transaction_owner: Option<u64>,
+}
+
+impl Database {
+    /// Copies the tables, saved queries, schedules and triggers. The copy is as large as the
+    /// data.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tables: self.tables.clone(),
+            saved_queries: self.saved_queries.clone(),
+            schedules: self.schedules.clone(),
+            triggers: self.triggers.clone(),
+            dirty: self.dirty,
+            transaction_owner: self.transaction_owner,
+        }
+    }
+
+    /// Puts back the state saved by `snapshot`, undoing every change made since.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.tables = snapshot.tables;
+        self.saved_queries = snapshot.saved_queries;
+        self.schedules = snapshot.schedules;
+        self.triggers = snapshot.triggers;
+        self.dirty = snapshot.dirty;
+        self.transaction_owner = snapshot.transaction_owner;
+    }
+
+    /// Runs `f` as a single unit: if it fails, every change it made is undone.
+    pub fn atomically<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Database) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let snapshot = self.snapshot();
+        let result = f(self);
+        if result.is_err() {
+            self.restore(snapshot);
+        }
+        result
+    }
+
+    /// Fails with `DatabaseLocked` when `statement` would write while another session's
+    /// transaction is open. The state rolled back to was copied when that transaction began,
+    /// so letting anyone else write meanwhile would lose their changes.
+    pub(crate) fn check_unlocked(
+        &self,
+        session: Option<&Session>,
+        statement: Option<&Statement>,
+    ) -> Result<(), DbError> {
+        let Some(owner) = self.transaction_owner else {
+            return Ok(());
+        };
+        if session.is_some_and(|s| s.id == owner) || statement.is_some_and(Statement::is_read_only)
+        {
+            return Ok(());
+        }
+        Err(DbError::DatabaseLocked)
+    }
+
+    /// Opens a transaction in `session`. Until it is committed or rolled back, only `session`
+    /// may change the database.
+    pub fn begin(&mut self, session: &mut Session) -> Result<(), DbError> {
+        if session.transaction.is_some() {
+            return Err(DbError::TransactionAlreadyOpen);
+        }
+        session.transaction = Some(Arc::new(self.snapshot()));
+        self.transaction_owner = Some(session.id);
+        Ok(())
+    }
+
+    /// Ends the transaction of `session`, keeping its changes.
+    pub fn commit(&mut self, session: &mut Session) -> Result<(), DbError> {
+        session.transaction.take().ok_or(DbError::NoTransaction)?;
+        session.savepoints.clear();
+        self.transaction_owner = None;
+        Ok(())
+    }
+
+    /// Ends the transaction of `session`, undoing every change made since it began.
+    pub fn rollback(&mut self, session: &mut Session) -> Result<(), DbError> {
+        let snapshot = session.transaction.take().ok_or(DbError::NoTransaction)?;
+        session.savepoints.clear();
+        self.restore(Snapshot::clone(&snapshot));
+        // The state rolled back from may have been flushed already.
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Ends `session` for good, rolling back the transaction it left open. That releases the
+    /// database to the other sessions, and the copies held by its savepoints.
+    pub fn end_session(&mut self, mut session: Session) {
+        if session.transaction.is_some() {
+            let _ = self.rollback(&mut session);
+        }
+    }
+
+    /// Takes a savepoint named `name` in the transaction of `session`. Each savepoint holds a
+    /// copy of the database, so long transactions should release the ones they no longer
+    /// need; past `MAX_SAVEPOINTS` new ones are refused.
+    pub fn savepoint(&self, session: &mut Session, name: String) -> Result<(), DbError> {
+        if session.transaction.is_none() {
+            return Err(DbError::NoTransaction);
+        }
+        if session.savepoints.len() >= MAX_SAVEPOINTS {
+            return Err(DbError::StorageFull(format!(
+                "savepoint limit of {} reached",
+                MAX_SAVEPOINTS
+            )));
+        }
+        session.savepoints.push((name, Arc::new(self.snapshot())));
+        Ok(())
+    }
+
+    /// Undoes every change made since the latest savepoint named `name`. The savepoint stays
+    /// and can be rolled back to again; the ones taken after it are released.
+    pub fn rollback_to_savepoint(
+        &mut self,
+        session: &mut Session,
+        name: &str,
+    ) -> Result<(), DbError> {
+        let i = Self::find_savepoint(session, name)?;
+        session.savepoints.truncate(i + 1);
+        self.restore(Snapshot::clone(&session.savepoints[i].1));
+        // The state rolled back from may have been flushed already.
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Forgets the latest savepoint named `name` and the ones taken after it, keeping the
+    /// changes made since.
+    pub fn release_savepoint(&self, session: &mut Session, name: &str) -> Result<(), DbError> {
+        let i = Self::find_savepoint(session, name)?;
+        session.savepoints.truncate(i);
+        Ok(())
+    }
+
+    fn find_savepoint(session: &Session, name: &str) -> Result<usize, DbError> {
+        session
+            .savepoints
+            .iter()
+            .rposition(|(saved, _)| saved == name)
+            .ok_or_else(|| DbError::SavepointNotFound(name.to_string()))
+    }
+
+    /// Runs `statement` like `execute_in` and undoes everything it did, returning the result
+    /// it would have had: the same checks and constraint violations, for the same principal
+    /// and session, without any change to the data, the stats or the session's variables and
+    /// transaction. Like `snapshot`, this copies the database first.
+    pub fn validate(
+        &mut self,
+        statement: Statement,
+        context: &mut ExecutionContext,
+    ) -> Result<ExecutionResult, DbError> {
+        let (snapshot, stats) = (self.snapshot(), self.stats.clone());
+        let session = context.session().clone();
+        let result = self.execute_in(statement, context);
+        self.restore(snapshot);
+        self.stats = stats;
+        *context.session() = session;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database, DbError,
+        engine::{
+            ExecutionResult, Value,
+            session::{ExecutionContext, Session},
+            transaction::MAX_SAVEPOINTS,
+        },
+        parser::parse,
+        storage::Storage,
+    };
+
+    #[test]
+    fn test_atomically() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id INT PRIMARY KEY)").unwrap())
+            .unwrap();
+        db.flush(&Storage::Memory).unwrap();
+
+        let failed: Result<(), DbError> = db.atomically(|db| {
+            db.execute(parse("INSERT INTO t VALUES (1)").unwrap())?;
+            db.execute(parse("CREATE TABLE u (id INT)").unwrap())?;
+            db.execute(parse("INSERT INTO t VALUES (1)").unwrap())?;
+            Ok(())
+        });
+        assert!(matches!(failed, Err(DbError::UniqueViolation(_))));
+        assert!(db.tables["t"].rows.is_empty());
+        assert!(!db.tables.contains_key("u"));
+        assert!(!db.is_dirty());
+
+        db.atomically(|db| db.execute(parse("INSERT INTO t VALUES (2)").unwrap()))
+            .unwrap();
+        assert_eq!(db.tables["t"].rows.len(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE t (id INT PRIMARY KEY)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO t VALUES (1)").unwrap())
+            .unwrap();
+        let executed = db.stats.queries_executed;
+        let mut session = Session::default();
+        session.variables.insert("id".into(), Value::Integer(2));
+        let mut context = ExecutionContext::new("ada", &mut session);
+        let mut validate = |sql: &str| db.validate(parse(sql).unwrap(), &mut context);
+
+        let ok = validate("INSERT INTO t VALUES (2), (3)");
+        assert!(matches!(ok, Ok(ExecutionResult::Message(_))));
+        assert!(matches!(
+            validate("INSERT INTO t VALUES (2), (1)"),
+            Err(DbError::UniqueViolation(_))
+        ));
+        assert!(matches!(
+            validate("DROP TABLE missing"),
+            Err(DbError::TableNotFound(_))
+        ));
+        validate("DROP TABLE t").unwrap();
+        // The dry run sees the principal and the session's variables, and keeps them.
+        let Ok(ExecutionResult::Data { rows, .. }) = validate("SELECT CURRENT_USER(), @id FROM t")
+        else {
+            panic!("expected data");
+        };
+        assert_eq!(rows, [[Value::Text("ada".into()), Value::Integer(2)]]);
+        validate("SET @id = 3").unwrap();
+        validate("BEGIN").unwrap();
+        assert_eq!(session.variable("id"), Value::Integer(2));
+        assert!(session.transaction.is_none());
+        assert_eq!(db.transaction_owner, None);
+        assert_eq!(db.tables["t"].rows.len(), 1);
+        assert_eq!(db.stats.queries_executed, executed);
+    }
+
+    #[test]
+    fn test_savepoints() {
+        let mut db = Database::new();
+        let mut session = Session::default();
+        let mut run = |sql: &str| db.execute_with_session(parse(sql).unwrap(), &mut session);
+        run("CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        assert!(matches!(run("SAVEPOINT a"), Err(DbError::NoTransaction)));
+        run("BEGIN").unwrap();
+        run("SAVEPOINT a").unwrap();
+        run("INSERT INTO t VALUES (1)").unwrap();
+        run("SAVEPOINT b").unwrap();
+        run("INSERT INTO t VALUES (2)").unwrap();
+        run("ROLLBACK TO SAVEPOINT b").unwrap();
+        run("INSERT INTO t VALUES (3)").unwrap();
+        // `b` is still there after rolling back to it.
+        run("ROLLBACK TO b").unwrap();
+        assert!(matches!(
+            run("RELEASE SAVEPOINT missing"),
+            Err(DbError::SavepointNotFound(_))
+        ));
+        run("RELEASE a").unwrap();
+        assert!(matches!(
+            run("ROLLBACK TO b"),
+            Err(DbError::SavepointNotFound(_))
+        ));
+        assert_eq!(db.tables["t"].rows, [[Value::Integer(1)]]);
+        assert!(session.savepoints.is_empty());
+        assert!(parse("ROLLBACK TO").is_err());
+
+        for _ in 0..MAX_SAVEPOINTS {
+            db.execute_with_session(parse("SAVEPOINT s").unwrap(), &mut session)
+                .unwrap();
+        }
+        let one_more = db.execute_with_session(parse("SAVEPOINT s").unwrap(), &mut session);
+        assert!(matches!(one_more, Err(DbError::StorageFull(_))));
+    }
+
+    #[test]
+    fn test_transactions() {
+        let mut db = Database::new();
+        let (mut a, mut b) = (Session::default(), Session::default());
+        let mut run = |sql: &str, session: &mut Session| {
+            db.execute_with_session(parse(sql).unwrap(), session)
+        };
+        run("CREATE TABLE t (id INT)", &mut a).unwrap();
+
+        run("BEGIN", &mut a).unwrap();
+        assert!(matches!(
+            run("BEGIN TRANSACTION", &mut a),
+            Err(DbError::TransactionAlreadyOpen)
+        ));
+        run("INSERT INTO t VALUES (1)", &mut a).unwrap();
+        run("SAVEPOINT sp", &mut a).unwrap();
+        // Other sessions read the database, but their writes would be lost by a rollback.
+        assert!(matches!(
+            run("INSERT INTO t VALUES (42)", &mut b),
+            Err(DbError::DatabaseLocked)
+        ));
+        assert!(matches!(run("BEGIN", &mut b), Err(DbError::DatabaseLocked)));
+        assert!(run("SELECT * FROM t", &mut b).is_ok());
+        assert!(matches!(
+            run("COMMIT", &mut b),
+            Err(DbError::DatabaseLocked)
+        ));
+        run("ROLLBACK TO SAVEPOINT sp", &mut a).unwrap();
+        run("ROLLBACK", &mut a).unwrap();
+        assert!(db.tables["t"].rows.is_empty());
+        assert!(a.savepoints.is_empty());
+
+        // Once the transaction ends, others write again, and a commit keeps its changes.
+        let mut run = |sql: &str, session: &mut Session| {
+            db.execute_with_session(parse(sql).unwrap(), session)
+        };
+        run("INSERT INTO t VALUES (42)", &mut b).unwrap();
+        run("BEGIN", &mut a).unwrap();
+        run("INSERT INTO t VALUES (1)", &mut a).unwrap();
+        run("COMMIT", &mut a).unwrap();
+        assert!(matches!(run("COMMIT", &mut a), Err(DbError::NoTransaction)));
+        assert!(matches!(
+            run("ROLLBACK", &mut b),
+            Err(DbError::NoTransaction)
+        ));
+        assert_eq!(
+            db.tables["t"].rows,
+            [[Value::Integer(42)], [Value::Integer(1)]]
+        );
+        db.execute_with_session(parse("BEGIN").unwrap(), &mut a)
+            .unwrap();
+        let copied = db.copy_rows("ada", "t", vec![vec![Value::Integer(2)]]);
+        assert!(matches!(copied, Err(DbError::DatabaseLocked)));
+        db.execute_with_session(parse("ROLLBACK").unwrap(), &mut a)
+            .unwrap();
+        let copied = db.copy_rows("ada", "t", vec![vec![Value::Integer(2)]]);
+        assert_eq!(copied.unwrap(), 1);
+    }
+}
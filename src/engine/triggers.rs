@@ -0,0 +1,227 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Database, DbError,
+    engine::{ExecutionResult, Value, session::Session},
+    parser::parse,
+};
+
+/// Triggers may fire other triggers, down to this many levels.
+const MAX_TRIGGER_DEPTH: usize = 16;
+
+/// The change to a table that fires a trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriggerEvent {
+    /// Rows inserted by `INSERT` or `COPY`.
+    Insert,
+    /// Rows rewritten by `INSERT ... ON CONFLICT DO UPDATE`.
+    Update,
+    /// Rows deleted. No statement deletes rows yet, so `create_trigger` refuses these.
+    Delete,
+}
+
+impl fmt::Display for TriggerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerEvent::Insert => write!(f, "INSERT"),
+            TriggerEvent::Update => write!(f, "UPDATE"),
+            TriggerEvent::Delete => write!(f, "DELETE"),
+        }
+    }
+}
+
+/// `CREATE TRIGGER name AFTER <event> ON table AS <statement>`: the statement runs once after
+/// every statement that changes rows of the table that way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub table: String,
+    pub event: TriggerEvent,
+    pub sql: String,
+}
+
+impl Database {
+    /// Registers a trigger on a stored table.
+    pub fn create_trigger(&mut self, name: String, trigger: Trigger) -> Result<(), DbError> {
+        if self.triggers.contains_key(&name) {
+            return Err(DbError::TriggerAlreadyExists(name));
+        }
+        if trigger.event == TriggerEvent::Delete {
+            return Err(DbError::ParseError(
+                "AFTER DELETE triggers are not supported; rows are never deleted".into(),
+            ));
+        }
+        if !self.tables.contains_key(&trigger.table) {
+            return Err(DbError::TableNotFound(trigger.table));
+        }
+        parse(&trigger.sql)?;
+        self.triggers.insert(name, trigger);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub fn drop_trigger(&mut self, name: &str) -> Result<(), DbError> {
+        self.triggers
+            .remove(name)
+            .ok_or_else(|| DbError::TriggerNotFound(name.to_string()))?;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Whether a change to `table_name` fires a trigger.
+    pub(crate) fn has_triggers(&self, table_name: &str) -> bool {
+        self.triggers.values().any(|t| t.table == table_name)
+    }
+
+    /// Runs the statements of the triggers on `table_name` for each of `events`, in the order
+    /// of their names. The caller undoes its change when this fails.
+    pub(crate) fn fire_triggers(
+        &mut self,
+        table_name: &str,
+        events: &[TriggerEvent],
+        session: &mut Session,
+    ) -> Result<(), DbError> {
+        let fired: Vec<(String, String)> = self
+            .triggers
+            .iter()
+            .filter(|(_, t)| t.table == table_name && events.contains(&t.event))
+            .map(|(name, t)| (name.clone(), t.sql.clone()))
+            .collect();
+        if fired.is_empty() {
+            return Ok(());
+        }
+        if self.trigger_depth == MAX_TRIGGER_DEPTH {
+            return Err(DbError::TriggerFailed(
+                fired[0].0.clone(),
+                format!("triggers nested more than {} deep", MAX_TRIGGER_DEPTH),
+            ));
+        }
+        self.trigger_depth += 1;
+        let mut result = Ok(());
+        for (name, sql) in fired {
+            let statement = parse(&sql).map_err(DbError::from);
+            result = statement
+                .and_then(|statement| self.execute_logged(statement, session, sql))
+                .map(|_| ())
+                .map_err(|e| match e {
+                    // Keep the innermost trigger that failed.
+                    e @ DbError::TriggerFailed(..) => e,
+                    e => DbError::TriggerFailed(name, e.to_string()),
+                });
+            if result.is_err() {
+                break;
+            }
+        }
+        self.trigger_depth -= 1;
+        result
+    }
+
+    /// Follows the rename of the table `from` to `to` in the triggers on it.
+    pub(crate) fn rename_triggered_table(&mut self, from: &str, to: &str) {
+        for trigger in self.triggers.values_mut() {
+            if trigger.table == from {
+                trigger.table = to.to_string();
+            }
+        }
+    }
+
+    /// Lists triggers as a `name | table | event | sql` result set.
+    pub fn list_triggers(&self) -> ExecutionResult {
+        let rows = self
+            .triggers
+            .iter()
+            .map(|(name, t)| {
+                vec![
+                    Value::Text(name.clone()),
+                    Value::Text(t.table.clone()),
+                    Value::Text(t.event.to_string()),
+                    Value::Text(t.sql.clone()),
+                ]
+            })
+            .collect();
+        ExecutionResult::listing(
+            &[
+                ("name", "TEXT"),
+                ("table", "TEXT"),
+                ("event", "TEXT"),
+                ("sql", "TEXT"),
+            ],
+            rows,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(db: &mut Database, sql: &str) -> Result<(), DbError> {
+        db.execute(parse(sql).unwrap()).map(|_| ())
+    }
+
+    #[test]
+    fn test_triggers() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT)",
+            "CREATE TABLE audit (event TEXT)",
+            "CREATE TRIGGER users_insert AFTER INSERT ON users AS INSERT INTO audit VALUES ('insert')",
+            "CREATE TRIGGER users_update AFTER UPDATE ON users AS INSERT INTO audit VALUES ('update')",
+        ] {
+            run(&mut db, sql).unwrap();
+        }
+        let audit = |db: &Database| db.tables["audit"].rows.clone();
+        let text = |s: &str| vec![Value::Text(s.into())];
+        let on_delete = "CREATE TRIGGER users_delete AFTER DELETE ON users AS SELECT 1";
+        assert!(matches!(
+            run(&mut db, on_delete),
+            Err(DbError::ParseError(_))
+        ));
+
+        // Once per statement, not per row.
+        run(&mut db, "INSERT INTO users VALUES (1, 'ada'), (2, 'bob')").unwrap();
+        assert_eq!(audit(&db), [text("insert")]);
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (1, 'x') ON CONFLICT (id) DO UPDATE SET name = 'ann'",
+        )
+        .unwrap();
+        assert_eq!(audit(&db), [text("insert"), text("update")]);
+        // Nothing changed, nothing fires.
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (1, 'x') ON CONFLICT DO NOTHING",
+        )
+        .unwrap();
+        assert_eq!(audit(&db).len(), 2);
+
+        // A failing trigger undoes the statement that fired it.
+        run(&mut db, "DROP TABLE audit").unwrap();
+        let err = run(&mut db, "INSERT INTO users VALUES (3, 'cy')").unwrap_err();
+        assert!(matches!(err, DbError::TriggerFailed(ref name, _) if name == "users_insert"));
+        assert_eq!(db.tables["users"].rows.len(), 2);
+
+        // Triggers firing themselves are cut off.
+        run(&mut db, "CREATE TABLE log (n INT)").unwrap();
+        run(
+            &mut db,
+            "CREATE TRIGGER again AFTER INSERT ON log AS INSERT INTO log VALUES (1)",
+        )
+        .unwrap();
+        assert!(run(&mut db, "INSERT INTO log VALUES (0)").is_err());
+        assert!(db.tables["log"].rows.is_empty());
+
+        run(&mut db, "ALTER TABLE log RENAME TO entries").unwrap();
+        assert_eq!(db.triggers["again"].table, "entries");
+        run(&mut db, "DROP TABLE entries").unwrap();
+        assert_eq!(db.triggers.len(), 2);
+        assert!(
+            run(
+                &mut db,
+                "CREATE TRIGGER t AFTER INSERT ON missing AS SHOW STATUS"
+            )
+            .is_err()
+        );
+    }
+}
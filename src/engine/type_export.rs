@@ -0,0 +1,182 @@
+use serde_json::{Value as Json, json};
+
+use crate::{
+    Database,
+    engine::{Column, Table, decimal::DecimalType},
+};
+
+/// The JSON type of the values stored in `column`. Columns are nullable unless PRIMARY or
+/// NOT NULL, and masked values read as text.
+fn json_types(column: &Column) -> Vec<&'static str> {
+    let mut types = match column.data_type.as_str() {
+        "INT" | "INTEGER" | "BIGINT" => vec!["integer"],
+        "REAL" => vec!["number"],
+        t if DecimalType::parse(t).is_some() => vec!["number"],
+        _ => vec!["string"],
+    };
+    if column.is_masked && !types.contains(&"string") {
+        types.push("string");
+    }
+    if !column.is_primary && !column.is_not_null {
+        types.push("null");
+    }
+    types
+}
+
+/// The TypeScript type of the values stored in `column`, following `json_types`.
+fn typescript_type(column: &Column) -> String {
+    let types: Vec<&str> = json_types(column)
+        .into_iter()
+        .map(|t| match t {
+            "integer" => "number",
+            other => other,
+        })
+        .collect();
+    types.join(" | ")
+}
+
+/// `snake_case` table names become `PascalCase` interface names.
+fn interface_name(table: &str) -> String {
+    let mut name: String = table
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// A property name, quoted unless it is a plain identifier.
+fn property_name(column: &str) -> String {
+    let plain = column.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && column
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if plain {
+        column.to_string()
+    } else {
+        Json::from(column).to_string()
+    }
+}
+
+fn doc_comment(comment: &Option<String>, indent: &str) -> String {
+    match comment {
+        Some(text) => format!("{}/** {} */\n", indent, text.replace("*/", "* /")),
+        None => String::new(),
+    }
+}
+
+impl Database {
+    fn sorted_tables(&self) -> Vec<&Table> {
+        let mut tables: Vec<&Table> = self.tables.values().collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        tables
+    }
+
+    /// A JSON Schema (draft 2020-12) describing a row of every stored table as an object, one
+    /// definition per table under `$defs`. Comments become descriptions.
+    pub fn json_schema(&self) -> Json {
+        let defs: serde_json::Map<String, Json> = self
+            .sorted_tables()
+            .into_iter()
+            .map(|table| {
+                let properties: serde_json::Map<String, Json> = table
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        let mut property = json!({ "type": json_types(column) });
+                        if let Some(comment) = &column.comment {
+                            property["description"] = json!(comment);
+                        }
+                        (column.name.clone(), property)
+                    })
+                    .collect();
+                let required: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+                let mut definition = json!({
+                    "type": "object",
+                    "properties": properties,
+                    "required": required,
+                    "additionalProperties": false,
+                });
+                if let Some(comment) = &table.comment {
+                    definition["description"] = json!(comment);
+                }
+                (table.name.clone(), definition)
+            })
+            .collect();
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$defs": defs,
+        })
+    }
+
+    /// TypeScript interfaces for a row of every stored table, named after the table in
+    /// `PascalCase`. Comments become doc comments.
+    pub fn typescript(&self) -> String {
+        let mut out = String::new();
+        for table in self.sorted_tables() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&doc_comment(&table.comment, ""));
+            out.push_str(&format!(
+                "export interface {} {{\n",
+                interface_name(&table.name)
+            ));
+            for column in &table.columns {
+                out.push_str(&doc_comment(&column.comment, "  "));
+                out.push_str(&format!(
+                    "  {}: {};\n",
+                    property_name(&column.name),
+                    typescript_type(column)
+                ));
+            }
+            out.push_str("}\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_type_export() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE user_accounts (id INT PRIMARY, email TEXT MASKED, age INT MASKED)",
+            "CREATE TABLE logs (at TIMESTAMP, level TEXT NOT NULL)",
+            "COMMENT ON TABLE user_accounts IS 'people who sign in'",
+            "COMMENT ON COLUMN user_accounts.email IS 'login'",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+
+        assert_eq!(
+            db.typescript(),
+            "export interface Logs {\n  at: string | null;\n  level: string;\n}\n\n\
+             /** people who sign in */\n\
+             export interface UserAccounts {\n  id: number;\n  /** login */\n  \
+             email: string | null;\n  age: number | string | null;\n}\n"
+        );
+
+        let schema = db.json_schema();
+        let users = &schema["$defs"]["user_accounts"];
+        assert_eq!(users["description"], "people who sign in");
+        assert_eq!(users["required"], json!(["id", "email", "age"]));
+        assert_eq!(users["properties"]["id"]["type"], json!(["integer"]));
+        assert_eq!(users["properties"]["email"]["description"], "login");
+        assert_eq!(
+            schema["$defs"]["logs"]["properties"]["at"]["type"],
+            json!(["string", "null"])
+        );
+    }
+}
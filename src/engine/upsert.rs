@@ -0,0 +1,156 @@
+use crate::{
+    DbError,
+    engine::{Table, Value, eval::eval, relation::Relation, session::Session},
+    parser::{ConflictAction, OnConflict},
+};
+
+/// What an `INSERT ... ON CONFLICT` did with its rows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpsertCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+impl Table {
+    /// Inserts `rows`, resolving PRIMARY/UNIQUE conflicts as `on_conflict` says instead of
+    /// failing. A row also conflicts with the rows inserted before it by the same call.
+    ///
+    /// Like `insert_rows`, the rows are applied as one change: on any error, inserted rows
+    /// are removed and updated rows restored. That includes the table growing by more than
+    /// `bytes_left`, as `DO UPDATE` can make rows larger.
+    pub fn upsert_rows(
+        &mut self,
+        rows: Vec<Vec<Value>>,
+        on_conflict: &OnConflict,
+        session: &Session,
+        bytes_left: Option<usize>,
+    ) -> Result<UpsertCounts, DbError> {
+        let start = self.rows.len();
+        let start_bytes = self.approx_bytes;
+        let mut replaced = Vec::new();
+        let mut counts = UpsertCounts::default();
+        for row in rows {
+            let upserted = self
+                .upsert_row(row, on_conflict, session, &mut replaced, &mut counts)
+                .and_then(|()| match bytes_left {
+                    Some(left) if self.approx_bytes > start_bytes + left => {
+                        Err(DbError::StorageFull(format!(
+                            "size limit reached, {} more bytes fit",
+                            left
+                        )))
+                    }
+                    _ => Ok(()),
+                });
+            if let Err(err) = upserted {
+                // Restored before truncating, as a row inserted by this call may have been
+                // updated by a later one.
+                for (pos, row) in replaced.into_iter().rev() {
+                    self.rows[pos] = row;
+                }
+                self.rows.truncate(start);
+                self.rebuild_indexes();
+                return Err(err);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Applies one row, recording the previous version of any row it updates in `replaced`.
+    fn upsert_row(
+        &mut self,
+        mut row: Vec<Value>,
+        on_conflict: &OnConflict,
+        session: &Session,
+        replaced: &mut Vec<(usize, Vec<Value>)>,
+        counts: &mut UpsertCounts,
+    ) -> Result<(), DbError> {
+        if row.len() != self.columns.len() {
+            return Err(DbError::ParseError("Columns count mismatch".into()));
+        }
+        self.convert_row(&mut row)?;
+        let Some(pos) = self.conflicting_row(&row, on_conflict.target.as_deref())? else {
+            self.insert_row(row)?;
+            counts.inserted += 1;
+            return Ok(());
+        };
+
+        let ConflictAction::DoUpdate(assignments) = &on_conflict.action else {
+            counts.skipped += 1;
+            return Ok(());
+        };
+        let relation = self.conflict_relation();
+        let current = &self.rows[pos];
+        let combined: Vec<Value> = current.iter().chain(&row).cloned().collect();
+        let mut updated = current.clone();
+        for (column, expr) in assignments {
+            updated[self.column_index(column)?] = eval(expr, &relation, &combined, session)?;
+        }
+        self.convert_row(&mut updated)?;
+        replaced.push((pos, self.replace_row(pos, updated)?));
+        counts.updated += 1;
+        Ok(())
+    }
+
+    /// The position of the stored row that `row` collides with on `target`, or on any
+    /// indexed column when there is no target.
+    fn conflicting_row(
+        &self,
+        row: &[Value],
+        target: Option<&str>,
+    ) -> Result<Option<usize>, DbError> {
+        let columns = match target {
+            Some(name) => {
+                let i = self.column_index(name)?;
+                if !self.indexes.contains_key(&i) {
+                    return Err(DbError::IndexNotFound(name.to_string()));
+                }
+                vec![i]
+            }
+            None => {
+                let mut columns: Vec<usize> = self.indexes.keys().copied().collect();
+                columns.sort_unstable();
+                columns
+            }
+        };
+
+        Ok(columns
+            .into_iter()
+            .find(|i| row[*i] != Value::Null && self.indexes[i].contains(&row[*i]))
+            .and_then(|i| self.rows.iter().position(|r| r[i] == row[i])))
+    }
+
+    /// The columns a `DO UPDATE SET` expression sees: those of the stored row, then those of
+    /// the rejected row as `excluded.column`.
+    pub(crate) fn conflict_relation(&self) -> Relation {
+        let mut relation = Relation::empty(self);
+        let excluded = self.columns.iter().map(|c| format!("excluded.{}", c.name));
+        relation.headers.extend(excluded);
+        relation
+    }
+
+    /// Swaps the row at `pos` for `row`, keeping the indexes in sync, and returns the old one.
+    fn replace_row(&mut self, pos: usize, row: Vec<Value>) -> Result<Vec<Value>, DbError> {
+        let current = &self.rows[pos];
+        for (i, index) in &self.indexes {
+            if row[*i] != current[*i] && row[*i] != Value::Null && index.contains(&row[*i]) {
+                return Err(DbError::UniqueViolation(self.columns[*i].name.clone()));
+            }
+        }
+
+        let size = |row: &[Value]| row.iter().map(Value::approximate_size).sum::<usize>();
+        self.approx_bytes = self.approx_bytes - size(current) + size(&row);
+        for (i, index) in &mut self.indexes {
+            index.remove(&current[*i]);
+            index.insert(row[*i].clone());
+        }
+        let old = std::mem::replace(&mut self.rows[pos], row);
+        self.unindex_row(pos, &old);
+        for (i, entries) in &mut self.row_index {
+            let positions = entries.entry(self.rows[pos][*i].clone()).or_default();
+            let at = positions.partition_point(|p| *p < pos);
+            positions.insert(at, pos);
+        }
+        Ok(old)
+    }
+}
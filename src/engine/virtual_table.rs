@@ -0,0 +1,197 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use crate::{
+    DbError,
+    engine::{Column, Value},
+};
+
+/// A read-only data source that can be queried like a regular table.
+///
+/// Implementors describe their schema and hand out rows on demand. Once registered with
+/// `Database::register_virtual_table`, the source can be used in `FROM` and `JOIN` clauses
+/// exactly like a table created with `CREATE TABLE`, but it is never written to disk.
+pub trait VirtualTable: Debug + Send + Sync {
+    /// The columns exposed by this source, in row order.
+    fn columns(&self) -> Vec<Column>;
+
+    /// Produces the current rows of the source. Every row must have one value per column.
+    fn rows(&self) -> Result<Box<dyn Iterator<Item = Vec<Value>> + '_>, DbError>;
+
+    /// How many rows `rows` would produce, if the source knows without producing them. Row
+    /// estimates count the rows of sources that don't.
+    fn row_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A virtual table backed by rows held in memory, e.g. converted from a `Vec` of structs.
+#[derive(Debug, Clone)]
+pub struct MemoryTable {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl MemoryTable {
+    pub fn new(columns: Vec<Column>, rows: Vec<Vec<Value>>) -> Self {
+        Self { columns, rows }
+    }
+
+    /// Builds a table from any collection by mapping each item into a row.
+    pub fn from_items<T>(
+        columns: Vec<Column>,
+        items: impl IntoIterator<Item = T>,
+        to_row: impl Fn(T) -> Vec<Value>,
+    ) -> Self {
+        Self {
+            columns,
+            rows: items.into_iter().map(to_row).collect(),
+        }
+    }
+}
+
+impl VirtualTable for MemoryTable {
+    fn columns(&self) -> Vec<Column> {
+        self.columns.clone()
+    }
+
+    fn rows(&self) -> Result<Box<dyn Iterator<Item = Vec<Value>> + '_>, DbError> {
+        Ok(Box::new(self.rows.iter().cloned()))
+    }
+
+    fn row_count(&self) -> Option<usize> {
+        Some(self.rows.len())
+    }
+}
+
+/// A virtual table that reads a comma-separated file on every scan.
+///
+/// The first line is treated as the header row. Fields that parse as integers become
+/// `Value::Integer`, empty fields become `Value::Null` and everything else is `Value::Text`.
+#[derive(Debug, Clone)]
+pub struct CsvTable {
+    path: PathBuf,
+    columns: Vec<Column>,
+}
+
+impl CsvTable {
+    /// Opens the file and reads its header to determine the schema.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, DbError> {
+        let path = path.into();
+        let file = File::open(&path)
+            .map_err(|e| DbError::IoError(format!("Could not open CSV file: {}", e)))?;
+
+        let mut header = String::new();
+        BufReader::new(file)
+            .read_line(&mut header)
+            .map_err(|e| DbError::IoError(format!("Read CSV header failed: {}", e)))?;
+
+        let columns = header
+            .trim_end()
+            .split(',')
+            .map(|name| Column {
+                name: name.trim().to_string(),
+                data_type: "TEXT".into(),
+                is_primary: false,
+                is_unique: false,
+                comment: None,
+                is_masked: false,
+                max_length: None,
+                is_not_null: false,
+            })
+            .collect();
+
+        Ok(Self { path, columns })
+    }
+
+    fn parse_field(field: &str) -> Value {
+        let field = field.trim();
+        if field.is_empty() {
+            Value::Null
+        } else if let Ok(num) = field.parse::<i32>() {
+            Value::Integer(num)
+        } else if let Ok(num) = field.parse::<i64>() {
+            Value::BigInt(num)
+        } else {
+            Value::Text(field.to_string())
+        }
+    }
+}
+
+impl VirtualTable for CsvTable {
+    fn columns(&self) -> Vec<Column> {
+        self.columns.clone()
+    }
+
+    fn rows(&self) -> Result<Box<dyn Iterator<Item = Vec<Value>> + '_>, DbError> {
+        let file = File::open(&self.path)
+            .map_err(|e| DbError::IoError(format!("Could not open CSV file: {}", e)))?;
+
+        let mut rows = Vec::new();
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = line.map_err(|e| DbError::IoError(format!("Read CSV failed: {}", e)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            rows.push(line.split(',').map(Self::parse_field).collect());
+        }
+
+        Ok(Box::new(rows.into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, engine::ExecutionResult, parser::parse};
+
+    fn text_column(name: &str) -> Column {
+        Column {
+            name: name.into(),
+            data_type: "TEXT".into(),
+            is_primary: false,
+            is_unique: false,
+            comment: None,
+            is_masked: false,
+            max_length: None,
+            is_not_null: false,
+        }
+    }
+
+    #[test]
+    fn test_select_and_join_virtual_table() {
+        let mut db = Database::new();
+        db.execute(parse("CREATE TABLE devs (id INT PRIMARY, name TEXT, team TEXT)").unwrap())
+            .unwrap();
+        db.execute(parse("INSERT INTO devs VALUES (1, 'Alice', 'core')").unwrap())
+            .unwrap();
+
+        let teams = MemoryTable::from_items(
+            vec![text_column("code"), text_column("title")],
+            [("core", "Core Engine"), ("web", "Web")],
+            |(code, title)| vec![Value::Text(code.into()), Value::Text(title.into())],
+        );
+        db.register_virtual_table("teams".into(), teams).unwrap();
+
+        match db.execute(parse("SELECT * FROM teams").unwrap()).unwrap() {
+            ExecutionResult::Data { rows, .. } => assert_eq!(rows.len(), 2),
+            _ => panic!("expected data"),
+        }
+
+        match db
+            .execute(parse("SELECT * FROM devs JOIN teams ON team = code").unwrap())
+            .unwrap()
+        {
+            ExecutionResult::Data { rows, .. } => {
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0][4], Value::Text("Core Engine".into()));
+            }
+            _ => panic!("expected data"),
+        }
+
+        let insert = db.execute(parse("INSERT INTO teams VALUES ('x', 'y')").unwrap());
+        assert!(matches!(insert, Err(DbError::ReadOnlyTable(_))));
+    }
+}
@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+
+use crate::{
+    DbError,
+    engine::{Value, relation::Relation},
+    parser::{Expr, OrderByDefinition, SelectItem, WindowFunction},
+};
+
+/// Headers of the columns holding the computed window values. No column name starts with `#`.
+const WINDOW_COLUMN: &str = "#window";
+
+/// Computes the window functions of the select list over the rows of `relation`, appending
+/// one column per call. Each call in `items` is replaced by a reference to its column,
+/// keeping the header the item had, and `ORDER BY` keys naming the alias of such an item are
+/// pointed at its column too, so the rows can be sorted and projected as usual.
+///
+/// A window is computed by sorting the row positions by partition, then by the window's own
+/// keys; the rows themselves keep their order. `*` is expanded first so it doesn't pick up
+/// the new columns.
+pub(crate) fn compute_windows(
+    relation: &mut Relation,
+    items: &mut Vec<SelectItem>,
+    order_by: &mut [OrderByDefinition],
+) -> Result<(), DbError> {
+    if !items.iter().any(SelectItem::has_window) {
+        return Ok(());
+    }
+
+    let mut expanded = Vec::with_capacity(items.len());
+    let mut windows = Vec::new();
+    for item in items.drain(..) {
+        match item {
+            SelectItem::Wildcard => {
+                expanded.extend(relation.headers.iter().map(|h| SelectItem::Expr {
+                    expr: Expr::Column(h.clone()),
+                    alias: None,
+                }))
+            }
+            SelectItem::Expr { mut expr, alias } if expr.has_window() => {
+                let alias = alias.unwrap_or_else(|| expr.to_string());
+                extract_windows(&mut expr, &mut windows);
+                if let Expr::Column(column) = &expr {
+                    for key in order_by.iter_mut().filter(|key| key.column == alias) {
+                        key.column = column.clone();
+                    }
+                }
+                expanded.push(SelectItem::Expr {
+                    expr,
+                    alias: Some(alias),
+                });
+            }
+            item => expanded.push(item),
+        }
+    }
+    *items = expanded;
+
+    for (i, window) in windows.iter().enumerate() {
+        let Expr::Window {
+            func,
+            partition_by,
+            order_by,
+        } = window
+        else {
+            unreachable!("only window calls are extracted");
+        };
+        let values = window_values(relation, *func, partition_by, order_by)?;
+        relation.headers.push(format!("{}{}", WINDOW_COLUMN, i));
+        for (row, value) in relation.rows.iter_mut().zip(values) {
+            row.push(value);
+        }
+    }
+    Ok(())
+}
+
+/// Moves the window calls of `expr` to `windows`, leaving a reference to the column each
+/// will be computed into.
+fn extract_windows(expr: &mut Expr, windows: &mut Vec<Expr>) {
+    let mut extract = |e: &mut Expr| extract_windows(e, windows);
+    match expr {
+        Expr::Window { .. } => {
+            let column = Expr::Column(format!("{}{}", WINDOW_COLUMN, windows.len()));
+            windows.push(std::mem::replace(expr, column));
+        }
+        Expr::Column(_)
+        | Expr::Literal(_)
+        | Expr::Variable(_)
+        | Expr::Exists(_)
+        | Expr::Subquery(_) => {}
+        Expr::Function { args, .. } => args.iter_mut().for_each(extract),
+        Expr::Aggregate { arg, .. } => arg.iter_mut().for_each(|a| extract(a)),
+        Expr::Binary { left, right, .. } => {
+            extract(left);
+            extract(right);
+        }
+        Expr::Not(inner)
+        | Expr::Negate(inner)
+        | Expr::IsNull { expr: inner, .. }
+        | Expr::Cast { expr: inner, .. } => extract(inner),
+        Expr::InList { expr, list, .. } => {
+            extract(expr);
+            list.iter_mut().for_each(extract);
+        }
+    }
+}
+
+/// The value of one window function for each row of `relation`, in row order.
+fn window_values(
+    relation: &Relation,
+    func: WindowFunction,
+    partition_by: &[String],
+    order_by: &[OrderByDefinition],
+) -> Result<Vec<Value>, DbError> {
+    let partition = partition_by
+        .iter()
+        .map(|column| relation.column_index(column))
+        .collect::<Result<Vec<_>, DbError>>()?;
+    let keys = order_by
+        .iter()
+        .map(|o| Ok((relation.column_index(&o.column)?, o.descending)))
+        .collect::<Result<Vec<_>, DbError>>()?;
+
+    let rows = &relation.rows;
+    let compare_partitions = |a: usize, b: usize| {
+        partition
+            .iter()
+            .map(|&i| rows[a][i].cmp(&rows[b][i]))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    };
+    let compare_keys = |a: usize, b: usize| {
+        keys.iter()
+            .map(|&(i, descending)| {
+                let ordering = rows[a][i].cmp(&rows[b][i]);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    };
+    let mut positions: Vec<usize> = (0..rows.len()).collect();
+    positions.sort_by(|&a, &b| compare_partitions(a, b).then_with(|| compare_keys(a, b)));
+
+    let mut values = vec![Value::Null; rows.len()];
+    let (mut row_number, mut rank, mut dense_rank) = (0, 0, 0);
+    for (n, &row) in positions.iter().enumerate() {
+        let previous = n.checked_sub(1).map(|p| positions[p]);
+        let new_partition = previous.is_none_or(|p| compare_partitions(p, row).is_ne());
+        if new_partition {
+            (row_number, rank, dense_rank) = (0, 0, 0);
+        }
+        row_number += 1;
+        if new_partition || previous.is_some_and(|p| compare_keys(p, row).is_ne()) {
+            rank = row_number;
+            dense_rank += 1;
+        }
+        values[row] = Value::Integer(match func {
+            WindowFunction::RowNumber => row_number,
+            WindowFunction::Rank => rank,
+            WindowFunction::DenseRank => dense_rank,
+        });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Database,
+        engine::{ExecutionResult, Value},
+        parser::parse,
+    };
+
+    fn query(db: &mut Database, sql: &str) -> (Vec<String>, Vec<Vec<Value>>) {
+        match db.execute(parse(sql).unwrap()).unwrap() {
+            ExecutionResult::Data { headers, rows, .. } => (headers, rows),
+            ExecutionResult::Message(m) => panic!("expected rows, got {}", m),
+        }
+    }
+
+    #[test]
+    fn test_window_functions() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE scores (player TEXT, team_id INT, score INT)",
+            "INSERT INTO scores VALUES ('ann', 1, 30), ('bob', 2, 10), ('cy', 1, 50), \
+             ('dee', 1, 30), ('eve', 2, 20)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let int = |n: i32| Value::Integer(n);
+        let text = |s: &str| Value::Text(s.into());
+
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT player, ROW_NUMBER() OVER (PARTITION BY team_id ORDER BY score DESC) AS rn, \
+             RANK() OVER (PARTITION BY team_id ORDER BY score DESC), \
+             DENSE_RANK() OVER (ORDER BY score) FROM scores ORDER BY team_id, rn",
+        );
+        assert_eq!(
+            headers,
+            [
+                "player",
+                "rn",
+                "RANK() OVER (PARTITION BY team_id ORDER BY score DESC)",
+                "DENSE_RANK() OVER (ORDER BY score)",
+            ]
+        );
+        assert_eq!(
+            rows,
+            [
+                [text("cy"), int(1), int(1), int(4)],
+                [text("ann"), int(2), int(2), int(3)],
+                [text("dee"), int(3), int(2), int(3)],
+                [text("eve"), int(1), int(1), int(2)],
+                [text("bob"), int(2), int(2), int(1)],
+            ]
+        );
+
+        // Windows see the filtered rows, and `*` keeps to the table's columns.
+        let (headers, rows) = query(
+            &mut db,
+            "SELECT *, ROW_NUMBER() OVER () * 10 AS n FROM scores WHERE team_id = 2",
+        );
+        assert_eq!(headers, ["player", "team_id", "score", "n"]);
+        assert_eq!(rows[1], [text("eve"), int(2), int(20), int(20)]);
+
+        for sql in [
+            "SELECT player FROM scores WHERE ROW_NUMBER() OVER () = 1",
+            "SELECT team_id, ROW_NUMBER() OVER () FROM scores GROUP BY team_id",
+            "SELECT RANK() OVER (ORDER BY missing) FROM scores",
+            "SELECT RANK(score) OVER () FROM scores",
+        ] {
+            let result = parse(sql).map(|statement| db.execute(statement));
+            assert!(!matches!(result, Ok(Ok(_))), "{}", sql);
+        }
+    }
+}
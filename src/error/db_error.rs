@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 /// Represents all possible errors that can occur within the RDBMS.
@@ -9,12 +11,104 @@ pub enum DbError {
     #[error("Table '{0}' not found")]
     TableNotFound(String),
 
+    #[error("Table '{0}' is read-only")]
+    ReadOnlyTable(String),
+
     #[error("Column '{0}' not found")]
     ColumnNotFound(String),
 
+    #[error("Column '{0}' is ambiguous, qualify it as table.column")]
+    AmbiguousColumn(String),
+
+    #[error("Column '{0}' already exists")]
+    ColumnAlreadyExists(String),
+
+    #[error("No index on column '{0}'")]
+    IndexNotFound(String),
+
+    #[error("Index '{0}' already exists")]
+    IndexAlreadyExists(String),
+
+    #[error("Index '{0}' not found")]
+    IndexNameNotFound(String),
+
     #[error("Unique constraint violation on column '{0}'")]
     UniqueViolation(String),
 
+    #[error("NOT NULL constraint violation on column '{0}'")]
+    NotNullViolation(String),
+
+    #[error("Foreign key violation: {0}")]
+    ForeignKeyViolation(String),
+
+    #[error("Saved query '{0}' already exists")]
+    QueryAlreadyExists(String),
+
+    #[error("Saved query '{0}' not found")]
+    QueryNotFound(String),
+
+    #[error("Schedule '{0}' already exists")]
+    ScheduleAlreadyExists(String),
+
+    #[error("Schedule '{0}' not found")]
+    ScheduleNotFound(String),
+
+    #[error("Trigger '{0}' already exists")]
+    TriggerAlreadyExists(String),
+
+    #[error("Trigger '{0}' not found")]
+    TriggerNotFound(String),
+
+    #[error("Trigger '{0}' failed: {1}")]
+    TriggerFailed(String, String),
+
+    #[error("Migration '{0}' is already registered with different SQL")]
+    MigrationAlreadyExists(String),
+
+    #[error("Migration '{0}' failed: {1}")]
+    MigrationFailed(String, String),
+
+    #[error("Storage full: {0}")]
+    StorageFull(String),
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityViolation(String),
+
+    #[error("Statement timed out after {0:?}")]
+    StatementTimeout(Duration),
+
+    #[error("Savepoint '{0}' not found")]
+    SavepointNotFound(String),
+
+    #[error("No transaction is open, start one with BEGIN")]
+    NoTransaction,
+
+    #[error("A transaction is already open")]
+    TransactionAlreadyOpen,
+
+    #[error("The database is locked by the open transaction of another session")]
+    DatabaseLocked,
+
+    #[error("Scalar subquery returned {0} rows, expected at most one")]
+    SubqueryTooManyRows(usize),
+
+    #[error("Type error: {0}")]
+    TypeError(String),
+
+    #[error("Column '{column}' expects {expected}, got {got}")]
+    TypeMismatch {
+        column: String,
+        expected: String,
+        got: String,
+    },
+
+    #[error("Value too long for column '{column}': {length} characters, at most {max_length}")]
+    ValueTooLong {
+        column: String,
+        max_length: usize,
+        length: usize,
+    },
+
     #[error("Parsing error: {0}")]
     ParseError(String),
 
@@ -1 +1,2 @@
 pub mod db_error;
+pub mod syntax_error;
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::DbError;
+
+/// A statement that doesn't parse, with where in the SQL the parser gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// Byte offset of the token the error points at, or of the end of the statement when the
+    /// parser ran out of tokens. `None` for errors not tied to a token.
+    pub position: Option<usize>,
+}
+
+impl SyntaxError {
+    pub fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position: Some(position),
+        }
+    }
+
+    /// The message followed by the line of `sql` holding the error and a caret under it:
+    ///
+    /// ```text
+    /// Expected '(' after table name
+    ///   CREATE TABLE users id INT
+    ///                      ^
+    /// ```
+    pub fn render(&self, sql: &str) -> String {
+        let Some(position) = self.position.filter(|&p| sql.is_char_boundary(p)) else {
+            return self.message.clone();
+        };
+        let start = sql[..position].rfind('\n').map_or(0, |i| i + 1);
+        let end = sql[position..]
+            .find('\n')
+            .map_or(sql.len(), |i| position + i);
+        let column = sql[start..position].chars().count();
+        format!(
+            "{}\n  {}\n  {}^",
+            self.message,
+            &sql[start..end],
+            " ".repeat(column)
+        )
+    }
+}
+
+impl From<String> for SyntaxError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            position: None,
+        }
+    }
+}
+
+impl From<SyntaxError> for String {
+    fn from(error: SyntaxError) -> Self {
+        error.message
+    }
+}
+
+impl From<SyntaxError> for DbError {
+    fn from(error: SyntaxError) -> Self {
+        DbError::ParseError(error.message)
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_script};
+
+    #[test]
+    fn test_syntax_error_position() {
+        let sql = "CREATE TABLE users id INT";
+        let err = parse(sql).unwrap_err();
+        assert_eq!(err.position, Some(19));
+        assert_eq!(
+            err.render(sql),
+            format!("{}\n  {}\n  {}^", err.message, sql, " ".repeat(19))
+        );
+
+        // Out of tokens, the error points past the end of the statement.
+        let err = parse("SELECT * FROM").unwrap_err();
+        assert_eq!(err.position, Some(13));
+
+        // Positions count from the start of the script, and the caret from its line.
+        let script = "SELECT 1;\n-- two\nSELEC 2;";
+        let err = parse_script(script).unwrap_err();
+        assert_eq!(err.position, Some(17));
+        assert_eq!(
+            err.render(script),
+            "Statement 2: Unknown command: SELEC\n  SELEC 2;\n  ^"
+        );
+        assert_eq!(SyntaxError::from("bad".to_string()).render(script), "bad");
+
+        // A bad last token is pointed at, also when a clause or nested statement reads it.
+        for (sql, token) in [
+            ("SELECT * FROM s WHERE id = 1 junk;", "junk"),
+            ("SELECT * FROM s LIMIT x;", "x"),
+            ("DROP TABLE u please;", "please"),
+            (
+                "CREATE TRIGGER x AFTER INSERT ON t AS garbage stuff;",
+                "garbage",
+            ),
+            (
+                "EXPLAIN SELECT * FROM t WHERE id IN (SELECT FROM u) LIMIT 1",
+                "FROM u",
+            ),
+            ("CREATE SCHEDULE s AS SELECT 1 AS a b EVERY '1h'", "b EVERY"),
+        ] {
+            let err = parse(sql).unwrap_err();
+            assert_eq!(err.position, sql.find(token), "{}", sql);
+        }
+    }
+}
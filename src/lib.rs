@@ -1,7 +1,11 @@
+pub mod auth;
+pub mod cli;
 pub mod engine;
 pub mod error;
 pub mod parser;
 pub mod storage;
+pub mod testing;
+pub mod web;
 
 pub use engine::Database;
 pub use error::db_error::DbError;
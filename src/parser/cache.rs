@@ -0,0 +1,411 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::engine::Value;
+use crate::error::syntax_error::SyntaxError;
+use crate::parser::{
+    ConflictAction, Expr, OnConflict, SelectItem, SelectStatement, Statement, parse_tokens,
+    statement_tokens,
+};
+
+/// Slot values are parsed as `FIRST_SENTINEL + slot` (integers) or `SENTINEL_TEXT` followed
+/// by the slot (text), which no real literal can produce since the template has none.
+/// Prepared statements keep their literals, so they move their integer sentinels past any
+/// literal in the way; see `free_sentinels`.
+const FIRST_SENTINEL: i32 = 1_000_000_000;
+const SENTINEL_TEXT: char = '\u{1}';
+
+/// Parsed statements keyed on their SQL with every literal replaced by a parameter slot, so
+/// `SELECT * FROM users WHERE id = 1` and `... WHERE id = 2` share one entry.
+///
+/// A template is parsed once with a unique sentinel value in each slot. It is only cached
+/// when every sentinel ends up exactly once in a literal of the statement; later statements
+/// of the same shape clone it and swap their own literals in. Anything else, such as the SQL
+/// text of `CREATE QUERY`, is parsed every time.
+///
+/// Statements prepared with `?` placeholders are kept in the same cache, keyed on their SQL.
+#[derive(Debug, Clone)]
+pub struct StatementCache {
+    capacity: usize,
+    /// `None` marks a template known not to be cacheable.
+    entries: HashMap<String, Option<PreparedStatement>>,
+    /// Keys in insertion order; the oldest is evicted when the cache is full.
+    order: VecDeque<String>,
+}
+
+/// A literal position in a parsed statement.
+enum Slot<'a> {
+    Value(&'a mut Value),
+    /// A `LIMIT` or `OFFSET` count.
+    Count(&'a mut usize),
+}
+
+impl Default for StatementCache {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Number of cached templates.
+    pub fn len(&self) -> usize {
+        self.entries.values().filter(|e| e.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parses `sql`, reusing the statement of an earlier query of the same shape. The flag
+    /// tells whether the cache answered.
+    pub fn parse(&mut self, sql: &str) -> Result<(Statement, bool), SyntaxError> {
+        let tokens = statement_tokens(sql)?;
+        let mut params = Vec::new();
+        let mut key = Vec::with_capacity(tokens.text.len());
+        let mut template = Vec::with_capacity(tokens.text.len());
+        for token in &tokens.text {
+            let slot = params.len() as i32;
+            if let Ok(num) = token.parse::<i32>() {
+                key.push("\0int");
+                template.push((FIRST_SENTINEL + slot).to_string());
+                params.push(Value::Integer(num));
+            } else if token.starts_with('\'') {
+                key.push("\0text");
+                template.push(format!("'{}{}'", SENTINEL_TEXT, slot));
+                params.push(Value::Text(super::unquote(token)));
+            } else {
+                key.push(token.as_str());
+                template.push(token.clone());
+            }
+        }
+        let key = key.join(" ");
+
+        if let Some(cached) = self.entries.get(&key) {
+            if let Some(statement) = cached.as_ref().and_then(|p| p.bind(&params).ok()) {
+                return Ok((statement, true));
+            }
+            return tokens.parse().map(|s| (s, false));
+        }
+
+        let statement = tokens.parse()?;
+        let cached = parse_tokens(&template)
+            .ok()
+            .filter(|t| binds_every_slot(t, params.len(), FIRST_SENTINEL))
+            .map(|template| PreparedStatement {
+                template,
+                params: params.len(),
+                first: FIRST_SENTINEL,
+            });
+        self.remember(key, cached);
+        Ok((statement, false))
+    }
+
+    /// Prepares `sql`, reusing the template of an earlier `prepare` of the same SQL. The flag
+    /// tells whether the cache answered.
+    pub fn prepare(&mut self, sql: &str) -> Result<(PreparedStatement, bool), String> {
+        let key = format!("\0prepare {}", sql);
+        if let Some(Some(prepared)) = self.entries.get(&key) {
+            return Ok((prepared.clone(), true));
+        }
+        let prepared = PreparedStatement::new(sql)?;
+        self.remember(key, Some(prepared.clone()));
+        Ok((prepared, false))
+    }
+
+    fn remember(&mut self, key: String, entry: Option<PreparedStatement>) {
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.entries.remove(&oldest);
+        }
+        self.order.push_back(key.clone());
+        self.entries.insert(key, entry);
+    }
+}
+
+/// A statement parsed once with each `?` standing for a value, bound to new values every
+/// time it runs. The values are bound into the parsed statement the way cached templates are
+/// filled, never spliced into the SQL, so they cannot change its meaning.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    template: Statement,
+    params: usize,
+    /// The sentinel of the first slot.
+    first: i32,
+}
+
+impl PreparedStatement {
+    /// Parses `sql`, e.g. `SELECT * FROM users WHERE id = ?`.
+    pub fn new(sql: &str) -> Result<Self, String> {
+        let mut tokens = statement_tokens(sql)?.text;
+        let params = tokens.iter().filter(|t| *t == "?").count();
+        let first = free_sentinels(&tokens, params);
+        for (i, token) in tokens.iter_mut().filter(|t| *t == "?").enumerate() {
+            *token = (i64::from(first) + i as i64).to_string();
+        }
+        let template = parse_tokens(&tokens)?;
+        if !binds_every_slot(&template, params, first) {
+            return Err("Parameters can only stand for values".into());
+        }
+        Ok(Self {
+            template,
+            params,
+            first,
+        })
+    }
+
+    /// How many values `bind` takes.
+    pub fn param_count(&self) -> usize {
+        self.params
+    }
+
+    /// The statement with the `?` placeholders replaced by `params`, in order.
+    pub fn bind(&self, params: &[Value]) -> Result<Statement, String> {
+        if params.len() != self.params {
+            return Err(format!(
+                "The query has {} parameters, {} given",
+                self.params,
+                params.len()
+            ));
+        }
+        bind(self.template.clone(), params, self.first)
+            .ok_or_else(|| "LIMIT and OFFSET take non-negative integers".into())
+    }
+}
+
+/// Parses `sql` with each `?` standing for the next value of `params`, e.g.
+/// `SELECT * FROM users WHERE id = ?`; see `PreparedStatement`.
+pub fn parse_with_params(sql: &str, params: &[Value]) -> Result<Statement, String> {
+    PreparedStatement::new(sql)?.bind(params)
+}
+
+/// The first integer sentinel of `params` consecutive ones that no integer among `tokens`
+/// equals, so a literal such as `1000000000` is never taken for a slot.
+fn free_sentinels(tokens: &[String], params: usize) -> i32 {
+    let literals: Vec<i64> = tokens.iter().filter_map(|t| t.parse().ok()).collect();
+    let mut first = i64::from(FIRST_SENTINEL);
+    while let Some(taken) = literals
+        .iter()
+        .find(|n| (first..first + params as i64).contains(n))
+    {
+        first = taken + 1;
+    }
+    // Past `i32::MAX` the slots parse as BIGINT and are rejected by `binds_every_slot`.
+    first.try_into().unwrap_or(i32::MAX)
+}
+
+/// The slot a sentinel literal stands for, with `first` the sentinel of slot 0.
+fn sentinel(slot: &Slot, first: i32) -> Option<usize> {
+    match slot {
+        Slot::Value(Value::Integer(n)) => n.checked_sub(first),
+        Slot::Value(Value::Text(text)) => text.strip_prefix(SENTINEL_TEXT)?.parse().ok(),
+        Slot::Count(n) => (**n as i64 - first as i64).try_into().ok(),
+        Slot::Value(
+            Value::BigInt(_)
+            | Value::Real(_)
+            | Value::Decimal(_)
+            | Value::Date(_)
+            | Value::Timestamp(_)
+            | Value::Null,
+        ) => None,
+    }
+    .and_then(|slot: i32| usize::try_from(slot).ok())
+}
+
+/// Whether each of the `count` slots appears exactly once in the template.
+fn binds_every_slot(template: &Statement, count: usize, first: i32) -> bool {
+    let mut seen = vec![0; count];
+    let mut template = template.clone();
+    visit_statement(&mut template, &mut |slot| {
+        if let Some(i) = sentinel(&slot, first)
+            && let Some(seen) = seen.get_mut(i)
+        {
+            *seen += 1;
+        }
+    });
+    seen.iter().all(|n| *n == 1)
+}
+
+/// Fills the slots of a cached template with `params`. `None` when a value doesn't fit its
+/// slot, e.g. a negative `LIMIT`, so the statement must be parsed to report the error.
+fn bind(mut template: Statement, params: &[Value], first: i32) -> Option<Statement> {
+    let mut ok = true;
+    visit_statement(&mut template, &mut |mut slot| {
+        let Some(param) = sentinel(&slot, first).and_then(|i| params.get(i)) else {
+            return;
+        };
+        match (&mut slot, param) {
+            (Slot::Value(value), param) => **value = param.clone(),
+            (Slot::Count(count), Value::Integer(n)) if *n >= 0 => **count = *n as usize,
+            (Slot::Count(_), _) => ok = false,
+        }
+    });
+    ok.then_some(template)
+}
+
+fn visit_statement(statement: &mut Statement, f: &mut dyn FnMut(Slot)) {
+    match statement {
+        Statement::Select(select) => visit_select(select, f),
+        Statement::Insert {
+            rows, on_conflict, ..
+        } => {
+            rows.iter_mut().flatten().for_each(|v| f(Slot::Value(v)));
+            if let Some(OnConflict {
+                action: ConflictAction::DoUpdate(assignments),
+                ..
+            }) = on_conflict
+            {
+                assignments.iter_mut().for_each(|(_, e)| visit_expr(e, f));
+            }
+        }
+        Statement::SetVariable { value, .. } => f(Slot::Value(value)),
+        Statement::Explain(inner) => visit_statement(inner, f),
+        Statement::ExplainAnalyze(select) => visit_select(select, f),
+        _ => {}
+    }
+}
+
+fn visit_select(select: &mut SelectStatement, f: &mut dyn FnMut(Slot)) {
+    for item in &mut select.columns {
+        if let SelectItem::Expr { expr, .. } = item {
+            visit_expr(expr, f);
+        }
+    }
+    for arg in select.table_args.iter_mut().flatten() {
+        f(Slot::Value(arg));
+    }
+    if let Some(subquery) = &mut select.subquery {
+        visit_select(subquery, f);
+    }
+    if let Some(predicate) = &mut select.where_clause {
+        visit_expr(predicate, f);
+    }
+    if let Some(limit) = &mut select.limit {
+        f(Slot::Count(limit));
+    }
+    if let Some(offset) = &mut select.offset {
+        f(Slot::Count(offset));
+    }
+}
+
+fn visit_expr(expr: &mut Expr, f: &mut dyn FnMut(Slot)) {
+    match expr {
+        Expr::Literal(value) => f(Slot::Value(value)),
+        Expr::Column(_) | Expr::Variable(_) | Expr::Window { .. } => {}
+        Expr::Function { args, .. } => args.iter_mut().for_each(|a| visit_expr(a, f)),
+        Expr::Aggregate { arg, .. } => {
+            if let Some(arg) = arg {
+                visit_expr(arg, f);
+            }
+        }
+        Expr::Binary { left, right, .. } => {
+            visit_expr(left, f);
+            visit_expr(right, f);
+        }
+        Expr::Not(inner)
+        | Expr::Negate(inner)
+        | Expr::IsNull { expr: inner, .. }
+        | Expr::Cast { expr: inner, .. } => visit_expr(inner, f),
+        Expr::InList { expr, list, .. } => {
+            visit_expr(expr, f);
+            list.iter_mut().for_each(|e| visit_expr(e, f));
+        }
+        Expr::Exists(subquery) | Expr::Subquery(subquery) => visit_select(subquery, f),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(statement: Statement) -> SelectStatement {
+        match statement {
+            Statement::Select(select) => *select,
+            other => panic!("expected select, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_statement_cache() {
+        let mut cache = StatementCache::new(2);
+        let (_, hit) = cache
+            .parse("SELECT * FROM users WHERE id = 1 AND name = 'ada' LIMIT 5")
+            .unwrap();
+        assert!(!hit);
+
+        let (statement, hit) = cache
+            .parse("SELECT * FROM users WHERE id = 7 AND name = 'bob' LIMIT 2")
+            .unwrap();
+        assert!(hit);
+        let statement = select(statement);
+        assert_eq!(
+            statement.where_clause.unwrap().to_string(),
+            "id = 7 AND name = 'bob'"
+        );
+        assert_eq!(statement.limit, Some(2));
+
+        // Clauses bind by position even when written out of order.
+        cache.parse("SELECT * FROM t OFFSET 1 LIMIT 2").unwrap();
+        let (statement, hit) = cache.parse("SELECT * FROM t OFFSET 3 LIMIT 4").unwrap();
+        assert!(hit);
+        let statement = select(statement);
+        assert_eq!((statement.limit, statement.offset), (Some(4), Some(3)));
+
+        // A cached shape still reports errors of its own values.
+        assert!(cache.parse("SELECT * FROM t OFFSET -1 LIMIT 4").is_err());
+        // A different literal type is a different shape.
+        let parsed = cache.parse("SELECT * FROM t OFFSET 'x' LIMIT 4");
+        assert!(!matches!(parsed, Ok((_, true))));
+
+        // The SQL text of a saved query is not a slot.
+        let sql = "CREATE QUERY q AS SELECT * FROM t WHERE id = 1";
+        cache.parse(sql).unwrap();
+        assert!(!cache.parse(sql).unwrap().1);
+    }
+
+    #[test]
+    fn test_parse_with_params() {
+        let params = [
+            Value::Integer(7),
+            Value::Text("o'brien; DROP TABLE t".into()),
+        ];
+        let sql = "SELECT * FROM users WHERE id = ? AND name = ? LIMIT 2";
+        let statement = select(parse_with_params(sql, &params).unwrap());
+        assert_eq!(
+            statement.where_clause.unwrap().to_string(),
+            "id = 7 AND name = 'o''brien; DROP TABLE t'"
+        );
+
+        let sql = "INSERT INTO t VALUES (?, ?)";
+        let Statement::Insert { rows, .. } =
+            parse_with_params(sql, &[Value::Null, Value::Integer(1)]).unwrap()
+        else {
+            panic!("expected insert");
+        };
+        assert_eq!(rows, [[Value::Null, Value::Integer(1)]]);
+
+        assert_eq!(
+            parse_with_params("SELECT * FROM t WHERE id = ?", &[]).unwrap_err(),
+            "The query has 1 parameters, 0 given"
+        );
+        // A literal equal to a sentinel stays a literal.
+        let sql = "SELECT * FROM t WHERE a = 1000000000 AND b = ? LIMIT 1000000001";
+        let statement = select(parse_with_params(sql, &[Value::Integer(5)]).unwrap());
+        assert_eq!(
+            statement.where_clause.unwrap().to_string(),
+            "a = 1000000000 AND b = 5"
+        );
+        assert_eq!(statement.limit, Some(1_000_000_001));
+
+        // Names are not values.
+        assert!(parse_with_params("SELECT * FROM ?", &[Value::Text("t".into())]).is_err());
+        assert!(parse_with_params("SELECT * FROM t LIMIT ?", &[Value::Integer(-1)]).is_err());
+    }
+}
@@ -0,0 +1,734 @@
+use std::fmt;
+
+use crate::engine::{Date, Timestamp, Value, real::parse_decimal};
+use crate::parser::{
+    OrderByDefinition, SelectStatement, identifier, is_integer, is_signed_number, name_parts,
+    parse_decimal_type, parse_literal, parse_order_by, parse_subquery, parse_subquery_body, quote,
+};
+
+/// An expression that produces a value for each output row.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Column(String),
+    Literal(Value),
+    /// A session variable such as `@team`, stored without the `@`.
+    Variable(String),
+    /// A scalar function call, e.g. `CURRENT_USER()`. Names are stored uppercased.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// An aggregate call; `arg` is `None` for `COUNT(*)`.
+    Aggregate {
+        func: AggregateFunction,
+        arg: Option<Box<Expr>>,
+    },
+    /// An arithmetic operation, comparison or logical connective such as `price * qty`,
+    /// `age >= 18` or `a AND b`.
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOperator,
+        right: Box<Expr>,
+    },
+    Not(Box<Expr>),
+    /// `-expr`, binding tighter than `*` and `/`.
+    Negate(Box<Expr>),
+    /// `expr IS [NOT] NULL`.
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (item, ...)`.
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `CAST(expr AS type)`, converting between `INT` and `TEXT`. The type is stored
+    /// uppercased, with `INTEGER` written as `INT`.
+    Cast {
+        expr: Box<Expr>,
+        data_type: String,
+    },
+    /// `EXISTS (SELECT ...)`: true when the subquery returns a row. The subquery may refer to
+    /// columns of the enclosing query's row.
+    Exists(Box<SelectStatement>),
+    /// `(SELECT ...)` used as a value: the single column of its only row, or `NULL` if it
+    /// returns none. Like `EXISTS`, it may refer to the enclosing query's row.
+    Subquery(Box<SelectStatement>),
+    /// `ROW_NUMBER() OVER (PARTITION BY team ORDER BY score DESC)`: a value computed for each
+    /// row from the rows of its partition, taken in the window's order. Only allowed in the
+    /// select list of a query without grouping.
+    Window {
+        func: WindowFunction,
+        partition_by: Vec<String>,
+        order_by: Vec<OrderByDefinition>,
+    },
+}
+
+/// Operators that combine two expressions. Arithmetic works on numbers, dates and timestamps.
+/// Comparisons and connectives yield `1` for true, `0` for false and `NULL` when the outcome is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn from_token(token: &str) -> Option<Self> {
+        match token.to_uppercase().as_str() {
+            "+" => Some(Self::Add),
+            "-" => Some(Self::Sub),
+            "*" => Some(Self::Mul),
+            "/" => Some(Self::Div),
+            "%" => Some(Self::Mod),
+            "=" => Some(Self::Eq),
+            "!=" | "<>" => Some(Self::NotEq),
+            "<" => Some(Self::Lt),
+            "<=" => Some(Self::LtEq),
+            ">" => Some(Self::Gt),
+            ">=" => Some(Self::GtEq),
+            "AND" => Some(Self::And),
+            "OR" => Some(Self::Or),
+            _ => None,
+        }
+    }
+
+    /// Binding strength; a higher value binds tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Mod => 6,
+            _ => 4,
+        }
+    }
+
+    /// Whether the operator computes a number rather than a truth value.
+    pub fn is_arithmetic(self) -> bool {
+        self.precedence() >= 5
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+            Self::Eq => "=",
+            Self::NotEq => "!=",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::And => "AND",
+            Self::Or => "OR",
+        };
+        f.write_str(symbol)
+    }
+}
+
+/// Aggregate functions usable in the select list. Without `GROUP BY` they fold the whole
+/// table into a single row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COUNT" => Some(Self::Count),
+            "SUM" => Some(Self::Sum),
+            "AVG" => Some(Self::Avg),
+            "MIN" => Some(Self::Min),
+            "MAX" => Some(Self::Max),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Count => "COUNT",
+            Self::Sum => "SUM",
+            Self::Avg => "AVG",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Functions computed over a window of rows rather than a single one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    /// The position of the row in its partition, from 1.
+    RowNumber,
+    /// The position of the first row of the partition with the same `ORDER BY` values, so
+    /// ties share a rank and leave a gap after them.
+    Rank,
+    /// Like `Rank`, but without the gaps.
+    DenseRank,
+}
+
+impl WindowFunction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "ROW_NUMBER" => Some(Self::RowNumber),
+            "RANK" => Some(Self::Rank),
+            "DENSE_RANK" => Some(Self::DenseRank),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WindowFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::RowNumber => "ROW_NUMBER",
+            Self::Rank => "RANK",
+            Self::DenseRank => "DENSE_RANK",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Functions that may be written without parentheses, as in standard SQL.
+const NILADIC_FUNCTIONS: [&str; 3] = ["CURRENT_DATE", "CURRENT_TIMESTAMP", "CURRENT_USER"];
+
+impl Expr {
+    /// Whether the expression contains an aggregate call anywhere inside it.
+    pub fn is_aggregate(&self) -> bool {
+        match self {
+            // Aggregates inside a subquery fold the subquery's rows, not ours.
+            Expr::Column(_)
+            | Expr::Literal(_)
+            | Expr::Variable(_)
+            | Expr::Exists(_)
+            | Expr::Subquery(_)
+            | Expr::Window { .. } => false,
+            Expr::Function { args, .. } => args.iter().any(Expr::is_aggregate),
+            Expr::Aggregate { .. } => true,
+            Expr::Binary { left, right, .. } => left.is_aggregate() || right.is_aggregate(),
+            Expr::Not(inner)
+            | Expr::Negate(inner)
+            | Expr::IsNull { expr: inner, .. }
+            | Expr::Cast { expr: inner, .. } => inner.is_aggregate(),
+            Expr::InList { expr, list, .. } => {
+                expr.is_aggregate() || list.iter().any(Expr::is_aggregate)
+            }
+        }
+    }
+
+    /// Whether the expression contains an `EXISTS` subquery, which needs the database to
+    /// evaluate.
+    pub fn has_subquery(&self) -> bool {
+        match self {
+            Expr::Column(_) | Expr::Literal(_) | Expr::Variable(_) | Expr::Window { .. } => false,
+            Expr::Exists(_) | Expr::Subquery(_) => true,
+            Expr::Function { args, .. } => args.iter().any(Expr::has_subquery),
+            Expr::Aggregate { arg, .. } => arg.as_deref().is_some_and(Expr::has_subquery),
+            Expr::Binary { left, right, .. } => left.has_subquery() || right.has_subquery(),
+            Expr::Not(inner)
+            | Expr::Negate(inner)
+            | Expr::IsNull { expr: inner, .. }
+            | Expr::Cast { expr: inner, .. } => inner.has_subquery(),
+            Expr::InList { expr, list, .. } => {
+                expr.has_subquery() || list.iter().any(Expr::has_subquery)
+            }
+        }
+    }
+
+    /// Whether the expression contains a window function anywhere inside it.
+    pub fn has_window(&self) -> bool {
+        match self {
+            Expr::Column(_)
+            | Expr::Literal(_)
+            | Expr::Variable(_)
+            | Expr::Exists(_)
+            | Expr::Subquery(_) => false,
+            Expr::Window { .. } => true,
+            Expr::Function { args, .. } => args.iter().any(Expr::has_window),
+            Expr::Aggregate { arg, .. } => arg.as_deref().is_some_and(Expr::has_window),
+            Expr::Binary { left, right, .. } => left.has_window() || right.has_window(),
+            Expr::Not(inner)
+            | Expr::Negate(inner)
+            | Expr::IsNull { expr: inner, .. }
+            | Expr::Cast { expr: inner, .. } => inner.has_window(),
+            Expr::InList { expr, list, .. } => {
+                expr.has_window() || list.iter().any(Expr::has_window)
+            }
+        }
+    }
+
+    /// Binding strength used to decide where `Display` needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary { op, .. } => op.precedence(),
+            Expr::Not(_) => 3,
+            Expr::Negate(_) => 7,
+            Expr::InList { .. } | Expr::IsNull { .. } => 4,
+            _ => u8::MAX,
+        }
+    }
+}
+
+/// Writes `expr`, parenthesized if it binds looser than its surrounding operator.
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expr, min_precedence: u8) -> fmt::Result {
+    if expr.precedence() < min_precedence {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+/// Renders the expression back as SQL; also used as the result header.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Column(name) => f.write_str(name),
+            Expr::Literal(Value::Text(text)) => write!(f, "{}", quote(text)),
+            Expr::Literal(Value::Integer(n)) => write!(f, "{}", n),
+            Expr::Literal(Value::BigInt(n)) => write!(f, "{}", n),
+            Expr::Literal(Value::Real(r)) => write!(f, "{}", r),
+            Expr::Literal(Value::Decimal(d)) => write!(f, "{}", d),
+            Expr::Literal(Value::Date(d)) => write!(f, "DATE '{}'", d),
+            Expr::Literal(Value::Timestamp(t)) => write!(f, "TIMESTAMP '{}'", t),
+            Expr::Literal(Value::Null) => f.write_str("NULL"),
+            Expr::Variable(name) => write!(f, "@{}", name),
+            Expr::Function { name, args }
+                if args.is_empty()
+                    && matches!(name.as_str(), "CURRENT_DATE" | "CURRENT_TIMESTAMP") =>
+            {
+                f.write_str(name)
+            }
+            Expr::Function { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expr::Aggregate { func, arg: None } => write!(f, "{}(*)", func),
+            Expr::Aggregate {
+                func,
+                arg: Some(arg),
+            } => write!(f, "{}({})", func, arg),
+            Expr::Binary { left, op, right } => {
+                // Comparisons don't chain, so a compound operand of one is always parenthesized.
+                let (left_min, right_min) = match op.precedence() {
+                    4 => (5, 5),
+                    p => (p, p + 1),
+                };
+                write_operand(f, left, left_min)?;
+                write!(f, " {} ", op)?;
+                write_operand(f, right, right_min)
+            }
+            Expr::Not(inner) => {
+                f.write_str("NOT ")?;
+                write_operand(f, inner, 3)
+            }
+            // `--` would start a comment.
+            Expr::Negate(inner) if inner.to_string().starts_with('-') => write!(f, "-({})", inner),
+            Expr::Negate(inner) => {
+                f.write_str("-")?;
+                write_operand(f, inner, 7)
+            }
+            Expr::IsNull { expr, negated } => {
+                write_operand(f, expr, 5)?;
+                f.write_str(if *negated { " IS NOT NULL" } else { " IS NULL" })
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                write_operand(f, expr, 5)?;
+                let list: Vec<String> = list.iter().map(|e| e.to_string()).collect();
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, " {}IN ({})", not, list.join(", "))
+            }
+            Expr::Cast { expr, data_type } => write!(f, "CAST({} AS {})", expr, data_type),
+            Expr::Exists(subquery) => write!(f, "EXISTS ({})", subquery),
+            Expr::Subquery(subquery) => write!(f, "({})", subquery),
+            Expr::Window {
+                func,
+                partition_by,
+                order_by,
+            } => {
+                let mut clauses = Vec::new();
+                if !partition_by.is_empty() {
+                    clauses.push(format!("PARTITION BY {}", partition_by.join(", ")));
+                }
+                if !order_by.is_empty() {
+                    let keys: Vec<String> = order_by
+                        .iter()
+                        .map(|o| format!("{}{}", o.column, if o.descending { " DESC" } else { "" }))
+                        .collect();
+                    clauses.push(format!("ORDER BY {}", keys.join(", ")));
+                }
+                write!(f, "{}() OVER ({})", func, clauses.join(" "))
+            }
+        }
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::slice::Iter<'a, String>>;
+
+/// Parses a full expression: operands and their unary `-`, combined with `*`, `/` and `%`,
+/// then `+` and `-`, comparisons, `[NOT] IN`, `IS [NOT] NULL`, `NOT`, `AND` and `OR`, binding
+/// in that order from tightest to loosest. Parentheses group explicitly.
+pub(super) fn parse_expr(iter: &mut Tokens) -> Result<Expr, String> {
+    parse_binary(iter, 1)
+}
+
+/// Precedence climbing over the `AND`/`OR` connectives.
+fn parse_binary(iter: &mut Tokens, min_precedence: u8) -> Result<Expr, String> {
+    let mut left = parse_not(iter)?;
+
+    while let Some(op) = iter.peek().and_then(|t| BinaryOperator::from_token(t)) {
+        if op.precedence() < min_precedence || op.precedence() > 2 {
+            break;
+        }
+        iter.next();
+        let right = parse_binary(iter, op.precedence() + 1)?;
+        left = Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+    Ok(left)
+}
+
+fn parse_not(iter: &mut Tokens) -> Result<Expr, String> {
+    if iter.peek().map(|t| t.to_uppercase()).as_deref() == Some("NOT") {
+        iter.next();
+        return Ok(Expr::Not(Box::new(parse_not(iter)?)));
+    }
+    parse_comparison(iter)
+}
+
+/// Parses an operand optionally followed by one comparison, `[NOT] IN (...)` list or
+/// `IS [NOT] NULL` test.
+fn parse_comparison(iter: &mut Tokens) -> Result<Expr, String> {
+    let left = parse_arithmetic(iter, 5)?;
+
+    if iter.peek().map(|t| t.to_uppercase()).as_deref() == Some("IS") {
+        iter.next();
+        let negated = iter.peek().map(|t| t.to_uppercase()).as_deref() == Some("NOT");
+        if negated {
+            iter.next();
+        }
+        if iter.next().map(|t| t.to_uppercase()).as_deref() != Some("NULL") {
+            return Err("Expected NULL after IS".into());
+        }
+        return Ok(Expr::IsNull {
+            expr: Box::new(left),
+            negated,
+        });
+    }
+
+    let negated = iter.peek().map(|t| t.to_uppercase()).as_deref() == Some("NOT");
+    if negated {
+        iter.next();
+        if iter.peek().map(|t| t.to_uppercase()).as_deref() != Some("IN") {
+            return Err("Expected IN after NOT".into());
+        }
+    }
+    if iter.peek().map(|t| t.to_uppercase()).as_deref() == Some("IN") {
+        iter.next();
+        return Ok(Expr::InList {
+            expr: Box::new(left),
+            list: parse_in_list(iter)?,
+            negated,
+        });
+    }
+
+    match iter.peek().and_then(|t| BinaryOperator::from_token(t)) {
+        Some(op) if op.precedence() == 4 => {
+            iter.next();
+            let right = parse_arithmetic(iter, 5)?;
+            Ok(Expr::Binary {
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            })
+        }
+        _ => Ok(left),
+    }
+}
+
+/// Precedence climbing over the arithmetic operators, which all associate to the left.
+fn parse_arithmetic(iter: &mut Tokens, min_precedence: u8) -> Result<Expr, String> {
+    let mut left = parse_unary(iter)?;
+
+    while let Some(op) = iter.peek().and_then(|t| BinaryOperator::from_token(t)) {
+        if !op.is_arithmetic() || op.precedence() < min_precedence {
+            break;
+        }
+        iter.next();
+        let right = parse_arithmetic(iter, op.precedence() + 1)?;
+        left = Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+    Ok(left)
+}
+
+/// Parses an operand with any number of `-` and `+` signs in front. A sign on a number is
+/// part of the literal, so `-2147483648` is an `INT`.
+fn parse_unary(iter: &mut Tokens) -> Result<Expr, String> {
+    let Some(sign) = iter.next_if(|t| matches!(t.as_str(), "-" | "+")) else {
+        return parse_operand(iter);
+    };
+    if let Some(number) = iter.next_if(|t| is_signed_number(sign, t)) {
+        let literal = match sign.as_str() {
+            "-" => format!("-{}", number),
+            _ => number.clone(),
+        };
+        return Ok(Expr::Literal(parse_literal(&literal)?));
+    }
+    let operand = parse_unary(iter)?;
+    Ok(match sign.as_str() {
+        "-" => Expr::Negate(Box::new(operand)),
+        _ => operand,
+    })
+}
+
+/// Parses the parenthesized, comma separated list following `IN`.
+fn parse_in_list(iter: &mut Tokens) -> Result<Vec<Expr>, String> {
+    if iter.next().map(|s| s.as_str()) != Some("(") {
+        return Err("Expected '(' after IN".into());
+    }
+
+    let mut list = Vec::new();
+    loop {
+        list.push(parse_expr(iter)?);
+        match iter.next().map(|s| s.as_str()) {
+            Some(",") => continue,
+            Some(")") => return Ok(list),
+            _ => return Err("Expected ')' after IN list".into()),
+        }
+    }
+}
+
+/// Whether `token` is a column name, optionally qualified by a table name or alias:
+/// `column` or `table.column`, where each part may be `"quoted"`.
+pub(crate) fn is_column_reference(token: &str) -> bool {
+    let is_identifier = |part: &str| {
+        let quoted = part.len() > 2 && part.starts_with('"') && part.ends_with('"');
+        quoted || !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    let parts = name_parts(token);
+    parts.len() <= 2 && parts.into_iter().all(is_identifier)
+}
+
+/// Parses the rest of `CAST(expr AS type)`, after the opening parenthesis.
+fn parse_cast(iter: &mut Tokens) -> Result<Expr, String> {
+    let expr = parse_expr(iter)?;
+    if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case("AS")) {
+        return Err("Expected AS in CAST".into());
+    }
+    let data_type = match iter.next().map(|t| t.to_uppercase()).as_deref() {
+        Some("INT" | "INTEGER") => "INT".to_string(),
+        Some("BIGINT") => "BIGINT".to_string(),
+        Some("REAL" | "FLOAT" | "DOUBLE") => "REAL".to_string(),
+        Some("DECIMAL" | "NUMERIC") => parse_decimal_type(iter)?,
+        Some("DATE") => "DATE".to_string(),
+        Some("TIMESTAMP" | "DATETIME") => "TIMESTAMP".to_string(),
+        Some("TEXT") => "TEXT".to_string(),
+        Some(other) => {
+            return Err(format!(
+                "Cannot CAST to {}: expected INT, BIGINT, REAL, DECIMAL, DATE, TIMESTAMP or TEXT",
+                other
+            ));
+        }
+        None => return Err("Expected a type after AS in CAST".into()),
+    };
+    if iter.next().map(|s| s.as_str()) != Some(")") {
+        return Err("Expected ')' after CAST type".into());
+    }
+    Ok(Expr::Cast {
+        expr: Box::new(expr),
+        data_type,
+    })
+}
+
+/// Parses a single operand: a literal, `@variable`, function, aggregate or window function
+/// call, a column, an `EXISTS (SELECT ...)` test, or a parenthesized expression.
+fn parse_operand(iter: &mut Tokens) -> Result<Expr, String> {
+    let token = iter.next().ok_or("Expected expression")?;
+
+    if token == "(" {
+        if iter
+            .peek()
+            .is_some_and(|t| t.eq_ignore_ascii_case("SELECT"))
+        {
+            return Ok(Expr::Subquery(Box::new(parse_subquery_body(iter)?)));
+        }
+        let inner = parse_expr(iter)?;
+        if iter.next().map(|s| s.as_str()) != Some(")") {
+            return Err("Expected ')' after expression".into());
+        }
+        return Ok(inner);
+    }
+
+    if let Some(name) = token.strip_prefix('@') {
+        if name.is_empty() {
+            return Err("Expected variable name after '@'".into());
+        }
+        return Ok(Expr::Variable(name.to_string()));
+    }
+    if token.starts_with('\'')
+        || is_integer(token)
+        || parse_decimal(token).is_some()
+        || token.eq_ignore_ascii_case("NULL")
+    {
+        return Ok(Expr::Literal(parse_literal(token)?));
+    }
+    // `DATE '2024-01-31'` and `TIMESTAMP '2024-01-31T12:00:00'`.
+    if let Some(text) = iter.peek().and_then(|t| t.strip_prefix('\'')) {
+        let text = text.strip_suffix('\'').unwrap_or(text);
+        let value = match token.to_uppercase().as_str() {
+            "DATE" => Some(Date::parse(text).map(Value::Date)),
+            "TIMESTAMP" => Some(Timestamp::parse(text).map(Value::Timestamp)),
+            _ => None,
+        };
+        if let Some(value) = value {
+            iter.next();
+            return value
+                .map(Expr::Literal)
+                .ok_or_else(|| format!("Invalid {} literal: '{}'", token.to_uppercase(), text));
+        }
+    }
+
+    if token.eq_ignore_ascii_case("EXISTS") && iter.peek().map(|s| s.as_str()) == Some("(") {
+        return Ok(Expr::Exists(Box::new(parse_subquery(iter)?)));
+    }
+    if token.eq_ignore_ascii_case("CAST") && iter.peek().map(|s| s.as_str()) == Some("(") {
+        iter.next();
+        return parse_cast(iter);
+    }
+
+    if iter.peek().map(|s| s.as_str()) != Some("(") {
+        let upper = token.to_uppercase();
+        if NILADIC_FUNCTIONS.contains(&upper.as_str()) {
+            return Ok(Expr::Function {
+                name: upper,
+                args: vec![],
+            });
+        }
+        if !is_column_reference(token) {
+            return Err(format!("Invalid column reference: {}", token));
+        }
+        return Ok(Expr::Column(identifier(token)));
+    }
+    iter.next();
+
+    if let Some(func) = AggregateFunction::from_name(token) {
+        let arg = if iter.peek().map(|s| s.as_str()) == Some("*") {
+            if func != AggregateFunction::Count {
+                return Err(format!("{}(*) is not supported", func));
+            }
+            iter.next();
+            None
+        } else {
+            Some(Box::new(parse_expr(iter)?))
+        };
+
+        if iter.next().map(|s| s.as_str()) != Some(")") {
+            return Err(format!("Expected ')' after {} argument", func));
+        }
+        return Ok(Expr::Aggregate { func, arg });
+    }
+    if let Some(func) = WindowFunction::from_name(token) {
+        return parse_window(iter, func);
+    }
+
+    let mut args = Vec::new();
+    if iter.peek().map(|s| s.as_str()) == Some(")") {
+        iter.next();
+    } else {
+        loop {
+            args.push(parse_expr(iter)?);
+            match iter.next().map(|s| s.as_str()) {
+                Some(",") => continue,
+                Some(")") => break,
+                _ => return Err(format!("Expected ')' after {} arguments", token)),
+            }
+        }
+    }
+
+    Ok(Expr::Function {
+        name: token.to_uppercase(),
+        args,
+    })
+}
+
+/// Parses `) OVER ([PARTITION BY <col>, ...] [ORDER BY <col> [ASC|DESC], ...])` following the
+/// opening parenthesis of a window function.
+fn parse_window(iter: &mut Tokens, func: WindowFunction) -> Result<Expr, String> {
+    if iter.next().map(|s| s.as_str()) != Some(")") {
+        return Err(format!("{}() takes no arguments", func));
+    }
+    if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case("OVER"))
+        || iter.next().map(|s| s.as_str()) != Some("(")
+    {
+        return Err(format!("Expected OVER (...) after {}()", func));
+    }
+    let mut partition_by = Vec::new();
+    if iter
+        .peek()
+        .is_some_and(|t| t.eq_ignore_ascii_case("PARTITION"))
+    {
+        iter.next();
+        if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case("BY")) {
+            return Err("Expected BY after PARTITION".into());
+        }
+        loop {
+            let column = iter.next().ok_or("Expected column in PARTITION BY")?;
+            if !is_column_reference(column) {
+                return Err(format!("Invalid column reference: {}", column));
+            }
+            partition_by.push(identifier(column));
+            if iter.peek().map(|s| s.as_str()) != Some(",") {
+                break;
+            }
+            iter.next();
+        }
+    }
+    let mut order_by = Vec::new();
+    if iter.peek().is_some_and(|t| t.eq_ignore_ascii_case("ORDER")) {
+        iter.next();
+        order_by = parse_order_by(iter)?;
+    }
+    if iter.next().map(|s| s.as_str()) != Some(")") {
+        return Err(format!("Expected ')' after the OVER clause of {}()", func));
+    }
+    Ok(Expr::Window {
+        func,
+        partition_by,
+        order_by,
+    })
+}
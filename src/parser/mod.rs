@@ -1,65 +1,846 @@
-use crate::engine::Value;
+mod cache;
+mod expr;
+
+use std::fmt;
+
+use crate::engine::{
+    Value, decimal::MAX_PRECISION, foreign_keys::DeleteAction, real::parse_decimal,
+    triggers::TriggerEvent,
+};
+use crate::error::syntax_error::SyntaxError;
+pub use cache::{PreparedStatement, StatementCache, parse_with_params};
+pub(crate) use expr::is_column_reference;
+use expr::parse_expr;
+pub use expr::{AggregateFunction, BinaryOperator, Expr, WindowFunction};
 
 /// The structure resulting from a successfully parsed SQL string.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     CreateTable {
         name: String,
         columns: Vec<ColumnDefinition>,
+        /// Declared inline (`team_id INT REFERENCES teams (id)`) or after the columns
+        /// (`FOREIGN KEY (team_id) REFERENCES teams (id)`).
+        foreign_keys: Vec<ForeignKeyDefinition>,
+        /// `CREATE TABLE IF NOT EXISTS`: an existing table is left as it is.
+        if_not_exists: bool,
+    },
+    DropTable {
+        name: String,
+        /// `DROP TABLE IF EXISTS`: a missing table is not an error.
+        if_exists: bool,
     },
+    CreateIndex {
+        name: String,
+        table_name: String,
+        column: String,
+    },
+    DropIndex(String),
     Insert {
         table_name: String,
-        values: Vec<Value>,
+        rows: Vec<Vec<Value>>,
+        on_conflict: Option<OnConflict>,
+    },
+    Select(Box<SelectStatement>),
+    AlterTable {
+        table_name: String,
+        action: AlterAction,
+    },
+    CreateQuery {
+        name: String,
+        sql: String,
+    },
+    ExecuteQuery(String),
+    DropQuery(String),
+    ShowQueries,
+    CreateSchedule {
+        name: String,
+        sql: String,
+        interval_secs: u64,
     },
-    Select {
+    DropSchedule(String),
+    ShowSchedules,
+    /// `CREATE TRIGGER name AFTER INSERT|UPDATE|DELETE ON table AS <statement>`.
+    CreateTrigger {
+        name: String,
         table_name: String,
-        columns: Vec<String>,
-        join: Option<JoinDefinition>,
+        event: TriggerEvent,
+        sql: String,
+    },
+    DropTrigger(String),
+    ShowTriggers,
+    ShowStatus,
+    ShowIndexStats,
+    SetVariable {
+        name: String,
+        value: Value,
+    },
+    /// `COMMENT ON TABLE t IS '...'` or `COMMENT ON COLUMN t.c IS '...'`. `IS NULL` removes
+    /// the comment.
+    Comment {
+        target: CommentTarget,
+        comment: Option<String>,
+    },
+    /// `BEGIN [TRANSACTION]`: opens a transaction in the session. Until it ends, other
+    /// sessions can read, changes not yet committed included, but not write.
+    Begin,
+    /// `COMMIT`: ends the session's transaction, keeping its changes.
+    Commit,
+    /// `ROLLBACK`: ends the session's transaction, undoing its changes.
+    Rollback,
+    /// `SAVEPOINT name`: remembers the current state of the database in the session's
+    /// transaction.
+    Savepoint(String),
+    /// `ROLLBACK TO [SAVEPOINT] name`: undoes every change made since the savepoint, which
+    /// stays available.
+    RollbackToSavepoint(String),
+    /// `RELEASE [SAVEPOINT] name`: forgets the savepoint and those taken after it.
+    ReleaseSavepoint(String),
+    /// `EXPLAIN <statement>`: the plan of the statement, which is not run.
+    Explain(Box<Statement>),
+    /// `EXPLAIN ANALYZE <select>`: runs the query and reports the rows, bytes and time of each
+    /// operator instead of its result.
+    ExplainAnalyze(Box<SelectStatement>),
+    /// `ANALYZE [table]`: collects the column statistics the planner estimates row counts
+    /// and orders joins from, for the table or for every stored table.
+    Analyze(Option<String>),
+}
+
+impl Statement {
+    /// Whether the statement leaves the database as it is: queries (saved ones included),
+    /// plans, `SHOW` and session variables.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Statement::Select(_)
+                | Statement::ExecuteQuery(_)
+                | Statement::Explain(_)
+                | Statement::ExplainAnalyze(_)
+                | Statement::ShowQueries
+                | Statement::ShowSchedules
+                | Statement::ShowTriggers
+                | Statement::ShowStatus
+                | Statement::ShowIndexStats
+                | Statement::SetVariable { .. }
+        )
+    }
+}
+
+/// The schema change requested by an `ALTER TABLE` statement.
+#[derive(Debug, Clone)]
+pub enum AlterAction {
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+    RenameTable(String),
+}
+
+/// What a `COMMENT ON` statement documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentTarget {
+    Table(String),
+    Column { table: String, column: String },
+}
+
+/// The `ON CONFLICT` clause of an `INSERT`: what to do with a row whose PRIMARY or UNIQUE
+/// value is already taken.
+#[derive(Debug, Clone)]
+pub struct OnConflict {
+    /// The unique column whose conflicts are handled, `None` for any of them.
+    pub target: Option<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConflictAction {
+    /// Keep the existing row and drop the new one.
+    DoNothing,
+    /// `DO UPDATE SET column = expr, ...` on the existing row, where `excluded.column` is the
+    /// value of the row that was rejected.
+    DoUpdate(Vec<(String, Expr)>),
+}
+
+/// The clauses of a parsed `SELECT` query.
+#[derive(Debug, Clone)]
+pub struct SelectStatement {
+    /// Optimizer hints from a `/*+ ... */` comment right after `SELECT`.
+    pub hints: Vec<Hint>,
+    pub table_name: String,
+    pub table_args: Option<Vec<Value>>,
+    /// Query producing the source rows in `FROM (SELECT ...) AS alias`, whose alias is then
+    /// the `table_name`.
+    pub subquery: Option<Box<SelectStatement>>,
+    /// Name the source table is referred to by, e.g. `u` in `FROM users u`.
+    pub table_alias: Option<String>,
+    /// Column whose index must answer the `WHERE` clause, from `USE INDEX (<column>)`.
+    pub use_index: Option<String>,
+    pub distinct: bool,
+    pub columns: Vec<SelectItem>,
+    /// Joins applied left to right over the running result.
+    pub joins: Vec<JoinDefinition>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<String>,
+    pub order_by: Vec<OrderByDefinition>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl SelectStatement {
+    pub fn has_hint(&self, hint: Hint) -> bool {
+        self.hints.contains(&hint)
+    }
+
+    /// Whether the query folds rows into groups, either explicitly or via aggregate calls.
+    pub fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty()
+            || self
+                .columns
+                .iter()
+                .any(|c| matches!(c, SelectItem::Expr { expr, .. } if expr.is_aggregate()))
+    }
+
+    /// Rewrites `ORDER BY` keys that name the alias of a plain column to that column, so the
+    /// rows can be sorted before projection renames it.
+    pub fn resolve_order_aliases(&mut self) {
+        for key in &mut self.order_by {
+            let target = self.columns.iter().find_map(|item| match item {
+                SelectItem::Expr {
+                    expr: Expr::Column(name),
+                    alias: Some(alias),
+                } if *alias == key.column => Some(name),
+                _ => None,
+            });
+            if let Some(name) = target {
+                key.column = name.clone();
+            }
+        }
+    }
+}
+
+/// Renders the statement back as SQL, e.g. for the subquery of an `EXISTS` predicate.
+impl fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SELECT ")?;
+        if !self.hints.is_empty() {
+            let hints: Vec<String> = self.hints.iter().map(|h| h.to_string()).collect();
+            write!(f, "/*+ {} */ ", hints.join(" "))?;
+        }
+        if self.distinct {
+            f.write_str("DISTINCT ")?;
+        }
+        let columns: Vec<String> = self.columns.iter().map(|c| c.to_string()).collect();
+        f.write_str(&columns.join(", "))?;
+
+        match (&self.subquery, &self.table_args) {
+            (Some(subquery), _) => write!(f, " FROM ({}) AS {}", subquery, self.table_name)?,
+            // The implicit source of a select without FROM
+            (None, Some(args)) if args.is_empty() && self.table_name == "dual" => {}
+            (None, Some(args)) => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|a| Expr::Literal(a.clone()).to_string())
+                    .collect();
+                write!(f, " FROM {}({})", self.table_name, args.join(", "))?;
+            }
+            (None, None) => write!(f, " FROM {}", self.table_name)?,
+        }
+        if let Some(alias) = &self.table_alias {
+            write!(f, " AS {}", alias)?;
+        }
+        if let Some(column) = &self.use_index {
+            write!(f, " USE INDEX ({})", column)?;
+        }
+
+        for join in &self.joins {
+            let kind = match join.kind {
+                JoinKind::Inner => "JOIN",
+                JoinKind::Left => "LEFT JOIN",
+                JoinKind::Cross => "CROSS JOIN",
+            };
+            write!(f, " {} {}", kind, join.table_name)?;
+            if let Some(alias) = &join.alias {
+                write!(f, " AS {}", alias)?;
+            }
+            if let Some(on) = &join.on {
+                write!(f, " ON {} = {}", on.left_column, on.right_column)?;
+            }
+        }
+        if let Some(predicate) = &self.where_clause {
+            write!(f, " WHERE {}", predicate)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", self.group_by.join(", "))?;
+        }
+        if !self.order_by.is_empty() {
+            let keys: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|o| format!("{}{}", o.column, if o.descending { " DESC" } else { "" }))
+                .collect();
+            write!(f, " ORDER BY {}", keys.join(", "))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Overrides for the planner's choices, written as `SELECT /*+ HASH_JOIN NO_INDEX */ ...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    /// Join on a hash table built from the right side instead of a nested loop.
+    HashJoin,
+    /// Join with a nested loop, the default.
+    NestedLoopJoin,
+    /// Scan the whole table even when an index could answer the `WHERE` clause.
+    NoIndex,
+}
+
+impl std::str::FromStr for Hint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "HASH_JOIN" => Ok(Self::HashJoin),
+            "NESTED_LOOP_JOIN" | "NL_JOIN" => Ok(Self::NestedLoopJoin),
+            "NO_INDEX" => Ok(Self::NoIndex),
+            other => Err(format!("Unknown optimizer hint: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::HashJoin => "HASH_JOIN",
+            Self::NestedLoopJoin => "NESTED_LOOP_JOIN",
+            Self::NoIndex => "NO_INDEX",
+        })
+    }
+}
+
+/// One entry of the select list.
+#[derive(Debug, Clone)]
+pub enum SelectItem {
+    Wildcard,
+    /// An expression, optionally renamed with `AS <alias>`.
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
     },
 }
 
+impl SelectItem {
+    /// Whether the item calls a window function.
+    pub fn has_window(&self) -> bool {
+        matches!(self, SelectItem::Expr { expr, .. } if expr.has_window())
+    }
+
+    /// The header this item produces when it is not a plain column: the alias if given,
+    /// otherwise the expression text.
+    pub fn header(&self) -> String {
+        match self {
+            SelectItem::Wildcard => "*".into(),
+            SelectItem::Expr {
+                alias: Some(alias), ..
+            } => alias.clone(),
+            SelectItem::Expr { expr, alias: None } => expr.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => f.write_str("*"),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+            SelectItem::Expr {
+                expr,
+                alias: Some(alias),
+            } => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+
 /// Metadata for creating a new column via SQL.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ColumnDefinition {
     pub name: String,
     pub data_type: String,
     pub is_primary: bool,
     pub is_unique: bool,
+    /// `MASKED`: the column reads as a mask unless the session may unmask it.
+    pub is_masked: bool,
+    /// The `n` of `VARCHAR(n)`, the most characters a value may have.
+    pub max_length: Option<usize>,
+    /// `NOT NULL`.
+    pub is_not_null: bool,
+}
+
+/// `column REFERENCES parent_table (parent_column) [ON DELETE action]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyDefinition {
+    pub column: String,
+    pub parent_table: String,
+    pub parent_column: String,
+    pub on_delete: DeleteAction,
 }
 
-/// Metadata for performing an INNER JOIN.
-#[derive(Debug)]
+/// Metadata for performing a join.
+#[derive(Debug, Clone)]
 pub struct JoinDefinition {
+    pub kind: JoinKind,
     pub table_name: String,
+    /// Name the joined table is referred to by, e.g. `t` in `JOIN teams t`.
+    pub alias: Option<String>,
+    /// The `ON left = right` equality; `None` only for a `CROSS JOIN`.
+    pub on: Option<JoinCondition>,
+}
+
+/// The column equality a join matches rows on.
+#[derive(Debug, Clone)]
+pub struct JoinCondition {
     pub left_column: String,
     pub right_column: String,
 }
 
-/// Splits the raw SQL string into tokens while handling parentheses and commas.
-fn tokenize(input: &str) -> Vec<String> {
-    input
-        .replace('(', " ( ")
-        .replace(')', " ) ")
-        .replace(',', " , ")
-        .split_whitespace()
-        .map(|s| s.to_string())
+/// How the rows of the two sides of a join are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only rows with a match on both sides.
+    Inner,
+    /// Every left row; those without a match get `NULL` for the right columns.
+    Left,
+    /// Every combination of a left and a right row.
+    Cross,
+}
+
+/// A single sort key of an `ORDER BY` clause.
+#[derive(Debug, Clone)]
+pub struct OrderByDefinition {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// The characters of `sql` outside `-- line` and `/* block */` comments, with their byte
+/// offsets and the quote of the string literal (`'`) or quoted identifier (`"`) they belong
+/// to, quotes included. Optimizer hints (`/*+ ... */`) are kept, and comment markers inside
+/// quotes are text. A block comment left open ends the characters with its opening `/`,
+/// marked with the quote `*`.
+fn code_chars(sql: &str) -> impl Iterator<Item = (usize, char, Option<char>)> + '_ {
+    let mut chars = sql.char_indices().peekable();
+    let mut quote = None;
+    std::iter::from_fn(move || {
+        loop {
+            let (i, c) = chars.next()?;
+            if quote.is_none() && matches!(c, '\'' | '"') {
+                quote = Some(c);
+                return Some((i, c, quote));
+            }
+            if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                }
+                return Some((i, c, Some(q)));
+            }
+            let next = chars.peek().map(|&(_, n)| n);
+            if c == '-' && next == Some('-') {
+                while chars.next_if(|&(_, n)| n != '\n').is_some() {}
+            } else if c == '/' && next == Some('*') && !sql[i..].starts_with("/*+") {
+                chars.next();
+                let mut previous = ' ';
+                let mut closed = false;
+                for (_, n) in chars.by_ref() {
+                    if previous == '*' && n == '/' {
+                        closed = true;
+                        break;
+                    }
+                    previous = n;
+                }
+                if !closed {
+                    return Some((i, c, Some('*')));
+                }
+            } else {
+                return Some((i, c, None));
+            }
+        }
+    })
+}
+
+/// The tokens of a statement with the byte offset each starts at, so errors can point at them.
+pub(crate) struct Tokens {
+    pub(crate) text: Vec<String>,
+    offsets: Vec<usize>,
+    /// Where the statement ends, for errors found past its last token.
+    end: usize,
+}
+
+impl Tokens {
+    /// Parses the tokens into a statement. An error points at the last token read, or past
+    /// the end when the parser ran out of them.
+    pub(crate) fn parse(&self) -> Result<Statement, SyntaxError> {
+        let mut iter = self.text.iter().peekable();
+        parse_statement(&mut iter).map_err(|message| {
+            let read = self.text.len() - iter.len();
+            let position = match read {
+                0 => self.offsets.first().copied().unwrap_or(self.end),
+                n if n == self.text.len() && self.ran_out() => self.end,
+                n => self.offsets[n - 1],
+            };
+            SyntaxError::new(message, position)
+        })
+    }
+
+    /// Whether a parse that failed after reading every token failed for want of another
+    /// rather than on the last one: given one more, the parser reads it too. The extra token
+    /// is a line break, which `tokenize` never yields.
+    fn ran_out(&self) -> bool {
+        let mut text = self.text.clone();
+        text.push("\n".to_string());
+        let mut iter = text.iter().peekable();
+        parse_statement(&mut iter).is_ok() || iter.len() == 0
+    }
+}
+
+/// Operators of more than one character, and the markers of an optimizer hint, lexed whole.
+const COMPOUND_OPERATORS: [&str; 6] = ["/*+", "*/", "<=", ">=", "<>", "!="];
+
+/// Splits the raw SQL string into tokens: words, parentheses, commas, operators and string
+/// literals. Operators need no spaces around them, so `a+1>=b` is `a`, `+`, `1`, `>=`, `b`.
+/// A literal is one token with its quotes, whatever it holds, so `'John Doe (admin)'` stays
+/// whole, and so does `'O''Brien'`, where a doubled quote stands for one. Comments are
+/// dropped, separating the tokens around them. A `"quoted identifier"` is kept in its word
+/// the same way, quotes included; see `identifier`. Signs of numbers are read by
+/// `attach_signs`. A quote left open at the end is an error pointing at it.
+fn tokenize(input: &str) -> Result<Tokens, SyntaxError> {
+    let mut tokens = Tokens {
+        text: Vec::new(),
+        offsets: Vec::new(),
+        end: input.trim_end().len(),
+    };
+    fn flush(tokens: &mut Tokens, token: &mut String, start: usize) {
+        if !token.is_empty() {
+            tokens.text.push(std::mem::take(token));
+            tokens.offsets.push(start);
+        }
+    }
+
+    let mut token = String::new();
+    let mut start = 0;
+    let mut in_literal = false;
+    // Where the open quote started, and where the last one started and closed; a doubled
+    // quote reopens the same one.
+    let (mut open_quote, mut closed_quote) = (None, None);
+    let mut next = 0;
+    // Characters left of a compound operator already pushed.
+    let mut skip = 0;
+    for (i, c, quote) in code_chars(input) {
+        if quote == Some('*') {
+            return Err(SyntaxError::new("Unterminated comment", i));
+        }
+        if skip > 0 {
+            skip -= 1;
+            next = i + c.len_utf8();
+            continue;
+        }
+        if quote == Some(c) {
+            open_quote = match (open_quote, closed_quote) {
+                (Some(opened), _) => {
+                    closed_quote = Some((opened, i));
+                    None
+                }
+                (None, Some((opened, closed))) if closed + 1 == i => Some(opened),
+                (None, _) => Some(i),
+            };
+        }
+        if i != next {
+            flush(&mut tokens, &mut token, start);
+        }
+        next = i + c.len_utf8();
+        // Outside a literal, a quote starts a new token unless it follows a closed literal
+        // directly, making an escaped `''`; anything else ends the closed literal.
+        let literal_quote = c == '\'' && quote == Some('\'');
+        if !in_literal && token.starts_with('\'') != literal_quote {
+            flush(&mut tokens, &mut token, start);
+        }
+        if token.is_empty() {
+            start = i;
+        }
+        if literal_quote {
+            in_literal = !in_literal;
+        }
+        if quote.is_some() {
+            token.push(c);
+        } else if c.is_whitespace() {
+            flush(&mut tokens, &mut token, start);
+        } else if matches!(c, '(' | ')' | ',') {
+            flush(&mut tokens, &mut token, start);
+            token.push(c);
+            flush(&mut tokens, &mut token, i);
+        } else if "=<>!+-*/%".contains(c) {
+            flush(&mut tokens, &mut token, start);
+            match COMPOUND_OPERATORS
+                .iter()
+                .find(|op| input[i..].starts_with(*op))
+            {
+                Some(op) => {
+                    token.push_str(op);
+                    skip = op.len() - 1;
+                }
+                None => token.push(c),
+            }
+            flush(&mut tokens, &mut token, i);
+        } else {
+            token.push(c);
+        }
+    }
+    flush(&mut tokens, &mut token, start);
+    if let Some(position) = open_quote {
+        let what = match input[position..].starts_with('\'') {
+            true => "string literal",
+            false => "quoted identifier",
+        };
+        return Err(SyntaxError::new(format!("Unterminated {}", what), position));
+    }
+    Ok(attach_signs(tokens))
+}
+
+/// Reads a `-` or `+` before a number as its sign where a value starts: first, or after `(`,
+/// `,` or an operator. So `(- 5)` and `(-5)` both hold the literal `-5`, which fits even
+/// where the parser expects a single token. After a value, a sign is the operator instead,
+/// and `x -5` reads as `x - 5`; after a keyword such as `SELECT` too, where `parse_unary`
+/// reads the sign.
+fn attach_signs(tokens: Tokens) -> Tokens {
+    let mut signed = Tokens {
+        text: Vec::with_capacity(tokens.text.len()),
+        offsets: Vec::with_capacity(tokens.offsets.len()),
+        end: tokens.end,
+    };
+    let mut iter = tokens.text.into_iter().zip(tokens.offsets).peekable();
+    while let Some((token, offset)) = iter.next() {
+        let after_value = signed.text.last().is_some_and(|t| {
+            t == ")" || t.starts_with(|c: char| c.is_alphanumeric() || "_'\"@".contains(c))
+        });
+        let (sign, number) = match token.split_at_checked(1) {
+            Some((sign @ ("-" | "+"), "")) if !after_value => {
+                match iter.next_if(|(t, _)| is_signed_number(sign, t)) {
+                    Some((number, _)) => (sign, number),
+                    None => ("", token),
+                }
+            }
+            Some((sign @ ("-" | "+"), number)) if is_signed_number(sign, number) => {
+                (sign, number.to_string())
+            }
+            _ => ("", token),
+        };
+        if sign.is_empty() {
+            signed.text.push(number);
+            signed.offsets.push(offset);
+        } else if after_value {
+            signed.text.extend([sign.to_string(), number]);
+            signed.offsets.extend([offset, offset + 1]);
+        } else {
+            let negative = sign == "-";
+            signed.text.push(if negative {
+                format!("-{}", number)
+            } else {
+                number
+            });
+            signed.offsets.push(offset);
+        }
+    }
+    signed
+}
+
+/// Entry point for the SQL parser. Converts raw text holding one statement, optionally ended
+/// by a `;`, into a Statement; see `parse_script` for several. Errors carry the position of
+/// the token they point at.
+pub fn parse(input: &str) -> Result<Statement, SyntaxError> {
+    statement_tokens(input)?.parse()
+}
+
+/// Parses a script of statements separated by `;`s, such as a SQL file, in order. `;`s inside
+/// string literals and comments don't separate statements. Fails on the first statement that
+/// doesn't parse, naming it, with the position counted from the start of the script.
+pub fn parse_script(sql: &str) -> Result<Vec<Statement>, SyntaxError> {
+    split_statements(sql)
+        .into_iter()
+        .enumerate()
+        .map(|(i, statement)| {
+            parse(statement).map_err(|e| SyntaxError {
+                message: format!("Statement {}: {}", i + 1, e.message),
+                position: e.position.map(|p| offset_in(sql, statement) + p),
+            })
+        })
+        .collect()
+}
+
+/// The byte offset of `part`, a slice of `sql`, within it.
+fn offset_in(sql: &str, part: &str) -> usize {
+    part.as_ptr() as usize - sql.as_ptr() as usize
+}
+
+/// The tokens of the one statement in `sql`, with offsets into `sql`. More than one statement
+/// is an error rather than trailing tokens to ignore.
+pub(crate) fn statement_tokens(sql: &str) -> Result<Tokens, SyntaxError> {
+    match split_statements(sql)[..] {
+        [statement] => {
+            let base = offset_in(sql, statement);
+            let mut tokens = tokenize(statement).map_err(|e| SyntaxError {
+                position: e.position.map(|p| p + base),
+                ..e
+            })?;
+            tokens.offsets.iter_mut().for_each(|offset| *offset += base);
+            tokens.end += base;
+            Ok(tokens)
+        }
+        [] => Err(SyntaxError::from("Empty query".to_string())),
+        ref statements @ [_, second, ..] => Err(SyntaxError::new(
+            format!("Expected a single statement, found {}", statements.len()),
+            offset_in(sql, second),
+        )),
+    }
+}
+
+/// Splits a script into its statements on the `;`s outside string literals and comments,
+/// dropping those holding nothing but comments.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for (i, c, quote) in code_chars(sql) {
+        if c == ';' && quote.is_none() {
+            statements.push(&sql[start..i]);
+            start = i + 1;
+        }
+    }
+    statements.push(&sql[start..]);
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|s| code_chars(s).any(|(_, c, _)| !c.is_whitespace()))
         .collect()
 }
 
-/// Entry point for the SQL parser. Converts raw text into a Statement.
-pub fn parse(input: &str) -> Result<Statement, String> {
-    let tokens = tokenize(input);
-    if tokens.is_empty() {
-        return Err("Empty query".into());
+/// Parses an already tokenized statement.
+fn parse_tokens(tokens: &[String]) -> Result<Statement, String> {
+    parse_statement(&mut tokens.iter().peekable())
+}
+
+/// Parses the next `len` tokens of `iter` on their own with `parse`, as for a nested
+/// statement, then moves `iter` past the tokens it read: all of them on success, up to the
+/// one at fault on error, so the error points at that token.
+fn parse_ahead<T>(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+    len: usize,
+    parse: impl FnOnce(&mut std::iter::Peekable<std::slice::Iter<String>>) -> Result<T, String>,
+) -> Result<T, String> {
+    let tokens: Vec<String> = iter.clone().take(len).cloned().collect();
+    let mut inner = tokens.iter().peekable();
+    let result = parse(&mut inner);
+    let read = match result {
+        Ok(_) => tokens.len(),
+        Err(_) => tokens.len() - inner.len(),
+    };
+    iter.by_ref().take(read).for_each(drop);
+    result
+}
+
+/// Parses the statement the tokens of `iter` hold, all of them: a token left over is an
+/// error, read so that it is the one the error points at.
+fn parse_statement(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let statement = parse_command(iter)?;
+    match iter.next() {
+        Some(token) => Err(format!("Unexpected token: {}", token)),
+        None => Ok(statement),
     }
+}
 
-    let mut iter = tokens.iter().peekable();
-    let command = iter.next().unwrap().to_uppercase();
+/// Parses the statement the tokens of `iter` start with, leaving it past the last token read.
+fn parse_command(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let command = iter.next().ok_or("Empty query")?.to_uppercase();
 
     match command.as_str() {
-        "CREATE" => parse_create(&mut iter),
-        "INSERT" => parse_insert(&mut iter),
-        "SELECT" => parse_select(&mut iter),
+        "CREATE" => parse_create(iter),
+        "INSERT" => parse_insert(iter),
+        "SELECT" => parse_select(iter),
+        "ALTER" => parse_alter(iter),
+        "EXECUTE" => {
+            let name = identifier(iter.next().ok_or("Expected query name")?);
+            Ok(Statement::ExecuteQuery(name))
+        }
+        "DROP" => parse_drop(iter),
+        "SHOW" => parse_show(iter),
+        "SET" => parse_set(iter),
+        "FIND" => parse_find_duplicates(iter),
+        "COMMENT" => parse_comment(iter),
+        "BEGIN" => {
+            parse_keywords(iter, &["TRANSACTION"])?;
+            Ok(Statement::Begin)
+        }
+        "COMMIT" => Ok(Statement::Commit),
+        "SAVEPOINT" => Ok(Statement::Savepoint(parse_savepoint_name(iter)?)),
+        "ROLLBACK" => {
+            let Some(next) = iter.next() else {
+                return Ok(Statement::Rollback);
+            };
+            if !next.eq_ignore_ascii_case("TO") {
+                return Err(
+                    "Expected TO SAVEPOINT or the end of the statement after ROLLBACK".into(),
+                );
+            }
+            parse_keywords(iter, &["SAVEPOINT"])?;
+            Ok(Statement::RollbackToSavepoint(parse_savepoint_name(iter)?))
+        }
+        "RELEASE" => {
+            parse_keywords(iter, &["SAVEPOINT"])?;
+            Ok(Statement::ReleaseSavepoint(parse_savepoint_name(iter)?))
+        }
+        "DESCRIBE" => {
+            let table = iter.next().ok_or("Expected table name")?;
+            if let Some(token) = iter.next() {
+                return Err(format!("Unexpected token: {}", token));
+            }
+            // SELECT * FROM pragma_table_info('<table>')
+            Ok(Statement::Select(Box::new(SelectStatement {
+                hints: Vec::new(),
+                table_name: "pragma_table_info".to_string(),
+                table_args: Some(vec![Value::Text(identifier(table))]),
+                subquery: None,
+                table_alias: None,
+                use_index: None,
+                distinct: false,
+                columns: vec![SelectItem::Wildcard],
+                joins: Vec::new(),
+                where_clause: None,
+                group_by: Vec::new(),
+                order_by: Vec::new(),
+                limit: None,
+                offset: None,
+            })))
+        }
+        "ANALYZE" => {
+            let table = iter.next().map(|token| identifier(token));
+            if let Some(token) = iter.next() {
+                return Err(format!("Unexpected token: {}", token));
+            }
+            Ok(Statement::Analyze(table))
+        }
+        "EXPLAIN" => {
+            let analyze = parse_keywords(iter, &["ANALYZE"])?;
+            let len = iter.len();
+            match parse_ahead(iter, len, parse_statement)? {
+                Statement::Select(select) if analyze => Ok(Statement::ExplainAnalyze(select)),
+                _ if analyze => Err("EXPLAIN ANALYZE only supports SELECT".into()),
+                statement => Ok(Statement::Explain(Box::new(statement))),
+            }
+        }
         _ => Err(format!("Unknown command: {}", command)),
     }
 }
@@ -77,17 +858,24 @@ pub fn parse(input: &str) -> Result<Statement, String> {
 fn parse_create(
     iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
 ) -> Result<Statement, String> {
-    if iter.next().map(|s| s.to_uppercase()) != Some("TABLE".to_string()) {
-        return Err("Expected TABLE after CREATE".into());
+    match iter.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("TABLE") => {}
+        Some("INDEX") => return parse_create_index(iter),
+        Some("QUERY") => return parse_create_query(iter),
+        Some("SCHEDULE") => return parse_create_schedule(iter),
+        Some("TRIGGER") => return parse_create_trigger(iter),
+        _ => return Err("Expected TABLE, INDEX, QUERY, SCHEDULE or TRIGGER after CREATE".into()),
     }
 
-    let name = iter.next().ok_or("Expected table name")?.clone();
+    let if_not_exists = parse_keywords(iter, &["IF", "NOT", "EXISTS"])?;
+    let name = identifier(iter.next().ok_or("Expected table name")?);
 
     if iter.next() != Some(&"(".to_string()) {
         return Err("Expected '('".into());
     }
 
     let mut columns = Vec::new();
+    let mut foreign_keys = Vec::new();
     while let Some(token) = iter.next() {
         if token == ")" {
             break;
@@ -95,12 +883,36 @@ fn parse_create(
         if token == "," {
             continue;
         }
+        if token.eq_ignore_ascii_case("FOREIGN") {
+            if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case("KEY")) {
+                return Err("Expected KEY after FOREIGN".into());
+            }
+            let column = parse_parenthesized_column(iter)?;
+            if !parse_keywords(iter, &["REFERENCES"])? {
+                return Err("Expected REFERENCES after FOREIGN KEY (column)".into());
+            }
+            foreign_keys.push(parse_references(iter, column)?);
+            continue;
+        }
 
-        let col_name = token.clone();
+        let col_name = identifier(token);
         let data_type = iter.next().ok_or("Expected column type")?.to_uppercase();
+        let mut max_length = None;
+        let data_type = match data_type.as_str() {
+            "FLOAT" | "DOUBLE" => "REAL".to_string(),
+            "DATETIME" => "TIMESTAMP".to_string(),
+            "DECIMAL" | "NUMERIC" => parse_decimal_type(iter)?,
+            "VARCHAR" => {
+                max_length = parse_varchar_length(iter)?;
+                data_type
+            }
+            _ => data_type,
+        };
 
         let mut is_primary = false;
         let mut is_unique = false;
+        let mut is_masked = false;
+        let mut is_not_null = false;
 
         while let Some(&next) = iter.peek() {
             match next.to_uppercase().as_str() {
@@ -112,6 +924,21 @@ fn parse_create(
                     is_unique = true;
                     iter.next();
                 }
+                "MASKED" => {
+                    is_masked = true;
+                    iter.next();
+                }
+                "NOT" => {
+                    iter.next();
+                    if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case("NULL")) {
+                        return Err("Expected NULL after NOT".into());
+                    }
+                    is_not_null = true;
+                }
+                "REFERENCES" => {
+                    iter.next();
+                    foreign_keys.push(parse_references(iter, col_name.clone())?);
+                }
                 "," | ")" => break,
                 _ => {
                     iter.next();
@@ -124,120 +951,1296 @@ fn parse_create(
             data_type,
             is_primary,
             is_unique,
+            is_masked,
+            max_length,
+            is_not_null,
         });
     }
 
-    Ok(Statement::CreateTable { name, columns })
+    Ok(Statement::CreateTable {
+        name,
+        columns,
+        foreign_keys,
+        if_not_exists,
+    })
 }
 
-/// Internal parser logic for the `INSERT INTO` statement.
-///
-/// ### How it works:
-/// 1. **Context Parsing**: Matches the boilerplate SQL syntax `INTO <table_name> VALUES`.
-/// 2. **Type Inference**: As it iterates through the values inside `(...)`, it attempts to
-///    categorize data types on the fly:
-///    - If a token can be parsed as a number (`token.parse::<i32>()`), it is stored as `Value::Integer`.
-///    - Otherwise, it is treated as a string and stored as `Value::Text`.
-/// 3. **Sanitization**: It strips single quotes `'` from text values to ensure
-///    the database stores the literal data, not the SQL formatting.
-fn parse_insert(
+/// Reads the `(precision[, scale])` that may follow `DECIMAL`, giving the type as stored in
+/// `Column::data_type`: `DECIMAL`, or `DECIMAL(p,s)` with the scale defaulting to 0.
+pub(crate) fn parse_decimal_type(
     iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
-) -> Result<Statement, String> {
-    if iter.next().map(|s| s.to_uppercase()) != Some("INTO".to_string()) {
-        return Err("Expected INTO after CREATE".into());
+) -> Result<String, String> {
+    if iter.next_if(|t| *t == "(").is_none() {
+        return Ok("DECIMAL".into());
+    }
+    let precision = iter
+        .next()
+        .and_then(|t| t.parse::<u32>().ok())
+        .filter(|p| (1..=MAX_PRECISION).contains(p))
+        .ok_or_else(|| format!("DECIMAL precision must be from 1 to {}", MAX_PRECISION))?;
+    let scale = if iter.next_if(|t| *t == ",").is_some() {
+        iter.next()
+            .and_then(|t| t.parse::<u32>().ok())
+            .filter(|s| *s <= precision)
+            .ok_or_else(|| format!("DECIMAL scale must be from 0 to {}", precision))?
+    } else {
+        0
+    };
+    if iter.next().map(|t| t.as_str()) != Some(")") {
+        return Err("Expected ')' after DECIMAL precision and scale".into());
     }
+    Ok(format!("DECIMAL({},{})", precision, scale))
+}
 
-    let name = iter.next().ok_or("Expected table name")?.clone();
-    if iter.next().map(|s| s.to_uppercase()) != Some("VALUES".to_string()) {
-        return Err("Expected VALUES after INTO".into());
+/// Reads the `(n)` that may follow `VARCHAR`, a length of at least 1.
+fn parse_varchar_length(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Option<usize>, String> {
+    if iter.next_if(|t| *t == "(").is_none() {
+        return Ok(None);
     }
+    let length = iter
+        .next()
+        .and_then(|t| t.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .ok_or("VARCHAR length must be a positive integer")?;
+    if iter.next().map(|t| t.as_str()) != Some(")") {
+        return Err("Expected ')' after VARCHAR length".into());
+    }
+    Ok(Some(length))
+}
 
-    if iter.next() != Some(&"(".to_string()) {
+/// Parses the `parent (column) [ON DELETE action]` following `REFERENCES` into the foreign
+/// key of `column`.
+fn parse_references(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+    column: String,
+) -> Result<ForeignKeyDefinition, String> {
+    let parent_table = identifier(iter.next().ok_or("Expected table name after REFERENCES")?);
+    let parent_column = parse_parenthesized_column(iter)?;
+    let on_delete = match parse_keywords(iter, &["ON", "DELETE"])? {
+        false => DeleteAction::NoAction,
+        true => match iter.next().map(|t| t.to_uppercase()).as_deref() {
+            Some("CASCADE") => DeleteAction::Cascade,
+            Some("SET") if parse_keywords(iter, &["NULL"])? => DeleteAction::SetNull,
+            Some("RESTRICT") => DeleteAction::NoAction,
+            Some("NO") if parse_keywords(iter, &["ACTION"])? => DeleteAction::NoAction,
+            _ => {
+                return Err(
+                    "Expected CASCADE, SET NULL, RESTRICT or NO ACTION after ON DELETE".into(),
+                );
+            }
+        },
+    };
+    Ok(ForeignKeyDefinition {
+        column,
+        parent_table,
+        parent_column,
+        on_delete,
+    })
+}
+
+/// Parses `(column)`.
+fn parse_parenthesized_column(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<String, String> {
+    if iter.next().map(String::as_str) != Some("(") {
         return Err("Expected '('".into());
     }
+    let column = identifier(iter.next().ok_or("Expected column name")?);
+    if iter.next().map(String::as_str) != Some(")") {
+        return Err("Expected ')' after the column name".into());
+    }
+    Ok(column)
+}
 
-    let mut values = Vec::new();
-    while let Some(token) = iter.next() {
-        if token == ")" {
-            break;
-        }
-        if token == "," {
-            continue;
-        }
-
-        if let Ok(num) = token.parse::<i32>() {
-            values.push(Value::Integer(num));
-        } else {
-            values.push(Value::Text(token.trim_matches('\'').to_string()));
+/// Consumes `keywords` if the next token is the first of them, e.g. `IF NOT EXISTS`, and
+/// tells whether they were there. Once the first matched, the others are required.
+fn parse_keywords(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+    keywords: &[&str],
+) -> Result<bool, String> {
+    if iter
+        .next_if(|t| t.eq_ignore_ascii_case(keywords[0]))
+        .is_none()
+    {
+        return Ok(false);
+    }
+    for keyword in &keywords[1..] {
+        if !iter.next().is_some_and(|t| t.eq_ignore_ascii_case(keyword)) {
+            return Err(format!("Expected {}", keywords.join(" ")));
         }
     }
+    Ok(true)
+}
 
-    Ok(Statement::Insert {
-        table_name: name,
-        values,
+/// Internal parser logic for `CREATE INDEX <name> ON <table> (<column>)`.
+fn parse_create_index(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let name = identifier(iter.next().ok_or("Expected index name")?);
+    if iter.next().map(|s| s.to_uppercase()) != Some("ON".to_string()) {
+        return Err("Expected ON after index name".into());
+    }
+    let table_name = identifier(iter.next().ok_or("Expected table name")?);
+    if iter.next().map(String::as_str) != Some("(") {
+        return Err("Expected '('".into());
+    }
+    let column = identifier(iter.next().ok_or("Expected column name")?);
+    if iter.next().map(String::as_str) != Some(")") {
+        return Err("Expected ')' after the indexed column".into());
+    }
+    Ok(Statement::CreateIndex {
+        name,
+        table_name,
+        column,
     })
 }
 
-/// Internal parser logic for the `SELECT` statement, including JOIN detection.
+/// Internal parser logic for `CREATE QUERY <name> AS <select>`.
 ///
-/// ### How it works:
-/// 1. **Column Selection**: Collects all tokens between `SELECT` and `FROM`. This supports
-///    both `*` (wildcard) and specific column lists (e.g., `id, name`).
-/// 2. **Source Table**: Identifies the primary table to query.
-/// 3. **Join Detection**: After the table name, it "peeks" ahead. If the next token is `JOIN`,
-///    it switches to "Join Mode":
-///    - It captures the secondary table name.
-///    - It skips the `ON` keyword.
-///    - It extracts the `left_column` and `right_column` used for the equality check.
-/// 4. **Encapsulation**: Returns a `Statement::Select` containing a `JoinDefinition`
-///    struct if a join was detected, otherwise `None`.
-fn parse_select(
+/// The remaining tokens are validated as a `SELECT` and stored as normalized SQL text,
+/// so the saved query is re-parsed against the current schema every time it runs.
+fn parse_create_query(
     iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
 ) -> Result<Statement, String> {
-    let mut columns = Vec::new();
-    while let Some(token) = iter.next() {
-        if token.to_uppercase() == "FROM" {
-            break;
-        }
-        if token != "," {
-            columns.push(token.clone());
-        }
+    let name = identifier(iter.next().ok_or("Expected query name")?);
+    if iter.next().map(|s| s.to_uppercase()) != Some("AS".to_string()) {
+        return Err("Expected AS after query name".into());
     }
 
-    let table_name = iter.next().ok_or("Expected table name")?.clone();
-    let mut join = None;
+    let sql = iter.clone().cloned().collect::<Vec<_>>().join(" ");
+    let len = iter.len();
+    match parse_ahead(iter, len, parse_statement)? {
+        Statement::Select(_) => Ok(Statement::CreateQuery { name, sql }),
+        _ => Err("Saved queries must be SELECT statements".into()),
+    }
+}
 
-    if let Some(token) = iter.next() {
-        let join_table = iter.next().ok_or("Expected join table")?.clone();
-        iter.next();
-        let left = iter.next().ok_or("Expected left col")?.clone();
-        iter.next();
-        let right = iter.next().ok_or("Expected right col")?.clone();
+/// Internal parser logic for `CREATE SCHEDULE <name> AS <statement> EVERY '<interval>'`.
+///
+/// Everything between `AS` and the final `EVERY` is the scheduled statement. It is validated
+/// here and stored as SQL text; the interval accepts a number with an `s`, `m`, `h` or `d`
+/// suffix, e.g. `'30m'` or `'24h'`.
+fn parse_create_schedule(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let name = identifier(iter.next().ok_or("Expected schedule name")?);
+    if iter.next().map(|s| s.to_uppercase()) != Some("AS".to_string()) {
+        return Err("Expected AS after schedule name".into());
+    }
 
-        join = Some(JoinDefinition {
-            table_name: join_table,
-            left_column: left,
-            right_column: right,
-        });
+    let rest: Vec<String> = iter.clone().cloned().collect();
+    let every = rest
+        .iter()
+        .rposition(|t| t.to_uppercase() == "EVERY")
+        .ok_or("Expected EVERY '<interval>' at the end of the schedule")?;
+    if rest.len() != every + 2 {
+        iter.by_ref().for_each(drop);
+        return Err("Expected a single interval after EVERY".into());
+    }
+
+    let sql = rest[..every].join(" ");
+    if let Statement::CreateSchedule { .. } = parse_ahead(iter, every, parse_statement)? {
+        return Err("A schedule cannot create other schedules".into());
     }
 
-    Ok(Statement::Select {
-        table_name: table_name,
-        columns: columns,
-        join: join,
+    let interval = iter.nth(1).ok_or("Expected an interval after EVERY")?;
+    let interval_secs = parse_interval(interval)?;
+    Ok(Statement::CreateSchedule {
+        name,
+        sql,
+        interval_secs,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::parser::{self, parse, tokenize};
-
+/// Internal parser logic for `CREATE TRIGGER name AFTER <event> ON table AS <statement>`.
+fn parse_create_trigger(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let name = identifier(iter.next().ok_or("Expected trigger name")?);
+    if !parse_keywords(iter, &["AFTER"])? {
+        return Err("Expected AFTER after trigger name".into());
+    }
+    let event = match iter.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("INSERT") => TriggerEvent::Insert,
+        Some("UPDATE") => TriggerEvent::Update,
+        Some("DELETE") => TriggerEvent::Delete,
+        _ => return Err("Expected INSERT, UPDATE or DELETE after AFTER".into()),
+    };
+    if !parse_keywords(iter, &["ON"])? {
+        return Err("Expected ON after the trigger event".into());
+    }
+    let table_name = identifier(iter.next().ok_or("Expected table name")?);
+    if !parse_keywords(iter, &["AS"])? {
+        return Err("Expected AS after the table name".into());
+    }
+
+    let sql = iter.clone().cloned().collect::<Vec<_>>().join(" ");
+    let len = iter.len();
+    if let Statement::CreateTrigger { .. } = parse_ahead(iter, len, parse_statement)? {
+        return Err("A trigger cannot create other triggers".into());
+    }
+    Ok(Statement::CreateTrigger {
+        name,
+        table_name,
+        event,
+        sql,
+    })
+}
+
+/// Converts an interval literal like `'24h'` into seconds.
+fn parse_interval(token: &str) -> Result<u64, String> {
+    let literal = token.trim_matches('\'');
+    let invalid = || format!("Invalid interval: {}", token);
+
+    let unit = literal.chars().last().ok_or_else(invalid)?;
+    let amount: u64 = literal[..literal.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    match amount.checked_mul(multiplier).ok_or_else(invalid)? {
+        0 => Err("Interval must be greater than zero".into()),
+        secs => Ok(secs),
+    }
+}
+
+/// Internal parser logic for the `DROP` family of statements.
+fn parse_drop(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    match iter.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("TABLE") => {
+            let if_exists = parse_keywords(iter, &["IF", "EXISTS"])?;
+            let name = identifier(iter.next().ok_or("Expected table name")?);
+            Ok(Statement::DropTable { name, if_exists })
+        }
+        Some("INDEX") => {
+            let name = identifier(iter.next().ok_or("Expected index name")?);
+            Ok(Statement::DropIndex(name))
+        }
+        Some("QUERY") => {
+            let name = identifier(iter.next().ok_or("Expected query name")?);
+            Ok(Statement::DropQuery(name))
+        }
+        Some("SCHEDULE") => {
+            let name = identifier(iter.next().ok_or("Expected schedule name")?);
+            Ok(Statement::DropSchedule(name))
+        }
+        Some("TRIGGER") => {
+            let name = identifier(iter.next().ok_or("Expected trigger name")?);
+            Ok(Statement::DropTrigger(name))
+        }
+        _ => Err("Expected TABLE, INDEX, QUERY, SCHEDULE or TRIGGER after DROP".into()),
+    }
+}
+
+/// Internal parser logic for `FIND DUPLICATES IN <table> (<column>, ...)`.
+///
+/// This is sugar for a grouped query: it lists every combination of the columns held by more
+/// than one row, with the number of rows as `occurrences`, most repeated first.
+fn parse_find_duplicates(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    if !parse_keywords(iter, &["DUPLICATES", "IN"])? {
+        return Err("Expected DUPLICATES IN after FIND".into());
+    }
+    let table = identifier(iter.next().ok_or("Expected table name")?);
+    if iter.next().map(String::as_str) != Some("(") {
+        return Err("Expected '(' after the table name".into());
+    }
+
+    let mut columns = Vec::new();
+    loop {
+        columns.push(identifier(iter.next().ok_or("Expected column name")?));
+        match iter.next().map(String::as_str) {
+            Some(",") => continue,
+            Some(")") => break,
+            _ => return Err("Expected ',' or ')' after column name".into()),
+        }
+    }
+    if let Some(token) = iter.next() {
+        return Err(format!("Unexpected token: {}", token));
+    }
+
+    let select = |table_name, subquery, columns| SelectStatement {
+        hints: Vec::new(),
+        table_name,
+        table_args: None,
+        subquery,
+        table_alias: None,
+        use_index: None,
+        distinct: false,
+        columns,
+        joins: Vec::new(),
+        where_clause: None,
+        group_by: Vec::new(),
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+    };
+    let occurrences = || Expr::Column("occurrences".to_string());
+
+    // SELECT <keys>, COUNT(*) AS occurrences FROM <table> GROUP BY <keys>
+    let mut items: Vec<SelectItem> = columns
+        .iter()
+        .map(|column| SelectItem::Expr {
+            expr: Expr::Column(column.clone()),
+            alias: None,
+        })
+        .collect();
+    items.push(SelectItem::Expr {
+        expr: Expr::Aggregate {
+            func: AggregateFunction::Count,
+            arg: None,
+        },
+        alias: Some("occurrences".to_string()),
+    });
+    let mut groups = select(table, None, items);
+    groups.group_by = columns.clone();
+
+    // SELECT * FROM (...) AS duplicates WHERE occurrences > 1
+    // ORDER BY occurrences DESC, <keys>
+    let mut duplicates = select(
+        "duplicates".to_string(),
+        Some(Box::new(groups)),
+        vec![SelectItem::Wildcard],
+    );
+    duplicates.where_clause = Some(Expr::Binary {
+        left: Box::new(occurrences()),
+        op: BinaryOperator::Gt,
+        right: Box::new(Expr::Literal(Value::Integer(1))),
+    });
+    duplicates.order_by = std::iter::once(OrderByDefinition {
+        column: "occurrences".to_string(),
+        descending: true,
+    })
+    .chain(columns.into_iter().map(|column| OrderByDefinition {
+        column,
+        descending: false,
+    }))
+    .collect();
+    Ok(Statement::Select(Box::new(duplicates)))
+}
+
+/// Parses `COMMENT ON TABLE t IS '...'` and `COMMENT ON COLUMN t.c IS '...'`. The comment is
+/// a single string literal, or `NULL`.
+fn parse_comment(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    if iter.next().map(|s| s.to_uppercase()).as_deref() != Some("ON") {
+        return Err("Expected ON after COMMENT".into());
+    }
+    let kind = iter.next().map(|s| s.to_uppercase());
+    let name = identifier(
+        iter.next()
+            .ok_or("Expected the name of the commented object")?,
+    );
+    let target = match kind.as_deref() {
+        Some("TABLE") => CommentTarget::Table(name),
+        Some("COLUMN") => {
+            let (table, column) = name.split_once('.').ok_or("Expected COLUMN table.column")?;
+            CommentTarget::Column {
+                table: table.to_string(),
+                column: column.to_string(),
+            }
+        }
+        _ => return Err("Expected TABLE or COLUMN after COMMENT ON".into()),
+    };
+    if iter.next().map(|s| s.to_uppercase()).as_deref() != Some("IS") {
+        return Err("Expected IS after the commented object".into());
+    }
+
+    let comment = match iter.next() {
+        Some(null) if null.eq_ignore_ascii_case("NULL") => None,
+        Some(text) if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') => {
+            Some(unquote(text))
+        }
+        _ => return Err("Expected a quoted comment or NULL after IS".into()),
+    };
+    Ok(Statement::Comment { target, comment })
+}
+
+/// The name ending a `SAVEPOINT`, `ROLLBACK TO` or `RELEASE` statement.
+fn parse_savepoint_name(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<String, String> {
+    let name = iter.next().ok_or("Expected savepoint name")?;
+    if let Some(token) = iter.next() {
+        return Err(format!("Unexpected token: {}", token));
+    }
+    Ok(identifier(name))
+}
+
+/// Internal parser logic for the `SHOW` family of statements.
+fn parse_show(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    match iter.next().map(|s| s.to_uppercase()).as_deref() {
+        Some("QUERIES") => Ok(Statement::ShowQueries),
+        Some("SCHEDULES") => Ok(Statement::ShowSchedules),
+        Some("TRIGGERS") => Ok(Statement::ShowTriggers),
+        Some("STATUS") => Ok(Statement::ShowStatus),
+        Some("INDEX") => {
+            if iter.next().map(|s| s.to_uppercase()).as_deref() != Some("STATS") {
+                return Err("Expected STATS after SHOW INDEX".into());
+            }
+            Ok(Statement::ShowIndexStats)
+        }
+        _ => Err("Expected QUERIES, SCHEDULES, TRIGGERS, STATUS or INDEX STATS after SHOW".into()),
+    }
+}
+
+/// Internal parser logic for `SET @name = <literal>`.
+fn parse_set(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let token = iter.next().ok_or("Expected variable after SET")?;
+    let name = token
+        .strip_prefix('@')
+        .filter(|n| !n.is_empty())
+        .ok_or("Expected @variable after SET")?
+        .to_string();
+
+    if iter.next() != Some(&"=".to_string()) {
+        return Err("Expected '=' after variable name".into());
+    }
+    let value = parse_literal(iter.next().ok_or("Expected value after '='")?)?;
+
+    if let Some(extra) = iter.next() {
+        return Err(format!("Unexpected token: {}", extra));
+    }
+    Ok(Statement::SetVariable { name, value })
+}
+
+/// Internal parser logic for the `INSERT INTO` statement.
+///
+/// ### How it works:
+/// 1. **Context Parsing**: Matches the boilerplate SQL syntax `INTO <table_name> VALUES`.
+///    One or more comma separated rows follow: `VALUES (1, 'a'), (2, 'b')`, optionally
+///    followed by `ON CONFLICT [(column)] DO NOTHING | DO UPDATE SET column = expr, ...`.
+/// 2. **Type Inference**: As it iterates through the values inside `(...)`, it attempts to
+///    categorize data types on the fly:
+///    - If a token can be parsed as a number (`token.parse::<i32>()`), it is stored as `Value::Integer`,
+///      or as `Value::BigInt` if it only fits in 64 bits.
+///    - A decimal such as `1.5` is stored as `Value::Real`.
+///    - Either may be signed, `-5` or `- 5` alike; see `attach_signs`.
+///    - Integers too large for a `BIGINT` are rejected.
+///    - Otherwise, it is treated as a string and stored as `Value::Text`.
+/// 3. **Sanitization**: It strips single quotes `'` from text values to ensure
+///    the database stores the literal data, not the SQL formatting.
+fn parse_insert(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    if iter.next().map(|s| s.to_uppercase()) != Some("INTO".to_string()) {
+        return Err("Expected INTO after INSERT".into());
+    }
+
+    let name = identifier(iter.next().ok_or("Expected table name")?);
+    if iter.next().map(|s| s.to_uppercase()) != Some("VALUES".to_string()) {
+        return Err("Expected VALUES after INTO".into());
+    }
+
+    let mut rows = Vec::new();
+    loop {
+        if iter.next() != Some(&"(".to_string()) {
+            return Err("Expected '('".into());
+        }
+
+        // Values and commas alternate up to the closing ')'.
+        let mut values = Vec::new();
+        if iter.next_if(|t| *t == ")").is_none() {
+            loop {
+                match iter.next().map(String::as_str) {
+                    Some(token @ ("," | ")")) => {
+                        return Err(format!("Expected a value, found '{}'", token));
+                    }
+                    Some(token) => values.push(parse_literal(token)?),
+                    None => return Err("Expected a value".into()),
+                }
+                match iter.next().map(String::as_str) {
+                    Some(",") => continue,
+                    Some(")") => break,
+                    Some(token) => {
+                        return Err(format!(
+                            "Expected ',' or ')' after a value, found '{}'",
+                            token
+                        ));
+                    }
+                    None => return Err("Expected ')' to close the row".into()),
+                }
+            }
+        }
+        rows.push(values);
+
+        if iter.next_if(|t| *t == ",").is_none() {
+            break;
+        }
+    }
+
+    let on_conflict = match iter.next() {
+        None => None,
+        Some(token) if token.eq_ignore_ascii_case("ON") => Some(parse_on_conflict(iter)?),
+        Some(token) => return Err(format!("Unexpected token: {}", token)),
+    };
+
+    Ok(Statement::Insert {
+        table_name: name,
+        rows,
+        on_conflict,
+    })
+}
+
+/// Parses the rest of an `ON CONFLICT` clause, after `ON`.
+fn parse_on_conflict(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<OnConflict, String> {
+    let mut keyword = |expected: &str| match iter.next() {
+        Some(token) if token.eq_ignore_ascii_case(expected) => Ok(()),
+        _ => Err(format!("Expected {}", expected)),
+    };
+    keyword("CONFLICT")?;
+
+    let target = if iter.next_if(|t| *t == "(").is_some() {
+        let column = iter.next().ok_or("Expected a column after ON CONFLICT (")?;
+        if iter.next() != Some(&")".to_string()) {
+            return Err("Expected ')' after the ON CONFLICT column".into());
+        }
+        Some(identifier(column))
+    } else {
+        None
+    };
+
+    if iter.next().map(|t| t.to_uppercase()).as_deref() != Some("DO") {
+        return Err("Expected DO after ON CONFLICT".into());
+    }
+    let action = match iter.next().map(|t| t.to_uppercase()).as_deref() {
+        Some("NOTHING") => ConflictAction::DoNothing,
+        Some("UPDATE") => {
+            if iter.next().map(|t| t.to_uppercase()).as_deref() != Some("SET") {
+                return Err("Expected SET after DO UPDATE".into());
+            }
+            let mut assignments = Vec::new();
+            loop {
+                let column = identifier(iter.next().ok_or("Expected a column after SET")?);
+                if iter.next().map(String::as_str) != Some("=") {
+                    return Err(format!("Expected '=' after {}", column));
+                }
+                assignments.push((column, parse_expr(iter)?));
+                if iter.next_if(|t| *t == ",").is_none() {
+                    break;
+                }
+            }
+            ConflictAction::DoUpdate(assignments)
+        }
+        _ => return Err("Expected NOTHING or UPDATE after ON CONFLICT ... DO".into()),
+    };
+    if let Some(token) = iter.next() {
+        return Err(format!("Unexpected token: {}", token));
+    }
+
+    Ok(OnConflict { target, action })
+}
+
+/// The text of a quoted literal token, with its quotes stripped and each escaped `''` read as
+/// one quote.
+pub(crate) fn unquote(token: &str) -> String {
+    let text = token.strip_prefix('\'').unwrap_or(token);
+    let text = text.strip_suffix('\'').unwrap_or(text);
+    text.replace("''", "'")
+}
+
+/// Writes `text` as a literal `unquote` reads back, doubling its quotes.
+pub(crate) fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Splits a name token at the dots outside double quotes, e.g. `t."first name"` into `t` and
+/// `"first name"`.
+pub(crate) fn name_parts(token: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in token.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => {
+                parts.push(&token[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&token[start..]);
+    parts
+}
+
+/// The name a token stands for, e.g. a table or a `table.column`. Unquoted names are
+/// folded to lowercase, so `Users` and `USERS` both name `users`. A `"Quoted"` part keeps its
+/// case and may hold spaces or be a keyword; a doubled `""` inside stands for one quote.
+pub(crate) fn identifier(token: &str) -> String {
+    name_parts(token)
+        .into_iter()
+        .map(
+            |part| match part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                Some(inner) => inner.replace("\"\"", "\""),
+                None => part.to_ascii_lowercase(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Writes `name` as an identifier `identifier` reads back, quoting it unless it is a plain
+/// lowercase name.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Converts a single literal token into a `Value`, inferring its type.
+/// Integers become `Value::Integer`, or `Value::BigInt` beyond 32 bits, decimals
+/// `Value::Real` and the keyword `NULL` `Value::Null`; anything else is unquoted and kept as
+/// text, so `'NULL'` is the text. Integers too large for a `BIGINT` are an error.
+pub(crate) fn parse_literal(token: &str) -> Result<Value, String> {
+    Ok(if token.eq_ignore_ascii_case("NULL") {
+        Value::Null
+    } else if let Ok(num) = token.parse::<i32>() {
+        Value::Integer(num)
+    } else if let Ok(num) = token.parse::<i64>() {
+        Value::BigInt(num)
+    } else if let Some(real) = parse_decimal(token) {
+        Value::Real(real)
+    } else if is_integer(token) {
+        return Err(format!("Numeric literal out of range: {}", token));
+    } else {
+        Value::Text(unquote(token))
+    })
+}
+
+/// Whether `token` is digits with an optional minus sign, whatever their size.
+pub(crate) fn is_integer(token: &str) -> bool {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether `sign` followed by `token` is a number, such as `-` and `2.5`, or `-` and
+/// `9223372036854775808`, whose digits alone are too large for a `BIGINT`.
+pub(crate) fn is_signed_number(sign: &str, token: &str) -> bool {
+    let signed = match sign {
+        "-" => format!("-{}", token),
+        _ => token.to_string(),
+    };
+    token.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && (signed.parse::<i64>().is_ok() || parse_decimal(&signed).is_some())
+}
+
+/// Collects the comma separated literals of a table function call, e.g. `(1, 100)`.
+/// Expects the opening parenthesis to be the next token.
+fn parse_table_args(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Vec<Value>, String> {
+    if iter.next() != Some(&"(".to_string()) {
+        return Err("Expected '('".into());
+    }
+
+    // Arguments and commas alternate up to the closing ')'.
+    let mut args = Vec::new();
+    if iter.next_if(|t| *t == ")").is_some() {
+        return Ok(args);
+    }
+    loop {
+        match iter.next().map(String::as_str) {
+            Some(token @ ("," | ")")) => {
+                return Err(format!("Expected an argument, found '{}'", token));
+            }
+            Some(token) => args.push(parse_literal(token)?),
+            None => return Err("Expected an argument".into()),
+        }
+        match iter.next().map(String::as_str) {
+            Some(",") => continue,
+            Some(")") => return Ok(args),
+            Some(token) => {
+                return Err(format!(
+                    "Expected ',' or ')' after an argument, found '{}'",
+                    token
+                ));
+            }
+            None => return Err("Expected ')' after table function arguments".into()),
+        }
+    }
+}
+
+/// Internal parser logic for the `SELECT` statement, including JOIN detection.
+///
+/// ### How it works:
+/// 1. **Column Selection**: Parses an optional `DISTINCT` and the comma separated select list
+///    up to `FROM`. This supports
+///    `*` (wildcard), specific column lists (e.g., `id, name`), aggregate calls such as
+///    `COUNT(*)`, literals, session variables (`@team`) and functions like `CURRENT_USER()`.
+/// 2. **Source Table**: Identifies the primary table to query. A `(` right after the name
+///    turns it into a table function call such as `generate_series(1, 10)`. Without a `FROM`
+///    clause the query runs against the single-row `dual` table function. A parenthesized
+///    `SELECT` with a mandatory alias makes a derived table: `FROM (SELECT ...) AS u`.
+/// 3. **Clause Loop**: After the table name, it "peeks" at the next keyword and hands off to
+///    the matching clause parser until the input is exhausted:
+///    - `[INNER] JOIN` or `LEFT [OUTER] JOIN` captures another table and the
+///      `ON left = right` equality check. `CROSS JOIN` (or `FROM a, b`) takes no condition.
+///      Any number of joins may follow each other.
+///    - `WHERE` parses a predicate such as `age >= 18 AND id IN (1, 2, 3)`.
+///    - `GROUP BY` collects the columns whose distinct values form the groups.
+///    - `ORDER BY` collects one or more sort keys, each optionally followed by `ASC`/`DESC`.
+///    - `LIMIT n` and `OFFSET m` capture the page of results to return.
+/// 4. **Encapsulation**: Returns a `Statement::Select` wrapping a `SelectStatement` whose
+///    `joins` lists the detected joins in order.
+fn parse_select(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    let hints = parse_hints(iter)?;
+    let distinct = iter.peek().map(|s| s.to_uppercase()) == Some("DISTINCT".to_string());
+    if distinct {
+        iter.next();
+    }
+
+    let mut columns = Vec::new();
+    let has_from = loop {
+        columns.push(parse_select_item(iter)?);
+        match iter.next().map(|s| s.to_uppercase()).as_deref() {
+            Some(",") => continue,
+            Some("FROM") => break true,
+            Some(other) => return Err(format!("Unexpected token in select list: {}", other)),
+            None => break false,
+        }
+    };
+
+    let mut subquery = None;
+    let (table_name, table_args, table_alias, use_index) = if has_from {
+        if iter.peek().map(|s| s.as_str()) == Some("(") {
+            subquery = Some(Box::new(parse_subquery(iter)?));
+            let alias = parse_alias(iter)?.ok_or("Expected an alias after subquery in FROM")?;
+            (alias, None, None, parse_use_index(iter)?)
+        } else {
+            let table_name = identifier(iter.next().ok_or("Expected table name")?);
+            let table_args = match iter.peek() {
+                Some(&next) if next == "(" => Some(parse_table_args(iter)?),
+                _ => None,
+            };
+            let table_alias = parse_alias(iter)?;
+            (table_name, table_args, table_alias, parse_use_index(iter)?)
+        }
+    } else {
+        ("dual".to_string(), Some(Vec::new()), None, None)
+    };
+
+    let mut select = SelectStatement {
+        hints,
+        table_name,
+        table_args,
+        subquery,
+        table_alias,
+        use_index,
+        distinct,
+        columns,
+        joins: Vec::new(),
+        where_clause: None,
+        group_by: Vec::new(),
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+    };
+
+    while let Some(token) = iter.next() {
+        match token.to_uppercase().as_str() {
+            "JOIN" => select.joins.push(parse_join(iter, JoinKind::Inner)?),
+            "INNER" => {
+                expect_join(iter)?;
+                select.joins.push(parse_join(iter, JoinKind::Inner)?);
+            }
+            "LEFT" => {
+                if iter.peek().map(|s| s.to_uppercase()).as_deref() == Some("OUTER") {
+                    iter.next();
+                }
+                expect_join(iter)?;
+                select.joins.push(parse_join(iter, JoinKind::Left)?);
+            }
+            "CROSS" => {
+                expect_join(iter)?;
+                select.joins.push(parse_join(iter, JoinKind::Cross)?);
+            }
+            // `FROM a, b` is shorthand for `FROM a CROSS JOIN b`
+            "," => select.joins.push(parse_join(iter, JoinKind::Cross)?),
+            "WHERE" => select.where_clause = Some(parse_expr(iter)?),
+            "GROUP" => select.group_by = parse_group_by(iter)?,
+            "ORDER" => select.order_by = parse_order_by(iter)?,
+            "LIMIT" => select.limit = Some(parse_count(iter, "LIMIT")?),
+            "OFFSET" => select.offset = Some(parse_count(iter, "OFFSET")?),
+            other => return Err(format!("Unexpected token: {}", other)),
+        }
+    }
+
+    Ok(Statement::Select(Box::new(select)))
+}
+
+/// Parses a parenthesized `(SELECT ...)`, consuming the tokens up to the matching `)`.
+fn parse_subquery(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<SelectStatement, String> {
+    iter.next(); // (
+    parse_subquery_body(iter)
+}
+
+/// Parses a parenthesized subquery whose `(` was already consumed, up to its `)`.
+fn parse_subquery_body(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<SelectStatement, String> {
+    let mut len = 0;
+    let mut depth = 0;
+    for token in iter.clone() {
+        match token.as_str() {
+            ")" if depth == 0 => break,
+            ")" => depth -= 1,
+            "(" => depth += 1,
+            _ => {}
+        }
+        len += 1;
+    }
+    if len == iter.len() {
+        iter.by_ref().for_each(drop);
+        return Err("Expected ')' after subquery".into());
+    }
+
+    let select = parse_ahead(iter, len, |inner| {
+        if inner.next().map(|s| s.to_uppercase()).as_deref() != Some("SELECT") {
+            return Err("Expected SELECT in subquery".into());
+        }
+        match parse_select(inner)? {
+            Statement::Select(select) => Ok(*select),
+            _ => unreachable!("parse_select only returns selects"),
+        }
+    })?;
+    iter.next(); // )
+    Ok(select)
+}
+
+/// Parses the optional `/*+ HINT ... */` comment at the start of a select.
+fn parse_hints(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Vec<Hint>, String> {
+    if !iter.peek().is_some_and(|t| t.starts_with("/*+")) {
+        return Ok(Vec::new());
+    }
+
+    let mut text = String::new();
+    loop {
+        let token = iter
+            .next()
+            .ok_or("Unterminated optimizer hint, expected */")?;
+        text.push_str(token);
+        text.push(' ');
+        if token.ends_with("*/") {
+            break;
+        }
+    }
+
+    let body = &text.trim()["/*+".len()..];
+    body.strip_suffix("*/")
+        .unwrap_or(body)
+        .split_whitespace()
+        .map(str::parse)
+        .collect()
+}
+
+/// Parses the optional `USE INDEX (<column>)` following the source table.
+fn parse_use_index(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Option<String>, String> {
+    if iter.peek().map(|s| s.to_uppercase()).as_deref() != Some("USE") {
+        return Ok(None);
+    }
+    iter.next();
+    if iter.next().map(|s| s.to_uppercase()).as_deref() != Some("INDEX") {
+        return Err("Expected INDEX after USE".into());
+    }
+    if iter.next().map(|s| s.as_str()) != Some("(") {
+        return Err("Expected '(' after USE INDEX".into());
+    }
+    let column = iter.next().ok_or("Expected index column")?;
+    if !is_column_reference(column) {
+        return Err(format!("Invalid column reference: {}", column));
+    }
+    let column = identifier(column);
+    if iter.next().map(|s| s.as_str()) != Some(")") {
+        return Err("Expected ')' after index column".into());
+    }
+    Ok(Some(column))
+}
+
+/// Parses a single select list entry: `*` or an expression with an optional `[AS] <alias>`.
+fn parse_select_item(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<SelectItem, String> {
+    if iter.peek().map(|s| s.as_str()) == Some("*") {
+        iter.next();
+        return Ok(SelectItem::Wildcard);
+    }
+    let expr = parse_expr(iter)?;
+    let alias = parse_alias(iter)?;
+    Ok(SelectItem::Expr { expr, alias })
+}
+
+/// Parses `BY <col> [, ...]` following the `GROUP` keyword.
+fn parse_group_by(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Vec<String>, String> {
+    if iter.next().map(|s| s.to_uppercase()) != Some("BY".to_string()) {
+        return Err("Expected BY after GROUP".into());
+    }
+
+    let mut columns = vec![identifier(
+        iter.next().ok_or("Expected column in GROUP BY")?,
+    )];
+    while iter.peek().map(|s| s.as_str()) == Some(",") {
+        iter.next();
+        columns.push(identifier(
+            iter.next().ok_or("Expected column in GROUP BY")?,
+        ));
+    }
+    Ok(columns)
+}
+
+/// Consumes the `JOIN` keyword that must follow `INNER` or `LEFT [OUTER]`.
+fn expect_join(iter: &mut std::iter::Peekable<std::slice::Iter<String>>) -> Result<(), String> {
+    if iter.next().map(|s| s.to_uppercase()).as_deref() != Some("JOIN") {
+        return Err("Expected JOIN".into());
+    }
+    Ok(())
+}
+
+/// Keywords that may follow a select item or table name and therefore can't be taken as
+/// its alias.
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "FROM", "JOIN", "INNER", "LEFT", "CROSS", "ON", "USE", "WHERE", "GROUP", "ORDER", "LIMIT",
+    "OFFSET",
+];
+
+/// Parses the optional `[AS] <alias>` following a select item or table name.
+fn parse_alias(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Option<String>, String> {
+    if iter.peek().map(|s| s.to_uppercase()).as_deref() == Some("AS") {
+        iter.next();
+        return Ok(Some(identifier(
+            iter.next().ok_or("Expected alias after AS")?,
+        )));
+    }
+
+    match iter.peek() {
+        Some(next)
+            if next.starts_with('"')
+                || next.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    && !CLAUSE_KEYWORDS.contains(&next.to_uppercase().as_str()) =>
+        {
+            Ok(iter.next().map(|alias| identifier(alias)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parses `<table> [[AS] <alias>] ON <left> = <right>` following the `JOIN` keyword, or just
+/// `<table> [[AS] <alias>]` for a cross join.
+fn parse_join(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+    kind: JoinKind,
+) -> Result<JoinDefinition, String> {
+    let table_name = identifier(iter.next().ok_or("Expected join table")?);
+    let alias = parse_alias(iter)?;
+    if kind == JoinKind::Cross {
+        return Ok(JoinDefinition {
+            kind,
+            table_name,
+            alias,
+            on: None,
+        });
+    }
+
+    if iter.next().map(|s| s.to_uppercase()) != Some("ON".to_string()) {
+        return Err("Expected ON after join table".into());
+    }
+    let left_column = iter.next().ok_or("Expected left col")?;
+    if iter.next() != Some(&"=".to_string()) {
+        return Err("Expected '=' in join condition".into());
+    }
+    let right_column = iter.next().ok_or("Expected right col")?;
+    for column in [left_column, right_column] {
+        if !is_column_reference(column) {
+            return Err(format!("Invalid column reference: {}", column));
+        }
+    }
+    let (left_column, right_column) = (identifier(left_column), identifier(right_column));
+
+    Ok(JoinDefinition {
+        kind,
+        table_name,
+        alias,
+        on: Some(JoinCondition {
+            left_column,
+            right_column,
+        }),
+    })
+}
+
+/// Parses the non-negative row count following `LIMIT` or `OFFSET`.
+fn parse_count(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+    clause: &str,
+) -> Result<usize, String> {
+    let token = iter
+        .next()
+        .ok_or_else(|| format!("Expected number after {}", clause))?;
+    token
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid {} value: {}", clause, token))
+}
+
+/// Parses `BY <col> [ASC|DESC] [, ...]` following the `ORDER` keyword.
+fn parse_order_by(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Vec<OrderByDefinition>, String> {
+    if iter.next().map(|s| s.to_uppercase()) != Some("BY".to_string()) {
+        return Err("Expected BY after ORDER".into());
+    }
+
+    let mut order_by = Vec::new();
+    loop {
+        let column = identifier(iter.next().ok_or("Expected column in ORDER BY")?);
+        let mut descending = false;
+
+        if let Some(&next) = iter.peek() {
+            match next.to_uppercase().as_str() {
+                "ASC" => {
+                    iter.next();
+                }
+                "DESC" => {
+                    descending = true;
+                    iter.next();
+                }
+                _ => {}
+            }
+        }
+        order_by.push(OrderByDefinition { column, descending });
+
+        if iter.peek().map(|s| s.as_str()) != Some(",") {
+            return Ok(order_by);
+        }
+        iter.next();
+    }
+}
+
+/// Internal parser logic for the `ALTER TABLE` statement.
+///
+/// ### How it works:
+/// 1. **Target Table**: Expects `TABLE <table_name>` right after `ALTER`.
+/// 2. **Action Dispatch**: Reads the next two keywords to decide what to change:
+///    - `DROP COLUMN <name>` removes a column.
+///    - `RENAME COLUMN <old> TO <new>` renames a column in place.
+fn parse_alter(
+    iter: &mut std::iter::Peekable<std::slice::Iter<String>>,
+) -> Result<Statement, String> {
+    if iter.next().map(|s| s.to_uppercase()) != Some("TABLE".to_string()) {
+        return Err("Expected TABLE after ALTER".into());
+    }
+
+    let table_name = identifier(iter.next().ok_or("Expected table name")?);
+    let action = iter.next().ok_or("Expected DROP or RENAME")?.to_uppercase();
+
+    if action == "RENAME" && iter.next_if(|t| t.eq_ignore_ascii_case("TO")).is_some() {
+        let to = identifier(iter.next().ok_or("Expected new table name")?);
+        let action = AlterAction::RenameTable(to);
+        return Ok(Statement::AlterTable { table_name, action });
+    }
+
+    if iter.next().map(|s| s.to_uppercase()) != Some("COLUMN".to_string()) {
+        return Err(format!("Expected COLUMN after {}", action));
+    }
+
+    let column = identifier(iter.next().ok_or("Expected column name")?);
+
+    let action = match action.as_str() {
+        "DROP" => AlterAction::DropColumn(column),
+        "RENAME" => {
+            if iter.next().map(|s| s.to_uppercase()) != Some("TO".to_string()) {
+                return Err("Expected TO after column name".into());
+            }
+            let to = identifier(iter.next().ok_or("Expected new column name")?);
+            AlterAction::RenameColumn { from: column, to }
+        }
+        _ => return Err(format!("Unsupported ALTER TABLE action: {}", action)),
+    };
+
+    Ok(Statement::AlterTable { table_name, action })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Value;
+    use crate::parser::{
+        BinaryOperator, Expr, Statement, parse, parse_script, split_statements, tokenize,
+    };
+
     #[test]
     pub fn test_tokenize() {
         let input = "SELECT(a,b)";
 
-        let res = tokenize(input);
+        let res = tokenize(input).unwrap().text;
         println!("{:?}", res);
+
+        assert_eq!(
+            tokenize("INSERT INTO users VALUES (1,'John Doe (admin), CEO')")
+                .unwrap()
+                .text,
+            [
+                "INSERT",
+                "INTO",
+                "users",
+                "VALUES",
+                "(",
+                "1",
+                ",",
+                "'John Doe (admin), CEO'",
+                ")"
+            ]
+        );
+        let Ok(Statement::Insert { rows, .. }) =
+            parse("INSERT INTO users VALUES (1, 'John Doe (admin)')")
+        else {
+            panic!("expected an INSERT");
+        };
+        assert_eq!(rows[0][1], Value::Text("John Doe (admin)".into()));
+        for sql in [
+            "INSERT INTO t VALUES (1 2)",
+            "INSERT INTO t VALUES (1,, 2)",
+            "INSERT INTO t VALUES (, 1)",
+            "INSERT INTO t VALUES (1,)",
+            "INSERT INTO t VALUES (1, 2",
+            "INSERT INTO t VALUES (1), (2",
+        ] {
+            assert!(parse(sql).is_err(), "{}", sql);
+        }
+        assert_eq!(
+            tokenize("name='a  b'--c").unwrap().text,
+            ["name", "=", "'a  b'"]
+        );
+        assert_eq!(
+            tokenize("a+1>=b*-2, c<>d").unwrap().text,
+            ["a", "+", "1", ">=", "b", "*", "-2", ",", "c", "<>", "d"]
+        );
+        assert_eq!(tokenize("1-2").unwrap().text, ["1", "-", "2"]);
+        assert_eq!(
+            tokenize("('O''Brien','''',x)").unwrap().text,
+            ["(", "'O''Brien'", ",", "''''", ",", "x", ")"]
+        );
+        // A quote or comment left open swallows nothing, not even the `;`.
+        for (sql, message, position) in [
+            ("SELECT 'unterminated;", "Unterminated string literal", 7),
+            (
+                "INSERT INTO t VALUES (2, 'x;",
+                "Unterminated string literal",
+                25,
+            ),
+            ("SELECT 'a''b", "Unterminated string literal", 7),
+            ("SELECT \"open FROM t", "Unterminated quoted identifier", 7),
+            ("SELECT 1 /* open", "Unterminated comment", 9),
+            ("SELECT 1 /* open; DROP TABLE t", "Unterminated comment", 9),
+        ] {
+            let err = parse(sql).unwrap_err();
+            assert_eq!(
+                (err.message.as_str(), err.position),
+                (message, Some(position))
+            );
+        }
+
+        // A sign belongs to the number where a value starts, and is an operator after one.
+        assert_eq!(
+            tokenize("(- 5, -5, + .5, x -5, x - -2.5, 1-2)")
+                .unwrap()
+                .text,
+            [
+                "(", "-5", ",", "-5", ",", ".5", ",", "x", "-", "5", ",", "x", "-", "-2.5", ",",
+                "1", "-", "2", ")"
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_sql_comments() {
+        let sql =
+            "SELECT id, -- the key\n name /* display; name */ FROM users WHERE name = '--x/*y*/'";
+        assert_eq!(
+            tokenize(sql).unwrap().text.join(" "),
+            "SELECT id , name FROM users WHERE name = '--x/*y*/'"
+        );
+        assert_eq!(
+            tokenize("SELECT/**/id FROM t").unwrap().text,
+            ["SELECT", "id", "FROM", "t"]
+        );
+        assert!(parse("SELECT /*+ NO_INDEX */ * FROM t -- done").is_ok());
+
+        let script = "-- setup: don't panic\nCREATE TABLE t (id INT); /* ; */\nINSERT INTO t VALUES (1); -- end";
+        assert_eq!(
+            split_statements(script),
+            [
+                "-- setup: don't panic\nCREATE TABLE t (id INT)",
+                "/* ; */\nINSERT INTO t VALUES (1)",
+            ]
+        );
+
+        let statements = parse_script(script).unwrap();
+        assert!(matches!(
+            statements[..],
+            [Statement::CreateTable { .. }, Statement::Insert { .. }]
+        ));
+        // A comment left open is not a statement holding nothing but comments.
+        assert!(parse_script("SELECT 1; /* open").is_err());
+        assert!(parse("SELECT * FROM t;").is_ok());
+        assert_eq!(
+            parse(script).unwrap_err().message,
+            "Expected a single statement, found 2"
+        );
+        assert_eq!(
+            parse_script("SELECT 1; SELEC 2").unwrap_err().message,
+            "Statement 2: Unknown command: SELEC"
+        );
+
+        // Whatever the statement, nothing may follow it.
+        for (sql, token) in [
+            ("DROP TABLE u please;", "please"),
+            ("CREATE TABLE t (id INT) extra junk;", "extra"),
+            ("ALTER TABLE t RENAME TO u extra;", "extra"),
+            ("CREATE INDEX i ON u (id) trailing;", "trailing"),
+            ("SHOW TRIGGERS now;", "now"),
+            ("DROP INDEX foo bar baz", "bar"),
+        ] {
+            let err = parse(sql).unwrap_err();
+            assert_eq!(
+                err.message,
+                format!("Unexpected token: {}", token),
+                "{}",
+                sql
+            );
+        }
     }
 
     #[test]
@@ -248,4 +2251,140 @@ mod tests {
             Err(e) => println!("Error: {}", e),
         }
     }
+
+    #[test]
+    pub fn test_qualified_columns() {
+        assert!(parse("SELECT u.id, t.name FROM users u JOIN teams t ON u.team_id = t.id").is_ok());
+        assert!(parse("SELECT a.b.c FROM users").is_err());
+        assert!(parse("SELECT .id FROM users").is_err());
+        assert!(parse("SELECT * FROM users JOIN teams ON users. = teams.id").is_err());
+    }
+
+    #[test]
+    pub fn test_derived_tables() {
+        let Ok(Statement::Select(select)) = parse(
+            "SELECT * FROM (SELECT id, name FROM users WHERE id IN (1, 2)) AS u JOIN teams t ON u.id = t.id",
+        ) else {
+            panic!("expected select");
+        };
+        assert_eq!(select.table_name, "u");
+        assert_eq!(select.joins.len(), 1);
+        let subquery = select.subquery.unwrap();
+        assert_eq!(subquery.table_name, "users");
+        assert_eq!(subquery.columns.len(), 2);
+        assert!(subquery.where_clause.is_some());
+
+        assert!(parse("SELECT * FROM (SELECT id FROM users)").is_err());
+        assert!(parse("SELECT * FROM (SELECT id FROM users u").is_err());
+        assert!(parse("SELECT * FROM (users) u").is_err());
+    }
+
+    #[test]
+    pub fn test_exists() {
+        let sql = "SELECT name FROM users AS u WHERE NOT EXISTS (SELECT * FROM orders WHERE user_id = u.id AND total > 10) ORDER BY name DESC LIMIT 5";
+        let Ok(Statement::Select(select)) = parse(sql) else {
+            panic!("expected select");
+        };
+        assert!(select.where_clause.as_ref().unwrap().has_subquery());
+        // Displaying renders the statement back as SQL.
+        assert_eq!(select.to_string(), sql);
+
+        assert!(parse("SELECT * FROM users WHERE EXISTS (SELECT * FROM orders").is_err());
+        assert!(parse("SELECT * FROM users WHERE EXISTS (1)").is_err());
+
+        let sql = "SELECT name, (SELECT COUNT(*) FROM devs WHERE devs.team_id = teams.id) AS size FROM teams";
+        let Ok(Statement::Select(select)) = parse(sql) else {
+            panic!("expected select");
+        };
+        assert_eq!(select.to_string(), sql);
+        assert!(parse("SELECT (SELECT id FROM devs FROM teams").is_err());
+    }
+
+    #[test]
+    pub fn test_arithmetic() {
+        let sql = "SELECT price * (qty + 1) - 2 % 3 AS total, a - (b - c) FROM orders \
+                   WHERE age + 1 > 30 ORDER BY total";
+        let Ok(Statement::Select(select)) = parse(sql) else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            select.to_string(),
+            sql.split_whitespace().collect::<Vec<_>>().join(" ")
+        );
+        let Some(Expr::Binary { op, .. }) = &select.where_clause else {
+            panic!("expected a comparison");
+        };
+        assert_eq!(*op, BinaryOperator::Gt);
+        assert!(parse("SELECT price *, qty FROM orders").is_err());
+
+        // A unary minus binds tighter than `*`, and applies to any operand.
+        let Ok(Statement::Select(select)) = parse("SELECT -v * 2, -(a + b), - -5, +v FROM n")
+        else {
+            panic!("expected select");
+        };
+        let super::SelectItem::Expr {
+            expr: Expr::Binary { left, op, .. },
+            ..
+        } = &select.columns[0]
+        else {
+            panic!("expected a product");
+        };
+        assert_eq!(*op, BinaryOperator::Mul);
+        assert!(matches!(&**left, Expr::Negate(inner) if matches!(&**inner, Expr::Column(_))));
+        assert_eq!(
+            select.to_string(),
+            "SELECT -v * 2, -(a + b), -(-5), v FROM n"
+        );
+        assert!(parse("SELECT 1 + -").is_err());
+
+        // Integers too large for a `BIGINT` are neither columns nor text.
+        for sql in [
+            "SELECT v FROM n WHERE v < 99999999999999999999",
+            "SELECT -99999999999999999999",
+            "INSERT INTO n VALUES (1, 99999999999999999999)",
+            "SET @v = 9223372036854775808",
+        ] {
+            let Err(err) = parse(sql) else {
+                panic!("expected an error for {}", sql);
+            };
+            assert!(err.to_string().contains("out of range"), "{}", err);
+        }
+    }
+
+    #[test]
+    pub fn test_cast() {
+        let Ok(Statement::Select(select)) =
+            parse("SELECT cast(code as integer) + 1, CAST(id AS text) FROM t")
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            select.to_string(),
+            "SELECT CAST(code AS INT) + 1, CAST(id AS TEXT) FROM t"
+        );
+        assert!(parse("SELECT CAST(id AS BLOB) FROM t").is_err());
+        assert!(parse("SELECT CAST(id TEXT) FROM t").is_err());
+        assert!(parse("SELECT CAST(id AS TEXT FROM t").is_err());
+    }
+
+    #[test]
+    pub fn test_is_read_only() {
+        for sql in [
+            "SELECT * FROM t",
+            "EXPLAIN SELECT * FROM t",
+            "SHOW STATUS",
+            "SET @x = 1",
+            "EXECUTE report",
+        ] {
+            assert!(parse(sql).unwrap().is_read_only(), "{}", sql);
+        }
+        for sql in [
+            "INSERT INTO t VALUES (1)",
+            "ALTER TABLE t DROP COLUMN name",
+            "DROP TABLE t",
+            "CREATE QUERY report AS SELECT * FROM t",
+        ] {
+            assert!(!parse(sql).unwrap().is_read_only(), "{}", sql);
+        }
+    }
 }
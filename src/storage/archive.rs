@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Database, DbError,
+    engine::{limits::Limits, schedules::unix_now},
+    storage::{Format, decode_with_ceiling},
+};
+
+/// Version of the archive layout, checked on import.
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Name of the archive entry describing its contents.
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// Name of the archive entry holding the database itself, in the binary format.
+const DATABASE_ENTRY: &str = "database.mdb";
+
+/// The largest manifest an import reads, in bytes.
+const MAX_MANIFEST_BYTES: usize = 1 << 20;
+
+/// The catalog of an archive: what it holds, so an import can check it got everything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    /// When the archive was written, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Every table with its row count, in name order.
+    pub tables: Vec<(String, usize)>,
+    pub saved_queries: usize,
+    pub schedules: usize,
+    /// `None` in archives written before triggers were listed.
+    #[serde(default)]
+    pub triggers: Option<usize>,
+}
+
+impl ArchiveManifest {
+    fn describe(db: &Database) -> Self {
+        let mut tables: Vec<(String, usize)> = db
+            .tables
+            .values()
+            .map(|t| (t.name.clone(), t.rows.len()))
+            .collect();
+        tables.sort();
+        Self {
+            version: ARCHIVE_VERSION,
+            created_at: unix_now(),
+            tables,
+            saved_queries: db.saved_queries.len(),
+            schedules: db.schedules.len(),
+            triggers: Some(db.triggers.len()),
+        }
+    }
+}
+
+/// Writes the whole database into a single tar file at `path`: a `manifest.json` listing its
+/// contents and the database in the binary format, encoded straight into the file. Like
+/// `save_with_format`, the archive is written next to `path` first, so a failed export leaves
+/// no partial file behind.
+pub fn export_archive(db: &Database, path: &Path) -> Result<ArchiveManifest, DbError> {
+    let failed = |e: std::io::Error| DbError::IoError(format!("Archive export failed: {}", e));
+    let manifest = ArchiveManifest::describe(db);
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| DbError::IoError(format!("Serialization failed: {}", e)))?;
+    let header = || {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_mtime(manifest.created_at);
+        header
+    };
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let written = (|| {
+        let file = File::create(&partial).map_err(failed)?;
+        let mut builder = tar::Builder::new(BufWriter::new(file));
+        let mut manifest_header = header();
+        manifest_header.set_size(manifest_bytes.len() as u64);
+        builder
+            .append_data(
+                &mut manifest_header,
+                MANIFEST_ENTRY,
+                manifest_bytes.as_slice(),
+            )
+            .map_err(failed)?;
+        let mut database_header = header();
+        let mut entry = builder
+            .append_writer(&mut database_header, DATABASE_ENTRY)
+            .map_err(failed)?;
+        Format::Binary.encode_to(db, &mut entry)?;
+        entry.finish().map_err(failed)?;
+        builder
+            .into_inner()
+            .and_then(|mut file| file.flush())
+            .map_err(failed)
+    })();
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err);
+    }
+    std::fs::rename(&partial, path).map_err(failed)?;
+    Ok(manifest)
+}
+
+/// Reads a database written by `export_archive`, checking it against the manifest: the
+/// archive version must be known and every table must have the rows the manifest lists.
+///
+/// The database is decoded as it is read, refusing an entry larger than
+/// `limits.max_load_bytes`, and must fit the other `limits`, which it keeps.
+pub fn import_archive(path: &Path, limits: Limits) -> Result<(Database, ArchiveManifest), DbError> {
+    let failed = |e: &dyn std::fmt::Display| {
+        DbError::IoError(format!(
+            "Archive import from '{}' failed: {}",
+            path.display(),
+            e
+        ))
+    };
+    let file = File::open(path).map_err(|e| failed(&e))?;
+    let mut archive = tar::Archive::new(BufReader::new(file));
+
+    let (mut manifest, mut database) = (None, None);
+    for entry in archive.entries().map_err(|e| failed(&e))? {
+        let mut entry = entry.map_err(|e| failed(&e))?;
+        let name = entry.path().map_err(|e| failed(&e))?.into_owned();
+        if name == Path::new(MANIFEST_ENTRY) {
+            let mut bytes = Vec::new();
+            (&mut entry)
+                .take(MAX_MANIFEST_BYTES as u64 + 1)
+                .read_to_end(&mut bytes)
+                .map_err(|e| failed(&e))?;
+            if bytes.len() > MAX_MANIFEST_BYTES {
+                return Err(failed(&format!("{} is too large", MANIFEST_ENTRY)));
+            }
+            let parsed: ArchiveManifest = serde_json::from_slice(&bytes).map_err(|e| failed(&e))?;
+            manifest = Some(parsed);
+        } else if name == Path::new(DATABASE_ENTRY) {
+            let too_large = |ceiling: usize| {
+                DbError::StorageFull(format!(
+                    "{} in '{}' is larger than the load limit of {} bytes",
+                    DATABASE_ENTRY,
+                    path.display(),
+                    ceiling
+                ))
+            };
+            let len = entry.size();
+            let decoded = decode_with_ceiling(
+                &mut entry,
+                len,
+                Format::Binary,
+                limits.max_load_bytes,
+                too_large,
+            )?;
+            database = Some(decoded);
+        }
+    }
+    let manifest = manifest.ok_or_else(|| failed(&format!("no {}", MANIFEST_ENTRY)))?;
+    if manifest.version != ARCHIVE_VERSION {
+        return Err(failed(&format!(
+            "unsupported archive version {}",
+            manifest.version
+        )));
+    }
+    let mut db = database.ok_or_else(|| failed(&format!("no {}", DATABASE_ENTRY)))?;
+
+    let found = ArchiveManifest::describe(&db);
+    if (&found.tables, found.saved_queries, found.schedules)
+        != (&manifest.tables, manifest.saved_queries, manifest.schedules)
+        || manifest.triggers.is_some_and(|n| Some(n) != found.triggers)
+    {
+        return Err(failed(&"the database does not match the manifest"));
+    }
+    db.limits = limits;
+    db.check_capacity(&[])?;
+    Ok((db, manifest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let dir = std::env::temp_dir().join(format!("martin-db-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backup.tar");
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)",
+            "INSERT INTO t VALUES (1, 'ada'), (2, 'grace')",
+            "CREATE QUERY names AS SELECT name FROM t",
+            "COMMENT ON TABLE t IS 'people'",
+            "CREATE TABLE log (id INT)",
+            "CREATE TRIGGER audit AFTER INSERT ON t AS INSERT INTO log VALUES (1)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let exported = export_archive(&db, &path).unwrap();
+        assert_eq!(
+            exported.tables,
+            [("log".to_string(), 0), ("t".to_string(), 2)]
+        );
+        assert_eq!(exported.triggers, Some(1));
+
+        let (mut imported, manifest) = import_archive(&path, Limits::default()).unwrap();
+        assert_eq!(manifest, exported);
+        assert_eq!(imported.tables["t"].rows, db.tables["t"].rows);
+        assert_eq!(imported.tables["t"].comment.as_deref(), Some("people"));
+        assert!(imported.saved_queries.contains_key("names"));
+        assert!(imported.triggers.contains_key("audit"));
+        // Indexes are rebuilt, so the primary key still holds.
+        assert!(matches!(
+            imported.execute(parse("INSERT INTO t VALUES (1, 'x')").unwrap()),
+            Err(DbError::UniqueViolation(_))
+        ));
+
+        // Limits apply to the database file and to what it holds.
+        for limits in [
+            Limits {
+                max_load_bytes: Some(16),
+                ..Limits::default()
+            },
+            Limits {
+                max_rows: Some(1),
+                ..Limits::default()
+            },
+        ] {
+            assert!(matches!(
+                import_archive(&path, limits),
+                Err(DbError::StorageFull(_))
+            ));
+        }
+
+        std::fs::write(&path, b"not an archive").unwrap();
+        assert!(matches!(
+            import_archive(&path, Limits::default()),
+            Err(DbError::IoError(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Database, DbError};
+
+/// File extension that selects the binary format.
+pub const BINARY_EXTENSION: &str = "mdb";
+
+/// Leading bytes of a binary database file, followed by the format version.
+const BINARY_MAGIC: &[u8; 4] = b"MDB\x01";
+
+/// The on-disk encodings a database can be stored in.
+///
+/// JSON is human readable and the historical default. The binary format is a compact bincode
+/// encoding that is considerably faster to load and save for larger databases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Binary,
+}
+
+/// Databases whose stored values take up to this many bytes are written as pretty JSON by
+/// `JsonStyle::Auto`; larger ones compact.
+pub const PRETTY_JSON_LIMIT: usize = 1 << 20;
+
+/// How the JSON format lays out a database file. Pretty output is easy to read and diff, but
+/// about three times larger and slower to write than compact output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonStyle {
+    Pretty,
+    Compact,
+    /// Pretty for databases holding at most `PRETTY_JSON_LIMIT` bytes, compact above.
+    #[default]
+    Auto,
+}
+
+impl JsonStyle {
+    /// Whether `db` is written pretty-printed.
+    pub fn is_pretty(self, db: &Database) -> bool {
+        match self {
+            JsonStyle::Pretty => true,
+            JsonStyle::Compact => false,
+            JsonStyle::Auto => db.data_size() <= PRETTY_JSON_LIMIT,
+        }
+    }
+}
+
+impl FromStr for JsonStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(JsonStyle::Pretty),
+            "compact" => Ok(JsonStyle::Compact),
+            "auto" => Ok(JsonStyle::Auto),
+            _ => Err(format!("Unknown JSON style: {}", s)),
+        }
+    }
+}
+
+impl Format {
+    /// Picks the format from the file extension: `.mdb` is binary, anything else JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case(BINARY_EXTENSION) => Format::Binary,
+            _ => Format::Json,
+        }
+    }
+
+    pub fn encode(self, db: &Database) -> Result<Vec<u8>, DbError> {
+        let mut bytes = Vec::new();
+        self.encode_to(db, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Encodes a database straight into `writer`, without building the whole output first.
+    /// JSON is laid out according to the database's `json_style`.
+    pub fn encode_to(self, db: &Database, writer: &mut impl Write) -> Result<(), DbError> {
+        let failed =
+            |e: &dyn fmt::Display| DbError::IoError(format!("Serialization failed: {}", e));
+        match self {
+            Format::Json if db.json_style.is_pretty(db) => {
+                serde_json::to_writer_pretty(writer, db).map_err(|e| failed(&e))
+            }
+            Format::Json => serde_json::to_writer(writer, db).map_err(|e| failed(&e)),
+            Format::Binary => {
+                writer.write_all(BINARY_MAGIC).map_err(|e| failed(&e))?;
+                bincode::serde::encode_into_std_write(db, writer, bincode::config::standard())
+                    .map(|_| ())
+                    .map_err(|e| failed(&e))
+            }
+        }
+    }
+
+    /// Decodes a database. Indexes are not part of either format and must be rebuilt.
+    pub fn decode(self, mut bytes: &[u8]) -> Result<Database, DbError> {
+        self.decode_from(&mut bytes)
+    }
+
+    /// Decodes a database while reading it, without buffering the whole input first.
+    pub fn decode_from(self, reader: &mut impl Read) -> Result<Database, DbError> {
+        let failed =
+            |e: &dyn fmt::Display| DbError::IoError(format!("Deserialization failed:{}", e));
+        match self {
+            Format::Json => serde_json::from_reader(reader).map_err(|e| failed(&e)),
+            Format::Binary => {
+                let mut magic = [0; BINARY_MAGIC.len()];
+                if reader.read_exact(&mut magic).is_err() || magic != *BINARY_MAGIC {
+                    return Err(DbError::IoError(
+                        "Not a binary martin-db file (bad header)".into(),
+                    ));
+                }
+                bincode::serde::decode_from_std_read(reader, bincode::config::standard())
+                    .map_err(|e| failed(&e))
+            }
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "binary" | "bin" | BINARY_EXTENSION => Ok(Format::Binary),
+            _ => Err(format!("Unknown storage format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "json",
+            Format::Binary => "binary",
+        })
+    }
+}
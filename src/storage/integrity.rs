@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{
+    Database, DbError,
+    engine::{Table, Value},
+    storage::Storage,
+};
+
+/// A problem found in a loaded database or next to its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// A `.partial` file left by a save that did not finish. The database file itself is
+    /// the last complete save.
+    OrphanedFile(PathBuf),
+    /// A row without one value per column.
+    MalformedRow {
+        table: String,
+        row: usize,
+        values: usize,
+        expected: usize,
+    },
+    /// A value repeated in a `PRIMARY KEY` or `UNIQUE` column, at its later occurrence. Like
+    /// inserts, several `NULL`s don't repeat each other.
+    DuplicateKey {
+        table: String,
+        row: usize,
+        column: String,
+    },
+    /// A `CREATE INDEX` definition naming a column the table does not have.
+    DanglingIndex {
+        table: String,
+        index: String,
+        column: String,
+    },
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityIssue::OrphanedFile(path) => {
+                write!(
+                    f,
+                    "orphaned file from an unfinished save: {}",
+                    path.display()
+                )
+            }
+            IntegrityIssue::MalformedRow {
+                table,
+                row,
+                values,
+                expected,
+            } => write!(
+                f,
+                "row {} of '{}' has {} values, expected {}",
+                row, table, values, expected
+            ),
+            IntegrityIssue::DuplicateKey { table, row, column } => write!(
+                f,
+                "row {} of '{}' repeats a value of unique column '{}'",
+                row, table, column
+            ),
+            IntegrityIssue::DanglingIndex {
+                table,
+                index,
+                column,
+            } => write!(
+                f,
+                "index '{}' of '{}' covers missing column '{}'",
+                index, table, column
+            ),
+        }
+    }
+}
+
+/// Looks for the problems a crash or a hand-edited file can leave behind: orphaned `.partial`
+/// files, rows that break the declared column count or `PRIMARY KEY` / `UNIQUE` constraints,
+/// and index definitions that no longer match the schema.
+pub fn check_integrity(db: &Database, storage: &Storage) -> Vec<IntegrityIssue> {
+    let mut issues: Vec<IntegrityIssue> = orphaned_file(storage)
+        .map(IntegrityIssue::OrphanedFile)
+        .into_iter()
+        .collect();
+    let mut names: Vec<&String> = db.tables.keys().collect();
+    names.sort();
+    for name in names {
+        issues.extend(table_issues(&db.tables[name]));
+    }
+    issues
+}
+
+/// Fixes what `check_integrity` finds and returns the issues it fixed. Malformed rows and the
+/// later copies of duplicate keys are dropped, dangling index definitions removed and
+/// orphaned files deleted. The repaired database is marked as changed.
+pub fn repair(db: &mut Database, storage: &Storage) -> Result<Vec<IntegrityIssue>, DbError> {
+    let issues = check_integrity(db, storage);
+    for issue in &issues {
+        match issue {
+            IntegrityIssue::OrphanedFile(path) => std::fs::remove_file(path).map_err(|e| {
+                DbError::IoError(format!("Could not remove {}: {}", path.display(), e))
+            })?,
+            IntegrityIssue::DanglingIndex { table, index, .. } => {
+                let table = db.tables.get_mut(table).expect("checked table");
+                table.secondary_indexes.remove(index);
+            }
+            IntegrityIssue::MalformedRow { .. } | IntegrityIssue::DuplicateKey { .. } => {}
+        }
+    }
+    let mut bad_rows: Vec<(&str, usize)> = issues
+        .iter()
+        .filter_map(|issue| match issue {
+            IntegrityIssue::MalformedRow { table, row, .. }
+            | IntegrityIssue::DuplicateKey { table, row, .. } => Some((table.as_str(), *row)),
+            _ => None,
+        })
+        .collect();
+    // A row can break several constraints; positions are removed from the end so the
+    // earlier ones stay valid.
+    bad_rows.sort();
+    bad_rows.dedup();
+    for &(table, row) in bad_rows.iter().rev() {
+        db.tables
+            .get_mut(table)
+            .expect("checked table")
+            .rows
+            .remove(row);
+    }
+    if !issues.is_empty() {
+        for table in db.tables.values_mut() {
+            table.rebuild_indexes();
+        }
+        db.mark_dirty();
+    }
+    Ok(issues)
+}
+
+/// The `.partial` file a save of `storage` writes before renaming it, if one is lying around.
+fn orphaned_file(storage: &Storage) -> Option<PathBuf> {
+    let Storage::File(path) = storage else {
+        return None;
+    };
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    Some(PathBuf::from(partial)).filter(|partial| partial.exists())
+}
+
+fn table_issues(table: &Table) -> Vec<IntegrityIssue> {
+    let mut issues = Vec::new();
+    for (index, column) in &table.secondary_indexes {
+        if table.column_index(column).is_err() {
+            issues.push(IntegrityIssue::DanglingIndex {
+                table: table.name.clone(),
+                index: index.clone(),
+                column: column.clone(),
+            });
+        }
+    }
+
+    let keys: Vec<usize> = (0..table.columns.len())
+        .filter(|&i| table.columns[i].is_primary || table.columns[i].is_unique)
+        .collect();
+    let mut seen: Vec<HashSet<&Value>> = vec![HashSet::new(); keys.len()];
+    for (row, values) in table.rows.iter().enumerate() {
+        if values.len() != table.columns.len() {
+            issues.push(IntegrityIssue::MalformedRow {
+                table: table.name.clone(),
+                row,
+                values: values.len(),
+                expected: table.columns.len(),
+            });
+            continue;
+        }
+        for (&i, seen) in keys.iter().zip(&mut seen) {
+            if values[i] != Value::Null && !seen.insert(&values[i]) {
+                issues.push(IntegrityIssue::DuplicateKey {
+                    table: table.name.clone(),
+                    row,
+                    column: table.columns[i].name.clone(),
+                });
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::limits::Limits,
+        parser::parse,
+        storage::{PersistenceManager, PersistencePolicy},
+    };
+
+    #[test]
+    fn test_integrity_repair() {
+        let dir = std::env::temp_dir().join(format!("martin-db-integrity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = Storage::new(dir.join("db.json"));
+        let partial = dir.join("db.json.partial");
+        std::fs::write(&partial, b"{").unwrap();
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)",
+            "INSERT INTO t VALUES (1, 'ada'), (2, 'grace')",
+            "CREATE INDEX t_name ON t (name)",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        assert_eq!(
+            check_integrity(&db, &storage),
+            [IntegrityIssue::OrphanedFile(partial.clone())]
+        );
+
+        // What a hand-edited file could hold.
+        let table = db.tables.get_mut("t").unwrap();
+        table
+            .rows
+            .push(vec![Value::Integer(1), Value::Text("dup".into())]);
+        table.rows.push(vec![Value::Integer(3)]);
+        table.rows.push(vec![Value::Integer(4), Value::Null]);
+        table.rows.push(vec![Value::Integer(5), Value::Null]);
+        table.columns[1].is_unique = true;
+        table
+            .secondary_indexes
+            .insert("t_gone".into(), "gone".into());
+        // As loading does.
+        table.rebuild_indexes();
+        let issues = check_integrity(&db, &storage);
+        assert_eq!(issues.len(), 4);
+        assert_eq!(
+            issues[2].to_string(),
+            "row 2 of 't' repeats a value of unique column 'id'"
+        );
+
+        db.flush(&Storage::Memory).unwrap();
+        assert_eq!(repair(&mut db, &storage).unwrap(), issues);
+        assert!(check_integrity(&db, &storage).is_empty());
+        assert!(!partial.exists());
+        assert!(db.is_dirty());
+        assert_eq!(db.tables["t"].rows.len(), 4);
+        assert!(!db.tables["t"].secondary_indexes.contains_key("t_gone"));
+
+        // A leftover `.partial` file alone is removed on load without `--repair`.
+        db.flush(&storage).unwrap();
+        std::fs::write(&partial, b"{").unwrap();
+        let persistence = PersistenceManager::new(storage, PersistencePolicy::EveryStatement);
+        let (_, fixed) = persistence.load_checked(Limits::default(), false).unwrap();
+        assert_eq!(fixed, [IntegrityIssue::OrphanedFile(partial.clone())]);
+        assert!(!partial.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,41 +1,196 @@
+mod archive;
+mod format;
+mod integrity;
+mod persistence;
+
 use crate::{Database, DbError};
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub use archive::{ArchiveManifest, export_archive, import_archive};
+pub use format::{BINARY_EXTENSION, Format, JsonStyle, PRETTY_JSON_LIMIT};
+pub use integrity::{IntegrityIssue, check_integrity, repair};
+pub use persistence::{PersistenceManager, PersistencePolicy};
 
 /// The filename where the database is persisted.
 pub const DB_FILE: &str = "database.json";
 
+/// The special path selecting a database that is never written to disk.
+pub const MEMORY_PATH: &str = ":memory:";
+
+/// Where a database is persisted. `Memory` databases live only as long as the process, which
+/// suits tests and ephemeral caches; loading gives an empty database and saving does nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    File(PathBuf),
+    Memory,
+}
+
+impl Storage {
+    /// Picks the storage for `path`, treating `:memory:` as the in-memory mode.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if path.as_os_str() == MEMORY_PATH {
+            Storage::Memory
+        } else {
+            Storage::File(path.to_path_buf())
+        }
+    }
+
+    pub fn is_memory(&self) -> bool {
+        *self == Storage::Memory
+    }
+
+    pub fn load(&self) -> Result<Database, DbError> {
+        self.load_with_ceiling(None)
+    }
+
+    /// Like `load`, but refuses to read more than `ceiling` bytes of the database file.
+    pub fn load_with_ceiling(&self, ceiling: Option<usize>) -> Result<Database, DbError> {
+        match self {
+            Storage::File(path) if path.exists() => {
+                load_with_ceiling(path, Format::from_path(path), ceiling)
+            }
+            Storage::File(_) | Storage::Memory => Ok(Database::new()),
+        }
+    }
+
+    pub fn save(&self, db: &Database) -> Result<(), DbError> {
+        match self {
+            Storage::File(path) => save_to_path(db, path),
+            Storage::Memory => Ok(()),
+        }
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::File(PathBuf::from(DB_FILE))
+    }
+}
+
+impl Database {
+    /// Opens the database stored at `path`, or a fresh in-memory one for `":memory:"`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Database, DbError> {
+        Storage::new(path).load()
+    }
+}
+
 /// Serializes the current Database state into a JSON file on disk.
 pub fn save_to_disk(db: &Database) -> Result<(), DbError> {
-    let json = serde_json::to_string_pretty(db)
-        .map_err(|err| DbError::IoError(format!("Serialization failed: {}", err)))?;
+    save_to_path(db, Path::new(DB_FILE))
+}
 
-    let mut file = File::create(DB_FILE)
-        .map_err(|err| DbError::IoError(format!("failed to create database file: {}", err)))?;
+/// Serializes the Database into the file at `path`, in the format implied by its extension.
+pub fn save_to_path(db: &Database, path: &Path) -> Result<(), DbError> {
+    save_with_format(db, path, Format::from_path(path))
+}
 
-    file.write_all(json.as_bytes())
-        .map_err(|err| DbError::IoError(format!(" Write to database file failed: {}", err)))?;
+/// Serializes the Database into the file at `path`, creating parent directories as needed.
+/// The encoding is written out as it is produced rather than built in memory first.
+pub fn save_with_format(db: &Database, path: &Path, format: Format) -> Result<(), DbError> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| DbError::IoError(format!("failed to create directory: {}", err)))?;
+    }
+
+    // Stream into a sibling file and swap it in, so a failed save never leaves a truncated
+    // database behind.
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let file = File::create(&partial)
+        .map_err(|err| DbError::IoError(format!("failed to create database file: {}", err)))?;
+    let mut writer = BufWriter::new(file);
+    let written = format.encode_to(db, &mut writer).and_then(|_| {
+        writer
+            .flush()
+            .map_err(|err| DbError::IoError(format!(" Write to database file failed: {}", err)))
+    });
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err);
+    }
 
-    Ok(())
+    std::fs::rename(&partial, path)
+        .map_err(|err| DbError::IoError(format!("failed to replace database file: {}", err)))
 }
 
 /// Loads the Database from disk and triggers the index reconstruction process.
 pub fn load_from_disk() -> Result<Database, DbError> {
-    if !Path::new(DB_FILE).exists() {
+    load_from_path(Path::new(DB_FILE))
+}
+
+/// Loads the Database stored at `path`, or an empty one if the file does not exist yet.
+/// The format is implied by the file extension.
+pub fn load_from_path(path: &Path) -> Result<Database, DbError> {
+    if !path.exists() {
         return Ok(Database::new());
     }
+    load_with_format(path, Format::from_path(path))
+}
 
-    let mut file =
-        File::open(DB_FILE).map_err(|e| DbError::IoError(format!("Could not open file: {}", e)))?;
+/// Loads the Database stored at `path` using an explicit format.
+pub fn load_with_format(path: &Path, format: Format) -> Result<Database, DbError> {
+    load_with_ceiling(path, format, None)
+}
 
-    let mut contents = String::new();
+/// Loads the Database stored at `path`, failing with `DbError::StorageFull` instead of
+/// exhausting memory when the file is larger than `ceiling` bytes.
+///
+/// The file is decoded as it is read, so the raw contents are never held in memory next to
+/// the decoded tables. The size is checked up front and again while reading, in case the
+/// file grows in the meantime.
+pub fn load_with_ceiling(
+    path: &Path,
+    format: Format,
+    ceiling: Option<usize>,
+) -> Result<Database, DbError> {
+    let file =
+        File::open(path).map_err(|e| DbError::IoError(format!("Could not open file: {}", e)))?;
+    let len = file
+        .metadata()
+        .map_err(|e| DbError::IoError(format!("Could not read file metadata: {}", e)))?
+        .len();
+    let too_large = |ceiling: usize| {
+        DbError::StorageFull(format!(
+            "database file '{}' is larger than the load limit of {} bytes",
+            path.display(),
+            ceiling
+        ))
+    };
+    decode_with_ceiling(BufReader::new(file), len, format, ceiling, too_large)
+}
 
-    file.read_to_string(&mut contents)
-        .map_err(|e| DbError::IoError(format!("Read file failed: {}", e)))?;
+/// Decodes a database of `len` bytes from `reader` as `load_with_ceiling` does, failing with
+/// `too_large(ceiling)` when there is more to read than `ceiling` bytes.
+fn decode_with_ceiling(
+    reader: impl Read,
+    len: u64,
+    format: Format,
+    ceiling: Option<usize>,
+    too_large: impl Fn(usize) -> DbError,
+) -> Result<Database, DbError> {
+    if let Some(ceiling) = ceiling
+        && len > ceiling as u64
+    {
+        return Err(too_large(ceiling));
+    }
 
-    let mut db: Database = serde_json::from_str(&contents)
-        .map_err(|e| DbError::IoError(format!("Deserialization failed:{}", e)))?;
+    let mut reader = CeilingReader {
+        inner: reader,
+        remaining: ceiling,
+        exceeded: false,
+    };
+    let decoded = format.decode_from(&mut reader);
+    if let (true, Some(ceiling)) = (reader.exceeded, ceiling) {
+        return Err(too_large(ceiling));
+    }
+    let mut db = decoded?;
 
     //rebuild indexes sinces we skipped them during Deserialization
     for table in db.tables.values_mut() {
@@ -43,3 +198,163 @@ pub fn load_from_disk() -> Result<Database, DbError> {
     }
     Ok(db)
 }
+
+/// Counts the bytes read and fails once more than `remaining` were requested.
+struct CeilingReader<R> {
+    inner: R,
+    remaining: Option<usize>,
+    exceeded: bool,
+}
+
+impl<R: Read> Read for CeilingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(remaining) = &mut self.remaining {
+            match remaining.checked_sub(n) {
+                Some(left) => *remaining = left,
+                None => {
+                    self.exceeded = true;
+                    return Err(io::Error::other("load limit exceeded"));
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Re-encodes the database at `from_path` into `to_path`, e.g. to move a JSON database to
+/// the faster binary format.
+pub fn convert(from_path: &Path, from: Format, to_path: &Path, to: Format) -> Result<(), DbError> {
+    let db = load_with_format(from_path, from)?;
+    save_with_format(&db, to_path, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_memory_storage() {
+        let storage = Storage::new(MEMORY_PATH);
+        assert!(storage.is_memory());
+
+        let mut db = Database::open(MEMORY_PATH).unwrap();
+        db.execute(parse("CREATE TABLE t (id INT)").unwrap())
+            .unwrap();
+        storage.save(&db).unwrap();
+        assert!(!Path::new(MEMORY_PATH).exists());
+        assert!(storage.load().unwrap().tables.is_empty());
+
+        assert_eq!(
+            Storage::new("data.json"),
+            Storage::File(PathBuf::from("data.json"))
+        );
+    }
+
+    #[test]
+    fn test_load_ceiling() {
+        let path =
+            std::env::temp_dir().join(format!("martin-db-ceiling-{}.json", std::process::id()));
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE t (id INT PRIMARY, name TEXT)",
+            "INSERT INTO t VALUES (1, 'a'), (2, 'b')",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        save_to_path(&db, &path).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len() as usize;
+
+        let storage = Storage::new(&path);
+        let loaded = storage.load_with_ceiling(Some(len)).unwrap();
+        assert_eq!(loaded.tables["t"].indexes[&0].len(), 2);
+        assert!(matches!(
+            storage.load_with_ceiling(Some(len - 1)),
+            Err(DbError::StorageFull(_))
+        ));
+
+        // The reader itself stops at the ceiling, whatever the file size said.
+        let bytes = std::fs::read(&path).unwrap();
+        let mut reader = CeilingReader {
+            inner: bytes.as_slice(),
+            remaining: Some(len / 2),
+            exceeded: false,
+        };
+        assert!(Format::Json.decode_from(&mut reader).is_err());
+        assert!(reader.exceeded);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_json_style() {
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE t (id INT PRIMARY, name TEXT)",
+            "INSERT INTO t VALUES (1, 'a'), (2, 'b')",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        let pretty = Format::Json.encode(&db).unwrap();
+        assert!(pretty.contains(&b'\n'));
+
+        db.json_style = JsonStyle::Compact;
+        let compact = Format::Json.encode(&db).unwrap();
+        assert!(!compact.contains(&b'\n'));
+        assert!(compact.len() < pretty.len());
+        let decoded = Format::Json.decode(&compact).unwrap();
+        assert_eq!(decoded.tables["t"].rows, db.tables["t"].rows);
+
+        db.json_style = JsonStyle::Auto;
+        db.execute(
+            parse(&format!(
+                "INSERT INTO t VALUES (3, '{}')",
+                "x".repeat(PRETTY_JSON_LIMIT)
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(!Format::Json.encode(&db).unwrap().contains(&b'\n'));
+        assert_eq!("compact".parse(), Ok(JsonStyle::Compact));
+    }
+
+    #[test]
+    fn test_convert_formats() {
+        let dir = std::env::temp_dir().join(format!("martin-db-convert-{}", std::process::id()));
+        let json = dir.join("old.json");
+        let binary = dir.join("new.mdb");
+
+        let mut db = Database::new();
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Alice')",
+            "CREATE QUERY everyone AS SELECT * FROM users",
+        ] {
+            db.execute(parse(sql).unwrap()).unwrap();
+        }
+        save_to_path(&db, &json).unwrap();
+        // saving streams into a temporary file that replaces the old one
+        assert!(!dir.join("old.json.partial").exists());
+        assert_eq!(
+            std::fs::read(&json).unwrap(),
+            Format::Json.encode(&db).unwrap()
+        );
+
+        convert(&json, Format::Json, &binary, Format::Binary).unwrap();
+        assert_eq!(Format::from_path(&binary), Format::Binary);
+        assert!(load_with_format(&binary, Format::Json).is_err());
+
+        let mut restored = load_from_path(&binary).unwrap();
+        assert_eq!(restored.tables["users"].rows, db.tables["users"].rows);
+        assert!(restored.saved_queries.contains_key("everyone"));
+        // indexes are rebuilt after loading
+        assert!(
+            restored
+                .execute(parse("INSERT INTO users VALUES (1, 'Dup')").unwrap())
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
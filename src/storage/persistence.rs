@@ -0,0 +1,222 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::{
+    Database, DbError,
+    engine::limits::Limits,
+    storage::{IntegrityIssue, Storage, integrity},
+};
+
+/// When a `PersistenceManager` writes pending changes to its storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistencePolicy {
+    /// Write-through: flush after every statement.
+    EveryStatement,
+    /// Write-back: flush once this many statements ran since the last flush.
+    EveryStatements(usize),
+    /// Write-back: flush once this much time passed since the last flush.
+    Interval(Duration),
+    /// Only flush when `PersistenceManager::flush` is called explicitly.
+    Manual,
+}
+
+/// Parses `always`, `manual`, a statement count such as `100`, or a number of seconds
+/// such as `30s`.
+impl FromStr for PersistencePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid persistence policy: {}", s);
+        match s.to_lowercase().as_str() {
+            "always" => Ok(Self::EveryStatement),
+            "manual" => Ok(Self::Manual),
+            other => {
+                let policy = match other.strip_suffix('s') {
+                    Some(secs) => {
+                        Self::Interval(Duration::from_secs(secs.parse().map_err(|_| invalid())?))
+                    }
+                    None => Self::EveryStatements(other.parse().map_err(|_| invalid())?),
+                };
+                match policy {
+                    Self::Interval(d) if d.is_zero() => Err(invalid()),
+                    Self::EveryStatements(0) => Err(invalid()),
+                    policy => Ok(policy),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for PersistencePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EveryStatement => f.write_str("always"),
+            Self::EveryStatements(n) => write!(f, "{}", n),
+            Self::Interval(d) => write!(f, "{}s", d.as_secs()),
+            Self::Manual => f.write_str("manual"),
+        }
+    }
+}
+
+/// Decides when a database's changes reach its storage, according to a `PersistencePolicy`.
+///
+/// Front ends report every executed statement with `after_statement` and call `tick`
+/// periodically so time-based policies flush even while idle. Clean databases are never
+/// written, whatever the policy.
+#[derive(Debug)]
+pub struct PersistenceManager {
+    storage: Storage,
+    policy: PersistencePolicy,
+    statements_since_flush: usize,
+    last_flush: Instant,
+}
+
+impl PersistenceManager {
+    pub fn new(storage: Storage, policy: PersistencePolicy) -> Self {
+        Self {
+            storage,
+            policy,
+            statements_since_flush: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub fn policy(&self) -> PersistencePolicy {
+        self.policy
+    }
+
+    /// Switches to `policy`, e.g. on a configuration reload. Pending changes stay pending and
+    /// the new policy counts from the last flush.
+    pub fn set_policy(&mut self, policy: PersistencePolicy) {
+        self.policy = policy;
+    }
+
+    /// Loads the database from the managed storage.
+    pub fn load(&self) -> Result<Database, DbError> {
+        self.storage.load()
+    }
+
+    /// Loads the database within `limits.max_load_bytes` and applies `limits` to it.
+    pub fn load_limited(&self, limits: Limits) -> Result<Database, DbError> {
+        let mut db = self.storage.load_with_ceiling(limits.max_load_bytes)?;
+        db.limits = limits;
+        Ok(db)
+    }
+
+    /// Like `load_limited`, then checks the integrity of the database. With `repair` the
+    /// issues found are fixed, the database saved and the issues returned as a report;
+    /// without it they are an `IntegrityViolation`, so a damaged database is never served or
+    /// overwritten. A leftover `.partial` file is no damage, as the database file is the
+    /// last complete save: it is removed and reported either way.
+    pub fn load_checked(
+        &self,
+        limits: Limits,
+        repair: bool,
+    ) -> Result<(Database, Vec<IntegrityIssue>), DbError> {
+        let mut db = self.load_limited(limits)?;
+        if repair {
+            // Saved right away, so the next start finds the repaired database.
+            let fixed = integrity::repair(&mut db, &self.storage)?;
+            if !fixed.is_empty() {
+                db.flush(&self.storage)?;
+            }
+            return Ok((db, fixed));
+        }
+        let (orphans, issues): (Vec<_>, Vec<_>) = integrity::check_integrity(&db, &self.storage)
+            .into_iter()
+            .partition(|issue| matches!(issue, IntegrityIssue::OrphanedFile(_)));
+        match issues.as_slice() {
+            [] => {
+                for orphan in &orphans {
+                    if let IntegrityIssue::OrphanedFile(path) = orphan {
+                        std::fs::remove_file(path).map_err(|e| {
+                            DbError::IoError(format!("Could not remove {}: {}", path.display(), e))
+                        })?;
+                    }
+                }
+                Ok((db, orphans))
+            }
+            [first, rest @ ..] => Err(DbError::IntegrityViolation(format!(
+                "{}{}; start with --repair to fix it",
+                first,
+                match rest.len() {
+                    0 => String::new(),
+                    n => format!(" and {} more issue(s)", n),
+                }
+            ))),
+        }
+    }
+
+    /// Records that a statement ran and flushes if the policy says so.
+    pub fn after_statement(&mut self, db: &mut Database) -> Result<(), DbError> {
+        self.statements_since_flush += 1;
+        match self.policy {
+            PersistencePolicy::EveryStatement => self.flush(db),
+            PersistencePolicy::EveryStatements(n) if self.statements_since_flush >= n => {
+                self.flush(db)
+            }
+            _ => self.tick(db),
+        }
+    }
+
+    /// Flushes if an `Interval` policy's period has elapsed.
+    pub fn tick(&mut self, db: &mut Database) -> Result<(), DbError> {
+        match self.policy {
+            PersistencePolicy::Interval(period) if self.last_flush.elapsed() >= period => {
+                self.flush(db)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes pending changes now, regardless of the policy.
+    pub fn flush(&mut self, db: &mut Database) -> Result<(), DbError> {
+        db.flush(&self.storage)?;
+        self.statements_since_flush = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_persistence_policies() {
+        assert_eq!("always".parse(), Ok(PersistencePolicy::EveryStatement));
+        assert_eq!("25".parse(), Ok(PersistencePolicy::EveryStatements(25)));
+        assert_eq!(
+            "30s".parse(),
+            Ok(PersistencePolicy::Interval(Duration::from_secs(30)))
+        );
+        assert!("0".parse::<PersistencePolicy>().is_err());
+        assert!("soon".parse::<PersistencePolicy>().is_err());
+
+        let mut db = Database::new();
+        let mut manager =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatements(2));
+        db.execute(parse("CREATE TABLE t (id INT)").unwrap())
+            .unwrap();
+        manager.after_statement(&mut db).unwrap();
+        assert!(db.is_dirty());
+        db.execute(parse("SELECT * FROM t").unwrap()).unwrap();
+        manager.after_statement(&mut db).unwrap();
+        assert!(!db.is_dirty());
+
+        let mut manager = PersistenceManager::new(Storage::Memory, PersistencePolicy::Manual);
+        db.execute(parse("INSERT INTO t VALUES (1)").unwrap())
+            .unwrap();
+        manager.after_statement(&mut db).unwrap();
+        manager.tick(&mut db).unwrap();
+        assert!(db.is_dirty());
+        manager.flush(&mut db).unwrap();
+        assert!(!db.is_dirty());
+    }
+}
@@ -0,0 +1,280 @@
+//! Random data for benchmarks and demos: rows that fit a table's schema, generated in bulk.
+//!
+//! Values follow the column types and names: an `INT PRIMARY KEY` counts up from the largest
+//! stored id, a `name` column gets names, an `email` column addresses and a `created_at`
+//! column timestamps. PRIMARY and UNIQUE columns never repeat a value, stored or generated.
+//! An `INT` column named after another table, like `user_id` or `team_id` for `users` and
+//! `teams`, takes its values from that table's primary key.
+
+use std::collections::HashSet;
+
+use crate::{
+    Database, DbError,
+    engine::{
+        Column, Decimal, Real, Table, Timestamp, Value, datetime::format_timestamp,
+        decimal::DecimalType, schedules::unix_now,
+    },
+    parser::Statement,
+};
+
+const FIRST_NAMES: &[&str] = &[
+    "ada", "grace", "alan", "linus", "margaret", "dennis", "barbara", "ken", "frances", "edsger",
+];
+const LAST_NAMES: &[&str] = &[
+    "lovelace", "hopper", "turing", "torvalds", "hamilton", "ritchie", "liskov", "thompson",
+    "allen", "dijkstra",
+];
+const CITIES: &[&str] = &[
+    "nairobi", "lagos", "berlin", "lima", "osaka", "toronto", "oslo", "cairo", "austin", "pune",
+];
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "delta", "echo", "kilo", "lima", "nova", "orbit", "pixel", "quartz", "river",
+    "solar", "tango", "vector", "zephyr",
+];
+
+/// Generated timestamps fall within this many seconds before now.
+const TIMESTAMP_SPAN: u64 = 365 * 24 * 60 * 60;
+
+/// A small xorshift generator: fast, seedable and good enough for test data.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    /// A generator whose output is determined by `seed`, so a data set can be reproduced.
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero.
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    /// `count` new rows for `table`. `references` are the values available to each column
+    /// that refers to another table, `None` for the other columns.
+    pub fn rows(
+        &mut self,
+        table: &Table,
+        count: usize,
+        references: &[Option<Vec<Value>>],
+    ) -> Vec<Vec<Value>> {
+        let mut taken: Vec<HashSet<Value>> = (0..table.columns.len())
+            .map(|i| table.indexes.get(&i).cloned().unwrap_or_default())
+            .collect();
+        let mut next_ids: Vec<i32> = (0..table.columns.len())
+            .map(|i| {
+                let max = table.rows.iter().filter_map(|row| match row[i] {
+                    Value::Integer(n) => Some(n),
+                    Value::BigInt(n) => i32::try_from(n).ok(),
+                    _ => None,
+                });
+                max.max().unwrap_or(0).saturating_add(1)
+            })
+            .collect();
+
+        (0..count)
+            .map(|_| {
+                (0..table.columns.len())
+                    .map(|i| {
+                        let column = &table.columns[i];
+                        if !(column.is_primary || column.is_unique) {
+                            return self.value(column, references[i].as_deref());
+                        }
+                        if matches!(column.data_type.as_str(), "INT" | "BIGINT") {
+                            let id = next_ids[i];
+                            next_ids[i] = id.saturating_add(1);
+                            return match column.data_type.as_str() {
+                                "BIGINT" => Value::BigInt(id.into()),
+                                _ => Value::Integer(id),
+                            };
+                        }
+                        let mut value = self.value(column, None);
+                        let mut attempt = 1;
+                        while taken[i].contains(&value) {
+                            attempt += 1;
+                            value = match self.value(column, None) {
+                                Value::Text(text) => Value::Text(format!("{}_{}", text, attempt)),
+                                other => other,
+                            };
+                        }
+                        taken[i].insert(value.clone());
+                        value
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// A value for a column without uniqueness constraint.
+    fn value(&mut self, column: &Column, references: Option<&[Value]>) -> Value {
+        if let Some(values) = references {
+            return match values {
+                [] => Value::Null,
+                values => self.pick(values).clone(),
+            };
+        }
+        let name = column.name.to_lowercase();
+        if column.data_type == "INT" {
+            let max = match name.as_str() {
+                n if n.contains("age") => 90,
+                n if n.contains("qty") || n.contains("quantity") => 100,
+                _ => 10_000,
+            };
+            return Value::Integer(self.below(max) as i32 + 1);
+        }
+        if column.data_type == "BIGINT" {
+            return Value::BigInt(self.below(i64::MAX as u64) as i64 + 1);
+        }
+        if matches!(column.data_type.as_str(), "DATE" | "TIMESTAMP") {
+            let secs = unix_now().saturating_sub(self.below(TIMESTAMP_SPAN)) as i64;
+            let at = Timestamp::from_secs(secs).unwrap();
+            return match column.data_type.as_str() {
+                "DATE" => Value::Date(at.date()),
+                _ => Value::Timestamp(at),
+            };
+        }
+        if column.data_type == "REAL" {
+            // Two decimals, like a price.
+            let cents = self.below(1_000_000) as f64;
+            return Value::Real(Real::new(cents / 100.0).unwrap());
+        }
+        if let Some(decimal_type) = DecimalType::parse(&column.data_type) {
+            let cents = Decimal::new(self.below(1_000_000).into(), 2).unwrap();
+            let zero = Decimal::from_integer(0);
+            return Value::Decimal(decimal_type.fit(cents).unwrap_or(zero));
+        }
+
+        let text = if name.contains("email") {
+            let first = self.pick(FIRST_NAMES);
+            let last = self.pick(LAST_NAMES);
+            format!("{}.{}@example.com", first, last)
+        } else if name.contains("name") {
+            let first = self.pick(FIRST_NAMES);
+            let last = self.pick(LAST_NAMES);
+            format!("{}_{}", first, last)
+        } else if name.contains("city") {
+            self.pick(CITIES).to_string()
+        } else if name.ends_with("_at") || name.contains("date") || name.contains("time") {
+            let secs = unix_now().saturating_sub(self.below(TIMESTAMP_SPAN));
+            format_timestamp(secs as i64)
+        } else {
+            let first = self.pick(WORDS);
+            let second = self.pick(WORDS);
+            format!("{}_{}", first, second)
+        };
+        Value::Text(text)
+    }
+}
+
+impl Database {
+    /// Inserts `count` generated rows into `table_name` and returns how many were inserted.
+    /// The rows go through the same checks as an `INSERT`, storage limits included.
+    pub fn generate_rows(
+        &mut self,
+        table_name: &str,
+        count: usize,
+        seed: u64,
+    ) -> Result<usize, DbError> {
+        if self.virtual_tables.contains_key(table_name) {
+            return Err(DbError::ReadOnlyTable(table_name.to_string()));
+        }
+        let table = self
+            .tables
+            .get(table_name)
+            .ok_or_else(|| DbError::TableNotFound(table_name.to_string()))?;
+        let references: Vec<Option<Vec<Value>>> = table
+            .columns
+            .iter()
+            .map(|column| self.referenced_keys(table_name, column))
+            .collect();
+        let rows = Generator::new(seed).rows(table, count, &references);
+
+        self.execute(Statement::Insert {
+            table_name: table_name.to_string(),
+            rows,
+            on_conflict: None,
+        })?;
+        Ok(count)
+    }
+
+    /// The primary key values of the table an `INT` column such as `user_id` refers to by
+    /// name, if there is one.
+    fn referenced_keys(&self, table_name: &str, column: &Column) -> Option<Vec<Value>> {
+        if column.data_type != "INT" || column.is_primary {
+            return None;
+        }
+        let prefix = column.name.to_lowercase().strip_suffix("_id")?.to_string();
+        let target = [format!("{}s", prefix), prefix]
+            .into_iter()
+            .filter(|name| name != table_name)
+            .find_map(|name| self.tables.get(&name))?;
+        let key = target.columns.iter().position(|c| c.is_primary)?;
+        Some(target.rows.iter().map(|row| row[key].clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_generate_rows() {
+        let mut db = Database::new();
+        let mut run = |sql: &str| db.execute(parse(sql).unwrap());
+        run("CREATE TABLE users (id INT PRIMARY KEY, email TEXT UNIQUE, age INT)").unwrap();
+        run("CREATE TABLE orders (id INT PRIMARY KEY, user_id INT, created_at TEXT)").unwrap();
+        run("INSERT INTO users VALUES (7, 'ada@example.com', 36)").unwrap();
+
+        assert_eq!(db.generate_rows("users", 500, 1).unwrap(), 500);
+        let users = &db.tables["users"];
+        assert_eq!(users.rows.len(), 501);
+        assert_eq!(users.rows[1][0], Value::Integer(8));
+        assert_eq!(users.indexes[&1].len(), 501);
+        assert!(
+            users.rows[1..]
+                .iter()
+                .all(|row| matches!(row[2], Value::Integer(1..=90)))
+        );
+
+        db.generate_rows("orders", 200, 2).unwrap();
+        let ids = &db.tables["users"].indexes[&0];
+        assert!(
+            db.tables["orders"]
+                .rows
+                .iter()
+                .all(|row| ids.contains(&row[1]))
+        );
+
+        // The same seed gives the same rows.
+        let mut other = Database::new();
+        other
+            .execute(parse("CREATE TABLE words (id INT, word TEXT)").unwrap())
+            .unwrap();
+        other.generate_rows("words", 10, 3).unwrap();
+        let first = other.tables["words"].rows.clone();
+        other.generate_rows("words", 10, 3).unwrap();
+        assert_eq!(other.tables["words"].rows[10..], first[..]);
+
+        assert!(matches!(
+            db.generate_rows("missing", 1, 0),
+            Err(DbError::TableNotFound(_))
+        ));
+    }
+}
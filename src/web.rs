@@ -0,0 +1,2016 @@
+//! The HTTP API: `app` builds the router the `web` binary serves on port 3000.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+use crate::{
+    Database,
+    auth::{AuthConfig, AuthProvider},
+    cli::{arg_value, json_style_from_args, limits_from_args, policy_from_args},
+    engine::{
+        Column, ExecutionResult, Real, Value,
+        limits::Limits,
+        plan::PlanNode,
+        schedules::unix_now,
+        session::{ExecutionContext, Session},
+    },
+    error::syntax_error::SyntaxError,
+    parser::{Statement, parse, split_statements},
+    storage::{IntegrityIssue, JsonStyle, PersistenceManager, PersistencePolicy, Storage},
+};
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{FromRequestParts, Path as UrlPath, Query, RawPathParams, Request, State},
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use tower_http::compression::CompressionLayer;
+
+/// Directory holding one database file per tenant.
+const TENANTS_DIR: &str = "tenants";
+
+/// Rows per chunk of a result streamed with `?format=ndjson`.
+const STREAM_CHUNK_ROWS: usize = 1000;
+
+/// The most sessions one principal keeps open in a database at once.
+const MAX_SESSIONS_PER_PRINCIPAL: usize = 16;
+
+/// A session kept between the requests that send the same `X-Session-Id` header, so its
+/// variables and open transaction carry over.
+struct IdleSession {
+    session: Session,
+    last_used: Instant,
+}
+
+struct AppStateInner {
+    db: Database,
+    persistence: PersistenceManager,
+    sessions: HashMap<String, IdleSession>,
+    /// Rejects the statements that would change the database.
+    read_only: bool,
+    /// Principals whose sessions see the values of `MASKED` columns.
+    unmask: Vec<String>,
+    /// Principals whose sessions see every user's statements in `system.queries`; everyone
+    /// when no auth provider is configured.
+    admins: Option<Vec<String>>,
+}
+
+impl AppStateInner {
+    fn new(db: Database, persistence: PersistenceManager, settings: &Settings) -> Self {
+        let mut state = Self {
+            db,
+            persistence,
+            sessions: HashMap::new(),
+            read_only: false,
+            unmask: Vec::new(),
+            admins: None,
+        };
+        state.configure(settings);
+        state
+    }
+
+    /// Applies reloadable settings. The data, the sessions and pending changes are kept.
+    fn configure(&mut self, settings: &Settings) {
+        self.db.limits = settings.limits;
+        self.db.json_style = settings.json_style;
+        self.db.statement_timeout = settings.statement_timeout;
+        self.persistence.set_policy(settings.policy);
+        self.read_only = settings.read_only;
+        self.unmask = settings.unmask.clone();
+        self.admins = settings.auth.is_some().then(|| settings.admins.clone());
+    }
+
+    /// Fails when the server is read-only and `stmt` is not.
+    fn check_writable(&self, stmt: &Statement) -> Result<(), String> {
+        if self.read_only && !stmt.is_read_only() {
+            return Err("The server is read-only".into());
+        }
+        Ok(())
+    }
+
+    /// Reports an executed statement to the persistence policy.
+    fn after_statement(&mut self) -> crate::Result<()> {
+        self.persistence.after_statement(&mut self.db)
+    }
+
+    /// Lets a time-based persistence policy flush while the database is idle.
+    fn tick(&mut self) -> crate::Result<()> {
+        self.persistence.tick(&mut self.db)
+    }
+
+    /// Drops the sessions unused for longer than `timeout`, rolling back the transactions
+    /// they left open so other sessions can write again.
+    fn reap_sessions(&mut self, timeout: Duration) {
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, idle)| idle.last_used.elapsed() > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            let idle = self.sessions.remove(&id).unwrap();
+            self.db.end_session(idle.session);
+        }
+    }
+
+    /// The session a request with `X-Session-Id: id` continues, or a new one. A session can
+    /// only be continued by the principal that opened it, and each principal keeps at most
+    /// `MAX_SESSIONS_PER_PRINCIPAL` of them open.
+    fn take_session(&mut self, id: Option<&str>, principal: &Principal) -> Result<Session, String> {
+        let mut session = match id.map(|id| (id, self.sessions.get(id))) {
+            Some((id, Some(idle))) if idle.session.user != principal.0 => {
+                return Err(format!("Session '{}' belongs to another user", id));
+            }
+            Some((id, Some(_))) => self.sessions.remove(id).unwrap().session,
+            Some(_)
+                if self
+                    .sessions
+                    .values()
+                    .filter(|idle| idle.session.user == principal.0)
+                    .count()
+                    >= MAX_SESSIONS_PER_PRINCIPAL =>
+            {
+                return Err(format!(
+                    "Too many open sessions for '{}', at most {}",
+                    principal.0, MAX_SESSIONS_PER_PRINCIPAL
+                ));
+            }
+            _ => Session::new(&principal.0),
+        };
+        // Checked on every request, so a reload that revokes the privilege applies at once.
+        session.unmask = self.unmask.contains(&principal.0);
+        session.admin = self
+            .admins
+            .as_ref()
+            .is_none_or(|admins| admins.contains(&principal.0));
+        Ok(session)
+    }
+
+    /// Keeps `session` for the next request with the same id. Sessions without one end here,
+    /// rolling back a transaction they began.
+    fn keep_session(&mut self, id: Option<String>, session: Session) {
+        match id {
+            Some(id) => {
+                let last_used = Instant::now();
+                self.sessions.insert(id, IdleSession { session, last_used });
+            }
+            None => self.db.end_session(session),
+        }
+    }
+
+    /// Persists pending changes regardless of the policy.
+    fn flush(&mut self) -> crate::Result<()> {
+        self.persistence.flush(&mut self.db)
+    }
+}
+
+/// Time spent waiting for the lock of one database, in microseconds.
+#[derive(Default)]
+struct LockMetrics {
+    read_acquisitions: AtomicU64,
+    read_wait_us: AtomicU64,
+    write_acquisitions: AtomicU64,
+    write_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+impl LockMetrics {
+    /// Counts an acquisition of the read or write lock that was requested at `started`.
+    fn record(&self, write: bool, started: Instant) {
+        let (acquisitions, wait_us) = if write {
+            (&self.write_acquisitions, &self.write_wait_us)
+        } else {
+            (&self.read_acquisitions, &self.read_wait_us)
+        };
+        let waited = started.elapsed().as_micros() as u64;
+        acquisitions.fetch_add(1, Ordering::Relaxed);
+        wait_us.fetch_add(waited, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(waited, Ordering::Relaxed);
+    }
+
+    /// The counters in the Prometheus text format.
+    fn render(&self) -> String {
+        [
+            ("lock_read_acquisitions_total", &self.read_acquisitions),
+            ("lock_read_wait_us_total", &self.read_wait_us),
+            ("lock_write_acquisitions_total", &self.write_acquisitions),
+            ("lock_write_wait_us_total", &self.write_wait_us),
+            ("lock_max_wait_us", &self.max_wait_us),
+        ]
+        .iter()
+        .map(|(name, value)| format!("martin_db_{} {}\n", name, value.load(Ordering::Relaxed)))
+        .collect()
+    }
+}
+
+/// A database behind its lock, recording how long every acquisition had to wait.
+struct Shared {
+    state: RwLock<AppStateInner>,
+    locks: LockMetrics,
+}
+
+impl Shared {
+    fn new(state: AppStateInner) -> Arc<Self> {
+        Arc::new(Self {
+            state: RwLock::new(state),
+            locks: LockMetrics::default(),
+        })
+    }
+
+    /// A request that panicked while holding the lock poisons it; the database stays served
+    /// rather than failing every later request.
+    fn read(&self) -> RwLockReadGuard<'_, AppStateInner> {
+        let started = Instant::now();
+        let guard = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        self.locks.record(false, started);
+        guard
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, AppStateInner> {
+        let started = Instant::now();
+        let guard = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        self.locks.record(true, started);
+        guard
+    }
+}
+
+type SharedState = Arc<Shared>;
+
+/// The default database served at `/` plus the tenant databases served under `/t/{tenant}/`.
+/// Only tenants bound to a principal in the settings exist. They are loaded lazily on first
+/// use and each one is locked and flushed independently.
+#[derive(Clone)]
+pub struct AppState {
+    default: SharedState,
+    tenants: Arc<Mutex<HashMap<String, SharedState>>>,
+    settings: Arc<RwLock<Settings>>,
+}
+
+impl AppState {
+    /// Serves `db` at `/`, flushing it through `persistence`.
+    pub fn new(db: Database, persistence: PersistenceManager, settings: Settings) -> Self {
+        Self {
+            default: Shared::new(AppStateInner::new(db, persistence, &settings)),
+            tenants: Arc::new(Mutex::new(HashMap::new())),
+            settings: Arc::new(RwLock::new(settings)),
+        }
+    }
+
+    /// Returns the tenant's database, loading it from its own file on first access.
+    fn tenant(&self, name: &str) -> Result<SharedState, String> {
+        let valid = !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(format!("Invalid tenant name: {}", name));
+        }
+
+        let mut tenants = self.tenants.lock().unwrap();
+        if let Some(state) = tenants.get(name) {
+            return Ok(state.clone());
+        }
+
+        // Tenants share the server's settings; those of an in-memory server are in-memory too.
+        let settings = self.settings();
+        let storage = if self.default.read().persistence.storage().is_memory() {
+            Storage::Memory
+        } else {
+            Storage::new(Path::new(TENANTS_DIR).join(format!("{}.json", name)))
+        };
+        let persistence = PersistenceManager::new(storage, settings.policy);
+        let (db, repaired) = persistence
+            .load_checked(settings.limits, settings.repair)
+            .map_err(|e| e.to_string())?;
+        report_repairs(&format!("tenant '{}'", name), &repaired);
+        let state = Shared::new(AppStateInner::new(db, persistence, &settings));
+        tenants.insert(name.to_string(), state.clone());
+        Ok(state)
+    }
+
+    /// Every database currently loaded in memory.
+    fn loaded(&self) -> Vec<SharedState> {
+        let mut all = vec![self.default.clone()];
+        all.extend(self.tenants.lock().unwrap().values().cloned());
+        all
+    }
+
+    fn settings(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Reads the settings again and applies them to every loaded database, without dropping
+    /// its data or sessions. Invalid settings are reported and the current ones kept.
+    fn reload(&self) -> Result<(), String> {
+        let settings = Settings::load()?;
+        for state in self.loaded() {
+            state.write().configure(&settings);
+        }
+        *self.settings.write().unwrap() = settings;
+        Ok(())
+    }
+}
+
+/// Extractor resolving the database a request targets: the tenant its principal is bound to,
+/// else the default database. A `{tenant}` path segment naming any other tenant is refused
+/// with `403`, so requests can neither reach another principal's tenant nor create new ones.
+struct Db(SharedState);
+
+impl FromRequestParts<AppState> for Db {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let params = RawPathParams::from_request_parts(parts, app)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        let Ok(Principal(principal)) = Principal::from_request_parts(parts, app).await;
+        let tenant = app
+            .settings
+            .read()
+            .unwrap()
+            .tenants
+            .get(&principal)
+            .cloned();
+        if let Some((_, requested)) = params.iter().find(|(key, _)| *key == "tenant")
+            && tenant.as_deref() != Some(requested)
+        {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Principal '{}' has no access to tenant '{}'",
+                    principal, requested
+                ),
+            ));
+        }
+
+        match tenant {
+            Some(tenant) => app
+                .tenant(&tenant)
+                .map(Db)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e)),
+            None => Ok(Db(app.default.clone())),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct QueryRequest {
+    sql: String,
+    /// Values for the `?` placeholders of `sql`, in order: integers, strings or `null`.
+    params: Option<Vec<serde_json::Value>>,
+}
+
+impl QueryRequest {
+    /// Parses the SQL. SQL with parameters is prepared once and bound on every request.
+    fn parse(&self, db: &mut Database) -> Result<Statement, SyntaxError> {
+        let Some(params) = &self.params else {
+            return db.parse_cached(&self.sql);
+        };
+        let params = params
+            .iter()
+            .map(|param| match param {
+                serde_json::Value::Null => Ok(Value::Null),
+                serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+                serde_json::Value::Number(n) if n.is_f64() => n
+                    .as_f64()
+                    .and_then(Real::new)
+                    .map(Value::Real)
+                    .ok_or_else(|| format!("Unsupported parameter {}", n)),
+                serde_json::Value::Number(n) => n
+                    .as_i64()
+                    .map(|n| i32::try_from(n).map_or(Value::BigInt(n), Value::Integer))
+                    .ok_or_else(|| format!("Unsupported parameter {}", n)),
+                other => Err(format!("Unsupported parameter {}", other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(db.prepare(&self.sql)?.bind(&params)?)
+    }
+}
+
+/// Query string options of `/query`.
+#[derive(Deserialize, Default)]
+struct QueryOptions {
+    /// Runs the statement and undoes it, reporting what it would have done.
+    #[serde(default)]
+    dry_run: bool,
+    /// How NULLs are written. Setting this or `numbers` switches the rows from the debug
+    /// rendering (`Integer(7)`, `Text("ada")`, `Null`) to plain JSON values.
+    nulls: Option<NullStyle>,
+    /// The placeholder written for NULL with `nulls=text`, `NULL` by default.
+    null_text: Option<String>,
+    numbers: Option<NumberStyle>,
+    #[serde(default)]
+    format: ResponseFormat,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ResponseFormat {
+    /// One JSON object holding every row.
+    #[default]
+    Json,
+    /// Newline-delimited JSON: a line with the headers and types, then one line per row,
+    /// streamed in chunks. Messages and errors still come back as a single JSON object.
+    Ndjson,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum NullStyle {
+    /// JSON `null`.
+    #[default]
+    Null,
+    /// Left out. Rows are then objects keyed by column header instead of arrays, so the
+    /// remaining values keep their names.
+    Omit,
+    /// The `null_text` placeholder, as a string.
+    Text,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum NumberStyle {
+    #[default]
+    Number,
+    /// Strings, for consumers whose numbers cannot hold every integer exactly.
+    String,
+}
+
+impl QueryOptions {
+    /// Renders the rows of a result as the options ask.
+    fn render_rows(&self, headers: &[String], rows: Vec<Vec<Value>>) -> Vec<serde_json::Value> {
+        rows.into_iter()
+            .map(|row| self.render_row(headers, row))
+            .collect()
+    }
+
+    fn render_row(&self, headers: &[String], row: Vec<Value>) -> serde_json::Value {
+        use serde_json::Value as Json;
+
+        if self.nulls.is_none() && self.numbers.is_none() {
+            return row
+                .into_iter()
+                .map(|v| Json::from(format!("{:?}", v)))
+                .collect();
+        }
+        let nulls = self.nulls.unwrap_or_default();
+        let null_text = self.null_text.as_deref().unwrap_or("NULL");
+        let value = |v: Value| match v {
+            Value::Integer(n) => match self.numbers.unwrap_or_default() {
+                NumberStyle::Number => Json::from(n),
+                NumberStyle::String => Json::from(n.to_string()),
+            },
+            Value::BigInt(n) => match self.numbers.unwrap_or_default() {
+                NumberStyle::Number => Json::from(n),
+                NumberStyle::String => Json::from(n.to_string()),
+            },
+            Value::Real(r) => match self.numbers.unwrap_or_default() {
+                NumberStyle::Number => Json::from(r.get()),
+                NumberStyle::String => Json::from(r.to_string()),
+            },
+            Value::Decimal(d) => match self.numbers.unwrap_or_default() {
+                NumberStyle::Number => Json::from(d.to_f64()),
+                NumberStyle::String => Json::from(d.to_string()),
+            },
+            Value::Date(d) => Json::from(d.to_string()),
+            Value::Timestamp(t) => Json::from(t.to_string()),
+            Value::Text(s) => Json::from(s),
+            Value::Null if nulls == NullStyle::Text => Json::from(null_text),
+            Value::Null => Json::Null,
+        };
+        match nulls {
+            NullStyle::Omit => headers
+                .iter()
+                .zip(row)
+                .filter(|(_, v)| *v != Value::Null)
+                .map(|(h, v)| (h.clone(), value(v)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            _ => row.into_iter().map(value).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct QueryResponse {
+    message: String,
+    headers: Vec<String>,
+    /// Arrays of values, or objects with `nulls=omit`; see `QueryOptions`.
+    rows: Vec<serde_json::Value>,
+    /// The type of each column, e.g. `INT`, `TEXT` or `TIMESTAMP`.
+    types: Vec<String>,
+    error: Option<String>,
+    /// Byte offset into the SQL of the token a syntax error points at.
+    position: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    /// Statements separated by `;`.
+    sql: String,
+    /// Applies all of the statements or none of them.
+    #[serde(default)]
+    atomic: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BatchStatus {
+    Ok,
+    Error,
+    /// Not run, because an earlier statement of an atomic batch failed.
+    Skipped,
+}
+
+/// The outcome of one statement of a batch, at position `index` (from 0).
+#[derive(Serialize)]
+struct BatchResult {
+    index: usize,
+    sql: String,
+    status: BatchStatus,
+    message: String,
+    /// Rows the statement inserted or updated.
+    rows_affected: usize,
+    headers: Vec<String>,
+    rows: Vec<serde_json::Value>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    /// Whether the changes of the batch were kept: false only when an atomic batch failed.
+    committed: bool,
+    results: Vec<BatchResult>,
+    /// A failure to persist the batch, which was applied in memory.
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SavedQuery {
+    name: String,
+    sql: String,
+}
+
+/// A stored table as listed by `/schema`, with its comment and those of its columns.
+#[derive(Serialize)]
+struct TableSchema {
+    name: String,
+    comment: Option<String>,
+    columns: Vec<Column>,
+    rows: usize,
+}
+
+#[derive(Serialize)]
+struct ExplainResponse {
+    plan: Option<PlanNode>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EstimateResponse {
+    estimated_rows: Option<usize>,
+    error: Option<String>,
+}
+
+/// The identity a request runs as. The `authenticate` layer in front of the handlers stores it
+/// in the request extensions; without an auth provider configured, requests run as `web`.
+#[derive(Clone)]
+struct Principal(String);
+
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .unwrap_or_else(|| Principal("web".into())))
+    }
+}
+
+/// Extractor admitting only administrators: the principals listed in the `admins` setting, or
+/// everyone when no auth provider is configured. Others are refused with `403`.
+struct Admin;
+
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Ok(Principal(principal)) = Principal::from_request_parts(parts, app).await;
+        let settings = app.settings.read().unwrap();
+        if settings.auth.is_none() || settings.admins.contains(&principal) {
+            Ok(Admin)
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("Principal '{}' is not an administrator", principal),
+            ))
+        }
+    }
+}
+
+/// Runs the request as the principal its credentials belong to, or refuses it with `401` when
+/// an auth provider is configured and they don't check out.
+async fn authenticate(State(app): State<AppState>, mut request: Request, next: Next) -> Response {
+    let Some(provider) = app.settings().auth else {
+        return next.run(request).await;
+    };
+    match provider.authenticate(request.headers()) {
+        Ok(principal) => {
+            request.extensions_mut().insert(Principal(principal));
+            next.run(request).await
+        }
+        Err(e) => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, provider.challenge())],
+            e,
+        )
+            .into_response(),
+    }
+}
+
+/// The `X-Session-Id` header of a request. Requests without one get a session of their own.
+fn session_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-session-id")
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Reads `--session-timeout <seconds>`, how long an unused session is kept. Defaults to five
+/// minutes.
+fn session_timeout_from_args() -> Result<Duration, String> {
+    match arg_value("session-timeout") {
+        Some(secs) => secs
+            .parse()
+            .map(Duration::from_secs)
+            .map_err(|_| format!("Invalid --session-timeout: {}", secs)),
+        None => Ok(Duration::from_secs(300)),
+    }
+}
+
+/// Reads `--statement-timeout <seconds>`, how long a SELECT may run. Unlimited by default.
+fn statement_timeout_from_args() -> Result<Option<Duration>, String> {
+    arg_value("statement-timeout")
+        .map(|secs| {
+            secs.parse()
+                .map(Duration::from_secs)
+                .map_err(|_| format!("Invalid --statement-timeout: {}", secs))
+        })
+        .transpose()
+}
+
+/// The settings a running server can reload: the command-line flags, overridden by the JSON
+/// file given with `--config <path>` when there is one. The database file itself is fixed.
+#[derive(Clone)]
+pub struct Settings {
+    pub policy: PersistencePolicy,
+    pub limits: Limits,
+    pub json_style: JsonStyle,
+    /// How long a session may stay unused before it is dropped.
+    pub session_timeout: Duration,
+    /// How long a SELECT may run before it is stopped with an error.
+    pub statement_timeout: Option<Duration>,
+    pub read_only: bool,
+    /// Repairs databases that fail the integrity check when they are loaded, instead of
+    /// refusing them.
+    pub repair: bool,
+    /// Principals allowed to see the values of `MASKED` columns.
+    pub unmask: Vec<String>,
+    /// Principals allowed to flush, bulk-load with COPY and reload the settings, and who see
+    /// the statements of every user in `system.queries`.
+    pub admins: Vec<String>,
+    /// The tenant each principal's requests go to. Principals not listed use the default
+    /// database, and tenants no principal is bound to are never created.
+    pub tenants: HashMap<String, String>,
+    /// Checks the credentials of every request; `None` lets them all in as `web`.
+    pub auth: Option<Arc<dyn AuthProvider>>,
+}
+
+impl Default for Settings {
+    /// The settings of a server started without flags.
+    fn default() -> Self {
+        Self {
+            policy: PersistencePolicy::EveryStatement,
+            limits: Limits::default(),
+            json_style: JsonStyle::Auto,
+            session_timeout: Duration::from_secs(300),
+            statement_timeout: None,
+            read_only: false,
+            repair: false,
+            unmask: Vec::new(),
+            admins: Vec::new(),
+            tenants: HashMap::new(),
+            auth: None,
+        }
+    }
+}
+
+/// The `--config` file. Every key is optional and takes the syntax of the matching flag, e.g.
+/// `{"persist": "30s", "max_size": "10m", "read_only": true}`. `unmask` and `admins` take a
+/// list of principals, where the flags take them comma-separated. `tenants` maps principals to their
+/// tenant, e.g. `{"ada": "acme"}`, and has no flag. Neither has `auth`; see `martin_db::auth`
+/// for the providers it selects.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    persist: Option<String>,
+    max_rows: Option<usize>,
+    max_size: Option<String>,
+    max_load_size: Option<String>,
+    json: Option<String>,
+    session_timeout: Option<u64>,
+    statement_timeout: Option<u64>,
+    read_only: Option<bool>,
+    repair: Option<bool>,
+    unmask: Option<Vec<String>>,
+    admins: Option<Vec<String>>,
+    tenants: Option<HashMap<String, String>>,
+    auth: Option<AuthConfig>,
+}
+
+impl Settings {
+    /// Reads the flags, then the `--config` file. Called again on every reload, so edits to
+    /// the file take effect and keys removed from it fall back to the flags.
+    pub fn load() -> Result<Self, String> {
+        let mut settings = Self {
+            policy: policy_from_args()?,
+            limits: limits_from_args()?,
+            json_style: json_style_from_args()?,
+            session_timeout: session_timeout_from_args()?,
+            statement_timeout: statement_timeout_from_args()?,
+            read_only: std::env::args().any(|arg| arg == "--read-only"),
+            repair: std::env::args().any(|arg| arg == "--repair"),
+            unmask: arg_value("unmask")
+                .map(|list| list.split(',').map(|p| p.trim().to_string()).collect())
+                .unwrap_or_default(),
+            admins: arg_value("admins")
+                .map(|list| list.split(',').map(|p| p.trim().to_string()).collect())
+                .unwrap_or_default(),
+            tenants: HashMap::new(),
+            auth: None,
+        };
+        let Some(path) = arg_value("config") else {
+            return Ok(settings);
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path, e))?;
+        let file: ConfigFile = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+        if let Some(policy) = file.persist {
+            settings.policy = policy.parse()?;
+        }
+        if let Some(max_rows) = file.max_rows {
+            settings.limits.max_rows = Some(max_rows);
+        }
+        if let Some(size) = file.max_size {
+            settings.limits.max_bytes = Some(Limits::parse_size(&size)?);
+        }
+        if let Some(size) = file.max_load_size {
+            settings.limits.max_load_bytes = Some(Limits::parse_size(&size)?);
+        }
+        if let Some(style) = file.json {
+            settings.json_style = style.parse()?;
+        }
+        if let Some(secs) = file.session_timeout {
+            settings.session_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = file.statement_timeout {
+            settings.statement_timeout = Some(Duration::from_secs(secs));
+        }
+        if let Some(read_only) = file.read_only {
+            settings.read_only = read_only;
+        }
+        if let Some(repair) = file.repair {
+            settings.repair = repair;
+        }
+        if let Some(unmask) = file.unmask {
+            settings.unmask = unmask;
+        }
+        if let Some(admins) = file.admins {
+            settings.admins = admins;
+        }
+        if let Some(tenants) = file.tenants {
+            settings.tenants = tenants;
+        }
+        if let Some(auth) = file.auth {
+            settings.auth = Some(auth.provider()?);
+        }
+        Ok(settings)
+    }
+}
+
+/// Prints what loading `what` fixed: anything with `--repair`, a leftover `.partial` file always.
+pub fn report_repairs(what: &str, repaired: &[IntegrityIssue]) {
+    if !repaired.is_empty() {
+        eprintln!("Repaired {}:", what);
+        for issue in repaired {
+            eprintln!("  {}", issue);
+        }
+    }
+}
+
+/// The routes of the HTTP API over `state`, with authentication and compression. Serve it
+/// with `axum::serve`, or drive it in-process with `tower::ServiceExt::oneshot`.
+pub fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(ui_handler))
+        .route("/query", post(query_handler))
+        .route("/batch", post(batch_handler))
+        .route("/explain", post(explain_handler))
+        .route("/estimate", post(estimate_handler))
+        .route(
+            "/queries",
+            get(list_queries_handler).post(save_query_handler),
+        )
+        .route("/flush", post(flush_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/schema", get(schema_handler))
+        .route("/schema.json", get(json_schema_handler))
+        .route("/schema.ts", get(typescript_handler))
+        .route("/copy/{table}", post(copy_handler))
+        .route("/admin/reload", post(reload_handler))
+        .route("/t/{tenant}/", get(ui_handler))
+        .route("/t/{tenant}/query", post(query_handler))
+        .route("/t/{tenant}/batch", post(batch_handler))
+        .route("/t/{tenant}/explain", post(explain_handler))
+        .route("/t/{tenant}/estimate", post(estimate_handler))
+        .route(
+            "/t/{tenant}/queries",
+            get(list_queries_handler).post(save_query_handler),
+        )
+        .route("/t/{tenant}/flush", post(flush_handler))
+        .route("/t/{tenant}/metrics", get(metrics_handler))
+        .route("/t/{tenant}/schema", get(schema_handler))
+        .route("/t/{tenant}/schema.json", get(json_schema_handler))
+        .route("/t/{tenant}/schema.ts", get(typescript_handler))
+        .route("/t/{tenant}/copy/{table}", post(copy_handler))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        // gzip or brotli, as the client's `Accept-Encoding` asks; streamed bodies included.
+        .layer(CompressionLayer::new())
+        .with_state(state)
+}
+
+// Background task that runs due `CREATE SCHEDULE` statements once per second, drops idle
+// sessions and gives time-based persistence policies a chance to flush. Schedules wait while
+// the server is read-only.
+pub async fn run_scheduler(app: AppState) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        ticker.tick().await;
+
+        let session_timeout = app.settings().session_timeout;
+        for state in app.loaded() {
+            let mut state_guard = state.write();
+            let now = unix_now();
+            if !state_guard.read_only {
+                for name in state_guard.db.due_schedules(now) {
+                    if let Err(e) = state_guard.db.run_schedule(&name, now) {
+                        eprintln!("Schedule '{}' failed: {}", name, e);
+                    }
+                    let _ = state_guard.after_statement();
+                }
+            }
+            state_guard.reap_sessions(session_timeout);
+            let _ = state_guard.tick();
+        }
+    }
+}
+
+// Background task reloading the settings whenever the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_hangup(app: AppState) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut hangups) = signal(SignalKind::hangup()) else {
+        eprintln!("Cannot listen for SIGHUP; use POST /admin/reload instead");
+        return;
+    };
+    while hangups.recv().await.is_some() {
+        match app.reload() {
+            Ok(()) => println!("Configuration reloaded"),
+            Err(e) => eprintln!("Configuration not reloaded: {}", e),
+        }
+    }
+}
+
+// Handler to execute SQL queries sent from the UI. With `?dry_run=true` the statement is
+// validated and rolled back, so nothing changes. With `?format=ndjson` result rows are streamed.
+async fn query_handler(
+    Db(state): Db,
+    principal: Principal,
+    Query(options): Query<QueryOptions>,
+    headers: HeaderMap,
+    Json(payload): Json<QueryRequest>,
+) -> Response {
+    let mut state_guard = state.write();
+    match payload.parse(&mut state_guard.db) {
+        Ok(stmt) => {
+            let id = session_id(&headers);
+            let mut session = match state_guard.take_session(id.as_deref(), &principal) {
+                Ok(session) => session,
+                Err(e) => {
+                    return Json(QueryResponse {
+                        message: "Session Error".into(),
+                        headers: vec![],
+                        types: vec![],
+                        rows: vec![],
+                        error: Some(e),
+                        position: None,
+                    })
+                    .into_response();
+                }
+            };
+            // A dry run changes nothing, so it is allowed on a read-only server.
+            if !options.dry_run
+                && let Err(e) = state_guard.check_writable(&stmt)
+            {
+                state_guard.keep_session(id, session);
+                return Json(QueryResponse {
+                    message: "Execution Error".into(),
+                    headers: vec![],
+                    types: vec![],
+                    rows: vec![],
+                    error: Some(e),
+                    position: None,
+                })
+                .into_response();
+            }
+            let mut context =
+                ExecutionContext::new(&principal.0, &mut session).with_sql(&payload.sql);
+            let result = if options.dry_run {
+                state_guard.db.validate(stmt, &mut context)
+            } else {
+                state_guard.db.execute_in(stmt, &mut context)
+            };
+            state_guard.keep_session(id, session);
+            match result {
+                Ok(result) => {
+                    if !options.dry_run {
+                        let _ = state_guard.after_statement();
+                    }
+                    match result {
+                        ExecutionResult::Message(m) => Json(QueryResponse {
+                            message: m,
+                            headers: vec![],
+                            types: vec![],
+                            rows: vec![],
+                            error: None,
+                            position: None,
+                        })
+                        .into_response(),
+                        ExecutionResult::Data {
+                            headers,
+                            rows,
+                            types,
+                        } if options.format == ResponseFormat::Ndjson => {
+                            stream_rows(options, headers, types, rows)
+                        }
+                        ExecutionResult::Data {
+                            headers,
+                            rows,
+                            types,
+                        } => Json(QueryResponse {
+                            message: "Success".into(),
+                            rows: options.render_rows(&headers, rows),
+                            headers,
+                            types,
+                            error: None,
+                            position: None,
+                        })
+                        .into_response(),
+                    }
+                }
+                Err(e) => Json(QueryResponse {
+                    message: "Execution Error".into(),
+                    headers: vec![],
+                    types: vec![],
+                    rows: vec![],
+                    error: Some(e.to_string()),
+                    position: None,
+                })
+                .into_response(),
+            }
+        }
+        Err(e) => Json(QueryResponse {
+            message: "Syntax Error".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: Some(e.message),
+            position: e.position,
+        })
+        .into_response(),
+    }
+}
+
+/// Writes a result as newline-delimited JSON from a blocking task, a chunk of rows at a time,
+/// so the body is never held in memory as a whole. The database lock is not held meanwhile.
+fn stream_rows(
+    options: QueryOptions,
+    headers: Vec<String>,
+    types: Vec<String>,
+    rows: Vec<Vec<Value>>,
+) -> Response {
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Result<String, Infallible>>(4);
+    tokio::task::spawn_blocking(move || {
+        let mut chunk = serde_json::json!({ "headers": headers, "types": types }).to_string();
+        chunk.push('\n');
+        for (i, row) in rows.into_iter().enumerate() {
+            chunk.push_str(&options.render_row(&headers, row).to_string());
+            chunk.push('\n');
+            // A closed channel means the client went away.
+            if (i + 1) % STREAM_CHUNK_ROWS == 0
+                && sender
+                    .blocking_send(Ok(std::mem::take(&mut chunk)))
+                    .is_err()
+            {
+                return;
+            }
+        }
+        if !chunk.is_empty() {
+            let _ = sender.blocking_send(Ok(chunk));
+        }
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response()
+}
+
+// Handler running several `;`-separated statements in one request and reporting each one. With
+// `atomic`, the first failure undoes the whole batch, including what it did to the session's
+// variables and savepoints, and the statements after it are skipped; otherwise every statement
+// runs and the ones that succeed are kept.
+async fn batch_handler(
+    Db(state): Db,
+    principal: Principal,
+    headers: HeaderMap,
+    Json(payload): Json<BatchRequest>,
+) -> impl IntoResponse {
+    let mut state_guard = state.write();
+    let state_guard = &mut *state_guard;
+    let id = session_id(&headers);
+    let mut session = match state_guard.take_session(id.as_deref(), &principal) {
+        Ok(session) => session,
+        Err(e) => {
+            return Json(BatchResponse {
+                committed: false,
+                results: vec![],
+                error: Some(e),
+            });
+        }
+    };
+    let snapshot = payload
+        .atomic
+        .then(|| (state_guard.db.snapshot(), session.clone()));
+
+    let mut results = Vec::new();
+    let mut failed = false;
+    for (index, sql) in split_statements(&payload.sql).into_iter().enumerate() {
+        let mut result = BatchResult {
+            index,
+            sql: sql.to_string(),
+            status: BatchStatus::Ok,
+            message: String::new(),
+            rows_affected: 0,
+            headers: vec![],
+            rows: vec![],
+            error: None,
+        };
+        if failed && payload.atomic {
+            result.status = BatchStatus::Skipped;
+            results.push(result);
+            continue;
+        }
+        let executed = state_guard
+            .db
+            .parse_cached(sql)
+            .map_err(String::from)
+            .and_then(|stmt| {
+                state_guard.check_writable(&stmt)?;
+                let mut context = ExecutionContext::new(&principal.0, &mut session).with_sql(sql);
+                state_guard
+                    .db
+                    .execute_in(stmt, &mut context)
+                    .map_err(|e| e.to_string())
+            });
+        if executed.is_ok() {
+            result.rows_affected = state_guard.db.stats.last_rows_written;
+        }
+        match executed {
+            Ok(ExecutionResult::Message(m)) => result.message = m,
+            Ok(ExecutionResult::Data { headers, rows, .. }) => {
+                result.message = "Success".into();
+                result.rows = QueryOptions::default().render_rows(&headers, rows);
+                result.headers = headers;
+            }
+            Err(e) => {
+                failed = true;
+                result.status = BatchStatus::Error;
+                result.error = Some(e);
+            }
+        }
+        results.push(result);
+    }
+
+    let committed = !(failed && payload.atomic);
+    if let (false, Some((snapshot, before))) = (committed, snapshot) {
+        state_guard.db.restore(snapshot);
+        session = before;
+    }
+    state_guard.keep_session(id, session);
+    let error = if committed {
+        state_guard.after_statement().err().map(|e| e.to_string())
+    } else {
+        None
+    };
+    Json(BatchResponse {
+        committed,
+        results,
+        error,
+    })
+}
+
+// Handler returning the execution plan of a query as a JSON tree, without running it
+async fn explain_handler(Db(state): Db, Json(payload): Json<QueryRequest>) -> impl IntoResponse {
+    let state_guard = state.read();
+
+    let result = parse(&payload.sql)
+        .map_err(String::from)
+        .and_then(|stmt| state_guard.db.plan(&stmt).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(plan) => Json(ExplainResponse {
+            plan: Some(plan),
+            error: None,
+        }),
+        Err(e) => Json(ExplainResponse {
+            plan: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Handler estimating how many rows a query would return, without running it
+async fn estimate_handler(Db(state): Db, Json(payload): Json<QueryRequest>) -> impl IntoResponse {
+    let state_guard = state.read();
+
+    let result = parse(&payload.sql).map_err(String::from).and_then(|stmt| {
+        state_guard
+            .db
+            .estimate_rows(&stmt)
+            .map_err(|e| e.to_string())
+    });
+
+    match result {
+        Ok(rows) => Json(EstimateResponse {
+            estimated_rows: Some(rows),
+            error: None,
+        }),
+        Err(e) => Json(EstimateResponse {
+            estimated_rows: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Handler describing every stored table, in name order
+async fn schema_handler(Db(state): Db) -> impl IntoResponse {
+    let state_guard = state.read();
+    let mut tables: Vec<TableSchema> = state_guard
+        .db
+        .tables
+        .values()
+        .map(|table| TableSchema {
+            name: table.name.clone(),
+            comment: table.comment.clone(),
+            columns: table.columns.clone(),
+            rows: table.rows.len(),
+        })
+        .collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(tables)
+}
+
+// Handler describing the rows of every table as a JSON Schema, with the values typed as
+// `/query?nulls=null` writes them
+async fn json_schema_handler(Db(state): Db) -> impl IntoResponse {
+    let schema = state.read().db.json_schema();
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        schema.to_string(),
+    )
+}
+
+// Handler describing the rows of every table as TypeScript interfaces
+async fn typescript_handler(Db(state): Db) -> impl IntoResponse {
+    let typescript = state.read().db.typescript();
+    (
+        [(
+            header::CONTENT_TYPE,
+            "application/typescript; charset=utf-8",
+        )],
+        typescript,
+    )
+}
+
+// Handler listing the saved queries stored in the catalog
+async fn list_queries_handler(Db(state): Db) -> impl IntoResponse {
+    let state_guard = state.read();
+    let queries: Vec<SavedQuery> = state_guard
+        .db
+        .saved_queries
+        .iter()
+        .map(|(name, sql)| SavedQuery {
+            name: name.clone(),
+            sql: sql.clone(),
+        })
+        .collect();
+    Json(queries)
+}
+
+// Handler saving a named query; it can then be run with `EXECUTE <name>`
+async fn save_query_handler(Db(state): Db, Json(payload): Json<SavedQuery>) -> impl IntoResponse {
+    let mut state_guard = state.write();
+    let saved = if state_guard.read_only {
+        Err("The server is read-only".to_string())
+    } else {
+        state_guard
+            .db
+            .save_query(payload.name.clone(), payload.sql)
+            .map_err(|e| e.to_string())
+    };
+
+    match saved {
+        Ok(()) => {
+            let _ = state_guard.after_statement();
+            Json(QueryResponse {
+                message: format!("Query '{}' saved", payload.name),
+                headers: vec![],
+                types: vec![],
+                rows: vec![],
+                error: None,
+                position: None,
+            })
+        }
+        Err(e) => Json(QueryResponse {
+            message: "Execution Error".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: Some(e),
+            position: None,
+        }),
+    }
+}
+
+// Handler writing pending changes to disk, for servers running a manual persistence policy
+async fn flush_handler(_: Admin, Db(state): Db) -> impl IntoResponse {
+    let mut state_guard = state.write();
+    match state_guard.flush() {
+        Ok(()) => Json(QueryResponse {
+            message: "Flushed".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: None,
+            position: None,
+        }),
+        Err(e) => Json(QueryResponse {
+            message: "Flush Error".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: Some(e.to_string()),
+            position: None,
+        }),
+    }
+}
+
+// Handler bulk-loading the rows streamed in the request body, in the binary format of
+// `martin_db::engine::copy`, into a table. No SQL is parsed; the rows are decoded as they
+// arrive and inserted all at once, or not at all
+async fn copy_handler(
+    _: Admin,
+    Db(state): Db,
+    principal: Principal,
+    UrlPath(params): UrlPath<HashMap<String, String>>,
+    body: Body,
+) -> impl IntoResponse {
+    let table = &params["table"];
+    let copied = async {
+        let mut decoder = {
+            let state_guard = state.read();
+            if state_guard.read_only {
+                return Err("The server is read-only".to_string());
+            }
+            state_guard.db.copy_decoder()
+        };
+        let mut chunks = body.into_data_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            decoder.feed(&chunk).map_err(|e| e.to_string())?;
+        }
+        let rows = decoder.finish().map_err(|e| e.to_string())?;
+
+        let mut state_guard = state.write();
+        let copied = state_guard
+            .db
+            .copy_rows(&principal.0, table, rows)
+            .map_err(|e| e.to_string())?;
+        state_guard.after_statement().map_err(|e| e.to_string())?;
+        Ok(copied)
+    };
+    match copied.await {
+        Ok(copied) => Json(QueryResponse {
+            message: format!("{} rows copied.", copied),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: None,
+            position: None,
+        }),
+        Err(e) => Json(QueryResponse {
+            message: "Copy Error".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: Some(e),
+            position: None,
+        }),
+    }
+}
+
+// Handler reloading the settings of the server, like SIGHUP, for every database
+async fn reload_handler(_: Admin, State(app): State<AppState>) -> impl IntoResponse {
+    match app.reload() {
+        Ok(()) => Json(QueryResponse {
+            message: "Configuration reloaded".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: None,
+            position: None,
+        }),
+        Err(e) => Json(QueryResponse {
+            message: "Reload Error".into(),
+            headers: vec![],
+            types: vec![],
+            rows: vec![],
+            error: Some(e),
+            position: None,
+        }),
+    }
+}
+
+// Handler exposing `SHOW STATUS` and lock contention in the Prometheus text format; unset
+// limits are omitted
+async fn metrics_handler(Db(state): Db) -> impl IntoResponse {
+    let state_guard = state.read();
+    let mut body = String::new();
+    for (name, value) in state_guard.db.status() {
+        if let Value::Integer(n) = value {
+            body.push_str(&format!("martin_db_{} {}\n", name, n));
+        }
+    }
+    let sessions = state_guard.sessions.len();
+    body.push_str(&format!("martin_db_sessions_open {}\n", sessions));
+    drop(state_guard);
+    body.push_str(&state.locks.render());
+    body
+}
+
+// A simple HTML UI with JavaScript to interact with our DB
+async fn ui_handler() -> Html<&'static str> {
+    Html(
+        r#"
+    <!DOCTYPE html>
+    <html>
+    <head>
+        <title>Pesapal Database Demo</title>
+        <style>
+            body { font-family: sans-serif; margin: 40px; background: #f4f4f9; }
+            .container { max-width: 800px; margin: auto; background: white; padding: 20px; border-radius: 8px; box-shadow: 0 2px 10px rgba(0,0,0,0.1); }
+            input { width: 80%; padding: 10px; }
+            button { padding: 10px 20px; cursor: pointer; background: #28a745; color: white; border: none; border-radius: 4px; }
+            table { width: 100%; border-collapse: collapse; margin-top: 20px; }
+            th, td { border: 1px solid #ddd; padding: 12px; text-align: left; }
+            th { background: #f8f9fa; }
+            .error { color: red; margin-top: 10px; white-space: pre; font-family: monospace; }
+            .plan details { margin-left: 20px; border-left: 2px solid #28a745; padding-left: 8px; }
+            .plan summary { cursor: pointer; padding: 4px 0; }
+            .plan .rows { color: #666; font-size: 0.9em; }
+        </style>
+    </head>
+    <body>
+        <div class="container">
+            <h1>Web Interface</h1>
+            <p>Run SQL queries against my Rust DB:</p>
+            <input type="text" id="sqlInput" placeholder="SELECT * FROM users..." value="SELECT * FROM devs JOIN teams ON team_id = id">
+            <button onclick="runQuery()">Execute</button>
+            <button onclick="explainQuery()">Explain</button>
+            <button onclick="saveQuery()">Save</button>
+            <button onclick="showStatus()">Status</button>
+            <div id="savedQueries"></div>
+            <div id="error" class="error"></div>
+            <div id="result"></div>
+        </div>
+
+        <script>
+            // An element holding `text` as text, never parsed as HTML, then `children`.
+            function el(tag, text, ...children) {
+                const node = document.createElement(tag);
+                if (text !== undefined) node.textContent = text;
+                node.append(...children);
+                return node;
+            }
+
+            function renderPlan(node) {
+                const rows = el('span', `(~${node.estimated_rows} rows)`);
+                rows.className = 'rows';
+                const summary = el('summary', undefined, el('b', node.operator), ` ${node.detail} `, rows);
+                const details = el('details', undefined, summary, ...node.children.map(renderPlan));
+                details.open = true;
+                return details;
+            }
+
+            async function explainQuery() {
+                const sql = document.getElementById('sqlInput').value;
+                const res = await fetch('explain', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ sql })
+                });
+                const data = await res.json();
+
+                if (data.error) {
+                    document.getElementById('error').innerText = data.error;
+                    document.getElementById('result').replaceChildren();
+                } else {
+                    document.getElementById('error').innerText = '';
+                    const plan = el('div', undefined, renderPlan(data.plan));
+                    plan.className = 'plan';
+                    document.getElementById('result').replaceChildren(el('h3', 'Query Plan'), plan);
+                }
+            }
+
+            async function loadSavedQueries() {
+                const res = await fetch('queries');
+                const queries = await res.json();
+                const saved = document.getElementById('savedQueries');
+                saved.replaceChildren();
+                if (queries.length === 0) return;
+                const list = el('p', 'Saved: ');
+                for (const q of queries) {
+                    const button = el('button', q.name);
+                    button.title = q.sql;
+                    button.addEventListener('click', () => runSaved(q.name));
+                    list.append(button, ' ');
+                }
+                saved.append(list);
+            }
+
+            function showStatus() {
+                document.getElementById('sqlInput').value = 'SHOW STATUS';
+                runQuery();
+            }
+
+            function runSaved(name) {
+                document.getElementById('sqlInput').value = `EXECUTE ${name}`;
+                runQuery();
+            }
+
+            async function saveQuery() {
+                const name = prompt('Name for this query:');
+                if (!name) return;
+                const sql = document.getElementById('sqlInput').value;
+                const res = await fetch('queries', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ name, sql })
+                });
+                const data = await res.json();
+                document.getElementById('error').innerText = data.error || '';
+                loadSavedQueries();
+            }
+
+            loadSavedQueries();
+
+            // Above this many rows, a SELECT without a LIMIT asks before running.
+            const LARGE_RESULT = 100000;
+
+            async function confirmLargeResult(sql) {
+                if (!/^\s*SELECT\b/i.test(sql) || /\bLIMIT\b/i.test(sql)) return true;
+                const res = await fetch('estimate', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ sql })
+                });
+                const data = await res.json();
+                // Errors are left for the query itself to report.
+                if (data.error || data.estimated_rows <= LARGE_RESULT) return true;
+                return confirm(`This query will return ~${data.estimated_rows.toLocaleString()} rows. `
+                    + 'Run it anyway? Cancel to add a LIMIT.');
+            }
+
+            // The query with a caret under the byte offset a syntax error points at.
+            function caret(sql, position) {
+                if (position === null || position === undefined) return '';
+                const bytes = new TextEncoder().encode(sql).slice(0, position);
+                const column = new TextDecoder().decode(bytes).length;
+                return `\n${sql}\n${' '.repeat(column)}^`;
+            }
+
+            async function runQuery() {
+                const sql = document.getElementById('sqlInput').value;
+                if (!await confirmLargeResult(sql)) return;
+                const res = await fetch('query', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ sql })
+                });
+                const data = await res.json();
+                
+                if (data.error) {
+                    document.getElementById('error').innerText = data.error + caret(sql, data.position);
+                    document.getElementById('result').replaceChildren();
+                } else {
+                    document.getElementById('error').innerText = '';
+                    const result = document.getElementById('result');
+                    result.replaceChildren(el('h3', data.message));
+                    if (data.headers.length > 0) {
+                        const head = el('tr', undefined, ...data.headers.map(h => el('th', h)));
+                        const body = data.rows.map(row =>
+                            el('tr', undefined, ...row.map(cell => el('td', String(cell)))));
+                        result.append(el('table', undefined,
+                            el('thead', undefined, head), el('tbody', undefined, ...body)));
+                    }
+                }
+            }
+        </script>
+    </body>
+    </html>
+    "#,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use tower::ServiceExt;
+
+    async fn query(app: &Router, sql: &str) -> (StatusCode, QueryResponse) {
+        query_at(app, "/query", sql).await
+    }
+
+    async fn query_at(app: &Router, path: &str, sql: &str) -> (StatusCode, QueryResponse) {
+        let request = Request::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "sql": sql }).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn app_with(settings: Settings) -> Router {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        app(AppState::new(Database::new(), persistence, settings))
+    }
+
+    async fn post(app: &Router, path: &str, body: impl Into<Body>) -> (StatusCode, Vec<u8>) {
+        let request = Request::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_app() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let state = AppState::new(Database::new(), persistence, Settings::default());
+        let app = app(state);
+
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'ada'), (2, 'bob')",
+        ] {
+            let (status, response) = query(&app, sql).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(response.error, None);
+        }
+        let (_, response) = query(&app, "SELECT name FROM users WHERE id = 2").await;
+        assert_eq!(response.headers, ["name"]);
+        let bob = format!("{:?}", Value::Text("bob".into()));
+        assert_eq!(response.rows, [serde_json::json!([bob])]);
+        let (_, response) = query(&app, "SELECT * FROM missing").await;
+        assert!(response.error.is_some());
+
+        let request = Request::get("/schema").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let state = AppState::new(Database::new(), persistence, Settings::default());
+        let app = app(state.clone());
+        let in_session = |id: String, sql: &str| {
+            let request = Request::post("/query")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-session-id", id)
+                .body(Body::from(serde_json::json!({ "sql": sql }).to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<QueryResponse>(&body).unwrap()
+            }
+        };
+
+        // Variables carry over between the requests of a session, and only those.
+        in_session("s0".into(), "SET @x = 7").await;
+        let response = in_session("s0".into(), "SELECT @x").await;
+        let seven = format!("{:?}", Value::Integer(7));
+        assert_eq!(response.rows, [serde_json::json!([seven])]);
+        let response = in_session("s1".into(), "SELECT @x").await;
+        assert_ne!(response.rows, [serde_json::json!([seven])]);
+
+        for i in 2..MAX_SESSIONS_PER_PRINCIPAL {
+            in_session(format!("s{}", i), "SELECT 1").await;
+        }
+        let response = in_session("one-too-many".into(), "SELECT 1").await;
+        assert!(response.error.unwrap().contains("Too many open sessions"));
+
+        // Idle sessions are dropped, making room for new ones.
+        std::thread::sleep(Duration::from_millis(2));
+        state.default.write().reap_sessions(Duration::ZERO);
+        assert!(state.default.read().sessions.is_empty());
+        let response = in_session("one-too-many".into(), "SELECT 1").await;
+        assert_eq!(response.error, None);
+    }
+
+    #[tokio::test]
+    async fn test_session_transactions() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let state = AppState::new(Database::new(), persistence, Settings::default());
+        let app = app(state.clone());
+        let in_session = |id: &str, sql: &str| {
+            let request = Request::post("/query")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-session-id", id)
+                .body(Body::from(serde_json::json!({ "sql": sql }).to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<QueryResponse>(&body).unwrap()
+            }
+        };
+        let rows = |state: &AppState| state.default.read().db.tables["t"].rows.clone();
+        query(&app, "CREATE TABLE t (id INT)").await;
+
+        // While `a` holds a transaction, `b` reads but cannot write.
+        for sql in ["BEGIN", "SAVEPOINT sp", "INSERT INTO t VALUES (1)"] {
+            assert_eq!(in_session("a", sql).await.error, None);
+        }
+        let response = in_session("b", "INSERT INTO t VALUES (42)").await;
+        assert!(response.error.unwrap().contains("locked"));
+        assert_eq!(in_session("b", "SELECT * FROM t").await.error, None);
+        in_session("a", "ROLLBACK TO SAVEPOINT sp").await;
+        assert!(rows(&state).is_empty());
+        assert_eq!(in_session("a", "COMMIT").await.error, None);
+        assert_eq!(
+            in_session("b", "INSERT INTO t VALUES (42)").await.error,
+            None
+        );
+
+        // A session that goes idle, or a request without one, rolls back what it left open.
+        in_session("a", "BEGIN").await;
+        in_session("a", "INSERT INTO t VALUES (1)").await;
+        std::thread::sleep(Duration::from_millis(2));
+        state.default.write().reap_sessions(Duration::ZERO);
+        assert_eq!(rows(&state), [[Value::Integer(42)]]);
+        query(&app, "BEGIN").await;
+        let (_, response) = query(&app, "INSERT INTO t VALUES (2)").await;
+        assert_eq!(response.error, None);
+        assert_eq!(
+            in_session("b", "INSERT INTO t VALUES (3)").await.error,
+            None
+        );
+        assert_eq!(rows(&state).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let state = AppState::new(Database::new(), persistence, Settings::default());
+        let app = app(state.clone());
+        let post = |path: &str, body: serde_json::Value| {
+            let request = Request::post(path)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-session-id", "s")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+            }
+        };
+        let column = |response: &serde_json::Value, key: &str| -> Vec<serde_json::Value> {
+            let results = response["results"].as_array().unwrap();
+            results.iter().map(|r| r[key].clone()).collect()
+        };
+
+        let sql = "CREATE TABLE t (id INT PRIMARY); INSERT INTO t VALUES (1), (2); \
+                   INSERT INTO t VALUES (1); SELECT * FROM t";
+        let response = post("/batch", serde_json::json!({ "sql": sql })).await;
+        assert_eq!(response["committed"], true);
+        assert_eq!(
+            column(&response, "status"),
+            ["ok", "ok", "error", "ok"].map(serde_json::Value::from)
+        );
+        assert_eq!(
+            column(&response, "rows_affected"),
+            [0, 2, 0, 0].map(serde_json::Value::from)
+        );
+        assert_eq!(column(&response, "index")[2], 2);
+
+        // A failed atomic batch leaves the data and the session as they were.
+        post("/query", serde_json::json!({ "sql": "SET @x = 1" })).await;
+        let sql = "SET @x = 2; BEGIN; SAVEPOINT a; INSERT INTO t VALUES (3); \
+                   INSERT INTO t VALUES (1); SELECT 1";
+        let response = post("/batch", serde_json::json!({ "sql": sql, "atomic": true })).await;
+        assert_eq!(response["committed"], false);
+        assert_eq!(
+            column(&response, "status"),
+            ["ok", "ok", "ok", "ok", "error", "skipped"].map(serde_json::Value::from)
+        );
+        assert_eq!(state.default.read().db.tables["t"].rows.len(), 2);
+        let response = post("/query", serde_json::json!({ "sql": "SELECT @x" })).await;
+        assert_eq!(
+            response["rows"],
+            serde_json::json!([[format!("{:?}", Value::Integer(1))]])
+        );
+        // The transaction the batch began is gone, and holds no lock.
+        for sql in ["ROLLBACK TO SAVEPOINT a", "COMMIT"] {
+            let response = post("/query", serde_json::json!({ "sql": sql })).await;
+            assert!(response["error"].is_string());
+        }
+        assert_eq!(state.default.read().db.transaction_owner, None);
+    }
+
+    #[tokio::test]
+    async fn test_tenants() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let settings = Settings {
+            tenants: HashMap::from([("web".to_string(), "acme".to_string())]),
+            ..Settings::default()
+        };
+        let state = AppState::new(Database::new(), persistence, settings);
+        let app = app(state.clone());
+
+        let (status, response) = query_at(&app, "/t/acme/query", "CREATE TABLE t (id INT)").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.error, None);
+        // Requests without a tenant in the path go to the principal's tenant too.
+        let (_, response) = query(&app, "SELECT * FROM t").await;
+        assert_eq!(response.error, None);
+        assert!(state.default.read().db.tables.is_empty());
+
+        let request = Request::get("/t/other/schema").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let request = Request::post("/t/other/query")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"sql": "SELECT 1"}"#))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(state.tenants.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes() {
+        let persistence =
+            PersistenceManager::new(Storage::Memory, PersistencePolicy::EveryStatement);
+        let keys = HashMap::from([
+            ("k1".to_string(), "ada".to_string()),
+            ("k2".to_string(), "ops".to_string()),
+        ]);
+        let settings = Settings {
+            admins: vec!["ops".into()],
+            auth: Some(AuthConfig::ApiKeys(keys).provider().unwrap()),
+            ..Settings::default()
+        };
+        let app = app(AppState::new(Database::new(), persistence, settings));
+
+        for (key, expected) in [("k1", StatusCode::FORBIDDEN), ("k2", StatusCode::OK)] {
+            let request = Request::post("/flush")
+                .header(header::AUTHORIZATION, format!("Bearer {}", key))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_params() {
+        let app = app_with(Settings::default());
+        query(
+            &app,
+            "CREATE TABLE users (id INT PRIMARY, name TEXT, score REAL)",
+        )
+        .await;
+
+        let with_params = |sql: &str, params: serde_json::Value| {
+            serde_json::json!({ "sql": sql, "params": params }).to_string()
+        };
+        let insert = "INSERT INTO users VALUES (?, ?, ?)";
+        for params in [
+            serde_json::json!([1, "ada", 1.5]),
+            serde_json::json!([2, "it's", null]),
+        ] {
+            let (status, body) = post(&app, "/query", with_params(insert, params)).await;
+            assert_eq!(status, StatusCode::OK);
+            let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(response.error, None);
+        }
+
+        let select = with_params(
+            "SELECT id FROM users WHERE name = ?",
+            serde_json::json!(["it's"]),
+        );
+        let (_, body) = post(&app, "/query", select).await;
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        let two = format!("{:?}", Value::Integer(2));
+        assert_eq!(response.rows, [serde_json::json!([two])]);
+
+        // Parameters bind values only, and must fill every placeholder.
+        for params in [serde_json::json!([true, "x", 1]), serde_json::json!([3])] {
+            let (_, body) = post(&app, "/query", with_params(insert, params)).await;
+            let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+            assert!(response.error.is_some());
+        }
+        let (_, response) = query(&app, "SELECT COUNT(*) FROM users").await;
+        assert_eq!(response.rows, [serde_json::json!([two])]);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson() {
+        let app = app_with(Settings::default());
+        query(&app, "CREATE TABLE t (id INT, name TEXT)").await;
+        query(&app, "INSERT INTO t VALUES (1, 'a'), (2, NULL), (3, 'c')").await;
+
+        let request = Request::post("/query?format=ndjson&nulls=omit")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"sql": "SELECT * FROM t ORDER BY id"}"#))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["headers"], serde_json::json!(["id", "name"]));
+        assert_eq!(lines[0]["types"], serde_json::json!(["INT", "TEXT"]));
+        // Rows follow the other options, here leaving out NULL fields.
+        assert!(lines[2].get("name").is_none());
+        assert!(lines[3].get("name").is_some());
+
+        // Statements without rows still answer with a single JSON object.
+        let request = Request::post("/query?format=ndjson")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"sql": "INSERT INTO t VALUES (4, 'd')"}"#))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.error, None);
+    }
+
+    #[tokio::test]
+    async fn test_copy() {
+        let app = app_with(Settings::default());
+        query(&app, "CREATE TABLE t (id INT PRIMARY, name TEXT)").await;
+
+        let mut rows = Vec::new();
+        for row in [
+            [Value::Integer(1), Value::Text("a".into())],
+            [Value::Integer(2), Value::Null],
+        ] {
+            crate::engine::copy::encode_row(&row, &mut rows);
+        }
+        let (status, body) = post(&app, "/copy/t", rows.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.message, "2 rows copied.");
+
+        // A truncated stream, or rows breaking a constraint, copy nothing.
+        let (_, body) = post(&app, "/copy/t", rows[..rows.len() - 1].to_vec()).await;
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.message, "Copy Error");
+        let (_, body) = post(&app, "/copy/t", rows).await;
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert!(response.error.is_some());
+        let (_, body) = post(&app, "/copy/missing", Vec::new()).await;
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert!(response.error.is_some());
+
+        let (_, response) = query(&app, "SELECT COUNT(*) FROM t").await;
+        let two = format!("{:?}", Value::Integer(2));
+        assert_eq!(response.rows, [serde_json::json!([two])]);
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let app = app_with(Settings {
+            read_only: true,
+            ..Settings::default()
+        });
+        let (_, response) = query(&app, "CREATE TABLE t (id INT)").await;
+        assert_eq!(response.error.as_deref(), Some("The server is read-only"));
+
+        // The test runner is started without `--read-only`, so reloading lifts it.
+        let (status, body) = post(&app, "/admin/reload", Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: QueryResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.message, "Configuration reloaded");
+        let (_, response) = query(&app, "CREATE TABLE t (id INT)").await;
+        assert_eq!(response.error, None);
+    }
+
+    #[test]
+    fn test_lock_metrics() {
+        let metrics = LockMetrics::default();
+        let ago = |ms| Instant::now() - Duration::from_millis(ms);
+        metrics.record(false, ago(2));
+        metrics.record(false, Instant::now());
+        metrics.record(true, ago(5));
+
+        let counter = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        assert_eq!(counter(&metrics.read_acquisitions), 2);
+        assert_eq!(counter(&metrics.write_acquisitions), 1);
+        assert!(counter(&metrics.read_wait_us) >= 2_000);
+        assert!(counter(&metrics.write_wait_us) >= 5_000);
+        // The longest single wait, not a sum.
+        let max_wait = counter(&metrics.max_wait_us);
+        assert!(max_wait >= 5_000);
+        assert!(max_wait < counter(&metrics.read_wait_us) + counter(&metrics.write_wait_us));
+
+        let rendered = metrics.render();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "martin_db_lock_read_acquisitions_total 2");
+        assert_eq!(lines[2], "martin_db_lock_write_acquisitions_total 1");
+        let max = format!(
+            "martin_db_lock_max_wait_us {}",
+            counter(&metrics.max_wait_us)
+        );
+        assert_eq!(lines[4], max);
+    }
+}